@@ -0,0 +1,58 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use sgf::borrowed::Parser;
+
+// times borrowed::Parser, which every parse goes through, on made up
+// games, as cargo bench --bench parse.
+// there's no harness: each case is run for about a second and the best
+// of five rounds is printed, in MB of input a second
+
+// a game of n moves, with a comment every tenth move and a few
+// variations, about the shape of a reviewed game
+fn game(n: usize) -> String {
+    let mut s = String::from("(;GM[1]FF[4]CA[UTF-8]SZ[19]KM[6.5]PB[Black]PW[White]RE[W+R]DT[2026-01-01]\n");
+    for i in 0..n {
+        let (x, y) = ((b'a' + (i * 7 % 19) as u8) as char, (b'a' + (i * 11 % 19) as u8) as char);
+        let color = if i % 2 == 0 { 'B' } else { 'W' };
+        s.push_str(&format!(";{}[{}{}]", color, x, y));
+        if i % 10 == 0 {
+            s.push_str("C[a longer comment on the move, with \\] an escaped bracket\nand a second line]");
+        }
+        if i % 50 == 49 && i + 1 < n {
+            s.push_str(&format!("\n(;B[{}{}];W[aa])\n(", y, x));
+        }
+    }
+    s.push_str(&")".repeat((n - 1) / 50 + 1));
+    s
+}
+
+fn run<F: FnMut()>(name: &str, bytes: usize, mut f: F) {
+    let mut best = Duration::MAX;
+    let mut iters = 0;
+    for _ in 0..5 {
+        let start = Instant::now();
+        iters = 0;
+        while start.elapsed() < Duration::from_millis(200) {
+            f();
+            iters += 1;
+        }
+        best = best.min(start.elapsed() / iters);
+    }
+    let rate = bytes as f64 / best.as_secs_f64() / 1e6;
+    println!("{:<24} {:>10.1?} {:>8.1} MB/s  ({} runs a round)", name, best, rate, iters);
+}
+
+fn main() {
+    for (name, n) in [("parse 300 moves", 300), ("parse 30000 moves", 30000)] {
+        let text = game(n);
+        run(name, text.len(), || {
+            black_box(Parser::new(black_box(&text)).parse().unwrap());
+        });
+    }
+    // the way parser::Parser and the owned tree functions read
+    let text = game(300);
+    run("normalized 300 moves", text.len(), || {
+        black_box(Parser::new(black_box(&text)).normalized().parse().unwrap());
+    });
+}
@@ -0,0 +1,1615 @@
+use std::env;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::path::Path;
+use std::process;
+
+use sgf::batch::{self, Jobs, Progress, Summary};
+use sgf::bookmark;
+use sgf::builder::GameBuilder;
+use sgf::codes;
+use sgf::digest;
+use sgf::edit::{EditScript, Op};
+use sgf::filter::{DateRange, GameFilter};
+use sgf::flashcard;
+use sgf::index::Index;
+use sgf::lint::{Level, LintConfig};
+use sgf::merge;
+use sgf::text;
+use sgf::naming::{self, Namer, Template};
+use sgf::translit::NameForm;
+use sgf::outline;
+use sgf::pairing;
+use sgf::patch;
+use sgf::point::Region;
+use sgf::position::{self, Position};
+use sgf::properties;
+use sgf::rating;
+use sgf::version;
+use sgf::review;
+use sgf::salvage;
+use sgf::render::{self, Diagram, RenderOptions};
+use sgf::repertoire::Repertoire;
+use sgf::transaction;
+use sgf::vertex::{Collection, GameTree};
+use sgf::writer::{AppStamp, Writer};
+
+fn usage(prog: &str) {
+    println!("usage: {} [filename]", prog);
+    println!("       {} index <path> -o <index> [--depth n] [--max-game-bytes n]", prog);
+    println!("       {} similar <file> --index <index> [--game n] [-n count]", prog);
+    println!("       {} repertoire <repertoire> <path>... [--max-game-bytes n]", prog);
+    println!("       {} cat <path>... [-o <file>] [--max-game-bytes n] [--min-moves n] [--result B+] [--player name] [--dated 2023..]", prog);
+    println!("       {} validate <path> [-j n] [--worker-memory 512M] [--ff n] [--config <toml>] [--allow E011,W002] [--fix]", prog);
+    println!("       {} --explain <code>", prog);
+    println!("       {} explain-prop <ident>", prog);
+    println!("       {} sample <path>... -n <count> [--seed n] [-o <file>] [--max-game-bytes n] [filters]", prog);
+    println!("       {} interleave <path>... [--shuffle] [--seed n] [-o <file>] [--max-game-bytes n] [filters]", prog);
+    println!("       {} profile <path>... [--nonstandard] [--max-game-bytes n]", prog);
+    println!("       {} dupes <path>... [-o <dir> [--name '{}'] [--names ascii|hash]] [--max-game-bytes n]", prog, naming::DEFAULT_TEMPLATE);
+    println!("       {} split <path>... -o <dir> [--name '{}'] [--names ascii|hash] [--unique] [--max-game-bytes n] [filters]", prog, naming::DEFAULT_TEMPLATE);
+    println!("       {} trend <path>... --player name [--json | --opponents] [--names ascii|hash] [--svg <file>] [-o <file>] [--max-game-bytes n]", prog);
+    println!("       {} match <file> <file>", prog);
+    println!("       {} bookmarks <file> [--game n] [--add <path> <label>] [--remove <path>] [-o <sgf>]", prog);
+    println!("       {} disable <file> <path> [--game n] [-o <sgf>]", prog);
+    println!("       {} enable <file> [<path> [n]] [--game n] [-o <sgf>]", prog);
+    println!("       {} show <file> [--at n | --bookmark label] [--game n] [--numbers n] [--svg <file>] [--png <file>] [--no-coords] [--no-last] [--position | --figures]", prog);
+    println!("       {} html <file> -o <html> [--game n] [--figures]", prog);
+    println!("       {} anki <file>... -o <deck.txt> [--select commented-branches|branches|comments] [--ascii] [--no-coords]", prog);
+    println!("       {} stats <file> [--game n]", prog);
+    println!("       {} clock <file> [--game n]", prog);
+    println!("       {} outline <file>", prog);
+    println!("       {} territory <file> [--game n] [-o <sgf>]", prog);
+    println!("       {} diffs <file> [--game n] [--values] [--threshold n] [-o <sgf> [--property] [--wrap n]]", prog);
+    println!("       {} blunders <file> [--game n] [--estimate] [--threshold n] [-o <html> [--size n]]", prog);
+    println!("       {} lang <file> [--keep <code> -o <sgf>]", prog);
+    println!("       {} chat <file> [--strip [-o <sgf>]]", prog);
+    println!("       {} authors <file> [--keep <author> | --strip <author>] [-o <sgf>]", prog);
+    println!("       {} crop <file> <region> [--drop] [-o <sgf>]", prog);
+    println!("       {} upgrade <file> [-o <sgf> | --split <dir> [--name '{}'] [--names ascii|hash]]", prog, naming::DEFAULT_TEMPLATE);
+    println!("       {} json <file> [-o <json>] | json --import <json> [-o <sgf>]", prog);
+    println!("       {} head <file> -n <moves> [-o <sgf>]", prog);
+    println!("       {} tail <file> -n <moves> [-o <sgf>]", prog);
+    println!("       {} merge <black's file> <white's file> [--comments join|attribute|black|white|ask] [-o <sgf>]", prog);
+    println!("       {} salvage <file> [-o <sgf>] [--verbose]", prog);
+    println!("       {} recode <path> [--to utf-8] [--from encoding] [-i] [-j n]", prog);
+    println!("       {} edit <path> [--set KM=6.5] [--delete GN] [--delete 'C>1'] [--rename 'PB:old=new'] [-i [--atomic]] [-j n]", prog);
+    println!("       {} from-diagram [file] [-o <sgf>]", prog);
+    println!("       {} new [--size 19] [--komi 6.5] [--handicap n] [--black name] [--white name] [-o <sgf>]", prog);
+    println!("       {} pairings <csv> [--event name] [--round n] [--date d] [--komi 6.5] [-o <dir>]", prog);
+    println!("       {} thumbs <path> -o <dir> [--size px] [--svg] [-j n] [--worker-memory 512M]", prog);
+    println!("       {} animate <file> -o <gif> [--game n] [--every n] [--delay 500ms] [--numbers n]", prog);
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+// files a run can't read are reported and skipped, but a file that
+// crashed it is a bug, so the run ends with an error once it is done
+fn finish(report: &batch::Report) {
+    if report.panicked > 0 {
+        fail(&format!("{} file(s) crashed the run", report.panicked));
+    }
+}
+
+// removes "--name value" (or "-n value") from args and returns the value
+fn take_option(args: &mut Vec<String>, names: &[&str]) -> Option<String> {
+    let i = args.iter().position(|a| names.contains(&a.as_str()))?;
+    if i + 1 >= args.len() {
+        fail(&format!("missing value for {}", args[i]));
+    }
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+// removes a "--flag" without a value and returns whether it was there
+fn take_flag(args: &mut Vec<String>, names: &[&str]) -> bool {
+    match args.iter().position(|a| names.contains(&a.as_str())) {
+        Some(i) => {
+            args.remove(i);
+            true
+        },
+        None => false,
+    }
+}
+
+fn take_number(args: &mut Vec<String>, names: &[&str]) -> Option<usize> {
+    let n = take_option(args, names)?;
+    Some(n.parse().unwrap_or_else(|_| fail(&format!("bad number: {}", n))))
+}
+
+fn max_game_bytes(args: &mut Vec<String>) -> Option<usize> {
+    let n = take_option(args, &["--max-game-bytes"])?;
+    Some(n.parse().unwrap_or_else(|_| fail(&format!("bad byte count: {}", n))))
+}
+
+// -j n threads, one per core by default or with -j 0. --worker-memory
+// 512M keeps each thread off files too big to parse in that much
+fn jobs(args: &mut Vec<String>) -> Jobs {
+    let mut jobs = match take_option(args, &["-j", "--jobs"]) {
+        Some(j) => Jobs::new(j.parse().unwrap_or_else(|_| fail(&format!("bad job count: {}", j)))),
+        None => Jobs::auto(),
+    };
+    if let Some(m) = take_option(args, &["--worker-memory"]) {
+        jobs = jobs.with_worker_memory(parse_size(&m).unwrap_or_else(|| fail(&format!("bad memory size: {}", m))));
+    }
+    jobs
+}
+
+// a byte count with an optional K, M or G suffix, in powers of 1024
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (digits, shift) = match s.chars().last()?.to_ascii_uppercase() {
+        'K' => (&s[..s.len() - 1], 10),
+        'M' => (&s[..s.len() - 1], 20),
+        'G' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn progress_bar(p: &Progress) {
+    let width = 40;
+    let filled = (p.done * width).checked_div(p.total).unwrap_or(width);
+    eprint!(
+        "\r[{}{}] {}/{} ({} skipped, {} errors)",
+        "=".repeat(filled),
+        " ".repeat(width - filled),
+        p.done,
+        p.total,
+        p.skipped,
+        p.errors,
+    );
+    if p.done == p.total {
+        eprintln!();
+    }
+}
+
+fn print_file(filename: &str) {
+    let data = match batch::read_file(filename) {
+        Ok(data) => data,
+        Err(e) => panic!("{:?}", e.kind()),
+    };
+
+    //let tokens = scanner::Scanner::new(&text).scan().unwrap();
+    //for tok in tokens {
+    //    println!("{:?}", tok);
+    //}
+    let coll = sgf::parse(&data).unwrap();
+    //for gt in coll.gametrees {
+    //    let gt2 = gt.strip_key("PB")
+    //        .strip_key("PW")
+    //        .strip_key("BR")
+    //        .strip_key("WR");
+    //    println!("{}", gt2);
+    //}
+    println!("{}", coll);
+}
+
+fn index(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]).unwrap_or_else(|| fail("index needs -o <index>"));
+    let max_bytes = max_game_bytes(&mut args);
+    let depth = match take_option(&mut args, &["--depth"]) {
+        Some(d) => d.parse().unwrap_or_else(|_| fail(&format!("bad depth: {}", d))),
+        None => sgf::index::DEFAULT_OPENING_DEPTH,
+    };
+    if args.len() != 1 {
+        fail("index needs exactly one path");
+    }
+
+    // an existing index is resumed: finished files are skipped and new
+    // entries are appended as they are read, so an interrupted run loses
+    // at most the game it was on, and the next one finishes its file
+    let resume = Path::new(&out).exists();
+    let mut index = if resume {
+        Index::load_interned(&out).unwrap_or_else(|e| fail(&format!("{}: {}", out, e)))
+    } else {
+        Index::new(depth).with_interning()
+    };
+    let mut f = if resume {
+        Index::open_to_append(&out).unwrap_or_else(|e| fail(&format!("{}: {}", out, e)))
+    } else {
+        let mut f = File::create(&out).unwrap_or_else(|e| fail(&format!("{}: {}", out, e)));
+        index.write_header(&mut f).unwrap_or_else(|e| fail(&e.to_string()));
+        f
+    };
+
+    let files = batch::sgf_files(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let report = batch::index_files(&mut index, &files, max_bytes, Some(&mut f), progress_bar)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", out, e)));
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    println!("{} games in index", index.len());
+    finish(&report);
+}
+
+// the indexed games whose openings share the most moves with a game, one
+// per line with the number shared
+fn similar(mut args: Vec<String>) {
+    let path = take_option(&mut args, &["--index"]).unwrap_or_else(|| fail("similar needs --index <index>"));
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let count = take_number(&mut args, &["-n", "--count"]).unwrap_or(10);
+    if args.len() != 1 {
+        fail("similar needs exactly one file");
+    }
+    let index = Index::load_interned(&path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {}", game)));
+    let (cols, rows) = gt.board_size();
+    if cols != rows {
+        fail("only square boards can be searched");
+    }
+    for (shared, entry) in index.nearest_sized(cols, &index.opening(gt), count) {
+        println!("{}\t{}\t{}", shared, entry.source, entry.game);
+    }
+}
+
+// where each game left the prepared lines, then how far the games got
+// into every line and the lines none of them entered
+fn repertoire(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    if args.len() < 2 {
+        fail("repertoire needs the repertoire and at least one path of games");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let book = coll.gametrees.first().unwrap_or_else(|| fail(&format!("{}: no game", args[0])));
+    let mut rep = Repertoire::new(book);
+    for arg in &args[1..] {
+        for path in batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))) {
+            let reader = batch::open_games(&path, max_bytes).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+            for (i, game) in reader.enumerate() {
+                let gt = match game {
+                    Ok(gt) => gt,
+                    Err(e) => {
+                        eprintln!("{}: game {}: {}", path.display(), i, e);
+                        continue;
+                    },
+                };
+                let found = rep.check(&gt);
+                let what = match &found.deviation {
+                    Some(d) => {
+                        let expected: Vec<String> = d.expected.iter().map(|m| rep.describe(m)).collect();
+                        format!("left at move {} with {}, the book has {}", d.number, rep.describe(&d.played), expected.join(", "))
+                    },
+                    None if found.completed => "played a line to its end".to_string(),
+                    None => "stopped in book".to_string(),
+                };
+                println!("{}:{}: {} moves in book, {}", path.display(), i, found.depth, what);
+            }
+        }
+    }
+    let lines = rep.lines();
+    for line in &lines {
+        println!("{}", line);
+    }
+    let unreached = lines.iter().filter(|l| l.entered == 0).count();
+    println!("{} of {} lines never reached", unreached, lines.len());
+}
+
+fn game_filter(args: &mut Vec<String>) -> GameFilter {
+    GameFilter {
+        min_moves: take_number(args, &["--min-moves"]),
+        result: take_option(args, &["--result"]),
+        player: take_option(args, &["--player"]),
+        dated: take_option(args, &["--dated"]).map(|d| DateRange::parse(&d).unwrap_or_else(|| fail(&format!("bad date range: {}", d)))),
+    }
+}
+
+fn cat(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let filter = game_filter(&mut args);
+    if args.is_empty() {
+        fail("cat needs at least one path");
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in &args {
+        files.extend(batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))));
+    }
+    let mut w: Box<dyn Write> = match &out {
+        Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let report = batch::concat_files(&files, max_bytes, &filter, &mut w).unwrap_or_else(|e| fail(&e.to_string()));
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    finish(&report);
+}
+
+fn sample(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let n = take_number(&mut args, &["-n"]).unwrap_or_else(|| fail("sample needs -n <count>"));
+    let seed = take_number(&mut args, &["--seed"]).unwrap_or(0) as u64;
+    let filter = game_filter(&mut args);
+    if args.is_empty() {
+        fail("sample needs at least one path");
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in &args {
+        files.extend(batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))));
+    }
+    let (games, report) = batch::sample_files(&files, n, seed, max_bytes, &filter);
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    let mut w: Box<dyn Write> = match &out {
+        Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    for gt in &games {
+        writeln!(w, "{}", gt).unwrap_or_else(|e| fail(&e.to_string()));
+    }
+    w.flush().unwrap_or_else(|e| fail(&e.to_string()));
+    finish(&report);
+}
+
+fn interleave(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let shuffle = take_flag(&mut args, &["--shuffle"]);
+    let seed = take_number(&mut args, &["--seed"]);
+    let filter = game_filter(&mut args);
+    if args.is_empty() {
+        fail("interleave needs at least one path");
+    }
+    // each path is one source, so a directory takes turns as a whole
+    let mut sources: Vec<Vec<PathBuf>> = Vec::new();
+    for arg in &args {
+        sources.push(batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))));
+    }
+    let how = if shuffle || seed.is_some() {
+        batch::Interleave::Shuffle(seed.unwrap_or(0) as u64)
+    } else {
+        batch::Interleave::RoundRobin
+    };
+    let mut w: Box<dyn Write> = match &out {
+        Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))))),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let report = batch::interleave_files(&sources, how, max_bytes, &filter, &mut w).unwrap_or_else(|e| fail(&e.to_string()));
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    finish(&report);
+}
+
+fn profile(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let nonstandard = take_flag(&mut args, &["--nonstandard"]);
+    if args.is_empty() {
+        fail("profile needs at least one path");
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in &args {
+        files.extend(batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))));
+    }
+    let (profile, report) = batch::profile_files(&files, max_bytes);
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    if nonstandard {
+        for (ident, count) in profile.nonstandard() {
+            println!("{}\t{}\t{}\t{}", ident, count.count, count.games, count.examples.join("\t"));
+        }
+    } else {
+        print!("{}", profile);
+    }
+    finish(&report);
+}
+
+// one line per group of games with the same content, whatever client
+// saved them. with -o every game is also written once to the directory,
+// one file each, named like split names them
+fn dupes(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let mut namer = out.as_ref().map(|out| namer(&mut args, out));
+    if args.is_empty() {
+        fail("dupes needs at least one path");
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in &args {
+        files.extend(batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))));
+    }
+    let (groups, report) = batch::duplicate_files(&files, max_bytes);
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    for group in &groups {
+        let games: Vec<String> = group.iter().map(|(path, game)| format!("{}:{}", path.display(), game)).collect();
+        println!("{}", games.join("\t"));
+    }
+    finish(&report);
+    if let (Some(out), Some(namer)) = (&out, &mut namer) {
+        let out = Path::new(out);
+        let (written, report) = batch::split_files(&files, max_bytes, &GameFilter::default(), namer, out, true).unwrap_or_else(|e| fail(&e.to_string()));
+        eprintln!("{} distinct games written to {}", written, out.display());
+        finish(&report);
+    }
+}
+
+// --names, for output read by systems that only take ascii names
+fn name_form(args: &mut Vec<String>) -> NameForm {
+    let Some(form) = take_option(args, &["--names"]) else { return NameForm::Keep };
+    let form = NameForm::parse(&form).unwrap_or_else(|e| fail(&e.to_string()));
+    form.check().unwrap_or_else(|e| fail(&e.to_string()));
+    form
+}
+
+// --name and --names, for files written to out_dir. files already there
+// are never overwritten; the new one gets a suffix instead
+fn namer(args: &mut Vec<String>, out_dir: &str) -> Namer {
+    let name = take_option(args, &["--name"]);
+    let names = name_form(args);
+    let template = match name {
+        Some(name) => Template::parse(&name).unwrap_or_else(|e| fail(&e.to_string())),
+        None => Template::default(),
+    };
+    Namer::new(template.with_names(names).unwrap_or_else(|e| fail(&e.to_string()))).in_dir(out_dir)
+}
+
+// one file per game, named from its game info
+fn split(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let out = take_option(&mut args, &["-o", "--output"]).unwrap_or_else(|| fail("split needs -o <dir>"));
+    let mut namer = namer(&mut args, &out);
+    let unique = take_flag(&mut args, &["--unique"]);
+    let filter = game_filter(&mut args);
+    if args.is_empty() {
+        fail("split needs at least one path");
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in &args {
+        files.extend(batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))));
+    }
+    let out = Path::new(&out);
+    let (written, report) = batch::split_files(&files, max_bytes, &filter, &mut namer, out, unique).unwrap_or_else(|e| fail(&e.to_string()));
+    for (path, err) in &report.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    println!("{} games written to {}", written, out.display());
+    finish(&report);
+}
+
+// the player's rating after each of their games as csv, or with
+// --opponents their record against each opponent. --json gives both
+fn trend(mut args: Vec<String>) {
+    let max_bytes = max_game_bytes(&mut args);
+    let player = take_option(&mut args, &["--player"]).unwrap_or_else(|| fail("trend needs --player name"));
+    let json = take_flag(&mut args, &["--json"]);
+    let opponents = take_flag(&mut args, &["--opponents"]);
+    let names = name_form(&mut args);
+    let svg = take_option(&mut args, &["--svg"]);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.is_empty() {
+        fail("trend needs at least one path");
+    }
+    let mut games = Vec::new();
+    for arg in &args {
+        for path in batch::sgf_files(arg).unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))) {
+            let reader = batch::open_games(&path, max_bytes).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+            for (i, game) in reader.enumerate() {
+                match game {
+                    Ok(gt) => games.push(gt),
+                    Err(e) => eprintln!("{}: game {}: {}", path.display(), i, e),
+                }
+            }
+        }
+    }
+    let mut trend = rating::trend(&player, &games);
+    if trend.points.is_empty() {
+        fail(&format!("no dated games with a result for {}", player));
+    }
+    for p in &mut trend.points {
+        p.opponent = names.apply(&p.opponent).unwrap_or_else(|e| fail(&e.to_string()));
+    }
+    for r in &mut trend.opponents {
+        r.opponent = names.apply(&r.opponent).unwrap_or_else(|e| fail(&e.to_string()));
+    }
+    if let Some(path) = svg {
+        std::fs::write(&path, trend.to_svg(600, 200)).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+    let text = if json {
+        format!("{}\n", trend.to_json())
+    } else if opponents {
+        trend.opponents_csv()
+    } else {
+        trend.to_csv()
+    };
+    match out {
+        Some(path) => std::fs::write(&path, text).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => print!("{}", text),
+    }
+}
+
+// a flashcard deck for Anki's text import from every game of the files
+fn anki(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]).unwrap_or_else(|| fail("anki needs -o <deck.txt>"));
+    let select = match take_option(&mut args, &["--select"]) {
+        Some(s) => flashcard::Select::parse(&s).unwrap_or_else(|| fail(&format!("bad selection: {}", s))),
+        None => flashcard::Select::CommentedBranches,
+    };
+    let face = if take_flag(&mut args, &["--ascii"]) { flashcard::Face::Ascii } else { flashcard::Face::Svg };
+    let opts = RenderOptions {
+        coordinates: !take_flag(&mut args, &["--no-coords"]),
+        ..RenderOptions::default()
+    };
+    if args.is_empty() {
+        fail("anki needs at least one file");
+    }
+    let mut cards = Vec::new();
+    for path in &args {
+        let coll = batch::parse_file(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+        for gt in &coll.gametrees {
+            cards.extend(flashcard::cards(gt, select));
+        }
+    }
+    let deck = flashcard::deck(&cards, face, &opts).unwrap_or_else(|e| fail(&e.to_string()));
+    std::fs::write(&out, deck).unwrap_or_else(|e| fail(&format!("{}: {}", out, e)));
+    println!("{} cards", cards.len());
+}
+
+// compares the first games of two files, failing when they differ
+fn match_files(args: Vec<String>) {
+    let [a, b] = args.as_slice() else {
+        fail("match needs two files");
+    };
+    let game = |path: &String| {
+        let coll = batch::parse_file(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+        coll.gametrees.into_iter().next().unwrap_or_else(|| fail(&format!("{}: no game", path)))
+    };
+    let (a, b) = (game(a), game(b));
+    if let Err(e) = a.check_same_size(&b) {
+        eprintln!("{}", e);
+    }
+    let found = digest::match_games(&a, &b);
+    println!("{}", found);
+    if !found.same_game() {
+        process::exit(1);
+    }
+}
+
+// lists a game's bookmarks, one "path<tab>label" per line, or adds or
+// removes one and writes the game back
+fn bookmarks(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let remove = take_option(&mut args, &["--remove"]);
+    let add = match args.iter().position(|a| a == "--add") {
+        Some(i) if i + 2 < args.len() => {
+            let label = args.remove(i + 2);
+            let path = args.remove(i + 1);
+            args.remove(i);
+            Some((path, label))
+        },
+        Some(_) => fail("--add needs a path and a label"),
+        None => None,
+    };
+    if args.len() != 1 {
+        fail("bookmarks needs exactly one file");
+    }
+    if add.is_none() && remove.is_none() {
+        let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+        let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+        for mark in gt.bookmarks() {
+            println!("{}\t{}", bookmark::format_path(&mark.path), mark.label);
+        }
+        return;
+    }
+    patch_game(&args[0], game, out, |gt| {
+        if let Some((path, label)) = add {
+            let path = bookmark::parse_path(&path).unwrap_or_else(|e| fail(&e.to_string()));
+            gt.add_bookmark(&path, &label).unwrap_or_else(|e| fail(&e.to_string()));
+        }
+        if let Some(path) = remove {
+            let path = bookmark::parse_path(&path).unwrap_or_else(|e| fail(&e.to_string()));
+            if gt.remove_bookmark(&path).is_none() {
+                fail(&format!("no bookmark at {}", bookmark::format_path(&path)));
+            }
+        }
+    });
+}
+
+// runs edit on a game of file and writes the file to out, or back in
+// place, through a patch: only what the edit changed is rewritten, and
+// files that aren't UTF-8 are refused rather than losing their text
+fn patch_game<R>(file: &str, game: usize, out: Option<String>, edit: impl FnOnce(&mut GameTree) -> R) -> R {
+    let text = batch::read_text(file).unwrap_or_else(|e| fail(&format!("{}: {}", file, e)));
+    let (patched, r) = patch::patch(&text, |coll| {
+        let gt = coll.gametrees.get_mut(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, file)));
+        edit(gt)
+    })
+    .unwrap_or_else(|e| fail(&format!("{}: {}", file, e)));
+    let path = out.unwrap_or_else(|| file.to_string());
+    transaction::replace_file(&path, patched.as_bytes()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    r
+}
+
+// hides the variation starting at path in the node it branches from
+fn disable(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.len() != 2 {
+        fail("disable needs a file and the path of a variation's first node");
+    }
+    let path = bookmark::parse_path(&args[1]).unwrap_or_else(|e| fail(&e.to_string()));
+    patch_game(&args[0], game, out, |gt| gt.disable_variation(&path).unwrap_or_else(|e| fail(&e.to_string())));
+}
+
+// lists the hidden variations, or puts the nth one hidden at path back
+fn enable(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.is_empty() || args.len() > 3 {
+        fail("enable needs a file, and the path of a node to put a variation back at");
+    }
+    if args.len() == 1 {
+        let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+        let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+        let mut last: Option<(Vec<usize>, usize)> = None;
+        for d in gt.disabled() {
+            let n = match &last {
+                Some((path, n)) if *path == d.path => n + 1,
+                _ => 0,
+            };
+            let first = d.gametree.sequence.nodes.first().map(|node| node.to_string()).unwrap_or_default();
+            println!("{}\t{}\t{} node(s) from {}", bookmark::format_path(&d.path), n, d.gametree.nodes().len(), first);
+            last = Some((d.path, n));
+        }
+        return;
+    }
+    let path = bookmark::parse_path(&args[1]).unwrap_or_else(|e| fail(&e.to_string()));
+    let n = match args.get(2) {
+        Some(n) => n.parse().unwrap_or_else(|_| fail(&format!("bad number: {}", n))),
+        None => 0,
+    };
+    patch_game(&args[0], game, out, |gt| gt.enable_variation(&path, n).unwrap_or_else(|e| fail(&e.to_string())));
+}
+
+fn show(mut args: Vec<String>) {
+    let at = take_number(&mut args, &["--at"]);
+    let mark = take_option(&mut args, &["--bookmark"]);
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let numbers = take_number(&mut args, &["--numbers"]).unwrap_or(0);
+    let svg = take_option(&mut args, &["--svg"]);
+    let png = take_option(&mut args, &["--png"]);
+    let position = take_flag(&mut args, &["--position"]);
+    let figures = take_flag(&mut args, &["--figures"]);
+    let opts = RenderOptions {
+        coordinates: !take_flag(&mut args, &["--no-coords"]),
+        mark_last: !take_flag(&mut args, &["--no-last"]),
+        numbers,
+        ..Default::default()
+    };
+    if args.len() != 1 {
+        fail("show needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    if figures {
+        if at.is_some() || mark.is_some() || svg.is_some() || png.is_some() || position {
+            fail("--figures draws every figure as text, on its own");
+        }
+        for (i, figure) in gt.figures().iter().enumerate() {
+            println!("{}\n{}", figure.caption(i + 1), render::ascii(&Diagram::figure(gt, figure), &opts.for_figure(figure)));
+        }
+        return;
+    }
+    let path = mark.map(|label| gt.find_bookmark(&label).unwrap_or_else(|| fail(&format!("no bookmark {}", label))));
+    if position {
+        match &path {
+            Some(path) => println!("{}", Position::at_path(gt, path).unwrap_or_else(|| fail("bookmark on a missing node"))),
+            None => println!("{}", Position::at(gt, at.unwrap_or(usize::MAX))),
+        }
+        return;
+    }
+    let diagram = match &path {
+        Some(path) => Diagram::at_path(gt, path).unwrap_or_else(|| fail("bookmark on a missing node")),
+        None => Diagram::at(gt, at.unwrap_or(usize::MAX)),
+    };
+    if let Some(path) = &png {
+        write_png(path, &diagram, &opts);
+    }
+    if let Some(path) = &svg {
+        let svg = render::svg(&diagram, &opts).unwrap_or_else(|e| fail(&e.to_string()));
+        std::fs::write(path, svg).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+    if png.is_none() && svg.is_none() {
+        print!("{}", render::ascii(&diagram, &opts));
+    }
+}
+
+#[cfg(feature = "png")]
+fn write_png(path: &str, diagram: &Diagram, opts: &RenderOptions) {
+    let data = sgf::raster::Rasterizer::new().png(diagram, opts).unwrap_or_else(|e| fail(&e.to_string()));
+    std::fs::write(path, data).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+}
+
+#[cfg(not(feature = "png"))]
+fn write_png(_: &str, _: &Diagram, _: &RenderOptions) {
+    fail("png output needs the png feature");
+}
+
+#[cfg(feature = "gif")]
+fn animate(mut args: Vec<String>) {
+    use sgf::animate::{self, AnimateOptions};
+
+    let out = take_option(&mut args, &["-o", "--output"]).unwrap_or_else(|| fail("animate needs -o <gif>"));
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let mut opts = AnimateOptions::default();
+    if let Some(every) = take_number(&mut args, &["--every"]) {
+        opts.every = every;
+    }
+    if let Some(delay) = take_option(&mut args, &["--delay"]) {
+        opts.delay_ms = animate::parse_delay(&delay).unwrap_or_else(|| fail(&format!("bad delay: {}", delay)));
+    }
+    opts.render.numbers = take_number(&mut args, &["--numbers"]).unwrap_or(0);
+    if args.len() != 1 {
+        fail("animate needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    let f = BufWriter::new(File::create(&out).unwrap_or_else(|e| fail(&format!("{}: {}", out, e))));
+    animate::gif(gt, &opts, f).unwrap_or_else(|e| fail(&format!("{}: {}", out, e)));
+}
+
+#[cfg(not(feature = "gif"))]
+fn animate(_: Vec<String>) {
+    fail("animate needs the gif feature");
+}
+
+fn html(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]).unwrap_or_else(|| fail("html needs -o <html>"));
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let figures = take_flag(&mut args, &["--figures"]);
+    if args.len() != 1 {
+        fail("html needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    let page = if figures {
+        sgf::html::figures_page(gt, &RenderOptions::default()).unwrap_or_else(|e| fail(&e.to_string()))
+    } else {
+        sgf::html::page(gt)
+    };
+    std::fs::write(&out, page).unwrap_or_else(|e| fail(&format!("{}: {}", out, e)));
+}
+
+fn stats(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    if args.len() != 1 {
+        fail("stats needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    print!("{}", gt.stats());
+}
+
+// games with their branch points and comments, and the byte range of
+// each
+fn outline(args: Vec<String>) {
+    if args.len() != 1 {
+        fail("outline needs exactly one file");
+    }
+    let data = batch::read_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let outline = outline::outline(&data).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    for symbol in outline.symbols() {
+        println!("{} ({}) {:?}", symbol.name, symbol.detail, symbol.span);
+        for child in &symbol.children {
+            println!("    {}: {} {:?}", child.name, child.detail, child.span);
+        }
+    }
+}
+
+fn territory(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.len() != 1 {
+        fail("territory needs exactly one file");
+    }
+    let mut coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    let estimate = gt.estimate();
+    let komi = gt.root().value("KM").and_then(|k| k.trim().parse().ok()).unwrap_or(0.0);
+    println!("estimate: {} (area, komi {})", estimate.result(komi), komi);
+    println!("dead: {}", estimate.dead().iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" "));
+    // groups we can't call either way
+    for group in estimate.unsettled() {
+        println!("check: {} group at {} ({} stones, {} liberties)", group.color.ident(), group.stones[0], group.stones.len(), group.liberties.len());
+    }
+    if let Some(path) = out {
+        coll.gametrees[game] = gt.with_territory();
+        Writer::new(AppStamp::Keep).write_file(&coll, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+}
+
+fn diffs(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let from_values = take_flag(&mut args, &["--values"]);
+    let property = take_flag(&mut args, &["--property"]);
+    let wrap = take_number(&mut args, &["--wrap"]);
+    let threshold = match take_option(&mut args, &["--threshold"]) {
+        Some(t) => t.parse().unwrap_or_else(|_| fail(&format!("bad threshold: {}", t))),
+        None => 3.0,
+    };
+    if args.len() != 1 {
+        fail("diffs needs exactly one file");
+    }
+    let mut coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    // V values from an engine when asked for, our own estimate otherwise
+    let scores = if from_values {
+        review::node_scores(gt)
+    } else {
+        review::estimated_scores(gt).into_iter().map(Some).collect()
+    };
+    let diffs = review::score_diffs(gt, &scores);
+    for diff in review::losses(&diffs, threshold) {
+        println!("{} {}: {:+.1}", diff.color.ident(), diff.number, diff.gain);
+    }
+    if let Some(path) = out {
+        let target = if property { review::DiffTarget::Property } else { review::DiffTarget::Comment };
+        coll.gametrees[game] = gt.with_score_diffs(&diffs, target);
+        let mut writer = Writer::new(AppStamp::Keep);
+        if let Some(width) = wrap {
+            writer = writer.with_wrap(width);
+        }
+        writer.write_file(&coll, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+}
+
+fn blunders(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let size = take_number(&mut args, &["--size"]).unwrap_or(160);
+    let estimate = take_flag(&mut args, &["--estimate"]);
+    let threshold = match take_option(&mut args, &["--threshold"]) {
+        Some(t) => t.parse().unwrap_or_else(|_| fail(&format!("bad threshold: {}", t))),
+        None => 3.0,
+    };
+    if args.len() != 1 {
+        fail("blunders needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    // losses come from V values attached by an engine unless asked to estimate
+    let scores = if estimate {
+        review::estimated_scores(gt).into_iter().map(Some).collect()
+    } else {
+        review::node_scores(gt)
+    };
+    let flagged = review::flagged_moves(gt, &review::score_diffs(gt, &scores), threshold);
+    for f in &flagged {
+        let reasons: Vec<String> = f.reasons.iter().map(|r| r.to_string()).collect();
+        println!("{} {}: {}", f.color.ident(), f.number, reasons.join(", "));
+    }
+    if let Some(path) = out {
+        let page = sgf::html::review_page(gt, &flagged, size).unwrap_or_else(|e| fail(&e.to_string()));
+        std::fs::write(&path, page).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+}
+
+fn lang(mut args: Vec<String>) {
+    let keep = take_option(&mut args, &["--keep"]);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.len() != 1 {
+        fail("lang needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let Some(keep) = keep else {
+        for (i, gt) in coll.gametrees.iter().enumerate() {
+            for (path, lang) in gt.comment_languages() {
+                println!("{}\t{:?}\t{}", i, path, lang.unwrap_or("-"));
+            }
+        }
+        return;
+    };
+    let filtered = Collection {
+        gametrees: coll.gametrees.iter().map(|gt| gt.keep_language(&keep)).collect(),
+    };
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(&filtered, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&filtered)),
+    }
+}
+
+// each player's clock after every move, estimated from TM and OT when
+// the record has no times. estimates start with '~'
+fn clock(mut args: Vec<String>) {
+    let game = take_number(&mut args, &["--game"]).unwrap_or(0);
+    if args.len() != 1 {
+        fail("clock needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gt = coll.gametrees.get(game).unwrap_or_else(|| fail(&format!("no game {} in {}", game, args[0])));
+    let clocks = gt.clocks();
+    if clocks.is_empty() {
+        fail("the game has no times and no TM or OT to estimate them from");
+    }
+    for clock in clocks {
+        println!("{}\t{}\t{}", clock.number, clock.color.ident(), clock);
+    }
+}
+
+// lists the chat that servers write into comments, or takes it out
+fn chat(mut args: Vec<String>) {
+    let strip = take_flag(&mut args, &["--strip"]);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.len() != 1 {
+        fail("chat needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    if strip {
+        let stripped = Collection {
+            gametrees: coll.gametrees.iter().map(|gt| gt.strip_chat()).collect(),
+        };
+        write_or_print(&stripped, out);
+        return;
+    }
+    for (i, gt) in coll.gametrees.iter().enumerate() {
+        for (path, events) in gt.chat() {
+            for event in events {
+                println!("{}\t{:?}\t{}\t{}\t{}", i, path, event.time, event.speaker, event.text);
+            }
+        }
+    }
+}
+
+// lists who tagged comments, or keeps or strips one reviewer's
+fn authors(mut args: Vec<String>) {
+    let keep = take_option(&mut args, &["--keep"]);
+    let strip = take_option(&mut args, &["--strip"]);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.len() != 1 {
+        fail("authors needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let gametrees = match (keep, strip) {
+        (Some(author), None) => coll.gametrees.iter().map(|gt| gt.keep_author(&author)).collect(),
+        (None, Some(author)) => coll.gametrees.iter().map(|gt| gt.strip_author(&author)).collect(),
+        (None, None) => {
+            for (i, gt) in coll.gametrees.iter().enumerate() {
+                for author in gt.comment_authors() {
+                    println!("{}\t{}\t{} comment(s)", i, author, gt.comments_by(&author).len());
+                }
+            }
+            return;
+        },
+        _ => fail("use only one of --keep and --strip"),
+    };
+    let filtered = Collection { gametrees };
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(&filtered, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&filtered)),
+    }
+}
+
+fn crop(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let drop = take_flag(&mut args, &["--drop"]);
+    if args.len() != 2 {
+        fail("crop needs a file and a region like aa:ii");
+    }
+    let region = Region::parse(&args[1]).unwrap_or_else(|| fail(&format!("bad region: {}", args[1])));
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    // without --drop, content outside the region is an error
+    let mut gametrees = Vec::new();
+    for (i, gt) in coll.gametrees.iter().enumerate() {
+        if drop {
+            gametrees.push(gt.crop(region));
+        } else {
+            gametrees.push(gt.crop_exact(region).unwrap_or_else(|e| fail(&format!("game {}: {}", i, e))));
+        }
+    }
+    let cropped = Collection { gametrees };
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(&cropped, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&cropped)),
+    }
+}
+
+fn upgrade(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let split = take_option(&mut args, &["--split"]);
+    let mut namer = split.as_ref().map(|dir| namer(&mut args, dir));
+    if args.len() != 1 {
+        fail("upgrade needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let upgraded = Collection {
+        gametrees: coll.gametrees.iter().map(|gt| gt.upgrade()).collect(),
+    };
+    let writer = Writer::new(AppStamp::Keep);
+    // a file per game, named like split names them
+    if let (Some(dir), Some(namer)) = (&split, &mut namer) {
+        for (i, gt) in upgraded.gametrees.into_iter().enumerate() {
+            batch::write_named(&writer, namer, Path::new(dir), Path::new(&args[0]), i, gt).unwrap_or_else(|e| fail(&e.to_string()));
+        }
+        return;
+    }
+    match out {
+        Some(path) => writer.write_file(&upgraded, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&upgraded)),
+    }
+}
+
+// every game cut after move n
+fn head(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let n = take_number(&mut args, &["-n"]).unwrap_or_else(|| fail("head needs -n <moves>"));
+    if args.len() != 1 {
+        fail("head needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let cut = Collection {
+        gametrees: coll.gametrees.iter().map(|gt| gt.head(n)).collect(),
+    };
+    write_or_print(&cut, out);
+}
+
+// the last n moves of every game, from the position before them
+fn tail(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let n = take_number(&mut args, &["-n"]).unwrap_or_else(|| fail("tail needs -n <moves>"));
+    if args.len() != 1 {
+        fail("tail needs exactly one file");
+    }
+    let coll = batch::parse_file(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let cut = Collection {
+        gametrees: coll.gametrees.iter().map(|gt| gt.tail(n)).collect(),
+    };
+    write_or_print(&cut, out);
+}
+
+fn write_or_print(coll: &Collection, out: Option<String>) {
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(coll, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(coll)),
+    }
+}
+
+// the collection as json for programs without an SGF reader, or back.
+// values are raw SGF, escapes and all, so the json round trips
+fn json(mut args: Vec<String>) {
+    let import = take_flag(&mut args, &["--import"]);
+    let out = take_option(&mut args, &["-o", "--output"]);
+    if args.len() != 1 {
+        fail("json needs exactly one file");
+    }
+    let path = &args[0];
+    if import {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+        let coll = Collection::from_json(&text).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+        write_or_print(&coll, out);
+        return;
+    }
+    let coll = batch::parse_file(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    match out {
+        Some(out) => std::fs::write(&out, coll.to_json()).unwrap_or_else(|e| fail(&format!("{}: {}", out, e))),
+        None => println!("{}", coll.to_json()),
+    }
+}
+
+// asks on the terminal which of two comments to keep
+fn ask_comment(number: usize, ident: &str, black: &str, white: &str) -> String {
+    eprintln!("move {}: {} differs\n  black: {}\n  white: {}", number, ident, black, white);
+    loop {
+        eprint!("keep [b]lack's, [w]hite's, [j]oin or [n]either? ");
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            fail("no answer");
+        }
+        match answer.trim() {
+            "b" => return black.to_string(),
+            "w" => return white.to_string(),
+            "j" => return text::join_paragraphs(&[black, white]),
+            "n" => return String::new(),
+            _ => {},
+        }
+    }
+}
+
+// unites the first games of the records each player kept. conflicts go
+// to stderr
+fn merge(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let mut comments = match take_option(&mut args, &["--comments"]).as_deref() {
+        None => merge::Comments::Join,
+        Some("ask") => merge::Comments::Ask(Box::new(ask_comment)),
+        Some(s) => merge::Comments::parse(s).unwrap_or_else(|| fail(&format!("bad comment strategy: {}", s))),
+    };
+    let [black, white] = args.as_slice() else {
+        fail("merge needs black's and white's record");
+    };
+    let game = |path: &String| {
+        let coll = batch::parse_file(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+        coll.gametrees.into_iter().next().unwrap_or_else(|| fail(&format!("{}: no game", path)))
+    };
+    let merged = merge::merge_halves_with(&game(black), &game(white), &mut comments).unwrap_or_else(|e| fail(&e.to_string()));
+    for conflict in &merged.conflicts {
+        eprintln!("{}", conflict);
+    }
+    let coll = Collection {
+        gametrees: vec![merged.tree],
+    };
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(&coll, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&coll)),
+    }
+}
+
+// writes out every game of a damaged file that still parses and
+// validates. what was repaired or lost goes to stderr
+fn salvage(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let verbose = take_flag(&mut args, &["-v", "--verbose"]);
+    if args.len() != 1 {
+        fail("salvage needs exactly one file");
+    }
+    let data = std::fs::read(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let found = salvage::salvage(&data);
+    if verbose {
+        for game in found.games.iter().filter(|g| g.repaired) {
+            eprintln!("bytes {}..{}: closed a cut off game", game.span.start, game.span.end);
+        }
+        for (span, reason) in &found.lost {
+            eprintln!("bytes {}..{}: {}", span.start, span.end, reason);
+        }
+    }
+    let repaired = found.games.iter().filter(|g| g.repaired).count();
+    eprintln!("{} games recovered ({} repaired), {} lost", found.games.len(), repaired, found.lost.len());
+    let coll = Collection {
+        gametrees: found.games.into_iter().map(|g| g.gametree).collect(),
+    };
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(&coll, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&coll)),
+    }
+}
+
+// --set, --delete and --rename run in the order given. without -i the
+// edited games are printed instead of written back, and with --atomic
+// either every file is written back or, when one fails, none are
+fn edit(mut args: Vec<String>) {
+    let in_place = take_flag(&mut args, &["-i", "--in-place"]);
+    let atomic = take_flag(&mut args, &["--atomic"]);
+    let jobs = jobs(&mut args);
+    let mut script = EditScript::new();
+    let mut rest = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        let parse = match arg.as_str() {
+            "--set" => Op::set,
+            "--delete" => Op::delete,
+            "--rename" => Op::rename,
+            _ => {
+                rest.push(arg);
+                continue;
+            },
+        };
+        let value = iter.next().unwrap_or_else(|| fail(&format!("missing value for {}", arg)));
+        script = script.with(parse(&value).unwrap_or_else(|e| fail(&e.to_string())));
+    }
+    if rest.len() != 1 {
+        fail("edit needs exactly one path");
+    }
+    if script.is_empty() {
+        fail("edit needs at least one --set, --delete or --rename");
+    }
+    if atomic && !in_place {
+        fail("--atomic needs -i");
+    }
+    let files = batch::sgf_files(&rest[0]).unwrap_or_else(|e| fail(&format!("{}: {}", rest[0], e)));
+    if !in_place {
+        for file in &files {
+            let mut coll = batch::parse_file(file).unwrap_or_else(|e| fail(&format!("{}: {}", file.display(), e)));
+            script.apply(&mut coll);
+            println!("{}", Writer::default().write(&coll));
+        }
+        return;
+    }
+    if atomic {
+        match batch::edit_files_atomic(&files, &script, jobs) {
+            Ok(changes) => {
+                let changed = changes.iter().filter(|n| **n > 0).count();
+                println!("{} files, {} changed ({} edits)", files.len(), changed, changes.iter().sum::<usize>());
+            },
+            Err(batch::AtomicError::Files(failed)) => {
+                for (file, e) in &failed {
+                    eprintln!("{}: {}", file.display(), e);
+                }
+                println!("{} files, {} failed, none changed", files.len(), failed.len());
+                process::exit(1);
+            },
+            Err(batch::AtomicError::Commit(e @ transaction::Error::Rollback(..))) => {
+                eprintln!("{}: {}", e.path().display(), e);
+                println!("{} files, putting them back failed, some may have their new content", files.len());
+                process::exit(1);
+            },
+            Err(batch::AtomicError::Commit(e)) => {
+                eprintln!("{}: {}", e.path().display(), e);
+                println!("{} files, none changed", files.len());
+                process::exit(1);
+            },
+        }
+        return;
+    }
+    let (mut changed, mut changes, mut failed) = (0, 0, 0);
+    for (file, result) in files.iter().zip(batch::edit_files(&files, &script, jobs)) {
+        match result {
+            Ok(0) => {},
+            Ok(n) => {
+                changed += 1;
+                changes += n;
+            },
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {}", file.display(), e);
+            },
+        }
+    }
+    println!("{} files, {} changed ({} edits), {} failed", files.len(), changed, changes, failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+// converts files to one encoding, finding each one's from CA or the
+// bytes. without -i it only tells what it would do
+#[cfg(feature = "encoding")]
+fn recode(mut args: Vec<String>) {
+    use sgf::recode::encoding;
+
+    let in_place = take_flag(&mut args, &["-i", "--in-place"]);
+    let jobs = jobs(&mut args);
+    let to = take_option(&mut args, &["--to"]).unwrap_or_else(|| "utf-8".to_string());
+    let to = encoding(&to).unwrap_or_else(|| fail(&format!("unknown encoding: {}", to)));
+    let from = take_option(&mut args, &["--from"]).map(|f| encoding(&f).unwrap_or_else(|| fail(&format!("unknown encoding: {}", f))));
+    if args.len() != 1 {
+        fail("recode needs exactly one path");
+    }
+    let files = batch::sgf_files(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let (mut converted, mut failed) = (0, 0);
+    for (file, result) in files.iter().zip(batch::recode_files(&files, from, to, in_place, jobs)) {
+        match result {
+            Ok(r) if r.changed => {
+                converted += 1;
+                println!("{}: {} ({}) to {}", file.display(), r.detection.encoding.name(), r.detection.source, to.name());
+            },
+            Ok(_) => {},
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {}", file.display(), e);
+            },
+        }
+    }
+    let verb = if in_place { "converted" } else { "to convert" };
+    println!("{} files, {} {}, {} already {}, {} failed", files.len(), converted, verb, files.len() - converted - failed, to.name(), failed);
+    if !in_place && converted > 0 {
+        eprintln!("run with -i to write them");
+    }
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "encoding"))]
+fn recode(_: Vec<String>) {
+    fail("recode needs the encoding feature");
+}
+
+// an empty game to start recording in, with the handicap stones placed
+fn new_game(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let size = take_number(&mut args, &["--size"]).unwrap_or(19);
+    let handicap = take_number(&mut args, &["--handicap"]).unwrap_or(0);
+    let komi = take_option(&mut args, &["--komi"]).map(|k| k.parse::<f64>().unwrap_or_else(|_| fail(&format!("bad komi: {}", k))));
+    let black = take_option(&mut args, &["--black"]);
+    let white = take_option(&mut args, &["--white"]);
+    if !args.is_empty() {
+        fail(&format!("unexpected argument: {}", args[0]));
+    }
+    if !(1..=52).contains(&size) {
+        fail(&format!("bad board size: {}", size));
+    }
+    let mut builder = GameBuilder::new(size);
+    if let Some(komi) = komi {
+        builder = builder.komi(komi);
+    }
+    if black.is_some() || white.is_some() {
+        builder = builder.players(black.as_deref().unwrap_or("Black"), white.as_deref().unwrap_or("White"));
+    }
+    let builder = builder.handicap(handicap).unwrap_or_else(|e| fail(&e.to_string()));
+    let coll = Collection {
+        gametrees: vec![builder.build()],
+    };
+    let writer = Writer::new(AppStamp::Keep);
+    match out {
+        Some(path) => writer.write_file(&coll, &path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => println!("{}", writer.write(&coll)),
+    }
+}
+
+// skeleton games for every board of a pairing sheet, with what the
+// options give filling in what the sheet leaves out. without -o they
+// are printed as one collection
+fn pairings(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let event = take_option(&mut args, &["--event"]);
+    let round = take_option(&mut args, &["--round"]);
+    let date = take_option(&mut args, &["--date"]);
+    let komi = take_option(&mut args, &["--komi"]).map(|k| k.parse::<f64>().unwrap_or_else(|_| fail(&format!("bad komi: {}", k))));
+    if args.len() != 1 {
+        fail("pairings needs exactly one file");
+    }
+    let text = std::fs::read_to_string(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let mut games = Vec::new();
+    for (i, mut p) in pairing::parse_pairings(&text).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e))).into_iter().enumerate() {
+        p.event = p.event.or_else(|| event.clone());
+        p.round = p.round.or_else(|| round.clone());
+        p.date = p.date.or_else(|| date.clone());
+        p.komi = p.komi.or(komi);
+        let gt = p.gametree().unwrap_or_else(|e| fail(&format!("{} vs {}: {}", p.black, p.white, e)));
+        games.push((p.file_name(i + 1), gt));
+    }
+    let writer = Writer::new(AppStamp::Keep);
+    let Some(dir) = out else {
+        let coll = Collection {
+            gametrees: games.into_iter().map(|(_, gt)| gt).collect(),
+        };
+        println!("{}", writer.write(&coll));
+        return;
+    };
+    std::fs::create_dir_all(&dir).unwrap_or_else(|e| fail(&format!("{}: {}", dir, e)));
+    // a skeleton may have been filled in since the last import, so no
+    // file is written over, and nothing is written if one would be
+    let mut names = HashSet::new();
+    for (name, _) in &games {
+        if !names.insert(name.to_lowercase()) {
+            fail(&format!("two games would be written to {}", name));
+        }
+        if Path::new(&dir).join(name).exists() {
+            fail(&format!("{} is already in {}, remove it or import somewhere else", name, dir));
+        }
+    }
+    for (name, gt) in &games {
+        let path = Path::new(&dir).join(name);
+        let coll = Collection {
+            gametrees: vec![gt.clone()],
+        };
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(writer.write(&coll).as_bytes()))
+            .unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+    }
+    println!("{} games written to {}", games.len(), dir);
+}
+
+fn from_diagram(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]);
+    let text = match args.as_slice() {
+        [] => io::read_to_string(io::stdin()).unwrap_or_else(|e| fail(&e.to_string())),
+        [path] => std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        _ => fail("from-diagram takes at most one file"),
+    };
+    let pos = position::parse_any(&text).unwrap_or_else(|e| fail(&e.to_string()));
+    let sgf = format!("{}\n", pos.to_gametree());
+    match out {
+        Some(path) => std::fs::write(&path, sgf).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))),
+        None => print!("{}", sgf),
+    }
+}
+
+fn thumbs(mut args: Vec<String>) {
+    let out = take_option(&mut args, &["-o", "--output"]).unwrap_or_else(|| fail("thumbs needs -o <dir>"));
+    let size = take_number(&mut args, &["--size"]).unwrap_or(96);
+    let jobs = jobs(&mut args);
+    let svg = take_flag(&mut args, &["--svg"]) || !cfg!(feature = "png");
+    if args.len() != 1 {
+        fail("thumbs needs exactly one path");
+    }
+    let root = Path::new(&args[0]);
+    let files = batch::sgf_files(root).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    let results = if svg {
+        batch::thumbnail_files(root, &files, Path::new(&out), "svg", jobs, |b| render::thumbnail_svg(b, size).map(String::into_bytes).map_err(|e| e.to_string()))
+    } else {
+        thumbnail_pngs(root, &files, Path::new(&out), size, jobs)
+    };
+    let mut count = 0;
+    let mut failed = 0;
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(n) => count += n,
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {}", path.display(), e);
+            },
+        }
+    }
+    println!("{} thumbnails written to {}", count, out);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(feature = "png")]
+fn thumbnail_pngs(root: &Path, files: &[PathBuf], out: &Path, size: usize, jobs: Jobs) -> Vec<Result<usize, String>> {
+    batch::thumbnail_files(root, files, out, "png", jobs, |b| sgf::raster::thumbnail(b, size as u32).map_err(|e| e.to_string()))
+}
+
+#[cfg(not(feature = "png"))]
+fn thumbnail_pngs(_: &Path, _: &[PathBuf], _: &Path, _: usize, _: Jobs) -> Vec<Result<usize, String>> {
+    unreachable!("png thumbnails need the png feature")
+}
+
+// --config, else the .sgflint.toml nearest to the validated path, with
+// the codes of --allow (or their names, comma separated) ignored on top
+fn lint_config(file: Option<String>, allow: Option<String>, path: &str) -> LintConfig {
+    let file = file.map(PathBuf::from).or_else(|| LintConfig::find(path));
+    let mut config = match file {
+        Some(file) => LintConfig::load(&file).unwrap_or_else(|e| fail(&format!("{}: {}", file.display(), e))),
+        None => LintConfig::new(),
+    };
+    for code in allow.iter().flat_map(|list| list.split(',')).filter(|c| !c.trim().is_empty()) {
+        config.set_level(code, Level::Ignore).unwrap_or_else(|e| fail(&e.to_string()));
+    }
+    config
+}
+
+fn validate(mut args: Vec<String>) {
+    let jobs = jobs(&mut args);
+    let ff = take_option(&mut args, &["--ff"]).map(|v| version::parse(&v).unwrap_or_else(|| fail(&format!("bad FF: {}", v))));
+    let config_file = take_option(&mut args, &["--config"]);
+    let allow = take_option(&mut args, &["--allow"]);
+    let fix = take_flag(&mut args, &["--fix"]);
+    if args.len() != 1 {
+        fail("validate needs exactly one path");
+    }
+    let config = lint_config(config_file, allow, &args[0]);
+    let files = batch::sgf_files(&args[0]).unwrap_or_else(|e| fail(&format!("{}: {}", args[0], e)));
+    // files that fail to fix are reported by the validation after
+    if fix {
+        for (file, fixed) in files.iter().zip(batch::fix_files(&files, jobs)) {
+            for d in fixed.unwrap_or_default() {
+                println!("{}: fixed: {}", file.display(), d);
+            }
+        }
+    }
+    let mut reports = batch::validate_files(&files, jobs, ff);
+    for report in &mut reports {
+        config.apply(&mut report.diagnostics);
+    }
+    for report in &reports {
+        for d in &report.diagnostics {
+            println!("{}: {}", report.path.display(), d);
+        }
+    }
+
+    let summary = Summary::new(&reports);
+    println!();
+    println!("{} files, {} clean, {} with errors", summary.files, summary.clean, summary.failed);
+    for (kind, count) in &summary.counts {
+        println!("{:>8} {} ({})", count, kind, config.severity(*kind));
+    }
+    if summary.failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn explain(args: Vec<String>) {
+    if args.len() != 1 {
+        fail("--explain needs a code, like E001");
+    }
+    let code = codes::lookup(&args[0]).unwrap_or_else(|| fail(&format!("unknown code: {}", args[0])));
+    println!("{} {}: {}", code.code, code.name, code.summary);
+    println!();
+    println!("{}", code.explanation);
+}
+
+fn explain_prop(args: Vec<String>) {
+    if args.len() != 1 {
+        fail("explain-prop needs a property, like KM");
+    }
+    let info = properties::describe(&args[0]).unwrap_or_else(|| fail(&format!("{} is not a standard property", args[0])));
+    println!("{} {}", info.ident, info.name);
+    println!("type:    {}", info.value_type);
+    println!("context: {}", info.context);
+    match info.until {
+        Some(until) => println!("FF:      {} to {}", info.since, until),
+        None => println!("FF:      {} on", info.since),
+    }
+    println!();
+    println!("{}", info.description);
+}
+
+// SGF_LOG=debug (or info, trace...) logs spans with their timings
+#[cfg(feature = "tracing")]
+fn init_tracing() {
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    let Some(level) = env::var("SGF_LOG").ok().and_then(|l| l.parse::<tracing::Level>().ok()) else {
+        return;
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr)
+        .init();
+}
+
+#[cfg(not(feature = "tracing"))]
+fn init_tracing() {}
+
+fn main() {
+    init_tracing();
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        usage(&args[0]);
+        return;
+    }
+
+    let rest = args[2..].to_vec();
+    match args[1].as_str() {
+        "index" => index(rest),
+        "similar" => similar(rest),
+        "repertoire" => repertoire(rest),
+        "validate" => validate(rest),
+        "cat" => cat(rest),
+        "sample" => sample(rest),
+        "interleave" => interleave(rest),
+        "profile" => profile(rest),
+        "dupes" => dupes(rest),
+        "split" => split(rest),
+        "trend" => trend(rest),
+        "match" => match_files(rest),
+        "show" => show(rest),
+        "bookmarks" => bookmarks(rest),
+        "disable" => disable(rest),
+        "enable" => enable(rest),
+        "animate" => animate(rest),
+        "html" => html(rest),
+        "anki" => anki(rest),
+        "stats" => stats(rest),
+        "outline" => outline(rest),
+        "territory" => territory(rest),
+        "diffs" => diffs(rest),
+        "blunders" => blunders(rest),
+        "lang" => lang(rest),
+        "clock" => clock(rest),
+        "json" => json(rest),
+        "authors" => authors(rest),
+        "chat" => chat(rest),
+        "upgrade" => upgrade(rest),
+        "head" => head(rest),
+        "tail" => tail(rest),
+        "merge" => merge(rest),
+        "salvage" => salvage(rest),
+        "recode" => recode(rest),
+        "edit" => edit(rest),
+        "crop" => crop(rest),
+        "from-diagram" => from_diagram(rest),
+        "new" => new_game(rest),
+        "pairings" => pairings(rest),
+        "thumbs" => thumbs(rest),
+        "--explain" | "explain" => explain(rest),
+        "explain-prop" => explain_prop(rest),
+        "-h" | "--help" | "help" => usage(&args[0]),
+        filename => print_file(filename),
+    }
+}
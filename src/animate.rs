@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use gif::{Encoder, Frame, Repeat};
+
+use super::raster::{Error, Rasterizer, Result};
+use super::render::{self, Diagram, RenderOptions};
+use super::vertex::GameTree;
+
+#[derive(Debug, Clone)]
+pub struct AnimateOptions {
+    // moves per frame
+    pub every: usize,
+    pub delay_ms: u32,
+    // how long the final position stays up before looping
+    pub final_delay_ms: u32,
+    pub render: RenderOptions,
+}
+
+impl Default for AnimateOptions {
+    fn default() -> Self {
+        AnimateOptions {
+            every: 1,
+            delay_ms: 500,
+            final_delay_ms: 3000,
+            render: RenderOptions::default(),
+        }
+    }
+}
+
+// "500ms", "0.5s", "2s", or a bare number of milliseconds
+pub fn parse_delay(s: &str) -> Option<u32> {
+    let s = s.trim();
+    if let Some(ms) = s.strip_suffix("ms") {
+        return ms.trim().parse().ok();
+    }
+    if let Some(secs) = s.strip_suffix('s') {
+        let secs: f64 = secs.trim().parse().ok()?;
+        return Some((secs * 1000.0).round() as u32);
+    }
+    s.parse().ok()
+}
+
+// the move numbers shown in each frame, always ending on the last move
+pub fn frames(moves: usize, every: usize) -> Vec<usize> {
+    let mut frames: Vec<usize> = (0..=moves).step_by(every.max(1)).collect();
+    if frames.last() != Some(&moves) {
+        frames.push(moves);
+    }
+    frames
+}
+
+// writes the main line as a looping gif
+pub fn gif<W: Write>(gt: &GameTree, opts: &AnimateOptions, out: W) -> Result<()> {
+    let rasterizer = Rasterizer::new();
+    let moves = gt.moves().len();
+    let numbers = frames(moves, opts.every);
+    let mut encoder: Option<Encoder<W>> = None;
+    let mut out = Some(out);
+    for (i, n) in numbers.iter().enumerate() {
+        let diagram = Diagram::at(gt, *n);
+        let mut pixmap = rasterizer.pixmap(&render::svg(&diagram, &opts.render)?)?;
+        let (w, h) = (pixmap.width() as u16, pixmap.height() as u16);
+        if encoder.is_none() {
+            let mut e = Encoder::new(out.take().unwrap(), w, h, &[]).map_err(gif_error)?;
+            e.set_repeat(Repeat::Infinite).map_err(gif_error)?;
+            encoder = Some(e);
+        }
+        let mut frame = Frame::from_rgba_speed(w, h, pixmap.data_mut(), 20);
+        let delay = if i + 1 == numbers.len() { opts.final_delay_ms } else { opts.delay_ms };
+        // gif delays are in hundredths of a second
+        frame.delay = (delay / 10).min(u16::MAX as u32) as u16;
+        encoder.as_mut().unwrap().write_frame(&frame).map_err(gif_error)?;
+    }
+    Ok(())
+}
+
+fn gif_error(e: gif::EncodingError) -> Error {
+    Error::RasterError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn animate1() {
+        assert_eq!(parse_delay("500ms"), Some(500));
+        assert_eq!(parse_delay("1.5s"), Some(1500));
+        assert_eq!(parse_delay("250"), Some(250));
+        assert_eq!(parse_delay("soon"), None);
+        assert_eq!(frames(5, 2), vec![0, 2, 4, 5]);
+        assert_eq!(frames(4, 2), vec![0, 2, 4]);
+        assert_eq!(frames(0, 3), vec![0]);
+    }
+
+    #[test]
+    fn animate2() {
+        let gt = Parser::new("(;SZ[5];B[bb];W[cc];B[dd])").parse().unwrap().gametrees.remove(0);
+        let opts = AnimateOptions {
+            every: 2,
+            render: RenderOptions {coordinates: false, cell: 8, ..Default::default()},
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        gif(&gt, &opts, &mut out).unwrap();
+        assert_eq!(&out[..6], b"GIF89a");
+    }
+}
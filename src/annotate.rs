@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::vertex::{GameTree, Node};
+
+#[derive(Debug)]
+pub enum Error {
+    NoSuchNode(Vec<usize>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoSuchNode(path) => {
+                let path: Vec<String> = path.iter().map(|i| i.to_string()).collect();
+                write!(f, "no node at [{}]", path.join(","))
+            },
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// a game tree with data of our own hung on its nodes, keyed by node
+// path. the tree itself is never touched, so engine evaluations or UI
+// state can travel with a game without ending up in the SGF
+#[derive(Debug, Clone)]
+pub struct AnnotatedTree<T> {
+    tree: GameTree,
+    // paths sort in preorder, so this walks the tree in order
+    notes: BTreeMap<Vec<usize>, T>,
+}
+
+impl<T> AnnotatedTree<T> {
+    pub fn new(tree: GameTree) -> Self {
+        AnnotatedTree {
+            tree,
+            notes: BTreeMap::new(),
+        }
+    }
+
+    pub fn tree(&self) -> &GameTree {
+        &self.tree
+    }
+
+    pub fn into_parts(self) -> (GameTree, BTreeMap<Vec<usize>, T>) {
+        (self.tree, self.notes)
+    }
+
+    // attaches value to the node at path, returning what was there
+    pub fn annotate(&mut self, path: &[usize], value: T) -> Result<Option<T>> {
+        if self.tree.node(path).is_none() {
+            return Err(Error::NoSuchNode(path.to_vec()));
+        }
+        Ok(self.notes.insert(path.to_vec(), value))
+    }
+
+    pub fn get(&self, path: &[usize]) -> Option<&T> {
+        self.notes.get(path)
+    }
+
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut T> {
+        self.notes.get_mut(path)
+    }
+
+    pub fn remove(&mut self, path: &[usize]) -> Option<T> {
+        self.notes.remove(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    // the annotated nodes in preorder
+    pub fn iter(&self) -> impl Iterator<Item = (&[usize], &Node, &T)> {
+        self.notes.iter().filter_map(|(path, value)| Some((path.as_slice(), self.tree.node(path)?, value)))
+    }
+
+    pub fn map<U, F: FnMut(&[usize], T) -> U>(self, mut f: F) -> AnnotatedTree<U> {
+        let mut notes = BTreeMap::new();
+        for (path, value) in self.notes {
+            let value = f(&path, value);
+            notes.insert(path, value);
+        }
+        AnnotatedTree {
+            tree: self.tree,
+            notes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn annotate1() {
+        let gt = gametree("(;GM[1];B[aa](;W[bb];B[cc])(;W[dd]))");
+        assert_eq!(gt.node(&[0, 1]).unwrap().value("W"), Some("dd"));
+        assert_eq!(gt.node(&[0, 0, 0]).unwrap().value("B"), Some("cc"));
+        assert!(gt.node(&[1]).is_none());
+        assert!(gt.node(&[0, 2]).is_none());
+        let mut tree = AnnotatedTree::new(gt.clone());
+        tree.annotate(&[0, 1], 0.25).unwrap();
+        tree.annotate(&[], 0.5).unwrap();
+        assert_eq!(tree.annotate(&[0, 1], 0.75).unwrap(), Some(0.25));
+        assert!(tree.annotate(&[3], 1.0).is_err());
+        let seen: Vec<(&[usize], f64)> = tree.iter().map(|(path, _, v)| (path, *v)).collect();
+        assert_eq!(seen, vec![(&[][..], 0.5), (&[0, 1][..], 0.75)]);
+        let tree = tree.map(|_, v| v > 0.6);
+        assert_eq!(tree.get(&[0, 1]), Some(&true));
+        assert_eq!(tree.tree().to_string(), gt.to_string());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeSet;
+
+use super::text::paragraphs;
+use super::vertex::{GameTree, Node, Sequence};
+
+// comments by several reviewers in one file are told apart by a tag
+// in front of each paragraph, like "[gm]: too slow". untagged paragraphs
+// have no author
+
+// the paragraph with author's tag in front. ']' and line breaks can't
+// be part of a tag, so they become spaces
+pub fn tag(author: &str, paragraph: &str) -> String {
+    let author: String = author.chars().map(|c| if c == ']' || c.is_control() { ' ' } else { c }).collect();
+    format!("[{}]: {}", author.trim(), paragraph.trim())
+}
+
+// the author of a tagged paragraph and the text after the tag
+pub fn split_tag(paragraph: &str) -> Option<(&str, &str)> {
+    let rest = paragraph.strip_prefix('[')?;
+    let close = rest.find(']')?;
+    let text = rest[close + 1..].strip_prefix(':')?;
+    let author = rest[..close].trim();
+    if author.is_empty() || author.contains('\n') {
+        return None;
+    }
+    Some((author, text.trim_start()))
+}
+
+pub fn author_of(paragraph: &str) -> Option<&str> {
+    split_tag(paragraph).map(|(author, _)| author)
+}
+
+// the paragraphs of a comment, split further at every line that starts
+// with a tag, since blank lines between paragraphs don't always survive
+// other programs
+pub fn entries(text: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    for p in paragraphs(text) {
+        let mut current: Vec<&str> = Vec::new();
+        for line in p.lines() {
+            if split_tag(line).is_some() && !current.is_empty() {
+                entries.push(current.join("\n"));
+                current.clear();
+            }
+            current.push(line);
+        }
+        entries.push(current.join("\n"));
+    }
+    entries
+}
+
+fn comment_entries(node: &Node) -> Vec<String> {
+    node.text("C").map(|t| entries(&t)).unwrap_or_default()
+}
+
+impl Node {
+    // adds text to the comment as author's, every paragraph tagged
+    pub fn add_comment(&mut self, author: &str, text: &str) {
+        let mut all = comment_entries(self);
+        all.extend(paragraphs(text).iter().map(|p| tag(author, p)));
+        self.set_paragraphs("C", &all);
+    }
+
+    // author's paragraphs of the comment, without their tags
+    pub fn comments_by(&self, author: &str) -> Vec<String> {
+        comment_entries(self)
+            .iter()
+            .filter_map(|p| split_tag(p))
+            .filter(|(by, _)| *by == author)
+            .map(|(_, text)| text.to_string())
+            .collect()
+    }
+
+    // keeps the paragraphs of the comment whose author keep accepts,
+    // dropping C when none are left
+    pub fn filter_comments<F: Fn(Option<&str>) -> bool>(&self, keep: F) -> Self {
+        let mut node = self.clone();
+        if node.has("C") {
+            let kept: Vec<String> = comment_entries(self).into_iter().filter(|p| keep(author_of(p))).collect();
+            node.set_paragraphs("C", &kept);
+        }
+        node
+    }
+}
+
+impl GameTree {
+    // everyone who has tagged a comment, in order of name
+    pub fn comment_authors(&self) -> Vec<String> {
+        let mut authors = BTreeSet::new();
+        for (_, node) in self.nodes() {
+            for p in comment_entries(node) {
+                if let Some(author) = author_of(&p) {
+                    authors.insert(author.to_string());
+                }
+            }
+        }
+        authors.into_iter().collect()
+    }
+
+    // author's comments with their node paths, one entry per paragraph
+    pub fn comments_by(&self, author: &str) -> Vec<(Vec<usize>, String)> {
+        let mut comments = Vec::new();
+        for (path, node) in self.nodes() {
+            for text in node.comments_by(author) {
+                comments.push((path.clone(), text));
+            }
+        }
+        comments
+    }
+
+    pub fn filter_comments<F: Fn(Option<&str>) -> bool + Copy>(&self, keep: F) -> Self {
+        GameTree {
+            sequence: Sequence {
+                nodes: self.sequence.nodes.iter().map(|node| node.filter_comments(keep)).collect(),
+            },
+            gametrees: self.gametrees.iter().map(|gt| Box::new(gt.filter_comments(keep))).collect(),
+        }
+    }
+
+    // only author's comments, the untagged ones gone too
+    pub fn keep_author(&self, author: &str) -> Self {
+        self.filter_comments(|by| by == Some(author))
+    }
+
+    // every comment but author's
+    pub fn strip_author(&self, author: &str) -> Self {
+        self.filter_comments(|by| by != Some(author))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn authors1() {
+        let mut gt = Parser::new("(;GM[1]C[game start];B[aa]C[[gm\\]: slow\n\n[kim\\]: fine])").parse().unwrap().gametrees.remove(0);
+        gt.node_mut(&[]).unwrap().add_comment("gm", "vital point\n\nsee the variation");
+        assert_eq!(gt.root().value("C"), Some("game start\n\n[gm\\]: vital point\n\n[gm\\]: see the variation"));
+        assert_eq!(gt.comment_authors(), vec!["gm", "kim"]);
+        assert_eq!(gt.comments_by("gm"), vec![(vec![], "vital point".to_string()), (vec![], "see the variation".to_string()), (vec![0], "slow".to_string())]);
+        assert_eq!(gt.strip_author("gm").to_string(), "(;GM[1]C[game start];B[aa]C[[kim\\]: fine])");
+        assert_eq!(gt.keep_author("kim").to_string(), "(;GM[1];B[aa]C[[kim\\]: fine])");
+        assert_eq!(tag("a]b\n", " x "), "[a b]: x");
+        assert_eq!(split_tag("[gm] not a tag"), None);
+        assert_eq!(split_tag("[]: x"), None);
+        assert_eq!(entries("[a]: x\ny\n[b]: z\n\nw"), vec!["[a]: x\ny", "[b]: z", "w"]);
+    }
+}
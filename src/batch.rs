@@ -0,0 +1,1084 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+#[cfg(feature = "board")]
+use super::board::Board;
+use super::codes;
+use super::digest;
+use super::edit::EditScript;
+use super::filter::GameFilter;
+use super::index::Index;
+use super::naming::Namer;
+use super::parser::Parser;
+#[cfg(feature = "encoding")]
+use super::recode::{self, Recoded};
+use super::profile::Profile;
+use super::stream::GameReader;
+use super::patch;
+use super::transaction::{self, Transaction};
+use super::validate::{self, Diagnostic, Kind, Severity};
+use super::vertex::{Collection, GameTree};
+use super::writer::{AppStamp, Writer};
+
+// drops everything outside of ascii, for files that aren't valid utf-8
+pub fn filter_ascii(data: Vec<u8>) -> String {
+    let d: Vec<u8> = data.into_iter().filter(|b| *b <= 0x7f).collect();
+    String::from_utf8(d).unwrap()
+}
+
+pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    match fs::read_to_string(&path) {
+        Ok(data) => Ok(data),
+        Err(err) => match err.kind() {
+            ErrorKind::InvalidData => Ok(filter_ascii(fs::read(&path)?)),
+            _ => Err(err),
+        },
+    }
+}
+
+// the text of a file that is to be rewritten in place. filter_ascii
+// would lose what isn't ascii, so text in other encodings is refused
+pub fn read_text<P: AsRef<Path>>(path: P) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| match e.kind() {
+        ErrorKind::InvalidData => "not UTF-8, convert it with sgf recode first".to_string(),
+        _ => e.to_string(),
+    })
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display())))]
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Collection, String> {
+    let data = read_file(path).map_err(|e| e.to_string())?;
+    Parser::new(&data).parse().map_err(|e| e.to_string())
+}
+
+// reads the games of a file one at a time instead of all at once
+pub fn open_games<P: AsRef<Path>>(path: P, max_game_bytes: Option<usize>) -> io::Result<GameReader<fs::File>> {
+    let reader = GameReader::new(fs::File::open(path)?);
+    Ok(match max_game_bytes {
+        Some(max) => reader.with_max_bytes(max),
+        None => reader,
+    })
+}
+
+fn is_sgf(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("sgf"),
+        None => false,
+    }
+}
+
+// every .sgf file under the path, sorted so runs are reproducible.
+// a plain file is returned as is, whatever its extension
+pub fn sgf_files<P: AsRef<Path>>(path: P) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let p = entry?.path();
+            if p.is_dir() {
+                dirs.push(p);
+            } else if is_sgf(&p) {
+                files.push(p);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    pub total: usize,
+    // files handled so far, including skipped and failed ones
+    pub done: usize,
+    pub skipped: usize,
+    pub errors: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub progress: Progress,
+    pub errors: Vec<(PathBuf, String)>,
+    // files whose handling panicked. their errors are in errors too
+    pub panicked: usize,
+}
+
+// runs f, turning a panic into an error so one bad file can't end a run
+// of thousands. the panic hook has already printed where it happened
+pub fn isolate<R, F: FnOnce() -> R>(f: F) -> Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => payload.downcast_ref::<String>().cloned().unwrap_or_default(),
+        };
+        format!("panicked: {}", message)
+    })
+}
+
+// hands each game of the file at path to f, with its number, and counts
+// the file in report. games that fail to parse are reported and skipped;
+// a panic gives up on the rest of the file. with decode set, games that
+// aren't utf-8 are decoded rather than stripped to ascii
+fn each_game<F>(path: &Path, max_game_bytes: Option<usize>, decode: bool, report: &mut Report, mut f: F) -> io::Result<()>
+where
+    F: FnMut(usize, GameTree) -> io::Result<()>,
+{
+    let errors = report.errors.len();
+    let read = isolate(|| -> io::Result<()> {
+        match open_games(path, max_game_bytes) {
+            Ok(games) => {
+                let games = if decode { games.decoding() } else { games };
+                for (i, game) in games.enumerate() {
+                    match game {
+                        Ok(gt) => f(i, gt)?,
+                        Err(e) => report.errors.push((path.to_path_buf(), format!("game {}: {}", i, e))),
+                    }
+                }
+            },
+            Err(e) => report.errors.push((path.to_path_buf(), e.to_string())),
+        }
+        Ok(())
+    });
+    match read {
+        Ok(read) => read?,
+        Err(e) => {
+            report.panicked += 1;
+            report.errors.push((path.to_path_buf(), e));
+        },
+    }
+    if report.errors.len() > errors {
+        report.progress.errors += 1;
+    }
+    report.progress.done += 1;
+    Ok(())
+}
+
+// adds every file to the index, skipping files the index has all the
+// games of so an interrupted run can pick up where it stopped. games are
+// read one at a time, and when out is given new entries are appended to
+// it as they are read, so memory stays bounded by the largest game
+// rather than the largest file. a file is only marked done once all of
+// its games are in; one cut off or with errors is read again next time,
+// adding just the games still missing
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip_all, fields(files = files.len())))]
+pub fn index_files<F>(
+    index: &mut Index,
+    files: &[PathBuf],
+    max_game_bytes: Option<usize>,
+    mut out: Option<&mut dyn Write>,
+    mut progress: F,
+) -> io::Result<Report>
+where
+    F: FnMut(&Progress),
+{
+    let mut report = Report::default();
+    report.progress.total = files.len();
+    for path in files {
+        let source = path.to_string_lossy();
+        if index.is_done(&source) {
+            report.progress.skipped += 1;
+            report.progress.done += 1;
+            progress(&report.progress);
+            continue;
+        }
+        let errors = report.errors.len();
+        each_game(path, max_game_bytes, false, &mut report, |i, gt| {
+            if index.has_game(&source, i) {
+                return Ok(());
+            }
+            let entry = index.entry(&source, i, &gt);
+            if let Some(w) = out.as_mut() {
+                writeln!(w, "{}", entry.to_line())?;
+            }
+            index.insert(entry);
+            Ok(())
+        })?;
+        if report.errors.len() == errors {
+            if let Some(w) = out.as_mut() {
+                writeln!(w, "{}", Index::done_line(&source))?;
+            }
+            index.mark_done(&source);
+        }
+        if let Some(w) = out.as_mut() {
+            w.flush()?;
+        }
+        progress(&report.progress);
+    }
+    trace!(entries = index.len(), errors = report.progress.errors, skipped = report.progress.skipped, "indexed");
+    Ok(report)
+}
+
+// copies every game of every file into one collection written to out,
+// one game at a time. games the filter rejects are left out
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip_all, fields(files = files.len())))]
+pub fn concat_files(files: &[PathBuf], max_game_bytes: Option<usize>, filter: &GameFilter, out: &mut dyn Write) -> io::Result<Report> {
+    let mut report = Report::default();
+    report.progress.total = files.len();
+    for path in files {
+        each_game(path, max_game_bytes, false, &mut report, |_, gt| {
+            if filter.matches(&gt) {
+                writeln!(out, "{}", gt)?;
+            }
+            Ok(())
+        })?;
+    }
+    out.flush()?;
+    Ok(report)
+}
+
+// writes gt to a file of its own under out_dir, named by namer, and
+// gives its path. the file is made new, so a name taken since namer
+// gave it gets the next one rather than being overwritten
+pub fn write_named(writer: &Writer, namer: &mut Namer, out_dir: &Path, source: &Path, game: usize, gt: GameTree) -> io::Result<PathBuf> {
+    let coll = Collection { gametrees: vec![gt] };
+    let data = writer.try_write(&coll).map_err(|e| io::Error::other(e.to_string()))?;
+    loop {
+        let out = out_dir.join(namer.name(&coll.gametrees[0], source, game));
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match fs::OpenOptions::new().write(true).create_new(true).open(&out) {
+            Ok(mut f) => {
+                f.write_all(data.as_bytes()).map_err(|e| io::Error::other(format!("{}: {}", out.display(), e)))?;
+                return Ok(out);
+            },
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(io::Error::other(format!("{}: {}", out.display(), e))),
+        }
+    }
+}
+
+// writes every game of files the filter keeps to a file of its own
+// under out_dir, named by namer as write_named does. with unique set,
+// games with the same content as one already written are left out.
+// games in other encodings are written as utf-8. gives the number written
+pub fn split_files(
+    files: &[PathBuf],
+    max_game_bytes: Option<usize>,
+    filter: &GameFilter,
+    namer: &mut Namer,
+    out_dir: &Path,
+    unique: bool,
+) -> io::Result<(usize, Report)> {
+    let writer = Writer::new(AppStamp::Keep);
+    let mut seen = HashSet::new();
+    let mut written = 0;
+    let mut report = Report::default();
+    report.progress.total = files.len();
+    for path in files {
+        each_game(path, max_game_bytes, true, &mut report, |i, gt| {
+            if !filter.matches(&gt) || (unique && !seen.insert(digest::content_hash(&gt))) {
+                return Ok(());
+            }
+            write_named(&writer, namer, out_dir, path, i, gt)?;
+            written += 1;
+            Ok(())
+        })?;
+    }
+    Ok((written, report))
+}
+
+// the property profile of every game in files, read one game at a time
+pub fn profile_files(files: &[PathBuf], max_game_bytes: Option<usize>) -> (Profile, Report) {
+    let mut profile = Profile::new();
+    let mut report = Report::default();
+    report.progress.total = files.len();
+    for path in files {
+        // nothing here returns an io error
+        let _ = each_game(path, max_game_bytes, false, &mut report, |_, gt| {
+            profile.add(&gt);
+            Ok(())
+        });
+    }
+    (profile, report)
+}
+
+// the games of files that have the same content as another, by
+// digest::content_hash: groups of file and game number, in the order
+// they were read. games are read one at a time, and decoded from their
+// encoding so text outside of ascii counts. games that can't be decoded
+// are reported instead of compared
+pub fn duplicate_files(files: &[PathBuf], max_game_bytes: Option<usize>) -> (Vec<Vec<(PathBuf, usize)>>, Report) {
+    let mut seen: BTreeMap<u64, usize> = BTreeMap::new();
+    let mut groups: Vec<Vec<(PathBuf, usize)>> = Vec::new();
+    let mut report = Report::default();
+    report.progress.total = files.len();
+    for path in files {
+        let _ = each_game(path, max_game_bytes, true, &mut report, |i, gt| {
+            let group = *seen.entry(digest::content_hash(&gt)).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push((path.clone(), i));
+            Ok(())
+        });
+    }
+    groups.retain(|g| g.len() > 1);
+    (groups, report)
+}
+
+// a small seeded generator (splitmix64) so samples are reproducible
+// without pulling in a rand crate
+pub struct SplitMix(u64);
+
+impl SplitMix {
+    pub fn new(seed: u64) -> Self {
+        SplitMix(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..n
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+// picks n games uniformly from all the games in files with reservoir
+// sampling, so only the sample is ever held in memory. the sample comes
+// back in the order the games were read
+pub fn sample_files(
+    files: &[PathBuf],
+    n: usize,
+    seed: u64,
+    max_game_bytes: Option<usize>,
+    filter: &GameFilter,
+) -> (Vec<GameTree>, Report) {
+    let mut rng = SplitMix::new(seed);
+    let mut report = Report::default();
+    report.progress.total = files.len();
+    let mut reservoir: Vec<(usize, GameTree)> = Vec::new();
+    let mut seen = 0;
+    for path in files {
+        let _ = each_game(path, max_game_bytes, false, &mut report, |_, gt| {
+            if !filter.matches(&gt) {
+                return Ok(());
+            }
+            if reservoir.len() < n {
+                reservoir.push((seen, gt));
+            } else {
+                let j = rng.below(seen as u64 + 1) as usize;
+                if j < n {
+                    reservoir[j] = (seen, gt);
+                }
+            }
+            seen += 1;
+            Ok(())
+        });
+    }
+    reservoir.sort_by_key(|(i, _)| *i);
+    (reservoir.into_iter().map(|(_, gt)| gt).collect(), report)
+}
+
+// how interleave_files picks the source of the next game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interleave {
+    // one game from each source in turn
+    RoundRobin,
+    // a seeded random pick among the sources that still have games
+    Shuffle(u64),
+}
+
+// the games of one source, read a file at a time
+struct SourceGames {
+    files: std::vec::IntoIter<PathBuf>,
+    current: Option<(PathBuf, std::iter::Enumerate<GameReader<fs::File>>)>,
+    max_game_bytes: Option<usize>,
+}
+
+impl SourceGames {
+    // the next game, or the file and error for one that failed
+    fn next_game(&mut self, report: &mut Report) -> Option<GameTree> {
+        loop {
+            if let Some((path, games)) = &mut self.current {
+                match isolate(|| games.next()) {
+                    Ok(Some((_, Ok(gt)))) => return Some(gt),
+                    Ok(Some((i, Err(e)))) => {
+                        report.errors.push((path.clone(), format!("game {}: {}", i, e)));
+                        continue;
+                    },
+                    Ok(None) => {
+                        report.progress.done += 1;
+                        self.current = None;
+                    },
+                    // the reader is in no state to go on
+                    Err(e) => {
+                        report.errors.push((path.clone(), e));
+                        report.panicked += 1;
+                        report.progress.done += 1;
+                        self.current = None;
+                    },
+                }
+            }
+            let path = self.files.next()?;
+            match open_games(&path, self.max_game_bytes) {
+                Ok(games) => self.current = Some((path, games.enumerate())),
+                Err(e) => {
+                    report.errors.push((path, e.to_string()));
+                    report.progress.done += 1;
+                },
+            }
+        }
+    }
+}
+
+// merges the games of several sources (each a list of files) into one
+// stream, one game per line. sources are read lazily, so nothing but the
+// current game of each is held in memory
+pub fn interleave_files(
+    sources: &[Vec<PathBuf>],
+    how: Interleave,
+    max_game_bytes: Option<usize>,
+    filter: &GameFilter,
+    out: &mut dyn Write,
+) -> io::Result<Report> {
+    let mut report = Report::default();
+    report.progress.total = sources.iter().map(|files| files.len()).sum();
+    let mut live: Vec<SourceGames> = sources
+        .iter()
+        .map(|files| SourceGames {
+            files: files.clone().into_iter(),
+            current: None,
+            max_game_bytes,
+        })
+        .collect();
+    let mut rng = match how {
+        Interleave::Shuffle(seed) => Some(SplitMix::new(seed)),
+        Interleave::RoundRobin => None,
+    };
+    let mut turn = 0;
+    while !live.is_empty() {
+        let i = match &mut rng {
+            Some(rng) => rng.below(live.len() as u64) as usize,
+            None => turn % live.len(),
+        };
+        match live[i].next_game(&mut report) {
+            Some(gt) => {
+                if filter.matches(&gt) {
+                    writeln!(out, "{}", gt)?;
+                }
+                turn = i + 1;
+            },
+            // keep the turn on the source that moved into this slot
+            None => {
+                live.remove(i);
+                turn = i;
+            },
+        }
+    }
+    // every file with an error counts once
+    let mut failed: Vec<&PathBuf> = report.errors.iter().map(|(path, _)| path).collect();
+    failed.sort();
+    failed.dedup();
+    report.progress.errors = failed.len();
+    out.flush()?;
+    Ok(report)
+}
+
+// rough peak memory for reading and parsing a file, per byte of it. it
+// was measured with a global allocator that tracks the peak of the heap
+// around Parser::new and parse, on a 120KB game of 20000 bare moves like
+// ;B[pd]: 79 bytes a byte, and one more for the text read. bare moves
+// cost the most, as every six bytes of them hold a node, a property, a
+// value and their tokens. a 240KB game with a 110 byte comment on each
+// move peaked at 6 bytes a byte, so for most files this is generous
+pub const PARSE_MEMORY_PER_BYTE: u64 = 80;
+
+// how a batch run is spread over threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jobs {
+    pub threads: usize,
+    // the most memory one worker may spend on a file. files whose
+    // estimate is over it fail instead of being loaded, so a run takes
+    // at most threads times this
+    pub worker_memory: Option<u64>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Jobs::auto()
+    }
+}
+
+impl Jobs {
+    // one thread per core
+    pub fn auto() -> Self {
+        Jobs::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    // 0 is auto
+    pub fn new(threads: usize) -> Self {
+        if threads == 0 {
+            return Jobs::auto();
+        }
+        Jobs {
+            threads,
+            worker_memory: None,
+        }
+    }
+
+    pub fn with_worker_memory(mut self, bytes: u64) -> Self {
+        self.worker_memory = Some(bytes);
+        self
+    }
+
+    // whether a worker may load the file at path
+    pub fn check_file(&self, path: &Path) -> Result<(), String> {
+        let Some(max) = self.worker_memory else { return Ok(()) };
+        let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+        let needed = size.saturating_mul(PARSE_MEMORY_PER_BYTE);
+        if needed > max {
+            return Err(format!("needs about {} bytes to parse, over the {} per worker", needed, max));
+        }
+        Ok(())
+    }
+
+    pub fn map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+    {
+        par_map(items, self.threads, f)
+    }
+
+    // map for work on files that can fail. a file over the worker memory
+    // fails without being read, and a panic fails only the file it hit
+    pub fn map_files<R, F>(&self, files: &[PathBuf], f: F) -> Vec<Result<R, String>>
+    where
+        R: Send,
+        F: Fn(&PathBuf) -> Result<R, String> + Sync,
+    {
+        self.map(files, |path| {
+            self.check_file(path)?;
+            isolate(|| f(path))?
+        })
+    }
+}
+
+// the stack of a worker thread: the main thread's on linux, where the
+// 2MB threads get by default can't walk a tree borrowed::MAX_NESTING
+// levels deep in a debug build
+const WORKER_STACK: usize = 8 << 20;
+
+// runs f over the items on up to `jobs` threads, keeping the input order
+pub fn par_map<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, items.len().max(1));
+    if jobs == 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let worker = thread::Builder::new().stack_size(WORKER_STACK);
+            worker
+                .spawn_scoped(scope, || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= items.len() {
+                        break;
+                    }
+                    let r = f(&items[i]);
+                    results.lock().unwrap()[i] = Some(r);
+                })
+                .expect("spawning a worker thread");
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(|r| r.unwrap()).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl FileReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = %path.display())))]
+// ff validates against that FF rather than the declared one
+pub fn validate_file(path: &Path, ff: Option<u8>) -> FileReport {
+    validate_checked(path, ff, &Jobs::new(1))
+}
+
+fn validate_checked(path: &Path, ff: Option<u8>, jobs: &Jobs) -> FileReport {
+    let data = jobs
+        .check_file(path)
+        .and_then(|_| read_file(path).map_err(|e| e.to_string()))
+        .map_err(|e| Diagnostic::parse_error(codes::UNREADABLE_FILE, e));
+    // every parse error, then whatever is wrong with the games that
+    // could be read around them
+    let diagnostics = match data {
+        Ok(data) => {
+            let (coll, errors) = Parser::new(&data).parse_with_recovery();
+            let mut diagnostics: Vec<Diagnostic> = errors.iter().map(|e| Diagnostic::parse_error(e.code(), e.to_string())).collect();
+            diagnostics.extend(validate::validate_as(&coll, ff));
+            diagnostics
+        },
+        Err(d) => vec![d],
+    };
+    FileReport {
+        path: path.to_path_buf(),
+        diagnostics,
+    }
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip_all, fields(files = files.len(), jobs = jobs.threads)))]
+pub fn validate_files(files: &[PathBuf], jobs: Jobs, ff: Option<u8>) -> Vec<FileReport> {
+    jobs.map(files, |path| {
+        isolate(|| validate_checked(path, ff, &jobs)).unwrap_or_else(|e| FileReport {
+            path: path.to_path_buf(),
+            diagnostics: vec![Diagnostic::parse_error(codes::INTERNAL_ERROR, e)],
+        })
+    })
+}
+
+// where the thumbnail of a game goes: the file's place under root is
+// mirrored under out_dir, with the game number added for collections
+pub fn thumbnail_path(root: &Path, path: &Path, out_dir: &Path, game: Option<usize>, ext: &str) -> PathBuf {
+    let rel = match path.strip_prefix(root) {
+        Ok(rel) if !rel.as_os_str().is_empty() => rel.to_path_buf(),
+        _ => PathBuf::from(path.file_name().unwrap_or_default()),
+    };
+    let stem = rel.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let name = match game {
+        Some(i) => format!("{}-{}.{}", stem, i, ext),
+        None => format!("{}.{}", stem, ext),
+    };
+    out_dir.join(rel.with_file_name(name))
+}
+
+// renders the final position of every game in every file. returns the
+// number of thumbnails written per file
+#[cfg(feature = "board")]
+pub fn thumbnail_files<F>(root: &Path, files: &[PathBuf], out_dir: &Path, ext: &str, jobs: Jobs, render: F) -> Vec<Result<usize, String>>
+where
+    F: Fn(&Board) -> Result<Vec<u8>, String> + Sync,
+{
+    jobs.map_files(files, |path| {
+        let coll = parse_file(path)?;
+        let many = coll.gametrees.len() > 1;
+        for (i, gt) in coll.gametrees.iter().enumerate() {
+            let out = thumbnail_path(root, path, out_dir, if many { Some(i) } else { None }, ext);
+            let data = render(&gt.final_position())?;
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&out, data).map_err(|e| format!("{}: {}", out.display(), e))?;
+        }
+        Ok(coll.gametrees.len())
+    })
+}
+
+// runs the script over each file, rewriting the ones it changes in
+// place. only the values it changes are rewritten, and a file is
+// replaced whole or not at all. gives the changes made to each file
+pub fn edit_files(files: &[PathBuf], script: &EditScript, jobs: Jobs) -> Vec<Result<usize, String>> {
+    jobs.map_files(files, |path| {
+        let text = read_text(path)?;
+        let (patched, changes) = patch::patch(&text, |coll| script.apply(coll)).map_err(|e| e.to_string())?;
+        if patched != text {
+            transaction::replace_file(path, patched.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        Ok(changes)
+    })
+}
+
+// why edit_files_atomic changed nothing, or not everything back
+#[derive(Debug)]
+pub enum AtomicError {
+    // these files couldn't be read, edited or staged, and no file was
+    // changed
+    Files(Vec<(PathBuf, String)>),
+    // putting the files in place failed. after a Rollback error some of
+    // them kept their new content
+    Commit(transaction::Error),
+}
+
+// edit_files, all or nothing: the edited files are staged beside the
+// originals and only replace them once every file has been read, edited
+// and staged. gives the changes made to each file
+pub fn edit_files_atomic(files: &[PathBuf], script: &EditScript, jobs: Jobs) -> Result<Vec<usize>, AtomicError> {
+    let txn = Transaction::new();
+    let results = jobs.map_files(files, |path| {
+        let text = read_text(path)?;
+        let (patched, changes) = patch::patch(&text, |coll| script.apply(coll)).map_err(|e| e.to_string())?;
+        if patched != text {
+            txn.stage(path, patched.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        Ok(changes)
+    });
+    let failed: Vec<(PathBuf, String)> = files
+        .iter()
+        .zip(&results)
+        .filter_map(|(path, r)| r.as_ref().err().map(|e| (path.clone(), e.clone())))
+        .collect();
+    // dropping the transaction throws away what was staged
+    if !failed.is_empty() {
+        return Err(AtomicError::Files(failed));
+    }
+    txn.commit().map_err(AtomicError::Commit)?;
+    Ok(results.into_iter().map(|r| r.unwrap_or_default()).collect())
+}
+
+// converts each file to the encoding `to`, writing the ones that change
+// back in place when write is set
+#[cfg(feature = "encoding")]
+pub fn recode_files(
+    files: &[PathBuf],
+    from: Option<&'static encoding_rs::Encoding>,
+    to: &'static encoding_rs::Encoding,
+    write: bool,
+    jobs: Jobs,
+) -> Vec<Result<Recoded, String>> {
+    jobs.map_files(files, |path| {
+        let data = fs::read(path).map_err(|e| e.to_string())?;
+        let recoded = recode::recode(&data, from, to).map_err(|e| e.to_string())?;
+        if write && recoded.changed {
+            transaction::replace_file(path, &recoded.data).map_err(|e| e.to_string())?;
+        }
+        Ok(recoded)
+    })
+}
+
+// fixes what validation can fix by itself in each file, rewriting the
+// ones it changes. like edit_files, only the fixed values are rewritten
+// and files that aren't UTF-8 are left alone. gives what was done to
+// each file
+pub fn fix_files(files: &[PathBuf], jobs: Jobs) -> Vec<Result<Vec<Diagnostic>, String>> {
+    jobs.map_files(files, |path| {
+        let text = read_text(path)?;
+        let (patched, fixed) = patch::patch(&text, validate::fix).map_err(|e| e.to_string())?;
+        if patched != text {
+            transaction::replace_file(path, patched.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        Ok(fixed)
+    })
+}
+
+// what a whole run found, for spotting problems that affect many files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub files: usize,
+    pub clean: usize,
+    pub failed: usize,
+    pub counts: BTreeMap<Kind, usize>,
+}
+
+impl Summary {
+    pub fn new(reports: &[FileReport]) -> Self {
+        let mut summary = Summary {
+            files: reports.len(),
+            ..Default::default()
+        };
+        for report in reports {
+            if report.diagnostics.is_empty() {
+                summary.clean += 1;
+            }
+            if report.has_errors() {
+                summary.failed += 1;
+            }
+            for d in &report.diagnostics {
+                *summary.counts.entry(d.kind).or_insert(0) += 1;
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::Op;
+
+    // the same game as in utf-8, and another one with the same ascii,
+    // in gb2312
+    const GBK_GAMES: &[u8] = b"(;GM[1]CA[gb2312]PB[\xc0\xee\xb2\xfd\xb8\xe4]PW[\xb2\xdc\xde\xb9\xee\xe7];B[pd])\n(;GM[1]CA[gb2312]PB[\xb9\xc5\xc1\xa6]PW[\xb2\xdc\xde\xb9\xee\xe7];B[pd])";
+
+    fn tempdir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sgf-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn batch1() {
+        let dir = tempdir("batch1");
+        fs::write(dir.join("a.sgf"), "(;GM[1];B[pd];W[dd])").unwrap();
+        fs::write(dir.join("sub/b.SGF"), "(;GM[1];B[dp])(;GM[1];B[qd])").unwrap();
+        fs::write(dir.join("sub/c.sgf"), "(;GM[1]").unwrap();
+        fs::write(dir.join("notes.txt"), "(;GM[1])").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        assert_eq!(files.len(), 3);
+
+        let mut index = Index::new(4);
+        let mut seen = Vec::new();
+        let report = index_files(&mut index, &files, None, None, |p| seen.push(p.clone())).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen[2], Progress {total: 3, done: 3, skipped: 0, errors: 1});
+
+        // a second run only retries the file that failed
+        let mut out: Vec<u8> = Vec::new();
+        let report = index_files(&mut index, &files, None, Some(&mut out), |_| {}).unwrap();
+        assert_eq!(report.progress.skipped, 2);
+        assert_eq!(index.len(), 3);
+        assert!(out.is_empty());
+
+        // the good games of a file with errors go in, but the file is read
+        // again next time without adding them twice
+        fs::write(dir.join("sub/c.sgf"), "(;GM[1];B[cc])(;GM[1]").unwrap();
+        for _ in 0..2 {
+            index_files(&mut index, &files, None, Some(&mut out), |_| {}).unwrap();
+            assert_eq!(index.len(), 4);
+        }
+        assert_eq!(String::from_utf8_lossy(&out).lines().count(), 1);
+        assert!(!index.is_done(&files[2].to_string_lossy()));
+        fs::write(dir.join("sub/c.sgf"), "(;GM[1];B[cc])(;GM[1];B[dd])").unwrap();
+        index_files(&mut index, &files, None, Some(&mut out), |_| {}).unwrap();
+        assert_eq!(index.len(), 5);
+        let source = files[2].to_string_lossy();
+        assert_eq!(String::from_utf8_lossy(&out).lines().last(), Some(Index::done_line(&source).as_str()));
+        assert!(index.is_done(&source));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch4() {
+        let dir = tempdir("batch4");
+        fs::write(dir.join("a.sgf"), "(;GM[1];B[pd];W[dd])\n(;GM[1];B[pd];W[dd];B[pp];W[dp])").unwrap();
+        fs::write(dir.join("b.sgf"), "(;GM[1];B[dp])(;GM[1]").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        let report = concat_files(&files, Some(24), &GameFilter::default(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "(;GM[1];B[pd];W[dd])\n(;GM[1];B[dp])\n");
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.progress.errors, 2);
+        let filter = GameFilter {
+            min_moves: Some(2),
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        concat_files(&files, Some(24), &filter, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "(;GM[1];B[pd];W[dd])\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sample1() {
+        let dir = tempdir("sample1");
+        let games: String = (0..50).map(|i| format!("(;GN[{}])", i)).collect();
+        fs::write(dir.join("a.sgf"), &games).unwrap();
+        fs::write(dir.join("b.sgf"), "(;GN[x])").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let (sample, report) = sample_files(&files, 10, 42, None, &GameFilter::default());
+        assert_eq!(sample.len(), 10);
+        assert!(report.errors.is_empty());
+        let names: Vec<&str> = sample.iter().map(|gt| gt.root().value("GN").unwrap()).collect();
+        let (again, _) = sample_files(&files, 10, 42, None, &GameFilter::default());
+        assert_eq!(names, again.iter().map(|gt| gt.root().value("GN").unwrap()).collect::<Vec<_>>());
+        let (other, _) = sample_files(&files, 10, 7, None, &GameFilter::default());
+        assert_ne!(names, other.iter().map(|gt| gt.root().value("GN").unwrap()).collect::<Vec<_>>());
+        let (all, _) = sample_files(&files, 100, 42, None, &GameFilter::default());
+        assert_eq!(all.len(), 51);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interleave1() {
+        let dir = tempdir("interleave1");
+        fs::write(dir.join("a.sgf"), "(;GN[a1])(;GN[a2])(;GN[a3])").unwrap();
+        fs::write(dir.join("sub/b.sgf"), "(;GN[b1])").unwrap();
+        fs::write(dir.join("sub/c.sgf"), "(;GN[b2])(;").unwrap();
+        let sources = vec![vec![dir.join("a.sgf")], sgf_files(dir.join("sub")).unwrap()];
+        let names = |how| {
+            let mut out = Vec::new();
+            let report = interleave_files(&sources, how, None, &GameFilter::default(), &mut out).unwrap();
+            assert_eq!(report.progress.errors, 1);
+            assert_eq!(report.progress.done, 3);
+            let text = String::from_utf8(out).unwrap();
+            text.lines().map(|line| line[5..7].to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(Interleave::RoundRobin), vec!["a1", "b1", "a2", "b2", "a3"]);
+        let shuffled = names(Interleave::Shuffle(42));
+        assert_eq!(shuffled, names(Interleave::Shuffle(42)));
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec!["a1", "a2", "a3", "b1", "b2"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "board")]
+    fn batch5() {
+        let dir = tempdir("batch5");
+        fs::write(dir.join("a.sgf"), "(;GM[1];B[pd];W[dd])").unwrap();
+        fs::write(dir.join("sub/b.sgf"), "(;GM[1];B[dp])(;GM[1];B[qd])").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let out = dir.join("thumbs");
+        let results = thumbnail_files(&dir, &files, &out, "txt", Jobs::new(2), |b| Ok(b.to_string().into_bytes()));
+        assert_eq!(results, vec![Ok(1), Ok(2)]);
+        assert!(out.join("a.txt").exists());
+        assert!(out.join("sub/b-1.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch2() {
+        let items: Vec<usize> = (0..100).collect();
+        assert_eq!(par_map(&items, 8, |i| i * 2), items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert!(par_map(&[] as &[usize], 8, |i| *i).is_empty());
+    }
+
+    #[test]
+    fn batch3() {
+        let dir = tempdir("batch3");
+        fs::write(dir.join("a.sgf"), "(;GM[1];B[pd];W[dd])").unwrap();
+        fs::write(dir.join("b.sgf"), "(;GM[1];B[pd]W[dd];B[aa]C[1]C[2])").unwrap();
+        fs::write(dir.join("c.sgf"), "(;GM[1]").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let reports = validate_files(&files, Jobs::new(4), None);
+        assert_eq!(reports[0].path, files[0]);
+        let summary = Summary::new(&reports);
+        assert_eq!((summary.files, summary.clean, summary.failed), (3, 1, 2));
+        assert_eq!(summary.counts[&Kind::ParseError], 1);
+        assert_eq!(summary.counts[&Kind::MultipleMoves], 1);
+        assert_eq!(summary.counts[&Kind::DuplicateProperty], 1);
+        // a is 20 bytes, b 34: only a fits in 2000 bytes a worker
+        let reports = validate_files(&files, Jobs::new(2).with_worker_memory(2000), None);
+        assert!(reports[0].diagnostics.is_empty());
+        assert!(reports[1].diagnostics[0].message.contains("per worker"));
+        assert_eq!(reports[1].diagnostics[0].code, codes::UNREADABLE_FILE);
+        assert!(Jobs::new(0).threads >= 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch6() {
+        let dir = tempdir("batch6");
+        fs::write(dir.join("a.sgf"), "(;GM[1]KM[5.5]GN[x];B[pd])").unwrap();
+        fs::write(dir.join("sub/b.sgf"), "(;GM[1]KM[6.5])").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let script = EditScript::new().with(Op::set("KM=6.5").unwrap()).with(Op::delete("GN").unwrap());
+        let changes: Vec<usize> = edit_files(&files, &script, Jobs::new(2)).into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(changes, vec![2, 0]);
+        assert_eq!(fs::read_to_string(dir.join("a.sgf")).unwrap(), "(;GM[1]KM[6.5];B[pd])");
+        // the rest of the text is left as it was
+        fs::write(dir.join("a.sgf"), "(;GM[1]DT[2024-03-01]\nKM[5.5]\n;B[pd])").unwrap();
+        fs::write(dir.join("sub/b.sgf"), b"(;GM[1]PB[J\xf6rg])").unwrap();
+        let results = edit_files(&files, &script, Jobs::new(2));
+        assert_eq!(results[0], Ok(1));
+        assert!(results[1].as_ref().unwrap_err().contains("UTF-8"));
+        assert_eq!(fs::read_to_string(dir.join("a.sgf")).unwrap(), "(;GM[1]DT[2024-03-01]\nKM[6.5]\n;B[pd])");
+        assert_eq!(fs::read(dir.join("sub/b.sgf")).unwrap(), b"(;GM[1]PB[J\xf6rg])");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch9() {
+        let dir = tempdir("batch9");
+        fs::write(dir.join("a.sgf"), "(;GM[1]KM[5.5];B[pd])").unwrap();
+        fs::write(dir.join("sub/b.sgf"), "(;GM[1]KM[6.5])").unwrap();
+        fs::write(dir.join("sub/c.sgf"), "(;GM[1]KM[0]").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let script = EditScript::new().with(Op::set("KM=7.5").unwrap());
+        let Err(AtomicError::Files(failed)) = edit_files_atomic(&files, &script, Jobs::new(2)) else { panic!() };
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, files[2]);
+        assert_eq!(fs::read_to_string(dir.join("a.sgf")).unwrap(), "(;GM[1]KM[5.5];B[pd])");
+        assert_eq!(sgf_files(&dir).unwrap().len(), 3);
+        fs::remove_file(&files[2]).unwrap();
+        assert_eq!(edit_files_atomic(&files[..2], &script, Jobs::new(2)).unwrap(), vec![1, 1]);
+        assert_eq!(fs::read_to_string(dir.join("sub/b.sgf")).unwrap(), "(;GM[1]KM[7.5])");
+        assert_eq!(fs::read_dir(dir.join("sub")).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch10() {
+        let dir = tempdir("batch10");
+        fs::write(dir.join("a.sgf"), "(;GM[1]DT[2024-03-01]PB[Ann\nLee]\nC[x\u{1}y  z])").unwrap();
+        fs::write(dir.join("b.sgf"), b"(;GM[1]PB[J\xf6rg\nB])").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let results = fix_files(&files, Jobs::new(2));
+        assert_eq!(results[0].as_ref().unwrap().len(), 2);
+        assert!(results[1].is_err());
+        assert_eq!(fs::read_to_string(dir.join("a.sgf")).unwrap(), "(;GM[1]DT[2024-03-01]PB[Ann Lee]\nC[xy  z])");
+        assert_eq!(fs::read(dir.join("b.sgf")).unwrap(), b"(;GM[1]PB[J\xf6rg\nB])");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch7() {
+        let dir = tempdir("batch7");
+        fs::write(dir.join("a.sgf"), "(;GM[1]AP[CGoban:3]PB[x];B[pd];W[])(;GM[1];B[dd])").unwrap();
+        fs::write(dir.join("sub/b.sgf"), "(;PB[x]GM[1]AP[OGS];B[pd];W[tt])(;GM[1]").unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let (groups, report) = duplicate_files(&files, None);
+        assert_eq!(groups, vec![vec![(files[0].clone(), 0), (files[1].clone(), 0)]]);
+        assert_eq!(report.progress.errors, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch11() {
+        let dir = tempdir("batch11");
+        fs::write(dir.join("a.sgf"), "(;GM[1]CA[UTF-8]PB[李昌镐]PW[曹薰铉];B[pd])").unwrap();
+        fs::write(dir.join("b.sgf"), GBK_GAMES).unwrap();
+        let files = sgf_files(&dir).unwrap();
+        let (groups, report) = duplicate_files(&files, None);
+        #[cfg(feature = "encoding")]
+        {
+            assert_eq!(groups, vec![vec![(files[0].clone(), 0), (files[1].clone(), 0)]]);
+            assert_eq!(report.progress.errors, 0);
+        }
+        // the games that aren't utf-8 can't be compared at all
+        #[cfg(not(feature = "encoding"))]
+        {
+            assert!(groups.is_empty());
+            assert_eq!(report.errors.len(), 2);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch8() {
+        let files: Vec<PathBuf> = ["a.sgf", "b.sgf", "c.sgf"].iter().map(PathBuf::from).collect();
+        let results = Jobs::new(2).map_files(&files, |path| {
+            if path.ends_with("b.sgf") {
+                panic!("bad {}", path.display());
+            }
+            Ok(path.display().to_string())
+        });
+        assert_eq!(results, vec![Ok("a.sgf".to_string()), Err("panicked: bad b.sgf".to_string()), Ok("c.sgf".to_string())]);
+        assert_eq!(isolate(|| -> usize { panic!("static") }), Err("panicked: static".to_string()));
+    }
+}
@@ -0,0 +1,521 @@
+use std::fmt;
+
+use super::info::is_pass;
+use super::point::{parse_point_list, Point};
+use super::vertex::{Color, GameTree, Node};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Board {
+    pub cols: usize,
+    pub rows: usize,
+    stones: Vec<Option<Color>>,
+    // stones captured by black and by white
+    pub black_captures: usize,
+    pub white_captures: usize,
+}
+
+// the effective change made by the setup properties of one node: a point
+// that AB turns from white to black shows up in both lists, while AE on
+// an empty point or AB on a black stone shows up in neither
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SetupDelta {
+    pub added: Vec<(Point, Color)>,
+    pub removed: Vec<(Point, Color)>,
+}
+
+impl SetupDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+// everything a node changed on the board, so a renderer only has to
+// redraw these intersections
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Delta {
+    pub placed: Vec<(Point, Color)>,
+    // stones taken away by setup or overwritten by the move
+    pub removed: Vec<(Point, Color)>,
+    pub captured: Vec<(Point, Color)>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.placed.is_empty() && self.removed.is_empty() && self.captured.is_empty()
+    }
+
+    // every intersection that may look different now
+    pub fn changed(&self) -> Vec<Point> {
+        let mut points: Vec<Point> = Vec::new();
+        for (p, _) in self.placed.iter().chain(&self.removed).chain(&self.captured) {
+            if !points.contains(p) {
+                points.push(*p);
+            }
+        }
+        points
+    }
+}
+
+// a chain of connected stones of one color with the empty points
+// around it. both lists are in row order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub color: Color,
+    pub stones: Vec<Point>,
+    pub liberties: Vec<Point>,
+}
+
+impl Group {
+    pub fn in_atari(&self) -> bool {
+        self.liberties.len() == 1
+    }
+}
+
+// which repeated positions are illegal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KoRule {
+    // only immediately retaking a ko
+    Simple,
+    // no board position may repeat
+    PositionalSuperko,
+    // no position may repeat with the same player to move
+    SituationalSuperko,
+}
+
+impl KoRule {
+    // the ko rule of the RU property, simple ko when unknown
+    pub fn from_rules(ru: &str) -> KoRule {
+        let ru = ru.trim().to_lowercase();
+        if ru.starts_with("chinese") || ru.contains("tromp") || ru == "goe" || ru == "ing" {
+            KoRule::PositionalSuperko
+        } else if ru.starts_with("aga") || ru == "nz" || ru.starts_with("new zealand") {
+            KoRule::SituationalSuperko
+        } else {
+            KoRule::Simple
+        }
+    }
+}
+
+// splitmix64, used to give every (point, color) pair its own random bits
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl Board {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Board {
+            cols,
+            rows,
+            stones: vec![None; cols * rows],
+            black_captures: 0,
+            white_captures: 0,
+        }
+    }
+
+    pub fn for_game(gt: &GameTree) -> Self {
+        let (cols, rows) = gt.board_size();
+        Board::new(cols, rows)
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.on_board(self.cols, self.rows)
+    }
+
+    fn index(&self, p: Point) -> usize {
+        p.y as usize * self.cols + p.x as usize
+    }
+
+    pub fn get(&self, p: Point) -> Option<Color> {
+        if !self.contains(p) {
+            return None;
+        }
+        self.stones[self.index(p)]
+    }
+
+    pub fn set(&mut self, p: Point, stone: Option<Color>) {
+        if self.contains(p) {
+            let i = self.index(p);
+            self.stones[i] = stone;
+        }
+    }
+
+    // every stone on the board, row by row
+    pub fn stones(&self) -> Vec<(Point, Color)> {
+        let mut stones = Vec::new();
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                let p = Point::new(x as u8, y as u8);
+                if let Some(color) = self.get(p) {
+                    stones.push((p, color));
+                }
+            }
+        }
+        stones
+    }
+
+    // a zobrist hash of the stones, ignoring captures and whose turn it is
+    pub fn hash(&self) -> u64 {
+        let mut h = 0;
+        for (i, stone) in self.stones.iter().enumerate() {
+            match stone {
+                Some(Color::Black) => h ^= mix(2 * i as u64),
+                Some(Color::White) => h ^= mix(2 * i as u64 + 1),
+                None => {},
+            }
+        }
+        h
+    }
+
+    pub fn neighbors(&self, p: Point) -> Vec<Point> {
+        let mut n = Vec::new();
+        if p.x > 0 {
+            n.push(Point::new(p.x - 1, p.y));
+        }
+        if p.y > 0 {
+            n.push(Point::new(p.x, p.y - 1));
+        }
+        if (p.x as usize) + 1 < self.cols {
+            n.push(Point::new(p.x + 1, p.y));
+        }
+        if (p.y as usize) + 1 < self.rows {
+            n.push(Point::new(p.x, p.y + 1));
+        }
+        n
+    }
+
+    // the chain of stones connected to p and its liberties
+    fn chain(&self, p: Point) -> (Vec<Point>, Vec<Point>) {
+        let color = match self.get(p) {
+            Some(color) => color,
+            None => return (Vec::new(), Vec::new()),
+        };
+        let mut stones = vec![p];
+        let mut liberties = Vec::new();
+        let mut i = 0;
+        while i < stones.len() {
+            for n in self.neighbors(stones[i]) {
+                match self.get(n) {
+                    Some(c) if c == color => {
+                        if !stones.contains(&n) {
+                            stones.push(n);
+                        }
+                    },
+                    Some(_) => {},
+                    None => {
+                        if !liberties.contains(&n) {
+                            liberties.push(n);
+                        }
+                    },
+                }
+            }
+            i += 1;
+        }
+        (stones, liberties)
+    }
+
+    // the group the stone at p belongs to, None on an empty point
+    pub fn group_at(&self, p: Point) -> Option<Group> {
+        let color = self.get(p)?;
+        let (mut stones, mut liberties) = self.chain(p);
+        let row_order = |p: &Point| (p.y, p.x);
+        stones.sort_by_key(row_order);
+        liberties.sort_by_key(row_order);
+        Some(Group { color, stones, liberties })
+    }
+
+    // liberties of the group at p, 0 on an empty point
+    pub fn liberties(&self, p: Point) -> usize {
+        self.chain(p).1.len()
+    }
+
+    // every group on the board, ordered by their first stone
+    pub fn groups(&self) -> Vec<Group> {
+        let mut groups: Vec<Group> = Vec::new();
+        for (p, _) in self.stones() {
+            if groups.iter().any(|g| g.stones.contains(&p)) {
+                continue;
+            }
+            groups.extend(self.group_at(p));
+        }
+        groups
+    }
+
+    // the groups of color that are down to one liberty
+    pub fn groups_in_atari(&self, color: Color) -> Vec<Group> {
+        self.groups().into_iter().filter(|g| g.color == color && g.in_atari()).collect()
+    }
+
+    // applies AE, AB and AW. setup simply overwrites whatever is on the
+    // board, and within a node the properties are applied in order
+    pub fn setup(&mut self, node: &Node) -> SetupDelta {
+        let mut delta = SetupDelta::default();
+        let before = self.clone();
+        let mut touched: Vec<Point> = Vec::new();
+        for prop in &node.props {
+            let stone = match prop.ident.as_str() {
+                "AB" => Some(Color::Black),
+                "AW" => Some(Color::White),
+                "AE" => None,
+                _ => continue,
+            };
+            for p in parse_point_list(&prop.values) {
+                if self.contains(p) {
+                    self.set(p, stone);
+                    if !touched.contains(&p) {
+                        touched.push(p);
+                    }
+                }
+            }
+        }
+        for p in touched {
+            let (old, new) = (before.get(p), self.get(p));
+            if old == new {
+                continue;
+            }
+            if let Some(c) = old {
+                delta.removed.push((p, c));
+            }
+            if let Some(c) = new {
+                delta.added.push((p, c));
+            }
+        }
+        delta
+    }
+
+    // plays a stone and returns the stones it captured. like most SGF
+    // readers this never refuses a move: an occupied point is simply
+    // overwritten, and a suicide removes the player's own stones
+    pub fn play(&mut self, color: Color, p: Point) -> Vec<Point> {
+        if !self.contains(p) {
+            return Vec::new();
+        }
+        self.set(p, Some(color));
+        let mut captured = Vec::new();
+        for n in self.neighbors(p) {
+            if self.get(n) != Some(color.opponent()) || captured.contains(&n) {
+                continue;
+            }
+            let (stones, liberties) = self.chain(n);
+            if liberties.is_empty() {
+                captured.extend(stones);
+            }
+        }
+        if captured.is_empty() {
+            let (stones, liberties) = self.chain(p);
+            if liberties.is_empty() {
+                // suicide, the captures go to the opponent
+                for s in &stones {
+                    self.set(*s, None);
+                }
+                self.add_captures(color.opponent(), stones.len());
+                return stones;
+            }
+        }
+        for s in &captured {
+            self.set(*s, None);
+        }
+        self.add_captures(color, captured.len());
+        captured
+    }
+
+    // the point the opponent may not retake on right after color played
+    // at p and captured exactly the stones in captured
+    pub fn ko_point(&self, color: Color, p: Point, captured: &[Point]) -> Option<Point> {
+        if captured.len() != 1 || self.get(p) != Some(color) {
+            return None;
+        }
+        let (stones, liberties) = self.chain(p);
+        if stones.len() == 1 && liberties == captured {
+            Some(captured[0])
+        } else {
+            None
+        }
+    }
+
+    fn add_captures(&mut self, color: Color, n: usize) {
+        match color {
+            Color::Black => self.black_captures += n,
+            Color::White => self.white_captures += n,
+        }
+    }
+
+    // the point a node's move is played on, None for passes and non-moves
+    pub fn move_point(&self, node: &Node) -> Option<(Color, Point)> {
+        let (color, value) = node.get_move()?;
+        if is_pass(value, self.cols, self.rows) {
+            return None;
+        }
+        let p = Point::from_sgf(value)?;
+        if !self.contains(p) {
+            return None;
+        }
+        Some((color, p))
+    }
+
+    // applies a node's setup and then its move, if any
+    pub fn apply(&mut self, node: &Node) -> Delta {
+        let setup = self.setup(node);
+        let mut delta = Delta {
+            placed: setup.added,
+            removed: setup.removed,
+            captured: Vec::new(),
+        };
+        if let Some((color, p)) = self.move_point(node) {
+            if let Some(old) = self.get(p) {
+                delta.removed.push((p, old));
+            }
+            delta.placed.push((p, color));
+            let captured = self.play(color, p);
+            // a suicide captures the mover's own stones
+            let lost = if captured.contains(&p) { color } else { color.opponent() };
+            delta.captured = captured.into_iter().map(|q| (q, lost)).collect();
+        }
+        delta
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.rows {
+            let mut line = String::new();
+            for x in 0..self.cols {
+                line.push(match self.get(Point::new(x as u8, y as u8)) {
+                    Some(Color::Black) => 'X',
+                    Some(Color::White) => 'O',
+                    None => '.',
+                });
+            }
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl GameTree {
+    // the position at the end of the main line
+    pub fn final_position(&self) -> Board {
+        let mut board = Board::for_game(self);
+        for node in self.mainline() {
+            board.apply(node);
+        }
+        board
+    }
+
+    // the effective setup change of every main line node that has one,
+    // keyed by the node's index in the main line
+    pub fn setup_deltas(&self) -> Vec<(usize, SetupDelta)> {
+        let mut board = Board::for_game(self);
+        let mut deltas = Vec::new();
+        for (i, node) in self.mainline().into_iter().enumerate() {
+            let delta = board.setup(node);
+            if let Some((color, p)) = board.move_point(node) {
+                board.play(color, p);
+            }
+            if !delta.is_empty() {
+                deltas.push((i, delta));
+            }
+        }
+        deltas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    fn p(s: &str) -> Point {
+        Point::from_sgf(s).unwrap()
+    }
+
+    #[test]
+    fn board1() {
+        let gt = gametree("(;SZ[5]AB[aa][bb]AW[cc];AE[aa][dd]AW[bb];AB[cc:dd])");
+        let board = gt.final_position();
+        assert_eq!(board.get(p("aa")), None);
+        assert_eq!(board.get(p("bb")), Some(Color::White));
+        assert_eq!(board.get(p("cc")), Some(Color::Black));
+        assert_eq!(board.get(p("dd")), Some(Color::Black));
+        assert_eq!(board.get(p("cd")), Some(Color::Black));
+    }
+
+    #[test]
+    fn board2() {
+        let gt = gametree("(;SZ[5]AB[aa][bb]AW[cc];AE[aa][dd]AW[bb])");
+        let deltas = gt.setup_deltas();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[1].0, 1);
+        let delta = &deltas[1].1;
+        assert_eq!(delta.removed, vec![(p("aa"), Color::Black), (p("bb"), Color::Black)]);
+        assert_eq!(delta.added, vec![(p("bb"), Color::White)]);
+    }
+
+    #[test]
+    fn board3() {
+        // white at aa is captured, then black at ea is a suicide
+        let gt = gametree("(;SZ[5];W[aa];B[ba];W[da];B[ab];W[eb];B[cc];B[ea])");
+        let board = gt.final_position();
+        assert_eq!(board.get(p("aa")), None);
+        assert_eq!(board.get(p("ea")), None);
+        assert_eq!(board.black_captures, 1);
+        assert_eq!(board.white_captures, 1);
+        assert_eq!(board.to_string().lines().next(), Some(".X.O."));
+    }
+
+    #[test]
+    fn group1() {
+        let gt = gametree("(;SZ[5]AB[aa][ba][bb]AW[ca][cb][ab][bc])");
+        let board = gt.final_position();
+        let group = board.group_at(p("ba")).unwrap();
+        assert_eq!(group.color, Color::Black);
+        assert_eq!(group.stones, vec![p("aa"), p("ba"), p("bb")]);
+        assert!(group.liberties.is_empty());
+        assert_eq!(board.group_at(p("ee")), None);
+        assert_eq!(board.liberties(p("ab")), 1);
+        assert_eq!(board.liberties(p("dd")), 0);
+        assert_eq!(board.groups().len(), 4);
+        let atari = board.groups_in_atari(Color::White);
+        assert_eq!(atari.len(), 1);
+        assert_eq!(atari[0].stones, vec![p("ab")]);
+        let board = gametree("(;SZ[5]AB[aa][ba]AW[ca][ab])").final_position();
+        let atari = board.groups_in_atari(Color::Black);
+        assert_eq!(atari.len(), 1);
+        assert_eq!(atari[0].liberties, vec![p("bb")]);
+    }
+
+    #[test]
+    fn hash1() {
+        let mut a = Board::new(9, 9);
+        let b = Board::new(9, 9);
+        assert_eq!(a.hash(), b.hash());
+        a.play(Color::Black, p("cc"));
+        assert_ne!(a.hash(), b.hash());
+        a.set(p("cc"), None);
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(KoRule::from_rules("Chinese"), KoRule::PositionalSuperko);
+        assert_eq!(KoRule::from_rules("AGA"), KoRule::SituationalSuperko);
+        assert_eq!(KoRule::from_rules("Japanese"), KoRule::Simple);
+    }
+
+    #[test]
+    fn delta1() {
+        let gt = gametree("(;SZ[5]AB[ba]AW[ca];W[aa];B[ab];AE[ca]AW[dd];B[ca])");
+        let mut board = Board::for_game(&gt);
+        let deltas: Vec<Delta> = gt.mainline().into_iter().map(|n| board.apply(n)).collect();
+        assert_eq!(deltas[0].placed.len(), 2);
+        assert!(deltas[1].captured.is_empty());
+        assert_eq!(deltas[2].placed, vec![(p("ab"), Color::Black)]);
+        assert_eq!(deltas[2].captured, vec![(p("aa"), Color::White)]);
+        assert_eq!(deltas[3].removed, vec![(p("ca"), Color::White)]);
+        assert_eq!(deltas[3].placed, vec![(p("dd"), Color::White)]);
+        assert!(deltas[4].captured.is_empty());
+        assert_eq!(deltas[4].changed(), vec![p("ca")]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use super::vertex::GameTree;
+use super::writer::{escape_text, unescape_text};
+
+// a private property, with our prefix so it doesn't clash with the ones
+// other programs keep. readers that don't know it pass it through
+pub const BOOKMARK: &str = "RSBM";
+
+#[derive(Debug)]
+pub enum Error {
+    NoSuchNode(Vec<usize>),
+    BadPath(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoSuchNode(path) => write!(f, "no node at {}", format_path(path)),
+            Error::BadPath(s) => write!(f, "bad node path: {}", s),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub path: Vec<usize>,
+    pub label: String,
+}
+
+// a node path as 0.0.1, and the root as an empty string
+pub fn format_path(path: &[usize]) -> String {
+    path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(".")
+}
+
+pub fn parse_path(s: &str) -> Result<Vec<usize>> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    s.split('.').map(|i| i.trim().parse().map_err(|_| Error::BadPath(s.to_string()))).collect()
+}
+
+impl GameTree {
+    // marks the node at path, replacing the label it had
+    pub fn add_bookmark(&mut self, path: &[usize], label: &str) -> Result<()> {
+        let node = self.node_mut(path).ok_or_else(|| Error::NoSuchNode(path.to_vec()))?;
+        node.set(BOOKMARK, vec![escape_text(label, false)]);
+        Ok(())
+    }
+
+    // the label the node had, if it was marked
+    pub fn remove_bookmark(&mut self, path: &[usize]) -> Option<String> {
+        let prop = self.node_mut(path)?.remove(BOOKMARK)?;
+        prop.values.first().map(|v| unescape_text(v))
+    }
+
+    // every bookmark in the order of the file
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        self.nodes()
+            .into_iter()
+            .filter_map(|(path, node)| {
+                Some(Bookmark {
+                    label: unescape_text(node.value(BOOKMARK)?),
+                    path,
+                })
+            })
+            .collect()
+    }
+
+    // where to jump for a label: the bookmark with exactly that label, or
+    // else the first whose label starts with it, ignoring case
+    pub fn find_bookmark(&self, label: &str) -> Option<Vec<usize>> {
+        let marks = self.bookmarks();
+        let lower = label.to_lowercase();
+        let found = marks.iter().find(|b| b.label == label).or_else(|| marks.iter().find(|b| b.label.to_lowercase().starts_with(&lower)))?;
+        Some(found.path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn bookmark1() {
+        let mut gt = Parser::new("(;GM[1];B[aa](;W[bb];B[cc])(;W[dd]))").parse().unwrap().gametrees.remove(0);
+        gt.add_bookmark(&[0, 1], "Joseki ]choice").unwrap();
+        gt.add_bookmark(&[0, 0, 0], "ladder").unwrap();
+        gt.add_bookmark(&[0, 0, 0], "Ladder works").unwrap();
+        assert!(gt.add_bookmark(&[0, 2], "x").is_err());
+        assert_eq!(gt.to_string(), "(;GM[1];B[aa](;W[bb];B[cc]RSBM[Ladder works])(;W[dd]RSBM[Joseki \\]choice]))");
+        let labels: Vec<(String, String)> = gt.bookmarks().into_iter().map(|b| (format_path(&b.path), b.label)).collect();
+        assert_eq!(labels, vec![("0.0.0".to_string(), "Ladder works".to_string()), ("0.1".to_string(), "Joseki ]choice".to_string())]);
+        assert_eq!(gt.find_bookmark("joseki"), Some(vec![0, 1]));
+        assert_eq!(gt.find_bookmark("tesuji"), None);
+        assert_eq!(gt.remove_bookmark(&[0, 1]), Some("Joseki ]choice".to_string()));
+        assert_eq!(gt.bookmarks().len(), 1);
+        assert_eq!(parse_path("0.0.1").unwrap(), vec![0, 0, 1]);
+        assert!(parse_path("").unwrap().is_empty() && parse_path("0.x").is_err());
+    }
+}
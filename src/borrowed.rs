@@ -0,0 +1,697 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use super::codes;
+use super::parser::{Error, Result, UnknownProperties, Warning};
+use super::properties::describe;
+use super::version;
+use super::vertex;
+
+// the syntax tree with its idents and values borrowed from the text they
+// were parsed from, for read-only work over big files where a String per
+// value is most of the cost. values are kept escaped and exactly as
+// written, and only idents the parser has to rewrite are owned. with
+// Parser::normalized, into_owned gives the tree parser::Parser builds. game trees, nodes and properties keep the
+// byte range of the text they came from, for editors and for patching
+// files in place
+
+#[derive(Debug, Clone)]
+pub struct Collection<'a> {
+    pub gametrees: Vec<GameTree<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameTree<'a> {
+    pub sequence: Sequence<'a>,
+    pub gametrees: Vec<GameTree<'a>>,
+    // from the "(" through the ")"
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sequence<'a> {
+    pub nodes: Vec<Node<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node<'a> {
+    pub props: Vec<Property<'a>>,
+    // from the ";" through the last value's "]"
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Property<'a> {
+    pub ident: Cow<'a, str>,
+    pub values: Vec<Cow<'a, str>>,
+    // from the ident through the last value's "]"
+    pub span: Range<usize>,
+}
+
+impl Collection<'_> {
+    pub fn into_owned(self) -> vertex::Collection {
+        vertex::Collection {
+            gametrees: self.gametrees.into_iter().map(|gt| gt.into_owned()).collect(),
+        }
+    }
+}
+
+impl<'a> GameTree<'a> {
+    pub fn root(&self) -> &Node<'a> {
+        &self.sequence.nodes[0]
+    }
+
+    // the nodes of the first line of play, like vertex::GameTree::mainline
+    pub fn mainline(&self) -> Vec<&Node<'a>> {
+        let mut nodes = Vec::new();
+        let mut gt = self;
+        loop {
+            nodes.extend(&gt.sequence.nodes);
+            match gt.gametrees.first() {
+                Some(child) => gt = child,
+                None => break,
+            }
+        }
+        nodes
+    }
+
+    pub fn into_owned(self) -> vertex::GameTree {
+        vertex::GameTree {
+            sequence: vertex::Sequence {
+                nodes: self.sequence.nodes.into_iter().map(|n| n.into_owned()).collect(),
+            },
+            gametrees: self.gametrees.into_iter().map(|gt| Box::new(gt.into_owned())).collect(),
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    pub fn get(&self, ident: &str) -> Option<&Property<'a>> {
+        self.props.iter().find(|p| p.ident == ident)
+    }
+
+    pub fn value(&self, ident: &str) -> Option<&str> {
+        self.get(ident).and_then(|p| p.values.first()).map(|v| v.as_ref())
+    }
+
+    pub fn has(&self, ident: &str) -> bool {
+        self.get(ident).is_some()
+    }
+
+    pub fn into_owned(self) -> vertex::Node {
+        vertex::Node {
+            props: self.props.into_iter().map(|p| p.into_owned()).collect(),
+        }
+    }
+}
+
+impl Property<'_> {
+    pub fn into_owned(self) -> vertex::Property {
+        vertex::Property {
+            ident: self.ident.into_owned(),
+            values: self.values.into_iter().map(|v| v.into_owned()).collect(),
+        }
+    }
+}
+
+// how many gametrees deep variations may go. the parser keeps a stack
+// of its own, but into_owned, clone, drop and everything else that walks
+// the tree recurse once a level, and a stack overflow can't be caught
+// like a panic, so deeper trees are an error
+pub const MAX_NESTING: usize = 1000;
+
+// the parser. it reads straight from the text, and parser::Parser runs
+// it and takes the tree with into_owned, so the two can't disagree
+pub struct Parser<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) cur: usize,
+    pub(crate) version: u8,
+    // skip properties with bad identifiers instead of failing
+    lenient: bool,
+    unknown: UnknownProperties,
+    // values through normalize_value, as parser::Parser has them
+    normalize: bool,
+    pub(crate) warnings: Vec<Warning>,
+    // set by parse_with_recovery: errors go here and parsing goes on
+    // from the next node or gametree
+    recover: bool,
+    errors: Vec<Error>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Parser {
+            text,
+            cur: 0,
+            version: version::DEFAULT,
+            lenient: false,
+            unknown: UnknownProperties::Keep,
+            normalize: false,
+            warnings: Vec::new(),
+            recover: false,
+            errors: Vec::new(),
+        }
+    }
+
+    // like parser::Parser::lenient
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    pub fn unknown_properties(mut self, unknown: UnknownProperties) -> Self {
+        self.unknown = unknown;
+        self
+    }
+
+    // values the way parser::Parser gives them: runs of whitespace made
+    // one and leading zeros dropped, with numbers over a u64 an error.
+    // without it values are exactly as the text has them
+    pub fn normalized(mut self) -> Self {
+        self.normalize = true;
+        self
+    }
+
+    // what the last parse skipped, when lenient or dropping unknown
+    // properties
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    // Scanner takes a NUL for the end of the input, unless it is escaped
+    // or in a value
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.cur).copied().filter(|b| *b != 0)
+    }
+
+    // past the character at cur, which may be more than one byte
+    pub(crate) fn skip_char(&mut self) {
+        if let Some(c) = self.text[self.cur..].chars().next() {
+            self.cur += c.len_utf8();
+        }
+    }
+
+    pub(crate) fn consume_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.peek() {
+            self.cur += 1;
+        }
+    }
+
+    // like (3:14), the line from 1 and the column from 0
+    fn position(&self, at: usize) -> String {
+        let before = &self.text[..at];
+        let row = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().map_or(0, |line| line.chars().count());
+        format!("({}:{})", row, col)
+    }
+
+    fn error_at(&self, at: usize, code: &'static str, msg: &str) -> Error {
+        Error::ParseError {
+            code,
+            message: format!("parse_error at {}: {}", self.position(at), msg),
+        }
+    }
+
+    pub(crate) fn create_error(&self, code: &'static str, msg: &str) -> Error {
+        self.error_at(self.cur, code, msg)
+    }
+
+    // keeps the error and goes on when recovering, fails otherwise
+    fn recover_from(&mut self, e: Error) -> Result<()> {
+        if !self.recover {
+            return Err(e);
+        }
+        self.errors.push(e);
+        Ok(())
+    }
+
+    // garbage before the first game is skipped, as Parser does
+    pub(crate) fn skip_to_game(&mut self) {
+        while let Some(b) = self.peek() {
+            match b {
+                b'(' => break,
+                b'\\' => {
+                    self.cur += 1;
+                    self.skip_char();
+                },
+                _ => self.skip_char(),
+            }
+        }
+    }
+
+    // reads up to the next ';', '(' or ')' outside a value
+    fn skip_to_boundary(&mut self) {
+        while let Some(b) = self.peek() {
+            match b {
+                b';' | b'(' | b')' => break,
+                b'[' => {
+                    self.cur += 1;
+                    self.cur = self.value_end().map_or(self.text.len(), |end| end + 1);
+                },
+                b'\\' => {
+                    self.cur += 1;
+                    self.skip_char();
+                },
+                _ => self.skip_char(),
+            }
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Collection<'a>> {
+        self.warnings.clear();
+        self.skip_to_game();
+        let mut gametrees = Vec::new();
+        while let Some(b'(') = self.peek() {
+            self.version = version::DEFAULT;
+            gametrees.push(self.parse_gametree()?);
+            // games in a collection are usually a line apart
+            self.consume_whitespace();
+        }
+        if gametrees.is_empty() {
+            return Err(self.create_error(codes::NO_GAME, "cannot have empty collection"));
+        }
+        trace!(games = gametrees.len(), "parsed");
+        Ok(Collection { gametrees })
+    }
+
+    // parses as much as it can, like SGFC: after an error it skips to
+    // the next node or gametree and goes on, and gametrees still open at
+    // the end are closed. gives every error with what was read, which
+    // has no games when none could be. the errors of parse are the first
+    // of these, but where parse stops at text after a game, this looks
+    // for more games in it
+    pub fn parse_with_recovery(&mut self) -> (Collection<'a>, Vec<Error>) {
+        self.recover = true;
+        self.errors.clear();
+        self.warnings.clear();
+        let mut gametrees = Vec::new();
+        loop {
+            // garbage before, between and after games
+            self.skip_to_game();
+            if self.peek().is_none() {
+                break;
+            }
+            self.version = version::DEFAULT;
+            match self.parse_gametree() {
+                Ok(gt) if gt.sequence.nodes.is_empty() => gametrees.extend(gt.gametrees),
+                Ok(gt) => gametrees.push(gt),
+                Err(e) => self.errors.push(e),
+            }
+        }
+        if gametrees.is_empty() && self.errors.is_empty() {
+            let e = self.create_error(codes::NO_GAME, "cannot have empty collection");
+            self.errors.push(e);
+        }
+        self.recover = false;
+        trace!(games = gametrees.len(), errors = self.errors.len(), "parsed with recovery");
+        (Collection { gametrees }, std::mem::take(&mut self.errors))
+    }
+
+    // a gametree and the variations in it, with a stack of the ones open
+    // instead of recursion, so the depth of the text can't overflow ours
+    fn parse_gametree(&mut self) -> Result<GameTree<'a>> {
+        let mut open = vec![self.open_gametree()?];
+        loop {
+            match self.peek() {
+                Some(b'(') if open.len() == MAX_NESTING => {
+                    let e = self.create_error(codes::TOO_DEEP, &format!("variations nested deeper than {}", MAX_NESTING));
+                    self.recover_from(e)?;
+                    // lost like a variation without nodes
+                    self.skip_gametree();
+                    self.consume_whitespace();
+                },
+                Some(b'(') => open.push(self.open_gametree()?),
+                Some(b')') => {
+                    self.cur += 1;
+                    if let Some(gt) = Parser::close_gametree(&mut open, self.cur) {
+                        return Ok(gt);
+                    }
+                    self.consume_whitespace();
+                },
+                None if self.recover => {
+                    let e = self.create_error(codes::UNEXPECTED_CHARACTER, "eof while waiting for ')'");
+                    self.errors.push(e);
+                    if let Some(gt) = Parser::close_gametree(&mut open, self.cur) {
+                        return Ok(gt);
+                    }
+                },
+                // nodes after the variations have nowhere to go
+                Some(b';') if self.recover => {
+                    let e = self.create_error(codes::UNEXPECTED_CHARACTER, "unexpected node after variations in parse_gametree");
+                    self.errors.push(e);
+                    self.parse_sequence()?;
+                },
+                Some(_) => {
+                    let e = self.create_error(codes::UNEXPECTED_CHARACTER, "unexpected character in parse_gametree");
+                    self.recover_from(e)?;
+                    self.skip_to_boundary();
+                },
+                None => return Err(self.create_error(codes::UNEXPECTED_CHARACTER, "unexpected EOF in parse_gametree")),
+            }
+        }
+    }
+
+    // the "(" at cur and the nodes after it. the gametree's span ends
+    // when close_gametree closes it
+    fn open_gametree(&mut self) -> Result<GameTree<'a>> {
+        let start = self.cur;
+        // past the "("
+        self.cur += 1;
+        self.consume_whitespace();
+        let sequence = self.parse_sequence()?;
+        self.consume_whitespace();
+        Ok(GameTree {
+            sequence,
+            gametrees: Vec::new(),
+            span: start..start,
+        })
+    }
+
+    // ends the innermost open gametree at end and hands it to the one
+    // around it. gives the gametree back when it was the outermost
+    fn close_gametree(open: &mut Vec<GameTree<'a>>, end: usize) -> Option<GameTree<'a>> {
+        let mut gt = open.pop()?;
+        gt.span.end = end;
+        let Some(parent) = open.last_mut() else { return Some(gt) };
+        // a variation whose nodes were all lost gives up its own
+        if gt.sequence.nodes.is_empty() {
+            parent.gametrees.extend(gt.gametrees);
+        } else {
+            parent.gametrees.push(gt);
+        }
+        None
+    }
+
+    // reads past the gametree at cur and everything in it, to the end of
+    // the text if it isn't closed
+    fn skip_gametree(&mut self) {
+        let mut open = 0;
+        while let Some(b) = self.peek() {
+            match b {
+                b'(' => open += 1,
+                b')' => {
+                    open -= 1;
+                    if open == 0 {
+                        self.cur += 1;
+                        return;
+                    }
+                },
+                b'[' => {
+                    self.cur += 1;
+                    self.cur = self.value_end().unwrap_or(self.text.len());
+                },
+                b'\\' => self.cur += 1,
+                _ => {},
+            }
+            self.skip_char();
+        }
+    }
+
+    fn parse_sequence(&mut self) -> Result<Sequence<'a>> {
+        let mut nodes = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b';') => nodes.push(self.parse_node()?),
+                Some(b'(' | b')') | None => break,
+                Some(_) if self.recover => {
+                    let e = self.create_error(codes::UNEXPECTED_CHARACTER, "unexpected character in parse_sequence");
+                    self.errors.push(e);
+                    self.skip_to_boundary();
+                },
+                Some(_) => break,
+            }
+            self.consume_whitespace();
+        }
+        if nodes.is_empty() {
+            // the caller drops the gametree, keeping its variations
+            let e = self.create_error(codes::EMPTY_GAMETREE, "cannot have empty node list");
+            self.recover_from(e)?;
+        }
+        Ok(Sequence { nodes })
+    }
+
+    fn parse_node(&mut self) -> Result<Node<'a>> {
+        let start = self.cur;
+        // past the ";"
+        self.cur += 1;
+        let mut end = self.cur;
+        self.consume_whitespace();
+        let mut props = Vec::new();
+        loop {
+            let at = self.cur;
+            let prop = match self.parse_propident() {
+                Ok(Some(ident)) => self.parse_property(at, ident),
+                Ok(None) => break,
+                Err(e) => Err(e),
+            };
+            match prop {
+                Ok(prop) if self.unknown != UnknownProperties::Keep && describe(&prop.ident).is_none() => {
+                    if self.unknown == UnknownProperties::Reject {
+                        let e = self.error_at(at, codes::UNKNOWN_PROPERTY, &format!("unknown property {}", prop.ident));
+                        self.recover_from(e)?;
+                    } else {
+                        self.warnings.push(Warning {
+                            code: codes::UNKNOWN_PROPERTY,
+                            message: format!("dropped unknown property {} at {}", prop.ident, self.position(at)),
+                        });
+                    }
+                },
+                Ok(prop) => props.push(prop),
+                Err(e) if self.lenient && e.code() == codes::BAD_IDENTIFIER => self.skip_property()?,
+                // the rest of the node goes
+                Err(e) => {
+                    self.recover_from(e)?;
+                    self.skip_to_boundary();
+                    break;
+                },
+            }
+            // parse_property has read the whitespace after the last value
+            end = start + self.text[start..self.cur].trim_end_matches([' ', '\t', '\r', '\n']).len();
+            self.consume_whitespace();
+        }
+        Ok(Node { props, span: start..end })
+    }
+
+    // the ident at cur, or None when there is no property there. before
+    // FF[4] lowercase letters are allowed and dropped
+    pub(crate) fn parse_propident(&mut self) -> Result<Option<Cow<'a, str>>> {
+        let start = self.cur;
+        if !self.peek().is_some_and(|b| b.is_ascii_alphabetic() || b == b'_') {
+            return Ok(None);
+        }
+        let mut end = start;
+        while self.text.as_bytes().get(end).is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+            end += 1;
+        }
+        let ident = &self.text[start..end];
+        if ident.bytes().all(|b| b.is_ascii_uppercase()) {
+            self.cur = end;
+            return Ok(Some(Cow::Borrowed(ident)));
+        }
+        let upper: String = ident.chars().filter(|c| c.is_ascii_uppercase()).collect();
+        if self.version >= 4 || upper.is_empty() {
+            let msg = format!("property identifiers must be uppercase letters, found '{}'", ident);
+            return Err(self.create_error(codes::BAD_IDENTIFIER, &msg));
+        }
+        self.cur = end;
+        Ok(Some(Cow::Owned(upper)))
+    }
+
+    // the values after an ident that parse_propident read from start
+    pub(crate) fn parse_property(&mut self, start: usize, ident: Cow<'a, str>) -> Result<Property<'a>> {
+        self.consume_whitespace();
+        let mut values = Vec::new();
+        let mut end = self.cur;
+        while let Some(b'[') = self.peek() {
+            values.push(self.parse_propvalue()?);
+            end = self.cur;
+            self.consume_whitespace();
+        }
+        if values.is_empty() {
+            return Err(self.create_error(codes::PROPERTY_WITHOUT_VALUE, "cannot have empty property list"));
+        }
+        if ident == "FF" {
+            if let Some(ff) = version::parse(&values[0]) {
+                self.version = ff;
+            }
+        }
+        Ok(Property {
+            ident,
+            values,
+            span: start..end,
+        })
+    }
+
+    // reads past a property with a bad identifier and its values
+    fn skip_property(&mut self) -> Result<()> {
+        let start = self.cur;
+        while self.peek().is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            self.cur += 1;
+        }
+        let message = format!(
+            "skipped '{}' at {}: property identifiers must be uppercase letters",
+            &self.text[start..self.cur],
+            self.position(start)
+        );
+        self.warnings.push(Warning { code: codes::BAD_IDENTIFIER, message });
+        self.consume_whitespace();
+        while let Some(b'[') = self.peek() {
+            self.parse_propvalue()?;
+            self.consume_whitespace();
+        }
+        Ok(())
+    }
+
+    // where the value that starts at cur ends: its "]", unless escaped
+    fn value_end(&self) -> Option<usize> {
+        let bytes = self.text.as_bytes();
+        let mut i = self.cur;
+        // most of a file is value text, so it is skipped a run at a time
+        // up to the next byte that matters
+        loop {
+            i += bytes.get(i..)?.iter().position(|b| matches!(b, b']' | b'\\'))?;
+            if bytes[i] == b']' {
+                return Some(i);
+            }
+            i += 2;
+        }
+    }
+
+    fn parse_propvalue(&mut self) -> Result<Cow<'a, str>> {
+        // past the "["
+        self.cur += 1;
+        let start = self.cur;
+        let Some(end) = self.value_end() else {
+            self.cur = self.text.len();
+            return Err(self.create_error(codes::UNTERMINATED_VALUE, "unexpected EOF while waiting for ']'"));
+        };
+        let raw = &self.text[start..end];
+        self.cur = end + 1;
+        if !self.normalize {
+            return Ok(Cow::Borrowed(raw));
+        }
+        normalize_value(raw).map_err(|msg| self.create_error(codes::NUMBER_TOO_LARGE, &msg))
+    }
+}
+
+// the value as Scanner's tokens spell it: runs of spaces, tabs and
+// carriage returns become one space, runs of newlines one newline and
+// numbers lose their leading zeros. the text is borrowed unless that
+// changes something
+pub(crate) fn normalize_value(raw: &str) -> std::result::Result<Cow<'_, str>, String> {
+    let bytes = raw.as_bytes();
+    let mut owned: Option<String> = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let b = bytes[i];
+        let token: Cow<str> = match b {
+            b' ' | b'\t' | b'\r' => {
+                while i < bytes.len() && matches!(bytes[i], b' ' | b'\t' | b'\r') {
+                    i += 1;
+                }
+                Cow::Borrowed(" ")
+            },
+            b'\n' => {
+                while i < bytes.len() && bytes[i] == b'\n' {
+                    i += 1;
+                }
+                Cow::Borrowed("\n")
+            },
+            b'0'..=b'9' => {
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let n: u64 = raw[start..i].parse().map_err(|e: std::num::ParseIntError| e.to_string())?;
+                Cow::Owned(n.to_string())
+            },
+            _ if b.is_ascii_alphabetic() || b == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                Cow::Borrowed(&raw[start..i])
+            },
+            _ => {
+                if b == b'\\' {
+                    i += 1;
+                }
+                i += raw[i..].chars().next().map_or(0, |c| c.len_utf8());
+                Cow::Borrowed(&raw[start..i])
+            },
+        };
+        match &mut owned {
+            Some(s) => s.push_str(&token),
+            None if token != raw[start..i] => {
+                let mut s = raw[..start].to_string();
+                s.push_str(&token);
+                owned = Some(s);
+            },
+            None => {},
+        }
+    }
+    Ok(match owned {
+        Some(s) => Cow::Owned(s),
+        None => Cow::Borrowed(raw),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrowed1() {
+        let text = "(;FF[4]GM[1]C[a \\] b\nc](;B[aa]C[007  x\n\n]) (;W[bb]N[\u{0}]))";
+        let coll = Parser::new(text).normalized().parse().unwrap();
+        let gt = &coll.gametrees[0];
+        assert!(matches!(gt.root().get("C").unwrap().values[0], Cow::Borrowed("a \\] b\nc")));
+        let node = &gt.gametrees[0].sequence.nodes[0];
+        assert!(matches!(node.get("C").unwrap().values[0], Cow::Owned(_)));
+        assert_eq!(node.value("C"), Some("7 x\n"));
+        assert_eq!(gt.mainline().len(), 2);
+        let owned = crate::parser::Parser::new(text).parse().unwrap();
+        assert_eq!(coll.into_owned().to_string(), owned.to_string());
+    }
+
+    #[test]
+    fn borrowed2() {
+        let coll = Parser::new("junk (;FF[3]AddBlack[aa]\n)\n(;GM[1])").parse().unwrap();
+        assert_eq!(coll.gametrees.len(), 2);
+        assert!(matches!(coll.gametrees[0].root().props[1].ident, Cow::Owned(_)));
+        assert_eq!(coll.into_owned().to_string(), "(;FF[3]AB[aa])(;GM[1])");
+        assert!(Parser::new("(;FF[4]AddBlack[aa])").parse().is_err());
+        assert!(Parser::new("(;C[x").parse().is_err());
+        assert!(Parser::new("(;C)").parse().is_err());
+        assert!(Parser::new("").parse().is_err());
+    }
+
+    #[test]
+    fn borrowed3() {
+        let text = "(;DT[2024-03-01]C[a  b] KM [6.5]\n;B[aa]C[99999999999999999999999])";
+        let coll = Parser::new(text).parse().unwrap();
+        let root = coll.gametrees[0].root();
+        assert_eq!(root.value("DT"), Some("2024-03-01"));
+        assert_eq!(root.value("C"), Some("a  b"));
+        assert_eq!(&text[root.get("KM").unwrap().span.clone()], "KM [6.5]");
+        assert_eq!(&text[root.span.clone()], ";DT[2024-03-01]C[a  b] KM [6.5]");
+        assert!(matches!(root.get("C").unwrap().values[0], Cow::Borrowed(_)));
+        assert!(Parser::new(text).normalized().parse().is_err());
+        let mut parser = Parser::new("(;FF[4]weird[x]B[aa])").lenient();
+        assert_eq!(parser.parse().unwrap().into_owned().to_string(), "(;FF[4]B[aa])");
+        assert_eq!(parser.warnings().len(), 1);
+    }
+
+    #[test]
+    fn borrowed4() {
+        let nested = |n: usize| format!("{}{}", "(;B[aa]C[(]".repeat(n), ")".repeat(n));
+        let coll = Parser::new(&nested(MAX_NESTING)).parse().unwrap().into_owned();
+        assert_eq!(coll.clone().to_string(), nested(MAX_NESTING));
+        let deep = nested(20_000) + "(;GM[1])";
+        assert_eq!(Parser::new(&deep).parse().unwrap_err().code(), codes::TOO_DEEP);
+        // the levels that fit are kept, and the game after is still read
+        let (coll, errors) = Parser::new(&deep).parse_with_recovery();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(coll.gametrees.len(), 2);
+        assert_eq!(coll.gametrees[0].mainline().len(), MAX_NESTING);
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+
+use super::point::Point;
+use super::vertex::{Color, GameTree, Node, Sequence};
+use super::writer::escape_text;
+
+#[derive(Debug)]
+pub enum Error {
+    // a line of GTP input we could not read, numbered from 1
+    GtpError(usize, String),
+    // a handicap the board has no fixed placement for
+    BadHandicap(usize, usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::GtpError(line, s) => write!(f, "line {}: {}", line, s),
+            Error::BadHandicap(n, size) => write!(f, "no fixed placement for {} handicap stones on {}x{}", n, size, size),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// reads moves in GTP notation, one per line: "B Q16", "white pass" or
+// a full "play b q16" command. blank lines, "#" comments and the "="
+// lines of engine responses are skipped
+pub fn parse_gtp_moves(text: &str, cols: usize, rows: usize) -> Result<Vec<(Color, Option<Point>)>> {
+    let mut moves = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() || line.starts_with('=') {
+            continue;
+        }
+        let err = |msg: &str| Error::GtpError(i + 1, format!("{}: {}", msg, line));
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words[0].eq_ignore_ascii_case("play") {
+            words.remove(0);
+        }
+        if words.len() != 2 {
+            return Err(err("expected a color and a vertex"));
+        }
+        let color = match words[0].to_lowercase().as_str() {
+            "b" | "black" => Color::Black,
+            "w" | "white" => Color::White,
+            _ => return Err(err("bad color")),
+        };
+        if words[1].eq_ignore_ascii_case("pass") {
+            moves.push((color, None));
+            continue;
+        }
+        match Point::from_gtp(words[1], rows) {
+            Some(p) if p.on_board(cols, rows) => moves.push((color, Some(p))),
+            _ => return Err(err("bad vertex")),
+        }
+    }
+    Ok(moves)
+}
+
+// the usual placement of n handicap stones on the star points, as GTP's
+// fixed_handicap gives it: 2 to 9 stones on odd boards from 7x7 up, at
+// most 4 on even ones
+pub fn handicap_points(n: usize, size: usize) -> Option<Vec<Point>> {
+    let max = match size {
+        7 => 4,
+        8..=25 if size.is_multiple_of(2) => 4,
+        9..=25 => 9,
+        _ => 0,
+    };
+    if !(2..=max).contains(&n) {
+        return None;
+    }
+    let edge = if size < 13 { 2 } else { 3 };
+    let (low, mid, high) = (edge as u8, (size / 2) as u8, (size - 1 - edge) as u8);
+    // lower left and upper right first, then lower right, upper left,
+    // the sides and the center as more stones are given
+    let mut points = vec![Point::new(low, high), Point::new(high, low)];
+    if n >= 3 {
+        points.push(Point::new(high, high));
+    }
+    if n >= 4 {
+        points.push(Point::new(low, low));
+    }
+    if n >= 6 {
+        points.push(Point::new(low, mid));
+        points.push(Point::new(high, mid));
+    }
+    if n >= 8 {
+        points.push(Point::new(mid, low));
+        points.push(Point::new(mid, high));
+    }
+    if n >= 5 && !n.is_multiple_of(2) {
+        points.push(Point::new(mid, mid));
+    }
+    Some(points)
+}
+
+// puts a game together a property and a move at a time, for recorders
+// that produce the SGF as play goes on
+#[derive(Debug, Clone)]
+pub struct GameBuilder {
+    root: Node,
+    moves: Vec<Node>,
+}
+
+impl Default for GameBuilder {
+    fn default() -> Self {
+        GameBuilder::new(19)
+    }
+}
+
+impl GameBuilder {
+    pub fn new(size: usize) -> Self {
+        let mut root = Node { props: Vec::new() };
+        root.set("GM", vec!["1".to_string()]);
+        root.set("FF", vec!["4".to_string()]);
+        root.set("SZ", vec![size.to_string()]);
+        GameBuilder {
+            root,
+            moves: Vec::new(),
+        }
+    }
+
+    pub fn set(mut self, ident: &str, value: &str) -> Self {
+        self.root.set(ident, vec![value.to_string()]);
+        self
+    }
+
+    pub fn komi(self, komi: f64) -> Self {
+        self.set("KM", &komi.to_string())
+    }
+
+    pub fn players(self, black: &str, white: &str) -> Self {
+        self.set("PB", &escape_text(black, false)).set("PW", &escape_text(white, false))
+    }
+
+    // places the stones in the root with HA, white to play first. 0 or 1
+    // stone is an even game and leaves the root alone
+    pub fn handicap(mut self, n: usize) -> Result<Self> {
+        if n <= 1 {
+            return Ok(self);
+        }
+        let (cols, rows) = self.board_size();
+        let points = match cols == rows {
+            true => handicap_points(n, cols),
+            false => None,
+        };
+        let points = points.ok_or(Error::BadHandicap(n, cols))?;
+        self.root.set("HA", vec![n.to_string()]);
+        self.root.set("AB", points.iter().map(|p| p.to_sgf()).collect());
+        self.root.set("PL", vec!["W".to_string()]);
+        Ok(self)
+    }
+
+    pub fn play(mut self, color: Color, p: Option<Point>) -> Self {
+        self.moves.push(Node::from_move(color, p));
+        self
+    }
+
+    pub fn moves_from_gtp(mut self, text: &str) -> Result<Self> {
+        let (cols, rows) = self.board_size();
+        for (color, p) in parse_gtp_moves(text, cols, rows)? {
+            self.moves.push(Node::from_move(color, p));
+        }
+        Ok(self)
+    }
+
+    fn board_size(&self) -> (usize, usize) {
+        let gt = GameTree {
+            sequence: Sequence { nodes: vec![self.root.clone()] },
+            gametrees: Vec::new(),
+        };
+        gt.board_size()
+    }
+
+    pub fn build(self) -> GameTree {
+        let mut nodes = vec![self.root];
+        nodes.extend(self.moves);
+        GameTree {
+            sequence: Sequence { nodes },
+            gametrees: Vec::new(),
+        }
+    }
+}
+
+impl GameTree {
+    // adds moves in GTP notation to the end of the main line and returns
+    // how many were added
+    pub fn append_gtp_moves(&mut self, text: &str) -> Result<usize> {
+        let (cols, rows) = self.board_size();
+        let moves = parse_gtp_moves(text, cols, rows)?;
+        let mut gt = self;
+        while !gt.gametrees.is_empty() {
+            gt = &mut gt.gametrees[0];
+        }
+        for (color, p) in &moves {
+            gt.sequence.nodes.push(Node::from_move(*color, *p));
+        }
+        Ok(moves.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn gtp1() {
+        let moves = parse_gtp_moves("B Q16\nW D4\n\n# comment\nplay b pass\n= \n", 19, 19).unwrap();
+        assert_eq!(moves, vec![(Color::Black, Some(Point::new(15, 3))), (Color::White, Some(Point::new(3, 15))), (Color::Black, None)]);
+        assert!(matches!(parse_gtp_moves("B Q16\nX D4", 19, 19), Err(Error::GtpError(2, _))));
+        assert!(parse_gtp_moves("B Z1", 19, 19).is_err());
+        assert!(parse_gtp_moves("B K10", 9, 9).is_err());
+    }
+
+    #[test]
+    fn gtp2() {
+        let mut gt = gametree("(;SZ[9];B[ee](;W[cc])(;W[gg]))");
+        assert_eq!(gt.append_gtp_moves("B C8\nW pass").unwrap(), 2);
+        assert_eq!(gt.to_string(), "(;SZ[9];B[ee](;W[cc];B[cb];W[])(;W[gg]))");
+    }
+
+    #[test]
+    fn builder1() {
+        let gt = GameBuilder::new(9).set("PB", "a").moves_from_gtp("B E5\nW C3").unwrap().build();
+        assert_eq!(gt.to_string(), "(;GM[1]FF[4]SZ[9]PB[a];B[ee];W[cg])");
+    }
+
+    #[test]
+    fn builder2() {
+        let gt = GameBuilder::new(19).komi(0.5).players("Me", "You]").handicap(3).unwrap().build();
+        assert_eq!(gt.to_string(), "(;GM[1]FF[4]SZ[19]KM[0.5]PB[Me]PW[You\\]]HA[3]AB[dp][pd][pp]PL[W])");
+        let nine = handicap_points(9, 19).unwrap();
+        assert_eq!(nine.len(), 9);
+        assert!(nine.contains(&Point::new(9, 9)) && nine.contains(&Point::new(3, 9)));
+        assert_eq!(handicap_points(5, 9).unwrap()[4], Point::new(4, 4));
+        assert!(handicap_points(5, 10).is_none() && handicap_points(2, 5).is_none());
+        assert!(matches!(GameBuilder::new(13).handicap(10), Err(Error::BadHandicap(10, 13))));
+        assert_eq!(GameBuilder::new(9).handicap(1).unwrap().build().to_string(), "(;GM[1]FF[4]SZ[9])");
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+
+use super::vertex::{GameTree, Node, Sequence};
+
+// the longest name a chat line may have, so prose with a time in it
+// isn't taken for chat
+const MAX_SPEAKER: usize = 32;
+
+// a chat line as servers write them into comments, one per line:
+// "12:34 alice: hi", "[12:34:56] bob [3k]: gl". a line counts only
+// with the time in front, so review comments like "Note: ..." stay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatEvent {
+    // as written, without brackets, with minutes and seconds in two
+    // digits
+    pub time: String,
+    pub speaker: String,
+    pub rank: Option<String>,
+    pub text: String,
+}
+
+impl ChatEvent {
+    pub fn parse(line: &str) -> Option<ChatEvent> {
+        let line = line.trim();
+        let (time, rest) = match line.strip_prefix('[') {
+            Some(rest) => {
+                let close = rest.find(']')?;
+                (&rest[..close], &rest[close + 1..])
+            },
+            None => line.split_once(' ')?,
+        };
+        if !is_time(time) {
+            return None;
+        }
+        let (who, text) = rest.split_once(": ").or_else(|| rest.strip_suffix(':').map(|who| (who, "")))?;
+        let who = who.trim();
+        let (speaker, rank) = match who.strip_suffix(']').and_then(|w| w.rsplit_once('[')) {
+            Some((speaker, rank)) => (speaker.trim_end(), Some(rank.to_string())),
+            None => (who, None),
+        };
+        if speaker.is_empty() || speaker.chars().count() > MAX_SPEAKER || speaker.contains(char::is_whitespace) {
+            return None;
+        }
+        Some(ChatEvent {
+            time: pad_time(time),
+            speaker: speaker.to_string(),
+            rank,
+            text: text.trim().to_string(),
+        })
+    }
+}
+
+// written back the same way for every server
+impl fmt::Display for ChatEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.time, self.speaker)?;
+        if let Some(rank) = &self.rank {
+            write!(f, " [{}]", rank)?;
+        }
+        write!(f, ":")?;
+        if !self.text.is_empty() {
+            write!(f, " {}", self.text)?;
+        }
+        Ok(())
+    }
+}
+
+// 12:34 or 12:34:56
+fn is_time(s: &str) -> bool {
+    let parts: Vec<&str> = s.split(':').collect();
+    (2..=3).contains(&parts.len()) && parts.iter().all(|p| (1..=2).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+// the scanner drops leading zeros, so 12:05 may come as 12:5
+fn pad_time(time: &str) -> String {
+    let mut parts = time.split(':');
+    let hours = parts.next().unwrap_or_default().to_string();
+    parts.fold(hours, |s, part| format!("{}:{:0>2}", s, part))
+}
+
+// the chat lines of a comment and what is left of it without them
+pub fn split_chat(comment: &str) -> (Vec<ChatEvent>, String) {
+    let mut events = Vec::new();
+    let mut rest: Vec<&str> = Vec::new();
+    for line in comment.lines() {
+        match ChatEvent::parse(line) {
+            Some(event) => events.push(event),
+            // don't let removed lines leave runs of blank ones behind
+            None if line.trim().is_empty() && rest.last().is_none_or(|l| l.trim().is_empty()) => {},
+            None => rest.push(line),
+        }
+    }
+    (events, rest.join("\n").trim().to_string())
+}
+
+impl Node {
+    pub fn chat(&self) -> Vec<ChatEvent> {
+        self.text("C").map(|c| split_chat(&c).0).unwrap_or_default()
+    }
+
+    // the comment without its chat lines, dropping C when nothing else
+    // was in it
+    pub fn strip_chat(&self) -> Self {
+        self.with_chat(&[])
+    }
+
+    // the chat lines of the comment replaced by events, after the rest
+    // of the comment with a blank line between
+    pub fn with_chat(&self, events: &[ChatEvent]) -> Self {
+        let mut node = self.clone();
+        let (_, rest) = self.text("C").map(|c| split_chat(&c)).unwrap_or_default();
+        let lines: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+        node.set_paragraphs("C", &[rest, lines.join("\n")]);
+        node
+    }
+}
+
+impl GameTree {
+    // the chat of every node that has some, with the node's path
+    pub fn chat(&self) -> Vec<(Vec<usize>, Vec<ChatEvent>)> {
+        self.nodes()
+            .into_iter()
+            .map(|(path, node)| (path, node.chat()))
+            .filter(|(_, events)| !events.is_empty())
+            .collect()
+    }
+
+    pub fn strip_chat(&self) -> Self {
+        GameTree {
+            sequence: Sequence {
+                nodes: self.sequence.nodes.iter().map(Node::strip_chat).collect(),
+            },
+            gametrees: self.gametrees.iter().map(|gt| Box::new(gt.strip_chat())).collect(),
+        }
+    }
+
+    // puts chat as GameTree::chat gives it back into the comments, in
+    // place of what they have. nodes not listed lose theirs
+    pub fn with_chat(&self, chat: &[(Vec<usize>, Vec<ChatEvent>)]) -> Self {
+        let mut gt = self.strip_chat();
+        for (path, events) in chat {
+            if let Some(node) = gt.node_mut(path) {
+                *node = node.with_chat(events);
+            }
+        }
+        gt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn chat1() {
+        let text = "(;GM[1]C[Note: 3-3 invasion later];B[pd]C[Good shape.\n12:01 alice [3k\\]: hi\n[12:01:30\\] bob: gl hf];W[dd]C[12:02 alice:])";
+        let gt = Parser::new(text).parse().unwrap().gametrees.remove(0);
+        let chat = gt.chat();
+        assert_eq!(chat.len(), 2);
+        assert_eq!(chat[0].0, vec![0]);
+        assert_eq!(
+            chat[0].1[0],
+            ChatEvent {
+                time: "12:01".to_string(),
+                speaker: "alice".to_string(),
+                rank: Some("3k".to_string()),
+                text: "hi".to_string(),
+            }
+        );
+        assert_eq!(chat[0].1[1].to_string(), "12:01:30 bob: gl hf");
+        assert_eq!(chat[1].1[0].text, "");
+        let stripped = gt.strip_chat();
+        assert_eq!(stripped.to_string(), "(;GM[1]C[Note: 3-3 invasion later];B[pd]C[Good shape.];W[dd])");
+        assert_eq!(stripped.with_chat(&chat).to_string(), "(;GM[1]C[Note: 3-3 invasion later];B[pd]C[Good shape.\n\n12:01 alice [3k\\]: hi\n12:01:30 bob: gl hf];W[dd]C[12:02 alice:])");
+        assert_eq!(ChatEvent::parse("at 12:30 we broke: tea"), None);
+        assert_eq!(ChatEvent::parse("12:30 two words: x"), None);
+    }
+}
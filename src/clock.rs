@@ -0,0 +1,187 @@
+use std::fmt;
+
+use super::info::Overtime;
+use super::vertex::{Color, GameTree};
+
+// the share of a player's moves an estimate has them play in main time
+// when there is overtime to fall back on
+const MAIN_TIME_SHARE: f64 = 0.75;
+
+// the mover's clock after a main line move
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveClock {
+    pub number: usize,
+    pub path: Vec<usize>,
+    pub color: Color,
+    // seconds left: of main time, or of the overtime period once in it
+    pub left: f64,
+    // in overtime, the byo-yomi periods or canadian stones left (OB, OW)
+    pub overtime: Option<u32>,
+    // made up from TM and OT because the record has no BL or WL. only
+    // fit for showing a clock that moves, not for saying how long a
+    // move took
+    pub estimated: bool,
+}
+
+// like 1:02:03 or 4:05, with a '~' in front when estimated and the
+// periods or stones left after it
+impl fmt::Display for MoveClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.left.max(0.0).round() as u64;
+        let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if self.estimated {
+            write!(f, "~")?;
+        }
+        if h > 0 {
+            write!(f, "{}:{:02}:{:02}", h, m, s)?;
+        } else {
+            write!(f, "{}:{:02}", m, s)?;
+        }
+        if let Some(n) = self.overtime {
+            write!(f, " ({})", n)?;
+        }
+        Ok(())
+    }
+}
+
+impl GameTree {
+    // the clock after every main line move that has one. records with
+    // BL or WL give the times they hold; records with only TM and OT get
+    // an estimate for every move
+    pub fn clocks(&self) -> Vec<MoveClock> {
+        let recorded = self.recorded_clocks();
+        if !recorded.is_empty() {
+            return recorded;
+        }
+        self.estimated_clocks()
+    }
+
+    fn recorded_clocks(&self) -> Vec<MoveClock> {
+        let mut clocks = Vec::new();
+        let mut number = 0;
+        for (i, node) in self.mainline().into_iter().enumerate() {
+            let Some((color, _)) = node.get_move() else { continue };
+            number += 1;
+            let (left, periods) = match color {
+                Color::Black => ("BL", "OB"),
+                Color::White => ("WL", "OW"),
+            };
+            let Some(left) = node.value(left).and_then(|v| v.trim().parse::<f64>().ok()).filter(|t| t.is_finite()) else {
+                continue;
+            };
+            clocks.push(MoveClock {
+                number,
+                path: vec![0; i],
+                color,
+                left,
+                overtime: node.value(periods).and_then(|v| v.trim().parse().ok()),
+                estimated: false,
+            });
+        }
+        clocks
+    }
+
+    // each player spends main time evenly over their moves. with byo-yomi
+    // or canadian overtime they run out of it three quarters of the way
+    // through and play the rest in overtime, a byo-yomi period or a
+    // canadian stone at a time. empty without TM or OT
+    pub fn estimated_clocks(&self) -> Vec<MoveClock> {
+        let info = self.game_info();
+        let main = info.time_limit.unwrap_or(0.0).max(0.0);
+        let overtime = info.overtime.unwrap_or(Overtime::Absolute);
+        if main == 0.0 && matches!(overtime, Overtime::Absolute | Overtime::Fischer { .. } | Overtime::Other(_)) {
+            return Vec::new();
+        }
+        let nodes = self.mainline();
+        let moves = |color: Color| nodes.iter().filter(|n| n.get_move().is_some_and(|(c, _)| c == color)).count();
+        let mut players = [Estimate::new(main, &overtime, moves(Color::Black)), Estimate::new(main, &overtime, moves(Color::White))];
+        let mut clocks = Vec::new();
+        let mut number = 0;
+        for (i, node) in nodes.iter().enumerate() {
+            let Some((color, _)) = node.get_move() else { continue };
+            number += 1;
+            let (left, overtime) = players[(color == Color::White) as usize].next(&overtime);
+            clocks.push(MoveClock {
+                number,
+                path: vec![0; i],
+                color,
+                left,
+                overtime,
+                estimated: true,
+            });
+        }
+        clocks
+    }
+}
+
+// one player's clock while estimating
+struct Estimate {
+    main: f64,
+    // moves played in main time, and how many there will be
+    played: usize,
+    in_main: usize,
+    // canadian stones played in the current period
+    stones: u32,
+}
+
+impl Estimate {
+    fn new(main: f64, overtime: &Overtime, moves: usize) -> Self {
+        let in_main = match overtime {
+            _ if main == 0.0 => 0,
+            Overtime::ByoYomi { .. } | Overtime::Canadian { .. } => (moves as f64 * MAIN_TIME_SHARE).ceil() as usize,
+            // the increment is spent as it comes, so it never shows
+            _ => moves + 1,
+        };
+        Estimate { main, played: 0, in_main, stones: 0 }
+    }
+
+    // the clock after the next move
+    fn next(&mut self, overtime: &Overtime) -> (f64, Option<u32>) {
+        if self.played < self.in_main {
+            self.played += 1;
+            return (self.main * (self.in_main - self.played) as f64 / self.in_main as f64, None);
+        }
+        match *overtime {
+            Overtime::ByoYomi { periods, seconds } => (seconds as f64, Some(periods)),
+            Overtime::Canadian { stones, seconds } if stones > 0 => {
+                self.stones = self.stones % stones + 1;
+                // the last stone of a period starts the next one
+                if self.stones == stones {
+                    (seconds as f64, Some(stones))
+                } else {
+                    (seconds as f64 * (stones - self.stones) as f64 / stones as f64, Some(stones - self.stones))
+                }
+            },
+            _ => (0.0, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn clock1() {
+        let gt = gametree("(;TM[600]OT[1x30 byo-yomi];B[aa];W[bb];B[cc];W[dd];B[ee];W[ff];B[gg];W[hh])");
+        let shown: Vec<String> = gt.clocks().iter().filter(|c| c.color == Color::Black).map(|c| c.to_string()).collect();
+        assert_eq!(shown, vec!["~6:40", "~3:20", "~0:00", "~0:30 (1)"]);
+        assert!(gt.clocks().iter().all(|c| c.estimated));
+        let gt = gametree("(;TM[0]OT[5/300 Canadian];B[aa];W[bb];B[cc])");
+        let shown: Vec<String> = gt.clocks().iter().map(|c| c.to_string()).collect();
+        assert_eq!(shown, vec!["~4:00 (4)", "~4:00 (4)", "~3:00 (3)"]);
+        let gt = gametree("(;TM[3600];B[aa];W[bb];B[cc])");
+        assert_eq!(gt.clocks()[2].to_string(), "~20:00");
+        assert!(gametree("(;OT[10 fischer];B[aa])").clocks().is_empty());
+        let gt = gametree("(;TM[600];B[aa]BL[590];W[bb];B[cc]BL[7265.4]OB[2])");
+        let clocks = gt.clocks();
+        assert_eq!(clocks.len(), 2);
+        assert_eq!((clocks[1].number, clocks[1].path.clone(), clocks[1].estimated), (3, vec![0, 0, 0], false));
+        assert_eq!(clocks[1].to_string(), "2:01:05 (2)");
+    }
+}
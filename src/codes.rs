@@ -0,0 +1,283 @@
+// stable codes for everything the scanner, parser and validator report.
+// E is an error by default and W a warning. codes are never reused or
+// renumbered, so they can be kept in scripts and suppression lists
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub explanation: &'static str,
+}
+
+pub const PARSE_ERROR: &str = "E000";
+pub const UNTERMINATED_VALUE: &str = "E001";
+pub const NO_GAME: &str = "E002";
+pub const EMPTY_GAMETREE: &str = "E003";
+pub const PROPERTY_WITHOUT_VALUE: &str = "E004";
+pub const BAD_IDENTIFIER: &str = "E005";
+pub const UNEXPECTED_CHARACTER: &str = "E006";
+pub const NUMBER_TOO_LARGE: &str = "E007";
+pub const UNREADABLE_FILE: &str = "E008";
+pub const UNKNOWN_PROPERTY: &str = "E009";
+pub const BAD_BOARD_SIZE: &str = "E010";
+pub const MULTIPLE_MOVES: &str = "E011";
+pub const CONFLICTING_SETUP: &str = "E012";
+pub const KO_VIOLATION: &str = "E013";
+pub const SUPERKO_VIOLATION: &str = "E014";
+pub const INTERNAL_ERROR: &str = "E015";
+pub const OCCUPIED_POINT: &str = "E016";
+pub const SUICIDE: &str = "E017";
+pub const TOO_DEEP: &str = "E018";
+pub const MISPLACED_ROOT_PROPERTY: &str = "W001";
+pub const DUPLICATE_PROPERTY: &str = "W002";
+pub const MIXED_MOVE_SETUP: &str = "W003";
+pub const BAD_POINT: &str = "W004";
+pub const DUPLICATE_POINT: &str = "W005";
+pub const NOT_IN_VERSION: &str = "W006";
+pub const NEWLINE_IN_SIMPLE_TEXT: &str = "W007";
+pub const CONTROL_CHARACTER: &str = "W008";
+pub const BLANK_VALUE: &str = "W009";
+
+pub const CATALOG: [Code; 28] = [
+    Code {
+        code: PARSE_ERROR,
+        name: "parse-error",
+        summary: "the file could not be parsed",
+        explanation: "The text is not valid SGF. This general code is used when the exact cause isn't known; \
+                      the more specific codes E001 to E007 are used when it is.",
+    },
+    Code {
+        code: UNTERMINATED_VALUE,
+        name: "unterminated-value",
+        summary: "a property value is missing its closing ']'",
+        explanation: "A value starts with '[' and ends at the next ']' that isn't escaped with a backslash. \
+                      The file ended before that ']'. Usually the file was cut off, or a ']' inside a comment \
+                      was written without its backslash, like C[see [1]] instead of C[see [1\\]].",
+    },
+    Code {
+        code: NO_GAME,
+        name: "no-game",
+        summary: "the file has no game in it",
+        explanation: "An SGF collection is one or more game trees, each starting with '('. The file is empty \
+                      or has no '(' at all, so it is probably not an SGF file.",
+    },
+    Code {
+        code: EMPTY_GAMETREE,
+        name: "empty-gametree",
+        summary: "a game tree or variation has no nodes",
+        explanation: "Every game tree, including each variation, starts with at least one node: a ';' after \
+                      the '('. Something like (;GM[1]()) has a variation with nothing in it.",
+    },
+    Code {
+        code: PROPERTY_WITHOUT_VALUE,
+        name: "property-without-value",
+        summary: "a property has no value",
+        explanation: "Every property has at least one value in brackets, even if it is empty: write B[] for a \
+                      pass rather than B.",
+    },
+    Code {
+        code: BAD_IDENTIFIER,
+        name: "bad-identifier",
+        summary: "a property identifier isn't all uppercase letters",
+        explanation: "FF[4] identifiers are uppercase letters only. Older versions allowed lowercase letters \
+                      that readers ignore, as in AddBlack for AB; those are accepted when the game declares \
+                      FF[1] to FF[3] or no FF at all. A lenient parser skips properties with bad identifiers \
+                      and keeps a warning for each.",
+    },
+    Code {
+        code: UNEXPECTED_CHARACTER,
+        name: "unexpected-character",
+        summary: "text where a node, variation or property was expected",
+        explanation: "Outside of values only '(', ')', ';', identifiers and whitespace are allowed. Stray text \
+                      between nodes usually means a value's '[' is missing.",
+    },
+    Code {
+        code: NUMBER_TOO_LARGE,
+        name: "number-too-large",
+        summary: "a number is too large to read",
+        explanation: "The scanner reads runs of digits as numbers, and this one doesn't fit in 64 bits.",
+    },
+    Code {
+        code: UNREADABLE_FILE,
+        name: "unreadable-file",
+        summary: "the file could not be read",
+        explanation: "The file couldn't be opened or read, or it was skipped because parsing it would take \
+                      more than the memory allowed per worker (see --worker-memory).",
+    },
+    Code {
+        code: UNKNOWN_PROPERTY,
+        name: "unknown-property",
+        summary: "a property that isn't in the specification",
+        explanation: "Only reported by parsers told to reject unknown properties, or to drop them with a \
+                      warning. By default they are kept as they are, since many programs add private ones; \
+                      sgf explain-prop describes the known ones.",
+    },
+    Code {
+        code: BAD_BOARD_SIZE,
+        name: "bad-board-size",
+        summary: "SZ is not a board size",
+        explanation: "SZ is a number from 1 to 52, or columns:rows for rectangular boards. When SZ isn't \
+                      a number at all, points are checked against a 19x19 board.",
+    },
+    Code {
+        code: MULTIPLE_MOVES,
+        name: "multiple-moves",
+        summary: "B and W in the same node",
+        explanation: "A node holds at most one move. Put the second move in a node of its own.",
+    },
+    Code {
+        code: CONFLICTING_SETUP,
+        name: "conflicting-setup",
+        summary: "a point is set up more than one way in a node",
+        explanation: "AB, AW and AE in one node must not share points, since the order they apply in isn't \
+                      defined.",
+    },
+    Code {
+        code: KO_VIOLATION,
+        name: "ko-violation",
+        summary: "a move retakes a ko at once",
+        explanation: "The move recreates the position before the opponent's last move. Only checked for games \
+                      whose rules use simple ko.",
+    },
+    Code {
+        code: SUPERKO_VIOLATION,
+        name: "superko-violation",
+        summary: "a move repeats an earlier position",
+        explanation: "The rules of the game (RU) forbid repeating a whole board position. The diagnostic \
+                      points at the node where the position first occurred.",
+    },
+    Code {
+        code: INTERNAL_ERROR,
+        name: "internal-error",
+        summary: "the file made this program crash",
+        explanation: "Handling the file hit a bug here rather than a problem in the file. Batch runs carry on \
+                      with the other files and exit with an error at the end. Please report it with the file.",
+    },
+    Code {
+        code: OCCUPIED_POINT,
+        name: "occupied-point",
+        summary: "a move is played on a stone",
+        explanation: "The point of the move already has a stone on it in the position the record reaches. \
+                      Readers replace the stone, but no rules allow the move. Often a setup node is missing \
+                      or a move was recorded at the wrong coordinates. A KO property in the node forces the \
+                      move and silences this.",
+    },
+    Code {
+        code: SUICIDE,
+        name: "suicide",
+        summary: "a move captures the player's own stones",
+        explanation: "The move leaves its own group without liberties and captures nothing, so the group is \
+                      taken off. Only checked for games whose rules (RU) forbid suicide, which is all but \
+                      New Zealand, Tromp-Taylor, Ing and GOE.",
+    },
+    Code {
+        code: TOO_DEEP,
+        name: "too-deep",
+        summary: "variations are nested too deeply to read",
+        explanation: "Each '(' inside a game tree starts a variation one level deeper. Working with a tree takes \
+                      stack for every level, so more than 1000 levels are refused rather than crashing the \
+                      program. Real games stay far below that; the file was probably made by a broken program \
+                      or on purpose.",
+    },
+    Code {
+        code: MISPLACED_ROOT_PROPERTY,
+        name: "misplaced-root-property",
+        summary: "a root property outside of the root node",
+        explanation: "AP, CA, FF, GM, ST and SZ describe the whole game and belong in its first node. Most \
+                      readers ignore them anywhere else.",
+    },
+    Code {
+        code: DUPLICATE_PROPERTY,
+        name: "duplicate-property",
+        summary: "a property appears more than once in a node",
+        explanation: "Each property may appear once per node; give it several values instead, as in AB[aa][bb]. \
+                      Readers disagree about which copy wins.",
+    },
+    Code {
+        code: MIXED_MOVE_SETUP,
+        name: "mixed-move-setup",
+        summary: "move and setup properties in the same node",
+        explanation: "FF[4] keeps moves (B, W) and setup (AB, AW, AE, PL) in separate nodes, since their order \
+                      inside a node isn't defined.",
+    },
+    Code {
+        code: BAD_POINT,
+        name: "bad-point",
+        summary: "a point that isn't on the board",
+        explanation: "The point is outside the board given by SZ, or isn't two letters. tt is a pass on boards \
+                      up to 19x19.",
+    },
+    Code {
+        code: DUPLICATE_POINT,
+        name: "duplicate-point",
+        summary: "a point list names a point more than once",
+        explanation: "Lists like AB and TR should name each point once, counting the points covered by \
+                      compressed ranges such as aa:cc.",
+    },
+    Code {
+        code: NOT_IN_VERSION,
+        name: "not-in-version",
+        summary: "something the game's FF doesn't allow",
+        explanation: "The game uses a property or value that its file format version lacks, like an FF[4] \
+                      property in an FF[3] game. sgf upgrade converts old games to FF[4].",
+    },
+    Code {
+        code: NEWLINE_IN_SIMPLE_TEXT,
+        name: "newline-in-simple-text",
+        summary: "a line break in a one-line text value",
+        explanation: "SimpleText values like PB, RE and EV are a single line. Readers turn line breaks in them \
+                      into spaces, but many show them as they are. sgf validate --fix does the conversion.",
+    },
+    Code {
+        code: CONTROL_CHARACTER,
+        name: "control-character",
+        summary: "a value holds control characters",
+        explanation: "Characters like NUL and vertical tab, left by some old Windows clients, mean nothing in a \
+                      value and make strict parsers reject the file. Line breaks and tabs are fine. sgf validate \
+                      --fix removes them.",
+    },
+    Code {
+        code: BLANK_VALUE,
+        name: "blank-value",
+        summary: "a one-line text value of only whitespace",
+        explanation: "A SimpleText value like PB[ ] that holds nothing but spaces, which tools then show or \
+                      search as if it were a name. sgf validate --fix empties it.",
+    },
+];
+
+// the entry for a code like E001 or a name like unterminated-value
+pub fn lookup(key: &str) -> Option<&'static Code> {
+    let key = key.trim();
+    CATALOG.iter().find(|c| c.code.eq_ignore_ascii_case(key) || c.name == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn error_code(text: &str) -> &'static str {
+        let owned = Parser::new(text).parse().unwrap_err().code();
+        assert_eq!(crate::borrowed::Parser::new(text).normalized().parse().unwrap_err().code(), owned);
+        owned
+    }
+
+    #[test]
+    fn codes1() {
+        assert_eq!(lookup("e001").unwrap().name, "unterminated-value");
+        assert_eq!(lookup("duplicate-point").unwrap().code, "W005");
+        assert!(lookup("E999").is_none());
+        assert_eq!(error_code("(;C[x"), UNTERMINATED_VALUE);
+        assert_eq!(error_code("(;C)"), PROPERTY_WITHOUT_VALUE);
+        assert_eq!(error_code("(;GM[1]())"), EMPTY_GAMETREE);
+        assert_eq!(error_code("no game"), NO_GAME);
+        assert_eq!(error_code("(;weird[])"), BAD_IDENTIFIER);
+        assert_eq!(error_code("(;FF[4]GM[1]AddBlack[aa])"), BAD_IDENTIFIER);
+        assert_eq!(error_code("(;C[99999999999999999999999])"), NUMBER_TOO_LARGE);
+        assert_eq!(error_code(&format!("{}{}", "(;B[aa]".repeat(2000), ")".repeat(2000))), TOO_DEEP);
+        for (i, a) in CATALOG.iter().enumerate() {
+            assert!(CATALOG[i + 1..].iter().all(|b| a.code != b.code && a.name != b.name));
+        }
+    }
+}
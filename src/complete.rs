@@ -0,0 +1,192 @@
+use super::borrowed::{GameTree, Node, Parser};
+use super::properties::{Context, PropertyInfo, PROPERTIES};
+use super::version;
+
+// what kind of node the cursor is in, which decides the properties that
+// fit there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeContext {
+    pub root: bool,
+    // the idents the node already has
+    pub present: Vec<String>,
+    // the FF of the game, to offer FF[3] properties in old games only
+    pub version: u8,
+}
+
+// one completion: the ident and the text to insert for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub ident: &'static str,
+    pub name: &'static str,
+    pub value_type: &'static str,
+    // the property with an example value, like KM[6.5]
+    pub template: String,
+    example: &'static str,
+}
+
+impl Completion {
+    // the template as an LSP snippet, with the value as the first tab
+    // stop: KM[${1:6.5}]
+    pub fn snippet(&self) -> String {
+        match self.example {
+            "" => format!("{}[$1]", self.ident),
+            example => format!("{}[${{1:{}}}]", self.ident, example),
+        }
+    }
+}
+
+impl NodeContext {
+    pub fn new(root: bool, version: u8) -> Self {
+        NodeContext {
+            root,
+            present: Vec::new(),
+            version,
+        }
+    }
+
+    pub fn with_present<S: AsRef<str>>(mut self, idents: &[S]) -> Self {
+        self.present = idents.iter().map(|i| i.as_ref().to_string()).collect();
+        self
+    }
+
+    // the context of the node at a byte offset of source, or None when the
+    // offset isn't in a node. source is read with recovery, as text being
+    // typed is rarely a whole game: a gametree left open runs to the end
+    pub fn at(source: &str, offset: usize) -> Option<Self> {
+        let (coll, _) = Parser::new(source).parse_with_recovery();
+        let gt = coll.gametrees.iter().find(|gt| gt.span.start <= offset && offset <= gt.span.end)?;
+        let version = gt.root().value("FF").and_then(version::parse).unwrap_or(version::DEFAULT);
+        let (root, node) = node_at(gt, offset, true)?;
+        let present: Vec<&str> = node.props.iter().map(|p| p.ident.as_ref()).collect();
+        Some(NodeContext::new(root, version).with_present(&present))
+    }
+
+    fn has_move(&self) -> bool {
+        self.present.iter().any(|i| i == "B" || i == "W")
+    }
+
+    fn has_setup(&self) -> bool {
+        self.present.iter().any(|i| PROPERTIES.iter().any(|p| p.ident == i && p.context == Context::Setup))
+    }
+
+    // whether a property belongs in the node: root properties only in the
+    // root, game info in the root, one move a node and moves and setup
+    // not mixed
+    fn fits(&self, info: &PropertyInfo) -> bool {
+        match info.context {
+            Context::Root | Context::GameInfo => self.root,
+            Context::Move if matches!(info.ident, "B" | "W") => !self.has_setup() && !self.has_move(),
+            Context::Move => !self.has_setup(),
+            Context::Setup => !self.has_move(),
+            Context::Inherit | Context::Any => true,
+        }
+    }
+}
+
+// the innermost node holding offset, and whether it is the game's root.
+// an offset between nodes gets the one before it
+fn node_at<'t, 'a>(gt: &'t GameTree<'a>, offset: usize, root: bool) -> Option<(bool, &'t Node<'a>)> {
+    if let Some(child) = gt.gametrees.iter().find(|child| child.span.contains(&offset)) {
+        return node_at(child, offset, false);
+    }
+    let i = gt.sequence.nodes.iter().rposition(|n| n.span.start <= offset)?;
+    Some((root && i == 0, &gt.sequence.nodes[i]))
+}
+
+// the properties that fit the node and start with prefix, in registry
+// order. ones the node already has are left out
+pub fn complete(context: &NodeContext, prefix: &str) -> Vec<Completion> {
+    PROPERTIES
+        .iter()
+        .filter(|p| p.ident.starts_with(prefix))
+        .filter(|p| context.version < 4 || p.until.is_none())
+        .filter(|p| !context.present.iter().any(|i| i == p.ident))
+        .filter(|p| context.fits(p))
+        .map(|p| {
+            let example = example(p);
+            Completion {
+                ident: p.ident,
+                name: p.name,
+                value_type: p.value_type,
+                template: format!("{}[{}]", p.ident, example),
+                example,
+            }
+        })
+        .collect()
+}
+
+// a value to start from for each property
+fn example(info: &PropertyInfo) -> &'static str {
+    match info.ident {
+        "FF" => "4",
+        "GM" => "1",
+        "SZ" => "19",
+        "CA" => "UTF-8",
+        "ST" => "2",
+        "KM" => "6.5",
+        "HA" => "2",
+        "DT" => "2026-01-01",
+        "RE" => "B+R",
+        "RU" => "Japanese",
+        "TM" => "3600",
+        "OT" => "5x30 byo-yomi",
+        "PL" => "B",
+        "LB" => "dd:A",
+        "AR" | "LN" => "aa:dd",
+        "DD" | "VW" | "FG" => "",
+        _ => match info.value_type {
+            "move" | "point" | "list of point" | "elist of point" => "dd",
+            "double" => "1",
+            "real" | "number" => "0",
+            _ => "",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idents(completions: &[Completion]) -> Vec<&str> {
+        completions.iter().map(|c| c.ident).collect()
+    }
+
+    #[test]
+    fn complete1() {
+        let root = NodeContext::new(true, 4).with_present(&["GM", "FF"]);
+        let found = complete(&root, "K");
+        assert_eq!(idents(&found), vec!["KM", "KO"]);
+        assert_eq!((found[0].template.as_str(), found[0].snippet()), ("KM[6.5]", "KM[${1:6.5}]".to_string()));
+        assert!(!idents(&complete(&root, "")).contains(&"GM"));
+        // a move node: no game info, setup or FF[3] properties
+        let node = NodeContext::new(false, 4).with_present(&["B"]);
+        let all = complete(&node, "");
+        assert!(idents(&all).contains(&"BM") && idents(&all).contains(&"C"));
+        assert!(["KM", "AB", "B", "W", "L"].iter().all(|i| !idents(&all).contains(i)));
+        assert!(idents(&complete(&NodeContext::new(false, 3), "L")).contains(&"L"));
+        assert_eq!(complete(&node, "C")[0].snippet(), "C[$1]");
+    }
+
+    #[test]
+    fn complete2() {
+        let source = "(;GM[1]FF[3]KM[6.5];B[pd]C[hi](;W[dd])(;AB[aa]))";
+        let root = NodeContext::at(source, 3).unwrap();
+        assert!(root.root && root.present == vec!["GM", "FF", "KM"] && root.version == 3);
+        let node = NodeContext::at(source, source.find("hi").unwrap()).unwrap();
+        assert!(!node.root && node.present == vec!["B", "C"]);
+        let setup = NodeContext::at(source, source.find("AB").unwrap()).unwrap();
+        assert_eq!(setup.present, vec!["AB"]);
+        assert!(!idents(&complete(&setup, "")).contains(&"W"));
+        // while typing: open values, gametrees and a half written ident.
+        // a value left open is dropped, but its node is still found
+        let typing = "(;GM[1]FF[4]KM[6.5];B[pd]C[hi";
+        assert_eq!(NodeContext::at(typing, typing.len()).unwrap().present, vec!["B"]);
+        let typing = "(;GM[1]FF[4]PB[Ann]\n;B[pd]\n;W[dd]K";
+        let node = NodeContext::at(typing, typing.len()).unwrap();
+        assert!(!node.root && node.present == vec!["W"]);
+        let typing = "(;GM[1]FF[4]P";
+        let root = NodeContext::at(typing, typing.len()).unwrap();
+        assert!(root.root && root.present == vec!["GM", "FF"]);
+        assert!(NodeContext::at("(;C[x]) ", 8).is_none());
+    }
+}
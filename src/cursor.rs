@@ -0,0 +1,169 @@
+use super::vertex::{GameTree, Node};
+
+// a place in a game tree that moves a node at a time, the way a game
+// browser walks it. moves that can't be made return None and leave the
+// cursor where it was. gametrees without nodes, which a parse never
+// gives but an edit can leave, are never stepped into, so the cursor is
+// always on a node
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    // the gametrees above the current one, outermost first
+    parents: Vec<&'a GameTree>,
+    gt: &'a GameTree,
+    // the current node in gt's sequence
+    index: usize,
+    // as GameTree::nodes gives it
+    path: Vec<usize>,
+}
+
+impl<'a> Cursor<'a> {
+    // at the root node, None when gt has none
+    pub fn new(gt: &'a GameTree) -> Option<Self> {
+        if gt.sequence.nodes.is_empty() {
+            return None;
+        }
+        Some(Cursor {
+            parents: Vec::new(),
+            gt,
+            index: 0,
+            path: Vec::new(),
+        })
+    }
+
+    // at the node at path, if there is one
+    pub fn at(gt: &'a GameTree, path: &[usize]) -> Option<Self> {
+        let mut cursor = Cursor::new(gt)?;
+        for &step in path {
+            cursor.down_to(step)?;
+        }
+        Some(cursor)
+    }
+
+    pub fn node(&self) -> &'a Node {
+        &self.gt.sequence.nodes[self.index]
+    }
+
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    // moves made from the root, counting every node
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    // how many nodes follow this one: 1 inside a sequence, the number of
+    // variations at the end of one
+    pub fn children(&self) -> usize {
+        if self.index + 1 < self.gt.sequence.nodes.len() {
+            1
+        } else {
+            self.gt.gametrees.len()
+        }
+    }
+
+    // the next node, along the first variation where the line splits
+    pub fn down(&mut self) -> Option<&'a Node> {
+        self.down_to(0)
+    }
+
+    // the nth node following this one
+    pub fn down_to(&mut self, n: usize) -> Option<&'a Node> {
+        if self.index + 1 < self.gt.sequence.nodes.len() {
+            if n != 0 {
+                return None;
+            }
+            self.index += 1;
+        } else {
+            let child = self.gt.gametrees.get(n).filter(|child| !child.sequence.nodes.is_empty())?;
+            self.parents.push(self.gt);
+            self.gt = child;
+            self.index = 0;
+        }
+        self.path.push(n);
+        Some(self.node())
+    }
+
+    pub fn up(&mut self) -> Option<&'a Node> {
+        if self.index > 0 {
+            self.index -= 1;
+        } else {
+            self.gt = self.parents.pop()?;
+            self.index = self.gt.sequence.nodes.len() - 1;
+        }
+        self.path.pop();
+        Some(self.node())
+    }
+
+    // which of its siblings this node is, and how many there are. a node
+    // inside a sequence, or the root, is the only one
+    pub fn variation(&self) -> (usize, usize) {
+        match (self.index, self.parents.last()) {
+            (0, Some(parent)) => (*self.path.last().unwrap(), parent.gametrees.len()),
+            _ => (0, 1),
+        }
+    }
+
+    // the first node of the next variation of the same parent
+    pub fn next_variation(&mut self) -> Option<&'a Node> {
+        let (i, n) = self.variation();
+        (i + 1..n).find_map(|j| self.switch(j))
+    }
+
+    pub fn prev_variation(&mut self) -> Option<&'a Node> {
+        let (i, _) = self.variation();
+        (0..i).rev().find_map(|j| self.switch(j))
+    }
+
+    fn switch(&mut self, i: usize) -> Option<&'a Node> {
+        self.gt = self.parents.last()?.gametrees.get(i).filter(|child| !child.sequence.nodes.is_empty())?;
+        *self.path.last_mut()? = i;
+        Some(self.node())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn cursor1() {
+        let gt = Parser::new("(;GM[1];B[aa](;W[bb];B[cc])(;W[dd])(;W[ee]))").parse().unwrap().gametrees.remove(0);
+        let mut cursor = Cursor::new(&gt).unwrap();
+        assert!(cursor.up().is_none() && cursor.is_root());
+        assert_eq!(cursor.down().unwrap().value("B"), Some("aa"));
+        assert_eq!(cursor.children(), 3);
+        assert!(cursor.next_variation().is_none());
+        assert_eq!(cursor.down().unwrap().value("W"), Some("bb"));
+        assert_eq!(cursor.next_variation().unwrap().value("W"), Some("dd"));
+        assert_eq!(cursor.next_variation().unwrap().value("W"), Some("ee"));
+        assert!(cursor.next_variation().is_none());
+        assert_eq!(cursor.variation(), (2, 3));
+        assert_eq!(cursor.path(), &[0, 2]);
+        assert_eq!(gt.node(cursor.path()).unwrap().value("W"), Some("ee"));
+        assert_eq!(cursor.prev_variation().unwrap().value("W"), Some("dd"));
+        assert!(cursor.down().is_none());
+        assert_eq!(cursor.up().unwrap().value("B"), Some("aa"));
+        assert_eq!(cursor.depth(), 1);
+        let mut cursor = Cursor::at(&gt, &[0, 0, 0]).unwrap();
+        assert_eq!(cursor.node().value("B"), Some("cc"));
+        assert!(cursor.next_variation().is_none());
+        assert_eq!(cursor.up().unwrap().value("W"), Some("bb"));
+        assert!(Cursor::at(&gt, &[0, 3]).is_none());
+        // nodeless gametrees, as edits can leave them
+        let mut gt = gt;
+        gt.gametrees[1].sequence.nodes.clear();
+        let mut cursor = Cursor::at(&gt, &[0, 0]).unwrap();
+        assert_eq!(cursor.next_variation().unwrap().value("W"), Some("ee"));
+        assert_eq!(cursor.prev_variation().unwrap().value("W"), Some("bb"));
+        assert!(Cursor::at(&gt, &[0, 1]).is_none());
+        assert_eq!(cursor.up().unwrap().value("B"), Some("aa"));
+        gt.sequence.nodes.clear();
+        assert!(Cursor::new(&gt).is_none());
+    }
+}
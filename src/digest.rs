@@ -0,0 +1,183 @@
+use std::fmt;
+
+use super::info::is_pass;
+use super::vertex::{GameTree, Node};
+use super::writer::{unescape_text, PREVIOUS_APPS};
+
+// properties about the file rather than the game: the programs that
+// wrote it and the encoding it used
+const FILE_PROPERTIES: [&str; 3] = ["AP", PREVIOUS_APPS, "CA"];
+
+// fewer moves than this could be the opening of any two games
+const MIN_COMMON_MOVES: usize = 20;
+// a cut off record has to hold most of the game to be taken for it
+const MIN_SIMILARITY: f64 = 0.75;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// the game in a form that two clients saving it agree on: values are
+// unescaped with runs of whitespace made one space, passes are written
+// B[], and the properties of a node and the values of a property are
+// sorted
+pub fn canonical(gt: &GameTree) -> String {
+    let (cols, rows) = gt.board_size();
+    let mut out = String::new();
+    write_gametree(gt, cols, rows, &mut out);
+    out
+}
+
+fn write_gametree(gt: &GameTree, cols: usize, rows: usize, out: &mut String) {
+    out.push('(');
+    for node in &gt.sequence.nodes {
+        write_node(node, cols, rows, out);
+    }
+    for child in &gt.gametrees {
+        write_gametree(child, cols, rows, out);
+    }
+    out.push(')');
+}
+
+fn write_node(node: &Node, cols: usize, rows: usize, out: &mut String) {
+    let mut props: Vec<(&str, Vec<String>)> = node
+        .props
+        .iter()
+        .filter(|p| !FILE_PROPERTIES.contains(&p.ident.as_str()))
+        .map(|p| {
+            let mut values: Vec<String> = p.values.iter().map(|v| value(&p.ident, v, cols, rows)).collect();
+            values.sort();
+            (p.ident.as_str(), values)
+        })
+        .collect();
+    props.sort();
+    out.push(';');
+    for (ident, values) in props {
+        out.push_str(ident);
+        for v in values {
+            out.push('[');
+            // escaped again so a value can't fake a property boundary
+            out.push_str(&v.replace('\\', "\\\\").replace(']', "\\]"));
+            out.push(']');
+        }
+    }
+}
+
+fn value(ident: &str, value: &str, cols: usize, rows: usize) -> String {
+    let text = unescape_text(value).split_whitespace().collect::<Vec<_>>().join(" ");
+    if matches!(ident, "B" | "W") && is_pass(&text, cols, rows) {
+        return String::new();
+    }
+    text
+}
+
+pub(crate) fn fnv(text: &str) -> u64 {
+    text.bytes().fold(FNV_OFFSET, |h, b| (h ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+// a 64 bit FNV-1a hash of the canonical form. it is the same on every
+// platform and release, so it can be stored and compared later
+pub fn content_hash(gt: &GameTree) -> u64 {
+    fnv(&canonical(gt))
+}
+
+// the moves of the main line, like B[pd], with passes as B[]
+pub fn moves(gt: &GameTree) -> Vec<String> {
+    let (cols, rows) = gt.board_size();
+    gt.mainline()
+        .into_iter()
+        .filter_map(|n| n.get_move())
+        .map(|(color, v)| format!("{}[{}]", color.ident(), value(color.ident(), v, cols, rows)))
+        .collect()
+}
+
+// how two records of a game compare, by the moves of their main lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameMatch {
+    pub moves: (usize, usize),
+    // the moves the two agree on from the start
+    pub common: usize,
+    // common over the longer game's moves, 1 for two empty games
+    pub score: f64,
+}
+
+impl GameMatch {
+    // one record is the other, or most of the start of it, as when a
+    // server cut the game off near the end. records that short or that
+    // far apart in length can't be told from two games sharing an opening
+    pub fn same_game(&self) -> bool {
+        self.common == self.moves.0.min(self.moves.1) && self.common >= MIN_COMMON_MOVES && self.score >= MIN_SIMILARITY
+    }
+}
+
+impl fmt::Display for GameMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} and {} moves, {} in common, similarity {:.2}",
+            if self.same_game() { "same game" } else { "different games" },
+            self.moves.0,
+            self.moves.1,
+            self.common,
+            self.score
+        )
+    }
+}
+
+// compares the main lines, so variations either record has don't count.
+// games on boards of different sizes have no moves in common, whatever
+// their coordinates
+pub fn match_games(a: &GameTree, b: &GameTree) -> GameMatch {
+    let same_size = a.check_same_size(b).is_ok();
+    let (a, b) = (moves(a), moves(b));
+    let common = if same_size { a.iter().zip(&b).take_while(|(x, y)| x == y).count() } else { 0 };
+    let longest = a.len().max(b.len());
+    GameMatch {
+        moves: (a.len(), b.len()),
+        common,
+        score: if longest == 0 { 1.0 } else { common as f64 / longest as f64 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn digest1() {
+        let kgs = gametree("(;GM[1]FF[4]CA[UTF-8]AP[CGoban:3]PB[Lee  Sedol]AB[dd][pp];B[qd]C[nice\\\n move];W[])");
+        let ogs = gametree("(;FF[4]GM[1]AP[OGS]XAP[Sabaki]AB[pp][dd]PB[Lee Sedol]\n;C[nice move]B[qd];W[tt])");
+        assert_eq!(canonical(&kgs), "(;AB[dd][pp]FF[4]GM[1]PB[Lee Sedol];B[qd]C[nice move];W[])");
+        assert_eq!(content_hash(&kgs), content_hash(&ogs));
+        // a different move, or one more, is a different game
+        assert_ne!(content_hash(&kgs), content_hash(&gametree("(;GM[1]FF[4]PB[Lee Sedol]AB[dd][pp];B[qc]C[nice move];W[])")));
+        assert_ne!(content_hash(&kgs), content_hash(&gametree("(;GM[1]FF[4]PB[Lee Sedol]AB[dd][pp];B[qd]C[nice move];W[];B[])")));
+        assert_eq!(content_hash(&gametree("(;)")), 0x09b11117f68c37b7);
+    }
+
+    #[test]
+    fn digest2() {
+        // n moves filling the board column by column
+        let line = |n: usize| -> String { (0..n).map(|i| format!(";{}[{}{}]", ["B", "W"][i % 2], (b'a' + (i / 19) as u8) as char, (b'a' + (i % 19) as u8) as char)).collect() };
+        let full = gametree(&format!("(;GM[1]PB[x]{};W[tt](;B[ss])(;B[rs]))", line(23)));
+        let cut = gametree(&format!("(;GM[1]PB[y]C[from the other server]{};W[])", line(23)));
+        let found = match_games(&full, &cut);
+        assert_eq!((found.moves, found.common), ((25, 24), 24));
+        assert!(found.same_game());
+        assert_eq!(found.to_string(), "same game: 25 and 24 moves, 24 in common, similarity 0.96");
+        let other = match_games(&full, &gametree("(;B[aa];W[ab];B[qq])"));
+        assert!(!other.same_game() && other.common == 2);
+        // a shared opening is not the same game, nor are empty records
+        let opening = match_games(&full, &gametree(&format!("(;{})", &line(5)[1..])));
+        assert!(!opening.same_game() && opening.common == 5);
+        let half = match_games(&gametree(&format!("(;{})", &line(40)[1..])), &gametree(&format!("(;{})", &line(20)[1..])));
+        assert!(!half.same_game() && half.common == 20);
+        assert!(!match_games(&gametree("(;GM[1])"), &gametree("(;GM[1])")).same_game());
+        let small = match_games(&gametree("(;SZ[9];B[cc];W[gg])"), &gametree("(;B[cc];W[gg])"));
+        assert!(!small.same_game() && small.common == 0);
+    }
+}
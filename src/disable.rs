@@ -0,0 +1,192 @@
+use std::fmt;
+use std::ops::Range;
+
+use super::bookmark::format_path;
+use super::borrowed;
+use super::mutate::{join_single, locate, split_after};
+use super::vertex::GameTree;
+use super::writer::{escape_text, unescape_text};
+
+// a hidden variation is kept in the comment of the node it branched
+// from, after what was there, as a line naming the place it goes back to
+// and then its sgf:
+//
+//   disabled variation 0:
+//   (;W[bb];B[cc])
+//
+// so an editor that knows nothing of it shows it as text and keeps it
+const MARKER: &str = "disabled variation ";
+
+#[derive(Debug)]
+pub enum Error {
+    NoSuchNode(Vec<usize>),
+    // the path is the root, which has nothing to hide it in
+    NotAVariation(Vec<usize>),
+    NoSuchVariation(Vec<usize>, usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoSuchNode(path) => write!(f, "no node at {}", format_path(path)),
+            Error::NotAVariation(path) => write!(f, "{} doesn't start a variation", format_path(path)),
+            Error::NoSuchVariation(path, n) => write!(f, "no disabled variation {} at {}", n, format_path(path)),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone)]
+pub struct Disabled {
+    // the node the variation branched from
+    pub path: Vec<usize>,
+    pub index: usize,
+    pub gametree: GameTree,
+}
+
+// the variations hidden in a comment: the text each takes up, with the
+// newlines before it, the place it goes back to and the variation. the
+// sgf is read verbatim, so its values come back exactly as they went in
+fn hidden_in(comment: &str) -> Vec<(Range<usize>, usize, GameTree)> {
+    let mut found = Vec::new();
+    let mut at = 0;
+    while let Some(i) = comment[at..].find(MARKER).map(|i| at + i) {
+        at = i + MARKER.len();
+        if i > 0 && !comment[..i].ends_with('\n') {
+            continue;
+        }
+        let Some(line) = comment[at..].find('\n').map(|n| at + n) else { break };
+        let Some(index) = comment[at..line].strip_suffix(':').and_then(|n| n.parse().ok()) else { continue };
+        let sgf = &comment[line + 1..];
+        if !sgf.starts_with('(') {
+            continue;
+        }
+        let Ok(coll) = borrowed::Parser::new(sgf).parse() else { continue };
+        let Some(gt) = coll.gametrees.into_iter().next() else { continue };
+        let end = line + 1 + gt.span.end;
+        // the blank line before it, one line when a parser has joined them
+        let start = comment[..i].trim_end_matches('\n').len();
+        found.push((start..end, index, gt.into_owned()));
+        at = end;
+    }
+    found
+}
+
+impl GameTree {
+    // hides the variation whose first node is at path in the node before
+    // it, which it gives. the variations after it move up one
+    pub fn disable_variation(&mut self, path: &[usize]) -> Result<Vec<usize>> {
+        let (&step, parent) = path.split_last().ok_or_else(|| Error::NotAVariation(Vec::new()))?;
+        if self.node(path).is_none() {
+            return Err(Error::NoSuchNode(path.to_vec()));
+        }
+        let (gt, i) = locate(self, parent).ok_or_else(|| Error::NoSuchNode(parent.to_vec()))?;
+        split_after(gt, i);
+        let hidden = gt.gametrees.remove(step);
+        join_single(gt);
+        let blob = escape_text(&format!("{}{}:\n{}", MARKER, step, hidden), false);
+        // the comment is added to as written, to keep its own escapes
+        let node = &mut gt.sequence.nodes[i];
+        match node.get_mut("C").and_then(|prop| prop.values.first_mut()) {
+            Some(comment) if !comment.is_empty() => {
+                comment.push_str("\n\n");
+                comment.push_str(&blob);
+            },
+            _ => node.set("C", vec![blob]),
+        }
+        Ok(parent.to_vec())
+    }
+
+    // every hidden variation, in the order of the file
+    pub fn disabled(&self) -> Vec<Disabled> {
+        let mut found = Vec::new();
+        for (path, node) in self.nodes() {
+            let comment = unescape_text(node.value("C").unwrap_or_default());
+            for (_, index, gametree) in hidden_in(&comment) {
+                found.push(Disabled {
+                    path: path.clone(),
+                    index,
+                    gametree,
+                });
+            }
+        }
+        found
+    }
+
+    // puts back the nth variation hidden at the node at path, where it
+    // was, and gives the path of its first node
+    pub fn enable_variation(&mut self, path: &[usize], n: usize) -> Result<Vec<usize>> {
+        let (gt, i) = locate(self, path).ok_or_else(|| Error::NoSuchNode(path.to_vec()))?;
+        let node = &mut gt.sequence.nodes[i];
+        let comment = unescape_text(node.value("C").unwrap_or_default());
+        let mut hidden = hidden_in(&comment);
+        if n >= hidden.len() {
+            return Err(Error::NoSuchVariation(path.to_vec(), n));
+        }
+        let (range, index, restored) = hidden.remove(n);
+        let rest = format!("{}{}", &comment[..range.start], &comment[range.end..]);
+        if rest.is_empty() {
+            node.remove("C");
+        } else {
+            node.set("C", vec![escape_text(&rest, false)]);
+        }
+        split_after(gt, i);
+        let index = index.min(gt.gametrees.len());
+        gt.gametrees.insert(index, Box::new(restored));
+        join_single(gt);
+        let mut first = path.to_vec();
+        first.push(index);
+        Ok(first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn disable1() {
+        let text = "(;GM[1];B[aa]C[good](;W[bb]C[a \\] b: c];B[cc])(;W[dd])(;W[ee]))";
+        let mut gt = gametree(text);
+        assert_eq!(gt.disable_variation(&[0, 0]).unwrap(), vec![0]);
+        assert_eq!(
+            gt.to_string(),
+            "(;GM[1];B[aa]C[good\n\ndisabled variation 0:\n(;W[bb\\]C[a \\\\\\] b: c\\];B[cc\\])](;W[dd])(;W[ee]))"
+        );
+        // the text survives a round trip through a file
+        let mut gt = gametree(&gt.to_string());
+        assert_eq!(gt.disable_variation(&[0, 0]).unwrap(), vec![0]);
+        // with one line left it is joined to the node it branched from
+        assert_eq!(gt.sequence.nodes.len(), 3);
+        let hidden: Vec<(Vec<usize>, usize, String)> = gt.disabled().into_iter().map(|d| (d.path, d.index, d.gametree.to_string())).collect();
+        assert_eq!(hidden[0], (vec![0], 0, "(;W[bb]C[a \\] b: c];B[cc])".to_string()));
+        assert_eq!(gt.enable_variation(&[0], 1).unwrap(), vec![0, 0]);
+        assert_eq!(gt.enable_variation(&[0], 0).unwrap(), vec![0, 0]);
+        assert_eq!(gt.to_string(), text);
+        assert!(gt.enable_variation(&[0], 0).is_err());
+        assert!(matches!(gt.disable_variation(&[]), Err(Error::NotAVariation(_))));
+        assert!(matches!(gt.disable_variation(&[0, 5]), Err(Error::NoSuchNode(_))));
+    }
+
+    #[test]
+    fn disable2() {
+        // values come back as they were, with no comment left behind, and
+        // text that only looks like a hidden variation is left alone
+        let text = "(;GM[1];B[aa](;W[bb]C[a  b]DT[2024-3-1];B[cc])(;W[dd]C[see disabled variation 0:\n(;B[ee\\])]))";
+        let mut gt = borrowed::Parser::new(text).parse().unwrap().into_owned().gametrees.remove(0);
+        gt.disable_variation(&[0, 0]).unwrap();
+        assert_eq!(gt.disabled().len(), 1);
+        assert_eq!(gt.enable_variation(&[0], 0).unwrap(), vec![0, 0]);
+        assert_eq!(gt.to_string(), text);
+        let mut gt = gametree("(;GM[1];B[aa]C[disabled variation x:\n(;W[bb\\])](;W[cc])(;W[dd]))");
+        assert!(gt.disabled().is_empty());
+        gt.disable_variation(&[0, 1]).unwrap();
+        assert_eq!(gt.disabled()[0].index, 1);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+use super::vertex::{Collection, GameTree, Node};
+use super::writer::{escape_text, unescape_text, COMPOSED_TEXT_PROPERTIES};
+
+#[derive(Debug)]
+pub enum Error {
+    ScriptError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ScriptError(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// one step of an edit script. values are plain text, escaped on the way in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    // sets a property of the root node
+    Set { ident: String, value: String },
+    // removes a property from every node, or only from the nodes more
+    // than deeper_than variations away from the main line
+    Delete { ident: String, deeper_than: Option<usize> },
+    // changes a value wherever the property has it, like a player's name
+    Rename { ident: String, from: String, to: String },
+}
+
+impl Op {
+    // "KM=6.5"
+    pub fn set(arg: &str) -> Result<Op> {
+        let (ident, value) = arg.split_once('=').ok_or_else(|| bad(arg, "expected IDENT=value"))?;
+        Ok(Op::Set {
+            ident: ident_of(ident, arg)?,
+            value: value.to_string(),
+        })
+    }
+
+    // "GN", or "C>1" for the C of nodes in variations of variations
+    pub fn delete(arg: &str) -> Result<Op> {
+        let (ident, deeper_than) = match arg.split_once('>') {
+            Some((ident, depth)) => (ident, Some(depth.trim().parse().map_err(|_| bad(arg, "expected IDENT>depth"))?)),
+            None => (arg, None),
+        };
+        Ok(Op::Delete {
+            ident: ident_of(ident, arg)?,
+            deeper_than,
+        })
+    }
+
+    // "PB:old name=new name"
+    pub fn rename(arg: &str) -> Result<Op> {
+        let (ident, names) = arg.split_once(':').ok_or_else(|| bad(arg, "expected IDENT:old=new"))?;
+        let (from, to) = names.split_once('=').ok_or_else(|| bad(arg, "expected IDENT:old=new"))?;
+        Ok(Op::Rename {
+            ident: ident_of(ident, arg)?,
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+fn bad(arg: &str, msg: &str) -> Error {
+    Error::ScriptError(format!("{}: {}", arg, msg))
+}
+
+fn ident_of(ident: &str, arg: &str) -> Result<String> {
+    let ident = ident.trim();
+    if ident.is_empty() || !ident.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(bad(arg, "property idents are uppercase letters"));
+    }
+    Ok(ident.to_string())
+}
+
+// edits run in order over every game of a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditScript {
+    pub ops: Vec<Op>,
+}
+
+impl EditScript {
+    pub fn new() -> Self {
+        EditScript::default()
+    }
+
+    pub fn with(mut self, op: Op) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    // applies the script to every game, returning the number of changes
+    pub fn apply(&self, coll: &mut Collection) -> usize {
+        coll.gametrees.iter_mut().map(|gt| self.apply_gametree(gt)).sum()
+    }
+
+    pub fn apply_gametree(&self, gt: &mut GameTree) -> usize {
+        let mut changes = 0;
+        for op in &self.ops {
+            match op {
+                Op::Set { ident, value } => {
+                    let value = escape_value(ident, value);
+                    let root = gt.root_mut();
+                    if root.get(ident).map(|p| p.values.as_slice()) != Some(std::slice::from_ref(&value)) {
+                        root.set(ident, vec![value]);
+                        changes += 1;
+                    }
+                },
+                Op::Delete { ident, deeper_than } => {
+                    each_node(gt, 0, &mut |node, depth| {
+                        if deeper_than.is_none_or(|d| depth > d) && node.remove(ident).is_some() {
+                            changes += 1;
+                        }
+                    });
+                },
+                Op::Rename { ident, from, to } => {
+                    let to = escape_value(ident, to);
+                    each_node(gt, 0, &mut |node, _| {
+                        let Some(prop) = node.get_mut(ident) else { return };
+                        for value in &mut prop.values {
+                            if unescape_text(value) == *from {
+                                *value = to.clone();
+                                changes += 1;
+                            }
+                        }
+                    });
+                },
+            }
+        }
+        changes
+    }
+}
+
+// the value as it is written: a composed value keeps its first ':' and
+// has only the text after it escaped
+fn escape_value(ident: &str, value: &str) -> String {
+    match value.split_once(':') {
+        Some((first, text)) if COMPOSED_TEXT_PROPERTIES.contains(&ident) => format!("{}:{}", escape_text(first, false), escape_text(text, true)),
+        _ => escape_text(value, false),
+    }
+}
+
+// every node with its variation depth: how many times its path leaves
+// the first line of play
+fn each_node<F: FnMut(&mut Node, usize)>(gt: &mut GameTree, depth: usize, f: &mut F) {
+    for node in &mut gt.sequence.nodes {
+        f(node, depth);
+    }
+    for (i, child) in gt.gametrees.iter_mut().enumerate() {
+        each_node(child, if i == 0 { depth } else { depth + 1 }, f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn collection(text: &str) -> Collection {
+        Parser::new(text).parse().unwrap()
+    }
+
+    #[test]
+    fn edit1() {
+        let mut coll = collection("(;PB[Lee]KM[5.5]GN[x];B[pd]C[a](;W[dd]C[b])(;W[dp]C[c](;B[pp]C[d])(;B[qq]C[e])))(;PB[Cho])");
+        let script = EditScript::new()
+            .with(Op::set("KM=6.5").unwrap())
+            .with(Op::delete("GN").unwrap())
+            .with(Op::delete("C>1").unwrap())
+            .with(Op::rename("PB:Lee=Lee ]Sedol").unwrap());
+        assert_eq!(script.apply(&mut coll), 5);
+        assert_eq!(
+            coll.to_string(),
+            "(;PB[Lee \\]Sedol]KM[6.5];B[pd]C[a](;W[dd]C[b])(;W[dp]C[c](;B[pp]C[d])(;B[qq])))(;PB[Cho]KM[6.5])"
+        );
+        // a second run finds nothing left to do
+        assert_eq!(script.apply(&mut coll), 0);
+        assert!(Op::set("KM").is_err() && Op::delete("c").is_err() && Op::delete("C>x").is_err());
+        assert!(Op::rename("PB=x").is_err());
+        let mut coll = collection("(;GM[1];B[aa]LB[aa:x])");
+        let script = EditScript::new().with(Op::set("AP=Tool:1.0:2").unwrap()).with(Op::rename("LB:aa:x=aa:y]").unwrap());
+        assert_eq!(script.apply(&mut coll), 2);
+        assert_eq!(coll.to_string(), "(;GM[1]AP[Tool:1.0\\:2];B[aa]LB[aa:y\\]])");
+    }
+}
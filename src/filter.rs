@@ -0,0 +1,175 @@
+use super::vertex::{Collection, GameTree};
+
+// an inclusive range of dates in DT's YYYY-MM-DD form, compared by
+// number, as the parser leaves 2024-03-01 as 2024-3-1, and so either
+// end can be as coarse as a year: "2023..", "..2019-06", "2020..2022"
+// or a single "2021"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl DateRange {
+    pub fn parse(s: &str) -> Option<DateRange> {
+        let bound = |b: &str| {
+            let b = b.trim();
+            if b.is_empty() {
+                Some(None)
+            } else if b.chars().all(|c| c.is_ascii_digit() || c == '-') {
+                Some(Some(b.to_string()))
+            } else {
+                None
+            }
+        };
+        match s.split_once("..") {
+            Some((from, to)) => Some(DateRange {
+                from: bound(from)?,
+                to: bound(to)?,
+            }),
+            None => {
+                let day = bound(s)??;
+                Some(DateRange {
+                    from: Some(day.clone()),
+                    to: Some(day),
+                })
+            },
+        }
+    }
+
+    pub fn contains(&self, date: &str) -> bool {
+        let date = date_key(date);
+        if let Some(from) = &self.from {
+            if date < date_key(from) {
+                return false;
+            }
+        }
+        if let Some(to) = &self.to {
+            // a coarse upper bound covers everything it is a prefix of
+            let to = date_key(to);
+            if date[..to.len().min(date.len())] > to[..] {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// the numbers of a date, year first, as far as there are any
+fn date_key(date: &str) -> Vec<u32> {
+    date.split('-').map_while(|part| part.trim().parse().ok()).collect()
+}
+
+// the first date in a DT value, which may list several
+pub fn first_date(dt: &str) -> Option<&str> {
+    let date = dt.split(',').next()?.trim();
+    if date.chars().take_while(|c| c.is_ascii_digit()).count() >= 4 {
+        Some(date)
+    } else {
+        None
+    }
+}
+
+// which games to keep when cutting a big dump down. unset criteria
+// match everything
+#[derive(Debug, Clone, Default)]
+pub struct GameFilter {
+    pub min_moves: Option<usize>,
+    // a prefix of RE, like "B+" or "W+R"
+    pub result: Option<String>,
+    // part of either player's name, ignoring case
+    pub player: Option<String>,
+    pub dated: Option<DateRange>,
+}
+
+impl GameFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_moves.is_none() && self.result.is_none() && self.player.is_none() && self.dated.is_none()
+    }
+
+    pub fn matches(&self, gt: &GameTree) -> bool {
+        let root = gt.root();
+        if let Some(min) = self.min_moves {
+            if gt.moves().len() < min {
+                return false;
+            }
+        }
+        if let Some(result) = &self.result {
+            let re = root.value("RE").unwrap_or("").trim().to_uppercase();
+            if !re.starts_with(&result.to_uppercase()) {
+                return false;
+            }
+        }
+        if let Some(player) = &self.player {
+            let player = player.to_lowercase();
+            let named = ["PB", "PW"].iter().any(|p| root.value(p).is_some_and(|name| name.to_lowercase().contains(&player)));
+            if !named {
+                return false;
+            }
+        }
+        if let Some(dated) = &self.dated {
+            match root.value("DT").and_then(first_date) {
+                Some(date) if dated.contains(date) => {},
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Collection {
+    pub fn retain<F: FnMut(&GameTree) -> bool>(&mut self, f: F) {
+        self.gametrees.retain(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn dates1() {
+        let range = DateRange::parse("2023..").unwrap();
+        assert!(range.contains("2023-01-05"));
+        assert!(!range.contains("2022-12-31"));
+        let range = DateRange::parse("2020..2021-06").unwrap();
+        assert!(range.contains("2021-06-30"));
+        assert!(!range.contains("2021-07-01"));
+        let range = DateRange::parse("2021").unwrap();
+        assert!(range.contains("2021-03-01"));
+        assert!(!range.contains("2022-03-01"));
+        assert!(DateRange::parse("last year").is_none());
+        // as the parser gives 2021-03-01
+        let range = DateRange::parse("2021-02-15..2021-03-01").unwrap();
+        assert!(range.contains("2021-3-1"));
+        assert!(range.contains("2021-2-20"));
+        assert!(!range.contains("2021-3-2"));
+        assert_eq!(first_date("2ü21-03-01"), None);
+        assert_eq!(first_date("2021-03-01,02"), Some("2021-03-01"));
+    }
+
+    #[test]
+    fn filter1() {
+        let text = "(;PB[Lee Sedol]PW[AlphaGo]RE[W+R]DT[2016-03-09];B[pd];W[dd])(;PB[a]PW[b]RE[B+3.5]DT[2023-01-01];B[pd])";
+        let mut coll = Parser::new(text).parse().unwrap();
+        let filter = GameFilter {
+            player: Some("sedol".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&coll.gametrees[0]));
+        assert!(!filter.matches(&coll.gametrees[1]));
+        let filter = GameFilter {
+            result: Some("b+".to_string()),
+            dated: DateRange::parse("2020.."),
+            ..Default::default()
+        };
+        coll.retain(|gt| filter.matches(gt));
+        assert_eq!(coll.gametrees.len(), 1);
+        let filter = GameFilter {
+            min_moves: Some(2),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&coll.gametrees[0]));
+    }
+}
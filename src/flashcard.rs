@@ -0,0 +1,173 @@
+use super::board::Board;
+use super::html::escape;
+use super::point::Point;
+use super::render::{self, ascii, svg, Diagram, RenderOptions};
+use super::vertex::{Color, GameTree, Node};
+use super::writer::unescape_text;
+
+// which nodes become cards. a card needs a move after its node to ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Select {
+    // nodes where the game splits into variations
+    Branches,
+    // nodes with a comment
+    Comments,
+    // nodes that are both, the usual shape of a problem or lesson
+    CommentedBranches,
+}
+
+impl Select {
+    pub fn parse(s: &str) -> Option<Select> {
+        match s {
+            "branches" => Some(Select::Branches),
+            "comments" => Some(Select::Comments),
+            "commented-branches" => Some(Select::CommentedBranches),
+            _ => None,
+        }
+    }
+}
+
+// how the position is drawn on the front
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    Ascii,
+    Svg,
+}
+
+#[derive(Debug, Clone)]
+pub struct Card {
+    // the node path of the position
+    pub path: Vec<usize>,
+    pub diagram: Diagram,
+    // the move that follows in the first variation, taken as the right one
+    pub answer: (Color, String),
+    // the answer's comment, or the position's when it has none
+    pub comment: Option<String>,
+}
+
+impl Card {
+    pub fn front(&self, face: Face, opts: &RenderOptions) -> render::Result<String> {
+        let board = match face {
+            Face::Ascii => format!("<pre>{}</pre>", escape(&ascii(&self.diagram, opts))),
+            // inline, so the deck needs no media files
+            Face::Svg => svg(&self.diagram, opts)?,
+        };
+        let to_play = match self.answer.0 {
+            Color::Black => "Black",
+            Color::White => "White",
+        };
+        Ok(format!("{}<br>{} to play", board, to_play))
+    }
+
+    pub fn back(&self) -> String {
+        let mut s = format!("{} {}", self.answer.0.ident(), self.answer.1);
+        if let Some(comment) = &self.comment {
+            s.push_str("<br>");
+            s.push_str(&escape(comment));
+        }
+        s
+    }
+}
+
+// the nodes of gt that select picks, each with its position
+pub fn cards(gt: &GameTree, select: Select) -> Vec<Card> {
+    let mut cards = Vec::new();
+    walk(gt, Board::for_game(gt), Vec::new(), select, &mut cards);
+    cards
+}
+
+fn walk(gt: &GameTree, mut board: Board, mut path: Vec<usize>, select: Select, cards: &mut Vec<Card>) {
+    let nodes = &gt.sequence.nodes;
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            path.push(0);
+        }
+        let last = board.move_point(node);
+        board.apply(node);
+        // the next node, or the first one of the first variation
+        let (next, branches) = match nodes.get(i + 1) {
+            Some(next) => (Some(next), false),
+            None => (gt.gametrees.first().and_then(|c| c.sequence.nodes.first()), gt.gametrees.len() > 1),
+        };
+        let commented = node.get("C").is_some();
+        let picked = match select {
+            Select::Branches => branches,
+            Select::Comments => commented,
+            Select::CommentedBranches => branches && commented,
+        };
+        if let (true, Some(next)) = (picked, next) {
+            if let Some(card) = card(node, next, &board, last, &path) {
+                cards.push(card);
+            }
+        }
+    }
+    for (i, child) in gt.gametrees.iter().enumerate() {
+        let mut p = path.clone();
+        p.push(i);
+        walk(child, board.clone(), p, select, cards);
+    }
+}
+
+fn card(node: &Node, next: &Node, board: &Board, last: Option<(Color, Point)>, path: &[usize]) -> Option<Card> {
+    let answer = match board.move_point(next) {
+        Some((color, p)) => (color, p.to_gtp(board.rows)),
+        None => (next.get_move()?.0, "pass".to_string()),
+    };
+    let mut diagram = Diagram::new(board.clone());
+    diagram.last = last.map(|(color, p)| (p, color));
+    let comment = next.value("C").or(node.value("C")).map(|c| unescape_text(c).trim().to_string());
+    Some(Card {
+        path: path.to_vec(),
+        diagram,
+        answer,
+        comment,
+    })
+}
+
+// a deck as an Anki text import: tab separated front and back, with the
+// header lines newer Anki reads to take the fields as html
+pub fn deck(cards: &[Card], face: Face, opts: &RenderOptions) -> render::Result<String> {
+    let mut s = String::from("#separator:tab\n#html:true\n");
+    for card in cards {
+        // fields are one line, without tabs
+        let field = |f: String| f.replace('\t', " ").replace('\n', "<br>");
+        let front = match face {
+            Face::Svg => card.front(face, opts)?.replace('\n', ""),
+            Face::Ascii => card.front(face, opts)?,
+        };
+        s.push_str(&field(front));
+        s.push('\t');
+        s.push_str(&field(card.back()));
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn flashcard1() {
+        let gt = Parser::new("(;GM[1]SZ[5];B[bb]C[what now?](;W[cc]C[good\nshape];B[dc]C[a & b])(;W[dd]))").parse().unwrap().gametrees.remove(0);
+        let found = cards(&gt, Select::CommentedBranches);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, vec![0]);
+        assert_eq!(found[0].answer, (Color::White, "C3".to_string()));
+        assert_eq!(found[0].back(), "W C3<br>good\nshape");
+        // the last move is marked, and white is asked to play
+        let opts = RenderOptions::default();
+        let front = found[0].front(Face::Ascii, &opts).unwrap();
+        assert!(front.contains(" #") && front.ends_with("White to play"));
+        // the comment on a position with one way on is a card too, once
+        // it has a next move; the last node has none
+        assert_eq!(cards(&gt, Select::Comments).len(), 2);
+        assert_eq!(cards(&gt, Select::Branches).len(), 1);
+        let text = deck(&found, Face::Svg, &opts).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].starts_with("<svg") && lines[2].ends_with("\tW C3<br>good<br>shape"));
+        assert_eq!(lines[2].matches('\t').count(), 1);
+    }
+}
@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+use super::borrowed::{GameTree, Parser};
+use super::parser::Result;
+
+// a change to make to the source: replace the bytes in range with text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+// rewrites only the part of source around range the way Writer would
+// write it, for format-on-type in editors: the node the range is in, or
+// the smallest game tree holding all of it. a range across games
+// rewrites each of them. None when there is nothing to change. the whole
+// source still has to parse
+pub fn format_range(source: &str, range: Range<usize>) -> Result<Option<Edit>> {
+    let coll = Parser::new(source).parse()?;
+    let overlapping: Vec<&GameTree> = coll.gametrees.iter().filter(|gt| overlaps(&gt.span, &range)).collect();
+    let edit = match overlapping.as_slice() {
+        [] => return Ok(None),
+        [gt] => enclosing(gt, &range),
+        [first, .., last] => Edit {
+            range: first.span.start..last.span.end,
+            text: overlapping.iter().map(|gt| (*gt).clone().into_owned().to_string()).collect(),
+        },
+    };
+    if source[edit.range.clone()] == edit.text {
+        return Ok(None);
+    }
+    Ok(Some(edit))
+}
+
+fn overlaps(span: &Range<usize>, range: &Range<usize>) -> bool {
+    span.start <= range.end && range.start <= span.end
+}
+
+fn contains(span: &Range<usize>, range: &Range<usize>) -> bool {
+    span.start <= range.start && range.end <= span.end
+}
+
+// the edit for the innermost node or variation of gt holding range
+fn enclosing(gt: &GameTree, range: &Range<usize>) -> Edit {
+    if let Some(node) = gt.sequence.nodes.iter().find(|n| contains(&n.span, range)) {
+        return Edit {
+            range: node.span.clone(),
+            text: node.clone().into_owned().to_string(),
+        };
+    }
+    match gt.gametrees.iter().find(|child| contains(&child.span, range)) {
+        Some(child) => enclosing(child, range),
+        None => Edit {
+            range: gt.span.clone(),
+            text: gt.clone().into_owned().to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(source: &str, edit: &Edit) -> String {
+        let mut s = source.to_string();
+        s.replace_range(edit.range.clone(), &edit.text);
+        s
+    }
+
+    #[test]
+    fn format1() {
+        let source = "(;GM[1]\n  SZ[19] ;B[pd]  C[ hi ]\n(;W[dd] ) (;W[dp]\n))\n(;GM[1] )";
+        let at = |s: &str| source.find(s).unwrap();
+        // inside C: only that node changes
+        let edit = format_range(source, at("hi")..at("hi") + 2).unwrap().unwrap();
+        assert_eq!(edit.text, ";B[pd]C[ hi ]");
+        assert_eq!(apply(source, &edit), "(;GM[1]\n  SZ[19] ;B[pd]C[ hi ]\n(;W[dd] ) (;W[dp]\n))\n(;GM[1] )");
+        // across both variations: the game they are in
+        let edit = format_range(source, at("dd")..at("dp")).unwrap().unwrap();
+        assert_eq!(edit.range, 0..at("\n(;GM"));
+        assert_eq!(edit.text, "(;GM[1]SZ[19];B[pd]C[ hi ](;W[dd])(;W[dp]))");
+        // a variation's closing paren
+        let edit = format_range(source, at("\n))") + 1..at("\n))") + 1).unwrap().unwrap();
+        assert_eq!(edit.text, "(;W[dp])");
+        // across games, and nothing left to do in a formatted one
+        let edit = format_range(source, at("SZ")..source.len()).unwrap().unwrap();
+        assert_eq!(edit.text.matches('(').count(), 4);
+        assert_eq!(format_range("(;GM[1];B[aa])", 8..9).unwrap(), None);
+        assert!(format_range("(;C[x", 0..1).is_err());
+    }
+}
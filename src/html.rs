@@ -0,0 +1,351 @@
+use std::fmt::Write;
+
+use super::board::Board;
+use super::info::is_pass;
+use super::json::JsonWriter;
+use super::point::Point;
+use super::render::{self, thumbnail_svg, Diagram, RenderOptions};
+use super::replay::Replay;
+use super::review::Flagged;
+use super::vertex::{GameTree, Node};
+use super::writer::unescape_text;
+
+pub use super::text::json_string;
+
+// a node's properties and the position after it, leaving its children
+// open: {"p":{...},"b":"...","c":[
+fn node_json(w: &mut JsonWriter, node: &Node, board: &Board) {
+    w.begin_object();
+    w.key("p");
+    w.begin_object();
+    for prop in &node.props {
+        w.key(&prop.ident);
+        w.begin_array();
+        for value in &prop.values {
+            w.string(value);
+        }
+        w.end_array();
+    }
+    w.end_object();
+    // the position after the node, so the page needs no rules engine
+    let stones: String = board.to_string().lines().collect();
+    w.key("b");
+    w.string(&stones);
+    w.key("c");
+    w.begin_array();
+}
+
+// a node of gt's sequence still to write, with the position before it,
+// or the end of one already written
+enum Step<'a> {
+    Node(&'a GameTree, usize, Board),
+    Close,
+}
+
+// the game as nested nodes: {"p": properties, "b": board, "c": children}
+pub fn json_tree(gt: &GameTree) -> String {
+    let board = Board::for_game(gt);
+    let mut w = JsonWriter::new();
+    w.begin_object();
+    w.key("cols");
+    w.number(board.cols);
+    w.key("rows");
+    w.number(board.rows);
+    w.key("root");
+    // walked with a stack, as a long game nests a level per move
+    let mut stack = Vec::new();
+    if gt.sequence.nodes.is_empty() {
+        w.begin_object();
+        w.key("p");
+        w.begin_object();
+        w.end_object();
+        w.key("b");
+        w.string("");
+        w.key("c");
+        w.begin_array();
+        w.end_array();
+        w.end_object();
+    } else {
+        stack.push(Step::Node(gt, 0, board));
+    }
+    while let Some(step) = stack.pop() {
+        let Step::Node(gt, i, mut board) = step else {
+            w.end_array();
+            w.end_object();
+            continue;
+        };
+        board.apply(&gt.sequence.nodes[i]);
+        node_json(&mut w, &gt.sequence.nodes[i], &board);
+        stack.push(Step::Close);
+        if i + 1 < gt.sequence.nodes.len() {
+            stack.push(Step::Node(gt, i + 1, board));
+        } else {
+            let children = gt.gametrees.iter().filter(|child| !child.sequence.nodes.is_empty());
+            for child in children.rev() {
+                stack.push(Step::Node(child, 0, board.clone()));
+            }
+        }
+    }
+    w.end_object();
+    w.finish()
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// a self-contained page with a clickable board, for sharing a game
+// without a server
+pub fn page(gt: &GameTree) -> String {
+    let title = title(gt);
+    let mut s = String::new();
+    s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(s, "<title>{}</title>", escape(&title));
+    s.push_str(STYLE);
+    s.push_str("</head>\n<body>\n");
+    let _ = writeln!(s, "<h1>{}</h1>", escape(&title));
+    s.push_str(BODY);
+    let _ = writeln!(s, "<script>\nconst GAME = {};", json_tree(gt));
+    s.push_str(SCRIPT);
+    s.push_str("</script>\n</body>\n</html>\n");
+    s
+}
+
+fn title(gt: &GameTree) -> String {
+    let root = gt.root();
+    match (root.value("PB"), root.value("PW")) {
+        (Some(b), Some(w)) => format!("{} (B) vs {} (W)", b, w),
+        _ => root.value("GN").unwrap_or("Game").to_string(),
+    }
+}
+
+// a static post-game review: one row per flagged move with the board
+// after it, why it was flagged and its comment
+pub fn review_page(gt: &GameTree, flagged: &[Flagged], thumb_size: usize) -> render::Result<String> {
+    let title = title(gt);
+    let replay = Replay::new(gt);
+    let (cols, rows) = gt.board_size();
+    let mut s = String::new();
+    s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(s, "<title>Review: {}</title>", escape(&title));
+    s.push_str(REVIEW_STYLE);
+    s.push_str("</head>\n<body>\n");
+    let _ = writeln!(s, "<h1>{}</h1>", escape(&title));
+    if flagged.is_empty() {
+        s.push_str("<p>No flagged moves.</p>\n");
+    } else {
+        s.push_str("<table>\n<tr><th>Move</th><th>Board</th><th>Why</th><th>Comment</th></tr>\n");
+        for f in flagged {
+            let node = replay.nodes().get(f.path.len());
+            // tt is a pass too, up to 19x19
+            let point = node.and_then(|n| n.get_move()).filter(|(_, v)| !is_pass(v, cols, rows)).and_then(|(_, v)| Point::from_sgf(v));
+            let at = point.map(|p| p.to_gtp(rows)).unwrap_or_else(|| "pass".to_string());
+            let reasons: Vec<String> = f.reasons.iter().map(|r| r.to_string()).collect();
+            let comment = node.and_then(|n| n.value("C")).map(unescape_text).unwrap_or_default();
+            let _ = writeln!(
+                s,
+                "<tr><td>{} {} {}</td><td>{}</td><td>{}</td><td class=\"comment\">{}</td></tr>",
+                f.number,
+                f.color.ident(),
+                at,
+                thumbnail_svg(&replay.position_after(f.number), thumb_size)?.trim_end(),
+                escape(&reasons.join(", ")),
+                escape(&comment)
+            );
+        }
+        s.push_str("</table>\n");
+    }
+    s.push_str("</body>\n</html>\n");
+    Ok(s)
+}
+
+// a printable page with a diagram for each figure of the main line, as
+// FG splits it, numbered in the figure's print mode
+pub fn figures_page(gt: &GameTree, opts: &RenderOptions) -> render::Result<String> {
+    let title = title(gt);
+    let mut s = String::new();
+    s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(s, "<title>{}</title>", escape(&title));
+    s.push_str(REVIEW_STYLE);
+    s.push_str("</head>\n<body>\n");
+    let _ = writeln!(s, "<h1>{}</h1>", escape(&title));
+    for (i, figure) in gt.figures().iter().enumerate() {
+        let diagram = render::svg(&Diagram::figure(gt, figure), &opts.for_figure(figure))?;
+        let _ = writeln!(s, "<figure>\n{}\n<figcaption>{}</figcaption>\n</figure>", diagram.trim_end(), escape(&figure.caption(i + 1)));
+    }
+    s.push_str("</body>\n</html>\n");
+    Ok(s)
+}
+
+const REVIEW_STYLE: &str = r##"<style>
+body { font-family: sans-serif; margin: 1em; }
+td, th { padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+.comment { white-space: pre-wrap; max-width: 30em; }
+</style>
+"##;
+
+const STYLE: &str = r##"<style>
+body { font-family: sans-serif; margin: 1em; }
+#board { width: 100%; max-width: 36em; }
+#nav button { font-size: 1em; margin-right: 0.3em; }
+#vars button { margin: 0.2em 0.3em 0 0; }
+#comment { white-space: pre-wrap; max-width: 36em; }
+</style>
+"##;
+
+const BODY: &str = r##"<svg id="board"></svg>
+<div id="nav">
+<button id="first">|&lt;</button><button id="prev">&lt;</button><button id="next">&gt;</button><button id="last">&gt;|</button>
+<span id="status"></span>
+</div>
+<div id="vars"></div>
+<p id="comment"></p>
+"##;
+
+const SCRIPT: &str = r##"const NS = "http://www.w3.org/2000/svg";
+const CELL = 24;
+let path = [GAME.root];
+
+function current() { return path[path.length - 1]; }
+
+function point(v) {
+  if (!v || v.length < 2) return null;
+  const x = v.charCodeAt(0) - 97, y = v.charCodeAt(1) - 97;
+  return x >= 0 && x < GAME.cols && y >= 0 && y < GAME.rows ? [x, y] : null;
+}
+
+function move(node) {
+  const v = (node.p.B || node.p.W || [])[0];
+  return point(v);
+}
+
+function el(name, attrs) {
+  const e = document.createElementNS(NS, name);
+  for (const k in attrs) e.setAttribute(k, attrs[k]);
+  return e;
+}
+
+function draw() {
+  const svg = document.getElementById("board");
+  const w = CELL * (GAME.cols + 1), h = CELL * (GAME.rows + 1);
+  svg.setAttribute("viewBox", "0 0 " + w + " " + h);
+  svg.innerHTML = "";
+  svg.appendChild(el("rect", { width: w, height: h, fill: "#dcb35c" }));
+  const pos = n => CELL * (n + 1);
+  for (let x = 0; x < GAME.cols; x++)
+    svg.appendChild(el("line", { x1: pos(x), y1: pos(0), x2: pos(x), y2: pos(GAME.rows - 1), stroke: "#000" }));
+  for (let y = 0; y < GAME.rows; y++)
+    svg.appendChild(el("line", { x1: pos(0), y1: pos(y), x2: pos(GAME.cols - 1), y2: pos(y), stroke: "#000" }));
+  const node = current();
+  const last = move(node);
+  for (let y = 0; y < GAME.rows; y++) {
+    for (let x = 0; x < GAME.cols; x++) {
+      const c = node.b[y * GAME.cols + x];
+      if (c === "X" || c === "O") {
+        svg.appendChild(el("circle", { cx: pos(x), cy: pos(y), r: CELL * 0.48, fill: c === "X" ? "#000" : "#fff", stroke: "#000" }));
+        if (last && last[0] === x && last[1] === y)
+          svg.appendChild(el("circle", { cx: pos(x), cy: pos(y), r: CELL * 0.2, fill: "none", stroke: c === "X" ? "#fff" : "#000", "stroke-width": 2 }));
+      }
+    }
+  }
+  // the next moves of every variation, clickable
+  node.c.forEach((child, i) => {
+    const p = move(child);
+    if (!p) return;
+    const mark = el("circle", { cx: pos(p[0]), cy: pos(p[1]), r: CELL * 0.3, fill: i === 0 ? "#3a3" : "#a33", opacity: 0.6 });
+    mark.style.cursor = "pointer";
+    mark.addEventListener("click", () => go(child));
+    svg.appendChild(mark);
+  });
+  const vars = document.getElementById("vars");
+  vars.innerHTML = "";
+  if (node.c.length > 1) {
+    node.c.forEach((child, i) => {
+      const b = document.createElement("button");
+      const p = move(child);
+      b.textContent = (i === 0 ? "main" : "var " + i) + (p ? " " + (child.p.B ? "B " : "W ") + child.p[child.p.B ? "B" : "W"][0] : "");
+      b.addEventListener("click", () => go(child));
+      vars.appendChild(b);
+    });
+  }
+  document.getElementById("comment").textContent = (node.p.C || [""])[0];
+  document.getElementById("status").textContent = "move " + path.filter(n => move(n)).length;
+}
+
+function go(child) { path.push(child); draw(); }
+function prev() { if (path.length > 1) { path.pop(); draw(); } }
+function next() { const n = current(); if (n.c.length) go(n.c[0]); }
+function first() { path = [GAME.root]; draw(); }
+function last() { let n = current(); while (n.c.length) { n = n.c[0]; path.push(n); } draw(); }
+
+document.getElementById("first").addEventListener("click", first);
+document.getElementById("prev").addEventListener("click", prev);
+document.getElementById("next").addEventListener("click", next);
+document.getElementById("last").addEventListener("click", last);
+document.addEventListener("keydown", e => {
+  if (e.key === "ArrowLeft") prev();
+  else if (e.key === "ArrowRight") next();
+  else if (e.key === "Home") first();
+  else if (e.key === "End") last();
+});
+draw();
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn json1() {
+        assert_eq!(json_string("a\"b\\c\n</script>"), "\"a\\\"b\\\\c\\n\\u003c/script>\"");
+    }
+
+    #[test]
+    fn json2() {
+        let gt = gametree("(;SZ[3];B[aa](;W[bb])(;W[cc]C[x]))");
+        let json = json_tree(&gt);
+        assert!(json.starts_with("{\"cols\":3,\"rows\":3,\"root\":{\"p\":{\"SZ\":[\"3\"]},\"b\":\".........\""));
+        assert!(json.contains("\"b\":\"X...O....\",\"c\":[]"));
+        assert!(json.contains("{\"p\":{\"W\":[\"cc\"],\"C\":[\"x\"]},\"b\":\"X.......O\",\"c\":[]}"));
+        assert!(json.ends_with("]}]}]}}"));
+        let long = gametree(&format!("(;SZ[3]{})", ";B[];W[]".repeat(50_000)));
+        assert!(json_tree(&long).ends_with(&format!("{}}}", "]}".repeat(100_001))));
+    }
+
+    #[test]
+    fn page1() {
+        let gt = gametree("(;PB[A<b>]PW[C];B[aa])");
+        let page = page(&gt);
+        assert!(page.contains("<title>A&lt;b&gt; (B) vs C (W)</title>"));
+        assert!(page.contains("const GAME = {"));
+        assert!(!page.contains("src=\""));
+    }
+
+    #[test]
+    fn figures1() {
+        let gt = gametree("(;SZ[9]PM[1];B[ee];W[cc]FG[0:The <fight>]PM[0];B[gg])");
+        let page = figures_page(&gt, &RenderOptions::default()).unwrap();
+        assert!(page.contains("<figcaption>Figure 1</figcaption>"));
+        assert!(page.contains("<figcaption>The &lt;fight&gt;</figcaption>"));
+        // the first figure numbers its move, the second hides them
+        assert_eq!(page.matches("fill=\"#fff\" text-anchor").count(), 1);
+    }
+
+    #[test]
+    fn review1() {
+        let gt = gametree("(;SZ[9];B[ee];W[cc]BM[1]C[a <slip>];B[gg])");
+        let flagged = crate::review::flagged_moves(&gt, &[], 3.0);
+        let page = review_page(&gt, &flagged, 64).unwrap();
+        assert!(page.contains("<td>2 W C7</td><td><svg"));
+        assert!(page.contains("<td>bad move</td><td class=\"comment\">a &lt;slip&gt;</td>"));
+        assert_eq!(page.matches("<circle").count(), 2);
+        let gt = gametree("(;SZ[9];B[ee];W[tt]BM[1])");
+        let page = review_page(&gt, &crate::review::flagged_moves(&gt, &[], 3.0), 64).unwrap();
+        assert!(page.contains("<td>2 W pass</td>"));
+    }
+}
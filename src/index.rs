@@ -0,0 +1,524 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::intern::Interner;
+use super::point::{canonical, Point, SYMMETRIES};
+use super::vertex::{Collection, GameTree};
+
+pub const DEFAULT_OPENING_DEPTH: usize = 20;
+
+const HEADER: &str = "# sgf index v1 depth=";
+// the line after the entries of a file once all of its games are in,
+// followed by the file's source
+const DONE: &str = "# done\t";
+
+#[derive(Debug)]
+pub enum Error {
+    IndexError(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IndexError(s) => write!(f, "{}", s),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+// what the index remembers about one game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    // usually the path of the file the game came from
+    pub source: Arc<str>,
+    // position of the game inside its collection
+    pub game: usize,
+    pub size: (usize, usize),
+    pub event: Option<Arc<str>>,
+    // the first moves in canonical orientation, stopping at the first pass
+    pub opening: Vec<Point>,
+}
+
+impl Entry {
+    // one tab separated line of the on-disk format
+    pub fn to_line(&self) -> String {
+        let opening: String = self.opening.iter().map(|p| p.to_sgf()).collect();
+        format!(
+            "{}\t{}\t{}x{}\t{}\t{}",
+            escape(&self.source),
+            self.game,
+            self.size.0,
+            self.size.1,
+            escape(self.event.as_deref().unwrap_or("")),
+            opening,
+        )
+    }
+
+    pub fn from_line(line: &str) -> Option<Entry> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 5 {
+            return None;
+        }
+        let (cols, rows) = fields[2].split_once('x')?;
+        let event = unescape(fields[3]);
+        let mut opening = Vec::new();
+        let chars: Vec<char> = fields[4].chars().collect();
+        for pair in chars.chunks(2) {
+            let s: String = pair.iter().collect();
+            opening.push(Point::from_sgf(&s)?);
+        }
+        Some(Entry {
+            source: unescape(fields[0]).into(),
+            game: fields[1].parse().ok()?,
+            size: (cols.parse().ok()?, rows.parse().ok()?),
+            event: if event.is_empty() { None } else { Some(event.into()) },
+            opening,
+        })
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub depth: usize,
+    entries: Vec<Entry>,
+    openings: BTreeMap<(usize, Vec<Point>), Vec<usize>>,
+    // the game numbers indexed from each source
+    games: HashMap<Arc<str>, HashSet<usize>>,
+    // sources whose games are all in
+    done: HashSet<Arc<str>>,
+    // shares one copy of each source and event between entries when set
+    interner: Option<Interner>,
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index::new(DEFAULT_OPENING_DEPTH)
+    }
+}
+
+impl Index {
+    pub fn new(depth: usize) -> Self {
+        Index {
+            depth,
+            entries: Vec::new(),
+            openings: BTreeMap::new(),
+            games: HashMap::new(),
+            done: HashSet::new(),
+            interner: None,
+        }
+    }
+
+    // an index that keeps a single copy of repeated sources and events,
+    // for archives too big to hold a string per entry
+    pub fn with_interning(mut self) -> Self {
+        if self.interner.is_none() {
+            let mut interner = Interner::new();
+            for entry in &mut self.entries {
+                intern_entry(&mut interner, entry);
+            }
+            self.games = self.games.drain().map(|(source, games)| (interner.intern_arc(source), games)).collect();
+            self.done = self.done.drain().map(|source| interner.intern_arc(source)).collect();
+            self.interner = Some(interner);
+        }
+        self
+    }
+
+    pub fn interner(&self) -> Option<&Interner> {
+        self.interner.as_ref()
+    }
+
+    // reads an index written by save or by appending entries. a partly
+    // written last line from an interrupted run is ignored
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Index> {
+        Index::load_with(path, false)
+    }
+
+    // load, sharing repeated strings as with_interning does
+    pub fn load_interned<P: AsRef<Path>>(path: P) -> Result<Index> {
+        Index::load_with(path, true)
+    }
+
+    fn load_with<P: AsRef<Path>>(path: P, interning: bool) -> Result<Index> {
+        let data = fs::read_to_string(path)?;
+        let mut lines = data.lines();
+        let depth = match lines.next().and_then(|h| h.strip_prefix(HEADER)) {
+            Some(depth) => depth.parse().map_err(|_| Error::IndexError(format!("bad depth: {}", depth)))?,
+            None => return Err(Error::IndexError("missing index header".to_string())),
+        };
+        let mut index = Index::new(depth);
+        if interning {
+            index = index.with_interning();
+        }
+        let lines: Vec<&str> = lines.collect();
+        for (i, line) in lines.iter().enumerate() {
+            let last = i + 1 == lines.len() && !data.ends_with('\n');
+            if let Some(source) = line.strip_prefix(DONE) {
+                // a cut off source would mark the wrong file
+                if !last {
+                    index.mark_done(&unescape(source));
+                }
+                continue;
+            }
+            match Entry::from_line(line) {
+                Some(entry) => index.insert(entry),
+                None if last => {},
+                None => return Err(Error::IndexError(format!("bad index line {}", i + 2))),
+            }
+        }
+        Ok(index)
+    }
+
+    // opens an index file to add entries to, as a resumed run does. a
+    // partly written last line, which load skipped, is cut off first, and
+    // a whole entry missing only its newline gets it
+    pub fn open_to_append<P: AsRef<Path>>(path: P) -> Result<fs::File> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)?;
+        let mut f = OpenOptions::new().write(true).open(path)?;
+        if !data.is_empty() && !data.ends_with('\n') {
+            let start = data.rfind('\n').map_or(0, |i| i + 1);
+            let line = &data[start..];
+            if start > 0 && (line.starts_with(DONE) || Entry::from_line(line).is_none()) {
+                f.set_len(start as u64)?;
+            } else {
+                f.seek(SeekFrom::End(0))?;
+                f.write_all(b"\n")?;
+            }
+        }
+        f.seek(SeekFrom::End(0))?;
+        Ok(f)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut f = io::BufWriter::new(fs::File::create(path)?);
+        self.write_header(&mut f)?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.to_line())?;
+        }
+        let mut done: Vec<&str> = self.done.iter().map(|s| s.as_ref()).collect();
+        done.sort();
+        for source in done {
+            writeln!(f, "{}", Index::done_line(source))?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+
+    pub fn write_header<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "{}{}", HEADER, self.depth)
+    }
+
+    // whether every game of this source is indexed. a run cut off in the
+    // middle of a file, or one that failed on some of its games, leaves
+    // it to be read again
+    pub fn is_done(&self, source: &str) -> bool {
+        self.done.contains(source)
+    }
+
+    pub fn has_game(&self, source: &str, game: usize) -> bool {
+        self.games.get(source).is_some_and(|games| games.contains(&game))
+    }
+
+    pub fn mark_done(&mut self, source: &str) {
+        let source = match &mut self.interner {
+            Some(interner) => interner.intern(source),
+            None => Arc::from(source),
+        };
+        self.done.insert(source);
+    }
+
+    // what an index file has after the entries of a source once
+    // mark_done is called for it
+    pub fn done_line(source: &str) -> String {
+        format!("{}{}", DONE, escape(source))
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn add_collection(&mut self, source: &str, coll: &Collection) {
+        for (i, gt) in coll.gametrees.iter().enumerate() {
+            self.add_gametree(source, i, gt);
+        }
+        self.mark_done(source);
+    }
+
+    pub fn add_gametree(&mut self, source: &str, game: usize, gt: &GameTree) {
+        let entry = self.entry(source, game, gt);
+        self.insert(entry);
+    }
+
+    pub fn entry(&self, source: &str, game: usize, gt: &GameTree) -> Entry {
+        let size = gt.board_size();
+        Entry {
+            source: source.into(),
+            game,
+            size,
+            event: gt.event().group_key().map(Arc::from),
+            opening: normalize(&self.opening(gt), size),
+        }
+    }
+
+    // the moves of gt the index would keep, as played, stopping at the
+    // first pass
+    pub fn opening(&self, gt: &GameTree) -> Vec<Point> {
+        let mut moves = Vec::new();
+        for (_, point) in gt.moves().into_iter().take(self.depth) {
+            match point {
+                Some(p) => moves.push(p),
+                None => break,
+            }
+        }
+        moves
+    }
+
+    pub fn insert(&mut self, mut entry: Entry) {
+        if let Some(interner) = &mut self.interner {
+            intern_entry(interner, &mut entry);
+        }
+        let key = (entry.size.0, entry.opening.clone());
+        // only square boards are normalized, so only they can be searched
+        if entry.size.0 == entry.size.1 {
+            self.openings.entry(key).or_default().push(self.entries.len());
+        }
+        self.games.entry(entry.source.clone()).or_default().insert(entry.game);
+        self.entries.push(entry);
+    }
+
+    // games on 19x19 starting with the given moves in any orientation
+    pub fn find_by_opening(&self, moves: &[Point]) -> Vec<&Entry> {
+        self.find_by_opening_sized(19, moves)
+    }
+
+    pub fn find_by_opening_sized(&self, size: usize, moves: &[Point]) -> Vec<&Entry> {
+        if moves.len() > self.depth {
+            return Vec::new();
+        }
+        let mut ids: Vec<usize> = Vec::new();
+        // a stored opening is some transform of the game, so the query has
+        // to be tried in every orientation against the stored prefixes
+        // a query off the board is only tried as it is, as it was stored
+        for sym in SYMMETRIES {
+            let query = sym.apply_all(moves, size).unwrap_or_else(|| moves.to_vec());
+            let start = (size, query.clone());
+            for ((s, opening), found) in self.openings.range(start..) {
+                if *s != size || !opening.starts_with(&query) {
+                    break;
+                }
+                ids.extend(found);
+            }
+        }
+        ids.sort();
+        ids.dedup();
+        ids.into_iter().map(|i| &self.entries[i]).collect()
+    }
+
+    // 19x19 games ranked by how many of their first moves agree with moves
+    // in some orientation, with the number shared
+    pub fn nearest(&self, moves: &[Point], limit: usize) -> Vec<(usize, &Entry)> {
+        self.nearest_sized(19, moves, limit)
+    }
+
+    // the longest shared openings come first, ties in index order. games
+    // sharing no moves are left out
+    pub fn nearest_sized(&self, size: usize, moves: &[Point], limit: usize) -> Vec<(usize, &Entry)> {
+        let moves = &moves[..moves.len().min(self.depth)];
+        // each stored opening was turned by whatever made all of it
+        // smallest, so every orientation of the query is tried against it
+        let queries: Vec<Vec<Point>> = SYMMETRIES.iter().map(|sym| sym.apply_all(moves, size).unwrap_or_else(|| moves.to_vec())).collect();
+        let mut found: Vec<(usize, &Entry)> = self
+            .entries
+            .iter()
+            .filter(|e| e.size == (size, size))
+            .filter_map(|e| {
+                let shared = queries.iter().map(|q| q.iter().zip(&e.opening).take_while(|(a, b)| a == b).count()).max()?;
+                (shared > 0).then_some((shared, e))
+            })
+            .collect();
+        found.sort_by_key(|(shared, _)| Reverse(*shared));
+        found.truncate(limit);
+        found
+    }
+
+    // games grouped by tournament, for games that name their event
+    pub fn by_event(&self) -> BTreeMap<&str, Vec<&Entry>> {
+        let mut groups: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+        for entry in &self.entries {
+            if let Some(event) = &entry.event {
+                groups.entry(event).or_default().push(entry);
+            }
+        }
+        groups
+    }
+}
+
+fn intern_entry(interner: &mut Interner, entry: &mut Entry) {
+    entry.source = interner.intern_arc(entry.source.clone());
+    if let Some(event) = entry.event.take() {
+        entry.event = Some(interner.intern_arc(event));
+    }
+}
+
+fn normalize(moves: &[Point], size: (usize, usize)) -> Vec<Point> {
+    if size.0 != size.1 {
+        return moves.to_vec();
+    }
+    canonical(moves, size.0).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn points(s: &[&str]) -> Vec<Point> {
+        s.iter().map(|p| Point::from_gtp(p, 19).unwrap()).collect()
+    }
+
+    fn index() -> Index {
+        let text = "(;GM[1]EV[Meijin];B[pd];W[dd];B[pq];W[dp])\
+                    (;GM[1];B[dp];W[pp];B[dd])\
+                    (;GM[1]EV[meijin ];B[qd];W[dd])\
+                    (;GM[1]SZ[9];B[ee])";
+        let coll = Parser::new(text).parse().unwrap();
+        let mut index = Index::new(3);
+        index.add_collection("games.sgf", &coll);
+        index
+    }
+
+    #[test]
+    fn index1() {
+        let index = index();
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.entries()[0].opening.len(), 3);
+        // D4 Q4 is Q16 D16 rotated, so both of the first two games match
+        let found = index.find_by_opening(&points(&["Q16", "D16"]));
+        assert_eq!(found.iter().map(|e| e.game).collect::<Vec<_>>(), vec![0, 1]);
+        let found = index.find_by_opening(&points(&["R16"]));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].game, 2);
+        assert_eq!(index.find_by_opening(&[]).len(), 3);
+        assert_eq!(index.find_by_opening_sized(9, &[Point::from_gtp("E5", 9).unwrap()]).len(), 1);
+    }
+
+    #[test]
+    fn index2() {
+        let index = index();
+        let groups = index.by_event();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups["meijin"].len(), 2);
+    }
+
+    #[test]
+    fn index3() {
+        let index = index();
+        let path = std::env::temp_dir().join(format!("sgf-index3-{}.tsv", std::process::id()));
+        index.save(&path).unwrap();
+        let loaded = Index::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.depth, 3);
+        assert_eq!(loaded.entries(), index.entries());
+        assert!(loaded.is_done("games.sgf") && loaded.has_game("games.sgf", 2));
+        assert_eq!(loaded.find_by_opening(&points(&["Q16", "D16"])).len(), 2);
+    }
+
+    #[test]
+    fn index7() {
+        let index = index();
+        let path = std::env::temp_dir().join(format!("sgf-index7-{}.tsv", std::process::id()));
+        let lines: Vec<String> = index.entries().iter().map(|e| e.to_line()).collect();
+        // an interrupted run, cut off in the middle of its third entry
+        let mut cut = lines[2].clone();
+        cut.truncate(3);
+        fs::write(&path, format!("{}{}\n{}\n{}\n{}", HEADER, 3, lines[0], lines[1], cut)).unwrap();
+        assert_eq!(Index::load(&path).unwrap().len(), 2);
+        let mut f = Index::open_to_append(&path).unwrap();
+        writeln!(f, "{}", lines[2]).unwrap();
+        drop(f);
+        assert_eq!(Index::load(&path).unwrap().len(), 3);
+        // a last entry that only lost its newline is kept
+        fs::write(&path, format!("{}{}\n{}", HEADER, 3, lines[0])).unwrap();
+        let mut f = Index::open_to_append(&path).unwrap();
+        writeln!(f, "{}", lines[1]).unwrap();
+        drop(f);
+        assert_eq!(Index::load(&path).unwrap().entries(), &index.entries()[..2]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn index4() {
+        let mut entry = index().entries()[0].clone();
+        entry.source = "odd\tname\\.sgf".into();
+        assert_eq!(Entry::from_line(&entry.to_line()), Some(entry));
+    }
+
+    #[test]
+    fn index6() {
+        let index = index();
+        // the second game is D4 Q4 D16 here; turned, the first starts Q16 D16
+        let found = index.nearest(&points(&["D4", "Q4", "D16"]), 10);
+        let ranked: Vec<(usize, usize)> = found.iter().map(|(shared, e)| (*shared, e.game)).collect();
+        // the third starts on a 3-4 point, which no turn of D4 is
+        assert_eq!(ranked, vec![(3, 1), (2, 0)]);
+        assert_eq!(index.nearest(&points(&["D4", "Q4", "D16"]), 1).len(), 1);
+        assert!(index.nearest(&points(&["K10"]), 10).is_empty());
+        let gt = Parser::new("(;B[pd];W[dd];B[])").parse().unwrap().gametrees.remove(0);
+        assert_eq!(index.opening(&gt), points(&["Q16", "D16"]));
+    }
+
+    #[test]
+    fn index5() {
+        let index = index().with_interning();
+        let entries = index.entries();
+        assert!(Arc::ptr_eq(&entries[0].source, &entries[3].source));
+        assert!(Arc::ptr_eq(entries[0].event.as_ref().unwrap(), entries[2].event.as_ref().unwrap()));
+        // one source and one event
+        assert_eq!(index.interner().unwrap().len(), 2);
+        assert_eq!(index.by_event()["meijin"].len(), 2);
+    }
+}
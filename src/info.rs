@@ -0,0 +1,527 @@
+use std::fmt;
+
+use super::properties::{describe, Context};
+use super::vertex::{Collection, Color, GameTree, Node};
+use super::writer::unescape_text;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WinReason {
+    Score(f64),
+    Resign,
+    Time,
+    Forfeit,
+    // "B+" with no further detail
+    Unspecified,
+}
+
+// the RE property
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    Win(Color, WinReason),
+    Draw,
+    // no result or suspended play
+    Void,
+    Unknown,
+}
+
+impl GameResult {
+    pub fn parse(s: &str) -> GameResult {
+        let s = s.trim();
+        match s {
+            "0" | "Draw" | "Jigo" => return GameResult::Draw,
+            "Void" => return GameResult::Void,
+            _ => {},
+        }
+        let (winner, reason) = match s.split_once('+') {
+            Some(parts) => parts,
+            None => return GameResult::Unknown,
+        };
+        let color = match Color::from_ident(winner.trim()) {
+            Some(color) => color,
+            None => return GameResult::Unknown,
+        };
+        let reason = match reason.trim() {
+            "" => WinReason::Unspecified,
+            "R" | "Resign" => WinReason::Resign,
+            "T" | "Time" => WinReason::Time,
+            "F" | "Forfeit" => WinReason::Forfeit,
+            r => match r.parse() {
+                Ok(n) => WinReason::Score(n),
+                Err(_) => return GameResult::Unknown,
+            },
+        };
+        GameResult::Win(color, reason)
+    }
+
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            GameResult::Win(color, _) => Some(*color),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::Win(color, reason) => {
+                write!(f, "{}+", color.ident())?;
+                match reason {
+                    WinReason::Score(n) => write!(f, "{}", n),
+                    WinReason::Resign => write!(f, "R"),
+                    WinReason::Time => write!(f, "T"),
+                    WinReason::Forfeit => write!(f, "F"),
+                    WinReason::Unspecified => Ok(()),
+                }
+            },
+            GameResult::Draw => write!(f, "0"),
+            GameResult::Void => write!(f, "Void"),
+            GameResult::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+// the OT property. the spec leaves it free text, so only the
+// common server spellings are recognized
+#[derive(Debug, Clone, PartialEq)]
+pub enum Overtime {
+    // absolute time, no overtime
+    Absolute,
+    ByoYomi { periods: u32, seconds: u32 },
+    Canadian { stones: u32, seconds: u32 },
+    Fischer { increment: u32 },
+    Other(String),
+}
+
+impl Overtime {
+    pub fn parse(s: &str) -> Overtime {
+        let lower = s.trim().to_lowercase();
+        if lower.is_empty() || lower == "none" || lower == "absolute" {
+            return Overtime::Absolute;
+        }
+        let words: Vec<&str> = lower.split_whitespace().collect();
+        for word in &words {
+            if let Some((a, b)) = word.split_once('x') {
+                if let (Some(periods), Some(seconds)) = (parse_count(a), parse_seconds(b)) {
+                    return Overtime::ByoYomi {periods, seconds};
+                }
+            }
+            if let Some((a, b)) = word.split_once('/') {
+                if let (Some(a), Some(b)) = (parse_seconds(a), parse_seconds(b)) {
+                    // "25/600" is stones per time, but some clients write it backwards
+                    let (stones, seconds) = if a <= b { (a, b) } else { (b, a) };
+                    return Overtime::Canadian {stones, seconds};
+                }
+            }
+        }
+        if lower.contains("fischer") || lower.contains("increment") {
+            for word in &words {
+                if let Some(increment) = parse_seconds(word.trim_start_matches('+')) {
+                    return Overtime::Fischer {increment};
+                }
+            }
+        }
+        Overtime::Other(s.trim().to_string())
+    }
+}
+
+impl fmt::Display for Overtime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Overtime::Absolute => write!(f, "none"),
+            Overtime::ByoYomi {periods, seconds} => write!(f, "{}x{} byo-yomi", periods, seconds),
+            Overtime::Canadian {stones, seconds} => write!(f, "{}/{} Canadian", stones, seconds),
+            Overtime::Fischer {increment} => write!(f, "{} fischer", increment),
+            Overtime::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+fn parse_count(s: &str) -> Option<u32> {
+    s.parse().ok()
+}
+
+// "30", "30s", "10m" or "10min". None for more seconds than fit
+fn parse_seconds(s: &str) -> Option<u32> {
+    if let Some(m) = s.strip_suffix("min").or_else(|| s.strip_suffix('m')) {
+        return m.parse::<u32>().ok()?.checked_mul(60);
+    }
+    s.strip_suffix('s').unwrap_or(s).parse().ok()
+}
+
+// RO, which the spec writes as "RN (type)" but which shows up as
+// "Round 3 of 5", "R3", "3/5", "01 (final)" and so on
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Round {
+    pub number: Option<u32>,
+    pub of: Option<u32>,
+    pub kind: Option<String>,
+}
+
+impl Round {
+    pub fn parse(s: &str) -> Round {
+        let mut round = Round::default();
+        let mut rest = s.trim().to_string();
+        if let Some(open) = rest.find('(') {
+            let close = rest[open..].find(')').map(|i| open + i).unwrap_or(rest.len());
+            let kind = normalize_text(&rest[open+1..close]);
+            if !kind.is_empty() {
+                round.kind = Some(kind);
+            }
+            rest.replace_range(open..(close + 1).min(rest.len()), " ");
+        }
+        let lower = rest.to_lowercase().replace('/', " of ");
+        let mut numbers = Vec::new();
+        let mut after_of = false;
+        for word in lower.split(|c: char| !c.is_alphanumeric()) {
+            if word == "of" {
+                after_of = true;
+                continue;
+            }
+            let digits: String = word.chars().filter(|c| c.is_ascii_digit()).collect();
+            // skips ordinal suffixes and prefixes like "r3" or "3rd"
+            if let Ok(n) = digits.parse::<u32>() {
+                numbers.push((n, after_of));
+            }
+            after_of = false;
+        }
+        for (n, of) in numbers {
+            if of {
+                round.of = round.of.or(Some(n));
+            } else if round.number.is_none() {
+                round.number = Some(n);
+            }
+        }
+        if round.kind.is_none() {
+            for kind in ["final", "semifinal", "semi-final", "quarterfinal", "playoff"] {
+                if lower.split_whitespace().any(|w| w == kind) {
+                    round.kind = Some(kind.to_string());
+                }
+            }
+        }
+        round
+    }
+}
+
+// the tournament side of the game info: EV, RO, PC and SO
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Event {
+    pub name: Option<String>,
+    pub round: Option<Round>,
+    pub place: Option<String>,
+    pub source: Option<String>,
+}
+
+impl Event {
+    // games of one tournament share this key regardless of spacing and case
+    pub fn group_key(&self) -> Option<String> {
+        self.name.as_ref().map(|name| name.to_lowercase())
+    }
+}
+
+// the scanner drops leading zeros, so 2016-03-09 may come as 2016-3-9.
+// padded back, dates compare and sort as text. each date of a list is
+// padded, and the parts of a partial one like 2016-03 or 03-10
+pub fn pad_date(date: &str) -> String {
+    let pad = |d: &str| d.split('-').map(|part| format!("{:0>2}", part.trim())).collect::<Vec<_>>().join("-");
+    date.split(',').map(pad).collect::<Vec<_>>().join(",")
+}
+
+// collapses runs of whitespace (including newlines) into single spaces
+pub fn normalize_text(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalized(node: &Node, ident: &str) -> Option<String> {
+    let s = normalize_text(node.value(ident)?);
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+impl GameTree {
+    pub fn event(&self) -> Event {
+        let root = self.root();
+        Event {
+            name: normalized(root, "EV"),
+            round: normalized(root, "RO").map(|ro| Round::parse(&ro)),
+            place: normalized(root, "PC"),
+            source: normalized(root, "SO"),
+        }
+    }
+
+    pub fn overtime(&self) -> Option<Overtime> {
+        self.root().value("OT").map(Overtime::parse)
+    }
+
+    // SZ is either "n" or "cols:rows", 19 when missing. sizes past what
+    // the point letters reach, or 0, are brought into 1..=52
+    pub fn board_size(&self) -> (usize, usize) {
+        let sz = match self.root().value("SZ") {
+            Some(sz) => sz,
+            None => return (19, 19),
+        };
+        let parsed = match sz.split_once(':') {
+            Some((c, r)) => c.trim().parse().ok().zip(r.trim().parse().ok()),
+            None => sz.trim().parse().ok().map(|n| (n, n)),
+        };
+        let (cols, rows): (usize, usize) = parsed.unwrap_or((19, 19));
+        (cols.clamp(1, 52), rows.clamp(1, 52))
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.root().value("RE").map(GameResult::parse)
+    }
+
+    // the last move of the main line with its move number
+    pub fn last_move(&self) -> Option<(usize, &Node)> {
+        let mut last = None;
+        let mut n = 0;
+        for node in self.mainline() {
+            if node.get_move().is_some() {
+                n += 1;
+                last = Some((n, node));
+            }
+        }
+        last
+    }
+
+    // a game is over once it has a result, or when it ends in two passes
+    pub fn is_finished(&self) -> bool {
+        match self.result() {
+            Some(GameResult::Unknown) => {},
+            Some(_) => return true,
+            None => {},
+        }
+        let (cols, rows) = self.board_size();
+        let moves: Vec<&str> = self.mainline()
+            .into_iter()
+            .filter_map(|node| node.get_move())
+            .map(|(_, value)| value)
+            .collect();
+        moves.len() >= 2 && moves[moves.len()-2..].iter().all(|v| is_pass(v, cols, rows))
+    }
+
+    // the node the game ended on when someone resigned
+    pub fn resignation_node(&self) -> Option<&Node> {
+        match self.result() {
+            Some(GameResult::Win(_, WinReason::Resign)) => self.mainline().last().copied(),
+            _ => None,
+        }
+    }
+}
+
+// the game-info properties of a game in typed fields. text is unescaped
+// with runs of whitespace made one space, and empty values count as
+// missing, as do numbers that don't read as numbers
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GameInfo {
+    pub black: Option<String>,
+    pub white: Option<String>,
+    pub black_rank: Option<String>,
+    pub white_rank: Option<String>,
+    pub black_team: Option<String>,
+    pub white_team: Option<String>,
+    pub result: Option<GameResult>,
+    pub komi: Option<f64>,
+    pub handicap: Option<u32>,
+    // columns and rows, 19x19 when SZ is missing
+    pub size: (usize, usize),
+    // DT with its whitespace collapsed and the leading zeros the parser
+    // drops put back, like 2016-03-09. it may list several dates
+    pub date: Option<String>,
+    pub event: Event,
+    pub name: Option<String>,
+    pub rules: Option<String>,
+    // main time in seconds
+    pub time_limit: Option<f64>,
+    pub overtime: Option<Overtime>,
+    pub opening: Option<String>,
+    pub annotator: Option<String>,
+    pub user: Option<String>,
+    pub copyright: Option<String>,
+    // GC, with its line breaks kept
+    pub comment: Option<String>,
+}
+
+fn text(node: &Node, ident: &str) -> Option<String> {
+    let s = normalize_text(&unescape_text(node.value(ident)?));
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+fn number<T: std::str::FromStr>(node: &Node, ident: &str) -> Option<T> {
+    node.value(ident)?.trim().parse().ok()
+}
+
+impl GameTree {
+    // where the game info is: the root, or in files that keep it further
+    // down, the first node of the main line that has any
+    pub fn info_node(&self) -> &Node {
+        let has_info = |node: &&Node| node.props.iter().any(|p| describe(&p.ident).is_some_and(|info| info.context == Context::GameInfo));
+        self.mainline().into_iter().find(has_info).unwrap_or(self.root())
+    }
+
+    pub fn game_info(&self) -> GameInfo {
+        let node = self.info_node();
+        let event = Event {
+            name: text(node, "EV"),
+            round: text(node, "RO").map(|ro| Round::parse(&ro)),
+            place: text(node, "PC"),
+            source: text(node, "SO"),
+        };
+        GameInfo {
+            black: text(node, "PB"),
+            white: text(node, "PW"),
+            black_rank: text(node, "BR"),
+            white_rank: text(node, "WR"),
+            black_team: text(node, "BT"),
+            white_team: text(node, "WT"),
+            result: text(node, "RE").map(|re| GameResult::parse(&re)),
+            komi: number(node, "KM").filter(|k: &f64| k.is_finite()),
+            handicap: number(node, "HA"),
+            size: self.board_size(),
+            date: text(node, "DT").map(|dt| pad_date(&dt)),
+            event,
+            name: text(node, "GN"),
+            rules: text(node, "RU"),
+            time_limit: number(node, "TM").filter(|t: &f64| t.is_finite()),
+            overtime: text(node, "OT").map(|ot| Overtime::parse(&ot)),
+            opening: text(node, "ON"),
+            annotator: text(node, "AN"),
+            user: text(node, "US"),
+            copyright: text(node, "CP"),
+            comment: node.value("GC").map(|gc| unescape_text(gc).trim().to_string()).filter(|gc| !gc.is_empty()),
+        }
+    }
+}
+
+impl Collection {
+    // the game info of each game, in order
+    pub fn game_info(&self) -> Vec<GameInfo> {
+        self.gametrees.iter().map(|gt| gt.game_info()).collect()
+    }
+}
+
+// B[] is a pass, and so is B[tt] on boards up to 19x19 for FF[3] compatibility
+pub fn is_pass(value: &str, cols: usize, rows: usize) -> bool {
+    value.is_empty() || (value == "tt" && cols <= 19 && rows <= 19)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn result1() {
+        assert_eq!(GameResult::parse("W+R"), GameResult::Win(Color::White, WinReason::Resign));
+        assert_eq!(GameResult::parse("B+3.5"), GameResult::Win(Color::Black, WinReason::Score(3.5)));
+        assert_eq!(GameResult::parse("B+"), GameResult::Win(Color::Black, WinReason::Unspecified));
+        assert_eq!(GameResult::parse("0"), GameResult::Draw);
+        assert_eq!(GameResult::parse("?"), GameResult::Unknown);
+        assert_eq!(GameResult::parse("W+Time").to_string(), "W+T");
+        assert_eq!(GameResult::parse("B+12.5").to_string(), "B+12.5");
+    }
+
+    #[test]
+    fn overtime1() {
+        assert_eq!(Overtime::parse("5x30 byo-yomi"), Overtime::ByoYomi {periods: 5, seconds: 30});
+        assert_eq!(Overtime::parse("3x30s byoyomi"), Overtime::ByoYomi {periods: 3, seconds: 30});
+        assert_eq!(Overtime::parse("25/600 Canadian"), Overtime::Canadian {stones: 25, seconds: 600});
+        assert_eq!(Overtime::parse("10m/25 canadian"), Overtime::Canadian {stones: 25, seconds: 600});
+        assert_eq!(Overtime::parse("Fischer +10s"), Overtime::Fischer {increment: 10});
+        assert_eq!(Overtime::parse(""), Overtime::Absolute);
+        assert_eq!(Overtime::parse("3x100000000m"), Overtime::Other("3x100000000m".to_string()));
+        assert_eq!(Overtime::parse("sudden death?"), Overtime::Other("sudden death?".to_string()));
+    }
+
+    #[test]
+    fn overtime2() {
+        for s in ["5x30 byo-yomi", "25/600 Canadian", "10 fischer", "none"] {
+            assert_eq!(Overtime::parse(s).to_string(), s);
+        }
+        let gt = gametree("(;GM[1]OT[1x60 byo-yomi])");
+        assert_eq!(gt.overtime(), Some(Overtime::ByoYomi {periods: 1, seconds: 60}));
+    }
+
+    #[test]
+    fn round1() {
+        let r = Round::parse("Round 3 of 5");
+        assert_eq!((r.number, r.of, r.kind), (Some(3), Some(5), None));
+        let r = Round::parse("01 (final)");
+        assert_eq!((r.number, r.of, r.kind.as_deref()), (Some(1), None, Some("final")));
+        let r = Round::parse("R4/7");
+        assert_eq!((r.number, r.of), (Some(4), Some(7)));
+        let r = Round::parse("3rd round, semifinal");
+        assert_eq!((r.number, r.kind.as_deref()), (Some(3), Some("semifinal")));
+        assert_eq!(Round::parse("final"), Round {number: None, of: None, kind: Some("final".to_string())});
+    }
+
+    #[test]
+    fn event1() {
+        let gt = gametree("(;GM[1]EV[  47th  Honinbo\n League ]RO[Round 2 of 7]PC[Tokyo]SO[])");
+        let ev = gt.event();
+        assert_eq!(ev.name.as_deref(), Some("47th Honinbo League"));
+        assert_eq!(ev.round.unwrap().number, Some(2));
+        assert_eq!(ev.place.as_deref(), Some("Tokyo"));
+        assert_eq!(ev.source, None);
+        let gt = gametree("(;GM[1]EV[47th honinbo League])");
+        assert_eq!(gt.event().group_key().unwrap(), "47th honinbo league");
+    }
+
+    #[test]
+    fn ending1() {
+        let gt = gametree("(;GM[1]RE[W+R];B[aa];W[bb];B[cc]C[resigns])");
+        assert!(gt.is_finished());
+        let (n, node) = gt.last_move().unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(node.value("B"), Some("cc"));
+        assert_eq!(gt.resignation_node().unwrap().value("C"), Some("resigns"));
+    }
+
+    #[test]
+    fn ending2() {
+        let gt = gametree("(;GM[1];B[aa];W[bb])");
+        assert!(!gt.is_finished());
+        assert!(gt.resignation_node().is_none());
+        let gt = gametree("(;GM[1]RE[?];B[aa];W[];B[tt])");
+        assert!(gt.is_finished());
+        let gt = gametree("(;GM[1]SZ[9];B[aa]C[no moves])");
+        assert_eq!(gt.board_size(), (9, 9));
+        assert_eq!(gametree("(;SZ[0])").board_size(), (1, 1));
+        assert_eq!(gametree("(;SZ[400:7])").board_size(), (52, 7));
+        let gt = gametree("(;GM[1]C[no moves])");
+        assert!(gt.last_move().is_none());
+    }
+
+    #[test]
+    fn game_info1() {
+        let coll = Parser::new("(;GM[1]SZ[13]PB[Lee  Sedol]BR[9p]PW[AlphaGo]RE[W+R]KM[7.5]HA[0]DT[2016-03-09]EV[Google DeepMind Challenge]RO[1]RU[Chinese]TM[7200]OT[3x60 byo-yomi]GC[first\ngame]PC[])(;GM[1]AP[x];PB[b]KM[six])").parse().unwrap();
+        let infos = coll.game_info();
+        let info = &infos[0];
+        assert_eq!((info.black.as_deref(), info.black_rank.as_deref(), info.white.as_deref()), (Some("Lee Sedol"), Some("9p"), Some("AlphaGo")));
+        assert_eq!(info.result, Some(GameResult::Win(Color::White, WinReason::Resign)));
+        assert_eq!((info.komi, info.handicap, info.size), (Some(7.5), Some(0), (13, 13)));
+        assert_eq!(info.event.round.as_ref().unwrap().number, Some(1));
+        assert_eq!(info.event.place, None);
+        assert_eq!(info.overtime, Some(Overtime::ByoYomi { periods: 3, seconds: 60 }));
+        assert_eq!(info.time_limit, Some(7200.0));
+        assert_eq!(info.comment.as_deref(), Some("first\ngame"));
+        assert_eq!(info.date.as_deref(), Some("2016-03-09"));
+        assert_eq!(pad_date("2016-3-9,10"), "2016-03-09,10");
+        // game info below the root is found, and a komi that isn't a number is left out
+        assert_eq!(infos[1].black.as_deref(), Some("b"));
+        assert_eq!(infos[1].komi, None);
+        assert_eq!(infos[1].rules, None);
+    }
+}
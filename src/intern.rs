@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// hands out one shared copy of each distinct string. archives repeat the
+// same sources, events, rule sets and player names across many games, so
+// keeping one copy of each cuts the memory of big indexes several-fold
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    // the shared copy of s, made on first sight
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(s) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = Arc::from(s);
+        self.strings.insert(shared.clone());
+        shared
+    }
+
+    // like intern, but reuses an Arc that is already the shared copy
+    pub fn intern_arc(&mut self, s: Arc<str>) -> Arc<str> {
+        match self.strings.get(&s) {
+            Some(shared) => shared.clone(),
+            None => {
+                self.strings.insert(s.clone());
+                s
+            },
+        }
+    }
+
+    // distinct strings held
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    // bytes of text held, each distinct string counted once
+    pub fn bytes(&self) -> usize {
+        self.strings.iter().map(|s| s.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern1() {
+        let mut interner = Interner::new();
+        let a = interner.intern("Japanese");
+        let b = interner.intern(&String::from("Japanese"));
+        assert!(Arc::ptr_eq(&a, &b));
+        let c = interner.intern_arc(Arc::from("Japanese"));
+        assert!(Arc::ptr_eq(&a, &c));
+        interner.intern("");
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.bytes(), 8);
+    }
+}
@@ -0,0 +1,511 @@
+use std::fmt;
+
+use super::borrowed;
+use super::text::json_string;
+use super::vertex::{Collection, GameTree, Node, Property, Sequence};
+
+// a collection as json, for programs that would rather not read SGF.
+// the layout follows the syntax tree, field for field, so it is what
+// the serde feature gives too:
+//
+//   {"gametrees":[{"sequence":{"nodes":[{"props":[{"ident":"B","values":["pd"]}]}]},"gametrees":[]}]}
+//
+// values are raw SGF, as they are in the file: C[a\]b] is "a\\]b" in
+// json, not "a]b". a collection read back so writes the same SGF, and
+// readers that want the text run it through writer::unescape_text
+
+#[derive(Debug)]
+pub enum Error {
+    // where in the text, in bytes
+    Syntax(usize, String),
+    // valid json that isn't a collection
+    Layout(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax(offset, msg) => write!(f, "bad json at byte {}: {}", offset, msg),
+            Error::Layout(msg) => write!(f, "not a collection: {}", msg),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// how deep arrays and objects may nest in json read back. a level of
+// variations takes two, and the values of the deepest node seven more,
+// so this reads what the SGF parser does
+const MAX_DEPTH: usize = 2 * borrowed::MAX_NESTING + 7;
+
+// writes json a piece at a time and puts in the commas. the callers
+// walk their trees with stacks of their own, so however deep a tree
+// is, nothing recurses. this one writer is behind all the json of the
+// crate that nests
+#[derive(Default)]
+pub(crate) struct JsonWriter {
+    out: String,
+    // whether the array or object open at each level has an item yet
+    open: Vec<bool>,
+    // a key was just written, and its value goes right after it
+    keyed: bool,
+}
+
+impl JsonWriter {
+    pub(crate) fn new() -> Self {
+        JsonWriter::default()
+    }
+
+    fn item(&mut self) {
+        if std::mem::take(&mut self.keyed) {
+            return;
+        }
+        if let Some(has_items) = self.open.last_mut() {
+            if *has_items {
+                self.out.push(',');
+            }
+            *has_items = true;
+        }
+    }
+
+    pub(crate) fn key(&mut self, key: &str) {
+        self.item();
+        self.out.push_str(&json_string(key));
+        self.out.push(':');
+        self.keyed = true;
+    }
+
+    pub(crate) fn string(&mut self, s: &str) {
+        self.item();
+        self.out.push_str(&json_string(s));
+    }
+
+    // anything that prints as a json number
+    pub(crate) fn number<N: fmt::Display>(&mut self, n: N) {
+        self.item();
+        self.out.push_str(&n.to_string());
+    }
+
+    pub(crate) fn begin_object(&mut self) {
+        self.item();
+        self.out.push('{');
+        self.open.push(false);
+    }
+
+    pub(crate) fn end_object(&mut self) {
+        self.open.pop();
+        self.out.push('}');
+    }
+
+    pub(crate) fn begin_array(&mut self) {
+        self.item();
+        self.out.push('[');
+        self.open.push(false);
+    }
+
+    pub(crate) fn end_array(&mut self) {
+        self.open.pop();
+        self.out.push(']');
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.out
+    }
+}
+
+impl Collection {
+    pub fn to_json(&self) -> String {
+        let mut w = JsonWriter::new();
+        w.begin_object();
+        w.key("gametrees");
+        w.begin_array();
+        // None closes the gametree opened before the children above it
+        let mut stack: Vec<Option<&GameTree>> = self.gametrees.iter().rev().map(Some).collect();
+        while let Some(step) = stack.pop() {
+            let Some(gt) = step else {
+                w.end_array();
+                w.end_object();
+                continue;
+            };
+            w.begin_object();
+            w.key("sequence");
+            w.begin_object();
+            w.key("nodes");
+            w.begin_array();
+            for node in &gt.sequence.nodes {
+                w.begin_object();
+                w.key("props");
+                w.begin_array();
+                for prop in &node.props {
+                    w.begin_object();
+                    w.key("ident");
+                    w.string(&prop.ident);
+                    w.key("values");
+                    w.begin_array();
+                    for value in &prop.values {
+                        w.string(value);
+                    }
+                    w.end_array();
+                    w.end_object();
+                }
+                w.end_array();
+                w.end_object();
+            }
+            w.end_array();
+            w.end_object();
+            w.key("gametrees");
+            w.begin_array();
+            stack.push(None);
+            stack.extend(gt.gametrees.iter().rev().map(|child| Some(child.as_ref())));
+        }
+        w.end_array();
+        w.end_object();
+        w.finish()
+    }
+
+    pub fn from_json(text: &str) -> Result<Collection> {
+        let mut reader = Reader { text, pos: 0 };
+        let value = reader.value()?;
+        reader.skip_space();
+        if reader.pos < text.len() {
+            return Err(Error::Syntax(reader.pos, "text after the collection".to_string()));
+        }
+        collection(&value)
+    }
+}
+
+// the json values a collection is made of. numbers, booleans and null
+// are read so the error can say where they don't belong
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+    Other(&'static str),
+}
+
+impl Value {
+    fn field(&self, key: &str, what: &str) -> Result<&Value> {
+        let Value::Object(fields) = self else {
+            return Err(Error::Layout(format!("{} is not an object", what)));
+        };
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .ok_or_else(|| Error::Layout(format!("{} has no \"{}\"", what, key)))
+    }
+
+    fn array(&self, what: &str) -> Result<&[Value]> {
+        match self {
+            Value::Array(items) => Ok(items),
+            _ => Err(Error::Layout(format!("{} is not an array", what))),
+        }
+    }
+
+    fn string(&self, what: &str) -> Result<&str> {
+        match self {
+            Value::Str(s) => Ok(s),
+            Value::Other(kind) => Err(Error::Layout(format!("{} is {}, not a string", what, kind))),
+            _ => Err(Error::Layout(format!("{} is not a string", what))),
+        }
+    }
+}
+
+fn collection(value: &Value) -> Result<Collection> {
+    let gametrees = value.field("gametrees", "the collection")?.array("gametrees")?;
+    Ok(Collection {
+        gametrees: gametrees.iter().map(gametree).collect::<Result<_>>()?,
+    })
+}
+
+// the variations are read with a stack of the gametrees still open, as
+// deep trees would overflow ours
+fn gametree(value: &Value) -> Result<GameTree> {
+    let mut open = vec![shallow_gametree(value)?];
+    loop {
+        let (_, variations) = open.last_mut().expect("the gametree read last is open");
+        match variations.next() {
+            Some(child) => open.push(shallow_gametree(child)?),
+            None => {
+                let (gt, _) = open.pop().expect("the gametree read last is open");
+                match open.last_mut() {
+                    Some((parent, _)) => parent.gametrees.push(Box::new(gt)),
+                    None => return Ok(gt),
+                }
+            },
+        }
+    }
+}
+
+// a gametree with its nodes, and its variations still to read
+fn shallow_gametree(value: &Value) -> Result<(GameTree, std::slice::Iter<'_, Value>)> {
+    let nodes = value.field("sequence", "a gametree")?.field("nodes", "a sequence")?.array("nodes")?;
+    // SGF has no empty sequences, and the rest of the crate counts on a
+    // root node
+    if nodes.is_empty() {
+        return Err(Error::Layout("a gametree has no nodes".to_string()));
+    }
+    let gametrees = value.field("gametrees", "a gametree")?.array("gametrees")?;
+    let gt = GameTree {
+        sequence: Sequence {
+            nodes: nodes.iter().map(node).collect::<Result<_>>()?,
+        },
+        gametrees: Vec::new(),
+    };
+    Ok((gt, gametrees.iter()))
+}
+
+fn node(value: &Value) -> Result<Node> {
+    let mut props = Vec::new();
+    for prop in value.field("props", "a node")?.array("props")? {
+        let ident = prop.field("ident", "a property")?.string("ident")?;
+        if ident.is_empty() || !ident.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(Error::Layout(format!("{} is not a property identifier", json_string(ident))));
+        }
+        let values = prop.field("values", ident)?.array(ident)?;
+        if values.is_empty() {
+            return Err(Error::Layout(format!("{} has no values", ident)));
+        }
+        props.push(Property {
+            ident: ident.to_string(),
+            values: values.iter().map(|v| v.string(ident).map(str::to_string)).collect::<Result<_>>()?,
+        });
+    }
+    Ok(Node { props })
+}
+
+// an array or object the reader is inside of, with what it has so far
+enum Open {
+    Array(Vec<Value>),
+    // the key of the field being read
+    Object(Vec<(String, Value)>, String),
+}
+
+impl Open {
+    fn push(&mut self, value: Value) {
+        match self {
+            Open::Array(items) => items.push(value),
+            Open::Object(fields, key) => fields.push((std::mem::take(key), value)),
+        }
+    }
+
+    fn closing(&self) -> u8 {
+        match self {
+            Open::Array(_) => b']',
+            Open::Object(..) => b'}',
+        }
+    }
+
+    fn close(self) -> Value {
+        match self {
+            Open::Array(items) => Value::Array(items),
+            Open::Object(fields, _) => Value::Object(fields),
+        }
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn error<T>(&self, msg: &str) -> Result<T> {
+        Err(Error::Syntax(self.pos, msg.to_string()))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        self.skip_space();
+        if self.peek() != Some(c) {
+            return self.error(&format!("expected '{}'", c as char));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // a value, with the arrays and objects it is made of kept on a stack
+    // instead of recursing, so no nesting can overflow ours
+    fn value(&mut self) -> Result<Value> {
+        let mut open: Vec<Open> = Vec::new();
+        'values: loop {
+            self.skip_space();
+            let mut value = match self.peek() {
+                Some(b'[' | b'{') if open.len() == MAX_DEPTH => return self.error("nested too deep"),
+                Some(b'[') => {
+                    self.pos += 1;
+                    self.skip_space();
+                    if self.peek() != Some(b']') {
+                        open.push(Open::Array(Vec::new()));
+                        continue;
+                    }
+                    self.pos += 1;
+                    Value::Array(Vec::new())
+                },
+                Some(b'{') => {
+                    self.pos += 1;
+                    self.skip_space();
+                    if self.peek() != Some(b'}') {
+                        open.push(Open::Object(Vec::new(), self.key()?));
+                        continue;
+                    }
+                    self.pos += 1;
+                    Value::Object(Vec::new())
+                },
+                _ => self.scalar()?,
+            };
+            // the value goes into the array or object around it, and
+            // closes those that end after it
+            while let Some(mut top) = open.pop() {
+                top.push(value);
+                self.skip_space();
+                match self.peek() {
+                    Some(b',') => {
+                        self.pos += 1;
+                        if let Open::Object(_, key) = &mut top {
+                            *key = self.key()?;
+                        }
+                        open.push(top);
+                        continue 'values;
+                    },
+                    Some(b) if b == top.closing() => {
+                        self.pos += 1;
+                        value = top.close();
+                    },
+                    _ => return self.error(&format!("expected ',' or '{}'", top.closing() as char)),
+                }
+            }
+            return Ok(value);
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some(b'"') => Ok(Value::Str(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => {
+                let len = self.text[self.pos..].find(|c: char| !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9')).unwrap_or(self.text.len() - self.pos);
+                self.pos += len;
+                Ok(Value::Other("a number"))
+            },
+            _ => {
+                for (word, kind) in [("true", "a boolean"), ("false", "a boolean"), ("null", "null")] {
+                    if self.text[self.pos..].starts_with(word) {
+                        self.pos += word.len();
+                        return Ok(Value::Other(kind));
+                    }
+                }
+                self.error("expected a value")
+            },
+        }
+    }
+
+    // the key of a field and the ':' after it
+    fn key(&mut self) -> Result<String> {
+        self.skip_space();
+        if self.peek() != Some(b'"') {
+            return self.error("expected a key");
+        }
+        let key = self.string()?;
+        self.expect(b':')?;
+        Ok(key)
+    }
+
+    // a string literal, from its opening quote
+    fn string(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let Some(i) = rest.find(['"', '\\']) else {
+                return self.error("unterminated string");
+            };
+            s.push_str(&rest[..i]);
+            self.pos += i + 1;
+            if rest.as_bytes()[i] == b'"' {
+                return Ok(s);
+            }
+            let Some(escape) = self.peek() else {
+                return self.error("unterminated string");
+            };
+            self.pos += 1;
+            match escape {
+                b'"' => s.push('"'),
+                b'\\' => s.push('\\'),
+                b'/' => s.push('/'),
+                b'b' => s.push('\u{8}'),
+                b'f' => s.push('\u{c}'),
+                b'n' => s.push('\n'),
+                b'r' => s.push('\r'),
+                b't' => s.push('\t'),
+                b'u' => {
+                    let mut c = self.hex4()?;
+                    // a surrogate pair, for characters past the first plane
+                    if (0xd800..0xdc00).contains(&c) && self.text[self.pos..].starts_with("\\u") {
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        if !(0xdc00..0xe000).contains(&low) {
+                            return self.error("bad surrogate pair");
+                        }
+                        c = 0x10000 + ((c - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    match char::from_u32(c) {
+                        Some(c) => s.push(c),
+                        None => return self.error("bad \\u escape"),
+                    }
+                },
+                _ => return self.error("bad escape"),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self.text.get(self.pos..self.pos + 4).filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()));
+        let Some(digits) = digits else {
+            return self.error("bad \\u escape");
+        };
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn json1() {
+        let coll = Parser::new("(;GM[1]C[a \"quote\"\\]\n李];B[aa](;W[bb])(;W[cc]AB[dd][ee]))(;GM[1])").parse().unwrap();
+        let json = coll.to_json();
+        assert!(json.starts_with(r#"{"gametrees":[{"sequence":{"nodes":[{"props":[{"ident":"GM","values":["1"]},{"ident":"C","values":["a \"quote\"\\]\n李"]}]}"#));
+        let back = Collection::from_json(&json).unwrap();
+        assert_eq!(back.to_string(), coll.to_string());
+        let spaced = r#" { "gametrees" : [ { "gametrees": [], "sequence": { "nodes": [ { "props": [ { "ident": "C", "values": ["\u00e9\ud83d\ude00"] } ] } ] } } ] } "#;
+        assert_eq!(Collection::from_json(spaced).unwrap().gametrees[0].root().value("C"), Some("é😀"));
+        assert!(matches!(Collection::from_json(r#"{"gametrees":[{"sequence":{"nodes":[]},"gametrees":[]}]}"#), Err(Error::Layout(_))));
+        assert!(matches!(Collection::from_json(r#"{"gametrees":[{"sequence":{"nodes":[{"props":[{"ident":"b","values":["aa"]}]}]},"gametrees":[]}]}"#), Err(Error::Layout(_))));
+        assert!(matches!(Collection::from_json(r#"{"gametrees":[1]}"#), Err(Error::Layout(_))));
+        assert!(matches!(Collection::from_json(r#"{"gametrees":[}"#), Err(Error::Syntax(14, _))));
+        assert!(Collection::from_json(r#"{"gametrees":[]} x"#).is_err());
+        let deep = "[".repeat(1_000_000);
+        assert!(matches!(Collection::from_json(&deep), Err(Error::Syntax(MAX_DEPTH, _))));
+        // as deep as the SGF parser reads, and no deeper
+        let nested = |n: usize| format!("{}{}", "(;B[aa]".repeat(n), ")".repeat(n));
+        let coll = Parser::new(&nested(borrowed::MAX_NESTING)).parse().unwrap();
+        assert_eq!(Collection::from_json(&coll.to_json()).unwrap().to_string(), nested(borrowed::MAX_NESTING));
+        let mut deeper = coll;
+        let gt = deeper.gametrees.remove(0);
+        deeper.gametrees.push(GameTree { sequence: gt.sequence.clone(), gametrees: vec![Box::new(gt)] });
+        assert!(matches!(Collection::from_json(&deeper.to_json()), Err(Error::Syntax(_, _))));
+    }
+}
@@ -0,0 +1,157 @@
+use super::vertex::{GameTree, Node, Sequence};
+
+// common short words of the latin script languages we can tell apart.
+// good enough for review comments, which are mostly a sentence or two
+const STOPWORDS: [(&str, &[&str]); 6] = [
+    ("en", &["the", "is", "and", "to", "this", "move", "of", "it", "white", "black", "a", "better", "here", "for", "not", "should"]),
+    ("de", &["der", "die", "das", "ist", "und", "nicht", "zug", "hier", "ein", "eine", "besser", "schwarz", "weiss", "weiß", "mit"]),
+    ("fr", &["le", "la", "les", "est", "et", "coup", "ici", "un", "une", "mieux", "noir", "blanc", "pas", "pour", "de"]),
+    ("es", &["el", "la", "los", "es", "y", "jugada", "aquí", "aqui", "un", "una", "mejor", "negro", "blanco", "no", "para"]),
+    ("nl", &["de", "het", "is", "en", "een", "zet", "hier", "beter", "zwart", "wit", "niet", "van", "dit"]),
+    ("pt", &["o", "a", "os", "é", "e", "jogada", "aqui", "um", "uma", "melhor", "preto", "branco", "não", "para"]),
+];
+
+// the language of a piece of text as an ISO 639-1 code, or None when
+// there is too little to go on (coordinates, "+", a lone word)
+pub fn detect(text: &str) -> Option<&'static str> {
+    let (mut hangul, mut kana, mut han, mut cyrillic, mut letters) = (0, 0, 0, 0, 0);
+    for c in text.chars() {
+        match c as u32 {
+            0xac00..=0xd7a3 | 0x1100..=0x11ff | 0x3130..=0x318f => hangul += 1,
+            0x3040..=0x30ff => kana += 1,
+            0x4e00..=0x9fff => han += 1,
+            0x0400..=0x04ff => cyrillic += 1,
+            _ if c.is_alphabetic() => letters += 1,
+            _ => {},
+        }
+    }
+    // scripts first, as long as they outweigh latin text. any kana means
+    // japanese even among kanji
+    if hangul > 0 && hangul >= kana + han && hangul >= letters {
+        return Some("ko");
+    }
+    if kana > 0 && kana + han >= letters {
+        return Some("ja");
+    }
+    if han > 0 && han >= letters {
+        return Some("zh");
+    }
+    if cyrillic > 0 && cyrillic >= letters {
+        return Some("ru");
+    }
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    let mut best = None;
+    let mut best_score = 0;
+    for (lang, stopwords) in STOPWORDS {
+        let score = words.iter().filter(|w| stopwords.contains(&w.as_str())).count();
+        if score > best_score {
+            best = Some(lang);
+            best_score = score;
+        } else if score == best_score {
+            // a tie says nothing
+            best = None;
+        }
+    }
+    if best_score >= 2 || (best_score == 1 && words.len() <= 3) {
+        best
+    } else {
+        None
+    }
+}
+
+// keeps the lines of a comment that are in lang. lines we can't place
+// stay, since they are usually coordinates or variations
+pub fn filter_comment(text: &str, lang: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if detect(line).is_some_and(|l| l != lang) {
+            continue;
+        }
+        // don't let dropped lines leave runs of blank ones behind
+        if line.trim().is_empty() && kept.last().is_none_or(|l| l.trim().is_empty()) {
+            continue;
+        }
+        kept.push(line);
+    }
+    kept.join("\n").trim().to_string()
+}
+
+impl GameTree {
+    // every comment with its node path and detected language
+    pub fn comment_languages(&self) -> Vec<(Vec<usize>, Option<&'static str>)> {
+        self.nodes()
+            .into_iter()
+            .filter_map(|(path, node)| node.value("C").map(|c| (path, detect(c))))
+            .collect()
+    }
+
+    // strips comment paragraphs that are not in lang, dropping C entirely
+    // when nothing is left
+    pub fn keep_language(&self, lang: &str) -> Self {
+        let mut nodes = Vec::new();
+        for node in &self.sequence.nodes {
+            nodes.push(node.keep_language(lang));
+        }
+        let mut gametrees = Vec::new();
+        for gt in &self.gametrees {
+            gametrees.push(Box::new(gt.keep_language(lang)));
+        }
+        GameTree {
+            sequence: Sequence { nodes },
+            gametrees,
+        }
+    }
+}
+
+impl Node {
+    pub fn keep_language(&self, lang: &str) -> Self {
+        let mut node = self.clone();
+        if let Some(comment) = self.value("C") {
+            let kept = filter_comment(comment, lang);
+            if kept.is_empty() {
+                node.remove("C");
+            } else {
+                node.set("C", vec![kept]);
+            }
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn detect1() {
+        assert_eq!(detect("This move is too slow, white should tenuki"), Some("en"));
+        assert_eq!(detect("Dieser Zug ist nicht gut, besser hier"), Some("de"));
+        assert_eq!(detect("Le coup est trop lent, mieux ici"), Some("fr"));
+        assert_eq!(detect("좋은 수입니다"), Some("ko"));
+        assert_eq!(detect("この手は悪い"), Some("ja"));
+        assert_eq!(detect("这步棋不好"), Some("zh"));
+        assert_eq!(detect("Q16"), None);
+        assert_eq!(detect("+"), None);
+    }
+
+    #[test]
+    fn language1() {
+        let gt = gametree("(;C[Good move, this is the vital point.\n\n좋은 수입니다];B[aa]C[좋은 수];W[bb]C[D4])");
+        let langs = gt.comment_languages();
+        assert_eq!(langs, vec![(vec![], Some("en")), (vec![0], Some("ko")), (vec![0, 0], None)]);
+        let en = gt.keep_language("en");
+        let nodes = en.mainline();
+        assert_eq!(nodes[0].value("C"), Some("Good move, this is the vital point."));
+        assert!(!nodes[1].has("C"));
+        assert_eq!(nodes[2].value("C"), Some("D4"));
+    }
+}
@@ -1,3 +1,136 @@
+// a debug event when the tracing feature is on, nothing otherwise
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
 pub mod scanner;
 pub mod parser;
+pub mod borrowed;
+pub mod format;
+pub mod outline;
 pub mod vertex;
+pub mod point;
+pub mod writer;
+pub mod text;
+pub mod json;
+pub mod builder;
+pub mod pairing;
+pub mod view;
+pub mod cursor;
+pub mod transform;
+pub mod edit;
+pub mod mutate;
+pub mod resize;
+pub mod info;
+pub mod clock;
+pub mod stats;
+pub mod profile;
+pub mod filter;
+pub mod naming;
+pub mod translit;
+pub mod language;
+pub mod authors;
+pub mod chat;
+pub mod intern;
+pub mod digest;
+pub mod repertoire;
+pub mod rating;
+pub mod index;
+pub mod annotate;
+pub mod bookmark;
+pub mod disable;
+pub mod review;
+pub mod merge;
+pub mod batch;
+pub mod transaction;
+pub mod patch;
+pub mod validate;
+pub mod codes;
+pub mod lint;
+pub mod version;
+pub mod properties;
+pub mod value;
+pub mod props;
+pub mod complete;
+pub mod stream;
+pub mod salvage;
+#[cfg(feature = "board")]
+pub mod board;
+#[cfg(feature = "board")]
+pub mod replay;
+#[cfg(feature = "board")]
+pub mod position;
+#[cfg(feature = "board")]
+pub mod life;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "render")]
+pub mod html;
+#[cfg(feature = "render")]
+pub mod flashcard;
+#[cfg(feature = "png")]
+pub mod raster;
+#[cfg(feature = "gif")]
+pub mod animate;
+#[cfg(feature = "encoding")]
+pub mod recode;
+
+// the syntax tree, under the name most users look for
+pub mod ast {
+    pub use super::vertex::{Collection, Color, GameTree, Node, Property, Sequence};
+}
+
+pub fn scan(text: &str) -> scanner::Result<Vec<scanner::Token>> {
+    scanner::Scanner::new(text).scan()
+}
+
+pub fn parse(text: &str) -> parser::Result<ast::Collection> {
+    parser::Parser::new(text).parse()
+}
+
+// parse without a String per value: the tree borrows from text, with
+// values exactly as written
+pub fn parse_borrowed(text: &str) -> parser::Result<borrowed::Collection<'_>> {
+    borrowed::Parser::new(text).parse()
+}
+
+pub fn write(coll: &ast::Collection) -> String {
+    writer::Writer::default().write(coll)
+}
+
+pub fn to_string(coll: &ast::Collection) -> String {
+    write(coll)
+}
+
+// what most programs need in one import: use sgf::prelude::*;
+pub mod prelude {
+    pub use super::ast::*;
+    #[cfg(feature = "board")]
+    pub use super::board::Board;
+    pub use super::builder::GameBuilder;
+    pub use super::cursor::Cursor;
+    pub use super::info::GameInfo;
+    pub use super::point::Point;
+    #[cfg(feature = "board")]
+    pub use super::position::Position;
+    pub use super::writer::{AppStamp, Writer};
+    pub use super::{parse, to_string};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api1() {
+        let coll = parse("(;GM[1];B[aa])").unwrap();
+        assert_eq!(write(&coll), "(;GM[1];B[aa])");
+        assert_eq!(to_string(&coll), write(&coll));
+        assert!(!scan("(;GM[1])").unwrap().is_empty());
+        assert!(parse("(;GM[1]").is_err());
+        assert_eq!(parse_borrowed("(;GM[1];B[aa])").unwrap().into_owned().to_string(), write(&coll));
+    }
+}
@@ -0,0 +1,314 @@
+use super::board::{Board, Group};
+use super::info::{GameResult, WinReason};
+use super::point::Point;
+use super::vertex::{Color, GameTree, Node};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    // pass-alive: nothing the opponent plays can capture it
+    Alive,
+    // inside an area the opponent owns unconditionally
+    Dead,
+    // neither, so a person has to look. counted as alive
+    Unsettled,
+}
+
+// the empty and opponent points around a color's stones, split into
+// connected regions
+struct Region {
+    points: Vec<Point>,
+    empty: Vec<Point>,
+    // indexes of the chains touching the region
+    chains: Vec<usize>,
+}
+
+fn regions(board: &Board, color: Color, chains: &[Group]) -> Vec<Region> {
+    let mut seen = vec![false; board.cols * board.rows];
+    let mut regions = Vec::new();
+    for y in 0..board.rows {
+        for x in 0..board.cols {
+            let start = Point::new(x as u8, y as u8);
+            if seen[y * board.cols + x] || board.get(start) == Some(color) {
+                continue;
+            }
+            seen[y * board.cols + x] = true;
+            let mut region = Region { points: vec![start], empty: Vec::new(), chains: Vec::new() };
+            let mut i = 0;
+            while i < region.points.len() {
+                let p = region.points[i];
+                if board.get(p).is_none() {
+                    region.empty.push(p);
+                }
+                for n in board.neighbors(p) {
+                    if board.get(n) == Some(color) {
+                        if let Some(c) = chains.iter().position(|g| g.stones.contains(&n)) {
+                            if !region.chains.contains(&c) {
+                                region.chains.push(c);
+                            }
+                        }
+                    } else if !seen[n.y as usize * board.cols + n.x as usize] {
+                        seen[n.y as usize * board.cols + n.x as usize] = true;
+                        region.points.push(n);
+                    }
+                }
+                i += 1;
+            }
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+// Benson's algorithm: the chains of color that stay alive even if the
+// opponent gets every move, and the regions they own outright. a region
+// is vital to a chain when all its empty points are the chain's
+// liberties, and a chain with two vital regions can't be captured
+pub fn pass_alive(board: &Board, color: Color) -> (Vec<Group>, Vec<Vec<Point>>) {
+    let chains: Vec<Group> = board.groups().into_iter().filter(|g| g.color == color).collect();
+    let regions = regions(board, color, &chains);
+    let mut alive = vec![true; chains.len()];
+    let mut kept = vec![true; regions.len()];
+    loop {
+        let mut changed = false;
+        for (c, chain) in chains.iter().enumerate() {
+            if !alive[c] {
+                continue;
+            }
+            let vital = regions
+                .iter()
+                .enumerate()
+                .filter(|(r, region)| {
+                    kept[*r] && region.chains.contains(&c) && region.empty.iter().all(|p| chain.liberties.contains(p))
+                })
+                .count();
+            if vital < 2 {
+                alive[c] = false;
+                changed = true;
+            }
+        }
+        for (r, region) in regions.iter().enumerate() {
+            if kept[r] && region.chains.iter().any(|c| !alive[*c]) {
+                kept[r] = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    // a region is owned when the opponent can't make room to live in it:
+    // every empty point touches one of the living chains around it
+    let owned = regions
+        .into_iter()
+        .zip(&kept)
+        .filter(|(region, k)| {
+            **k && region.empty.iter().all(|p| region.chains.iter().any(|c| chains[*c].liberties.contains(p)))
+        })
+        .map(|(region, _)| region.points)
+        .collect();
+    let chains = chains.into_iter().zip(&alive).filter(|(_, a)| **a).map(|(g, _)| g).collect();
+    (chains, owned)
+}
+
+// a guess at the life and death of every group of a final position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Estimate {
+    pub groups: Vec<(Group, Status)>,
+    // empty points and dead stones each color surrounds
+    pub black_territory: Vec<Point>,
+    pub white_territory: Vec<Point>,
+}
+
+impl Estimate {
+    pub fn new(board: &Board) -> Self {
+        let (black_alive, black_owned) = pass_alive(board, Color::Black);
+        let (white_alive, white_owned) = pass_alive(board, Color::White);
+        let mut groups = Vec::new();
+        for group in board.groups() {
+            let (alive, opponent_owned) = match group.color {
+                Color::Black => (&black_alive, &white_owned),
+                Color::White => (&white_alive, &black_owned),
+            };
+            let status = if alive.contains(&group) {
+                Status::Alive
+            } else if opponent_owned.iter().any(|r| group.stones.iter().all(|p| r.contains(p))) {
+                Status::Dead
+            } else {
+                Status::Unsettled
+            };
+            groups.push((group, status));
+        }
+        let mut estimate = Estimate {
+            groups,
+            black_territory: Vec::new(),
+            white_territory: Vec::new(),
+        };
+        estimate.count_territory(board);
+        estimate
+    }
+
+    // with the dead stones taken off, every empty area that only one
+    // color borders is that color's
+    fn count_territory(&mut self, board: &Board) {
+        let mut cleared = board.clone();
+        for p in self.dead() {
+            cleared.set(p, None);
+        }
+        let mut seen = vec![false; board.cols * board.rows];
+        for y in 0..board.rows {
+            for x in 0..board.cols {
+                let start = Point::new(x as u8, y as u8);
+                if seen[y * board.cols + x] || cleared.get(start).is_some() {
+                    continue;
+                }
+                seen[y * board.cols + x] = true;
+                let mut area = vec![start];
+                let mut borders: Vec<Color> = Vec::new();
+                let mut i = 0;
+                while i < area.len() {
+                    for n in cleared.neighbors(area[i]) {
+                        match cleared.get(n) {
+                            Some(c) if !borders.contains(&c) => borders.push(c),
+                            Some(_) => {},
+                            None if !seen[n.y as usize * board.cols + n.x as usize] => {
+                                seen[n.y as usize * board.cols + n.x as usize] = true;
+                                area.push(n);
+                            },
+                            None => {},
+                        }
+                    }
+                    i += 1;
+                }
+                match borders.as_slice() {
+                    [Color::Black] => self.black_territory.extend(area),
+                    [Color::White] => self.white_territory.extend(area),
+                    _ => {},
+                }
+            }
+        }
+        let row_order = |p: &Point| (p.y, p.x);
+        self.black_territory.sort_by_key(row_order);
+        self.white_territory.sort_by_key(row_order);
+    }
+
+    pub fn dead(&self) -> Vec<Point> {
+        self.groups.iter().filter(|(_, s)| *s == Status::Dead).flat_map(|(g, _)| g.stones.clone()).collect()
+    }
+
+    // groups the heuristic could not settle, for a person to check
+    pub fn unsettled(&self) -> Vec<&Group> {
+        self.groups.iter().filter(|(_, s)| *s == Status::Unsettled).map(|(g, _)| g).collect()
+    }
+
+    // area score, black minus white minus komi
+    pub fn score(&self, komi: f64) -> f64 {
+        let stones = |color| {
+            self.groups
+                .iter()
+                .filter(|(g, s)| g.color == color && *s != Status::Dead)
+                .map(|(g, _)| g.stones.len())
+                .sum::<usize>()
+        };
+        let black = stones(Color::Black) + self.black_territory.len();
+        let white = stones(Color::White) + self.white_territory.len();
+        black as f64 - white as f64 - komi
+    }
+
+    // the score as RE writes it, like B+4.5, and 0 for a draw
+    pub fn result(&self, komi: f64) -> GameResult {
+        let score = self.score(komi);
+        match score {
+            0.0 => GameResult::Draw,
+            _ if score > 0.0 => GameResult::Win(Color::Black, WinReason::Score(score)),
+            _ => GameResult::Win(Color::White, WinReason::Score(-score)),
+        }
+    }
+
+    // sets TB and TW on node, replacing any already there
+    pub fn mark(&self, node: &mut Node) {
+        for (ident, points) in [("TB", &self.black_territory), ("TW", &self.white_territory)] {
+            node.remove(ident);
+            if !points.is_empty() {
+                node.set(ident, points.iter().map(|p| p.to_sgf()).collect());
+            }
+        }
+    }
+}
+
+impl GameTree {
+    pub fn estimate(&self) -> Estimate {
+        Estimate::new(&self.final_position())
+    }
+
+    // the game with TB and TW from the estimate on its last node
+    pub fn with_territory(&self) -> Self {
+        let estimate = self.estimate();
+        let mut gt = self.clone();
+        let mut last = &mut gt;
+        while !last.gametrees.is_empty() {
+            last = &mut last.gametrees[0];
+        }
+        if let Some(node) = last.sequence.nodes.last_mut() {
+            estimate.mark(node);
+        }
+        gt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    fn p(s: &str) -> Point {
+        Point::from_sgf(s).unwrap()
+    }
+
+    // a black wall on columns b and c with two eyes at aa and ac, and
+    // white on column d with one more stone at ee
+    const TWO_EYES: &str = "(;SZ[5]AB[ba][bb][bc][bd][be][ab][ad][ae][ca][cb][cc][cd][ce]AW[da][db][dc][dd][de][ee])";
+
+    #[test]
+    fn benson1() {
+        let board = gametree(TWO_EYES).final_position();
+        let (alive, owned) = pass_alive(&board, Color::Black);
+        assert_eq!(alive.len(), 1);
+        assert_eq!(owned, vec![vec![p("aa")], vec![p("ac")]]);
+        // one eye isn't enough
+        let board = gametree("(;SZ[5]AB[ba][bb][bc][bd][be][ab][ac][ad][ae]AW[ca][cb][cc][cd][ce])").final_position();
+        assert!(pass_alive(&board, Color::Black).0.is_empty());
+    }
+
+    #[test]
+    fn estimate1() {
+        let gt = gametree("(;SZ[5]AB[ba][bb][bc][bd][be][ab][ad][ae][ca][cb][cc][cd][ce]AW[db][dc][dd][de]AB[eb])");
+        let estimate = gt.estimate();
+        assert_eq!(estimate.unsettled().len(), 2);
+        assert!(estimate.dead().is_empty());
+        let gt = gametree(TWO_EYES);
+        let estimate = gt.estimate();
+        assert_eq!(estimate.black_territory, vec![p("aa"), p("ac")]);
+        assert_eq!(estimate.unsettled().len(), 1);
+        assert!(estimate.dead().is_empty());
+        assert_eq!(estimate.white_territory.len(), 4);
+        assert_eq!(estimate.score(0.5), 4.5);
+        assert_eq!(estimate.result(0.5).to_string(), "B+4.5");
+        assert_eq!(estimate.result(5.0).to_string(), "0");
+        assert_eq!(estimate.result(6.5).to_string(), "W+1.5");
+    }
+
+    #[test]
+    fn estimate2() {
+        // a white stone inside black's eye is dead
+        let gt = gametree("(;SZ[5]AB[ba][bb][bc][bd][be][ab][ad][ae][ca][cb][cc][cd][ce]AW[aa][db][dc][dd][de])");
+        let estimate = gt.estimate();
+        assert_eq!(estimate.dead(), vec![p("aa")]);
+        assert_eq!(estimate.black_territory, vec![p("aa"), p("ac")]);
+        let marked = gt.with_territory();
+        assert_eq!(marked.root().value("TB"), Some("aa"));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::codes;
+use super::validate::{Diagnostic, Kind, Severity};
+
+// the file validate looks for in the validated directory and its parents
+pub const CONFIG_FILE: &str = ".sgflint.toml";
+
+#[derive(Debug)]
+pub enum Error {
+    // the line of the config file and what is wrong with it
+    ConfigError(usize, String),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ConfigError(0, s) => write!(f, "{}", s),
+            Error::ConfigError(line, s) => write!(f, "line {}: {}", line, s),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+// what to do with the diagnostics of one code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Ignore,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Option<Level> {
+        match s.trim() {
+            "ignore" | "allow" | "off" => Some(Level::Ignore),
+            "warn" | "warning" => Some(Level::Warning),
+            "error" | "deny" => Some(Level::Error),
+            _ => None,
+        }
+    }
+}
+
+// how strict validation is, by code. codes not listed keep the severity
+// the validator gives them. in a config file:
+//
+//     [severity]
+//     W002 = "ignore"
+//     multiple-moves = "warn"
+//     W004 = 'error'
+//
+// keys may be bare or quoted and levels quoted, in "basic" or 'literal'
+// strings as TOML has them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintConfig {
+    levels: BTreeMap<&'static str, Level>,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        LintConfig::default()
+    }
+
+    // sets a code, given as E011 or multiple-moves, to a level
+    pub fn with_level(mut self, code: &str, level: Level) -> Result<Self> {
+        self.set_level(code, level)?;
+        Ok(self)
+    }
+
+    pub fn set_level(&mut self, code: &str, level: Level) -> Result<()> {
+        let code = codes::lookup(code).ok_or_else(|| Error::ConfigError(0, format!("unknown code: {}", code)))?;
+        self.levels.insert(code.code, level);
+        Ok(())
+    }
+
+    pub fn level(&self, code: &str) -> Option<Level> {
+        self.levels.get(code).copied()
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = LintConfig::new();
+        let mut in_severity = false;
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            let fail = |msg: String| Error::ConfigError(i + 1, msg);
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if table.trim() != "severity" {
+                    return Err(fail(format!("unknown table [{}]", table.trim())));
+                }
+                in_severity = true;
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(fail(format!("expected code = \"level\", found {}", line)));
+            };
+            if !in_severity {
+                return Err(fail("codes go in the [severity] table".to_string()));
+            }
+            let key = unquote(key.trim());
+            let value = unquote(value.trim());
+            let level = Level::parse(value).ok_or_else(|| fail(format!("unknown level \"{}\" for {}", value, key)))?;
+            config.set_level(key, level).map_err(|e| fail(e.to_string()))?;
+        }
+        Ok(config)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        LintConfig::parse(&fs::read_to_string(path)?)
+    }
+
+    // the config file nearest to path: in it if it is a directory, else
+    // beside it, or in a parent
+    pub fn find<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        let start = if path.is_dir() { path } else { path.parent()? };
+        let start = start.canonicalize().ok()?;
+        start.ancestors().map(|dir| dir.join(CONFIG_FILE)).find(|file| file.is_file())
+    }
+
+    // the severity diagnostics of kind get, for summaries
+    pub fn severity(&self, kind: Kind) -> Severity {
+        match self.level(kind.code()) {
+            Some(Level::Warning) => Severity::Warning,
+            Some(Level::Error) => Severity::Error,
+            _ => kind.severity(),
+        }
+    }
+
+    // drops the ignored diagnostics and sets the severity of the rest
+    pub fn apply(&self, diagnostics: &mut Vec<Diagnostic>) {
+        diagnostics.retain(|d| self.level(d.code) != Some(Level::Ignore));
+        for d in diagnostics {
+            match self.level(d.code) {
+                Some(Level::Warning) => d.severity = Severity::Warning,
+                Some(Level::Error) => d.severity = Severity::Error,
+                _ => {},
+            }
+        }
+    }
+}
+
+// a # starts a comment unless it is inside a "basic" or 'literal' string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {},
+        }
+    }
+    line
+}
+
+fn unquote(s: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|q| s.strip_prefix(*q).and_then(|s| s.strip_suffix(*q)))
+        .unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint1() {
+        let text = "# uploads\n[severity]\nW002 = \"ignore\"  # editors do this\n\"multiple-moves\" = \"warn\"\nW004 = 'x'\n'W001' = 'ignore' # 'odd'\n";
+        assert!(matches!(LintConfig::parse(text), Err(Error::ConfigError(5, _))));
+        let config = LintConfig::parse(&text.replace("'x'", "\"error\"")).unwrap();
+        assert_eq!(config.level("E011"), Some(Level::Warning));
+        assert_eq!(config.level("W001"), Some(Level::Ignore));
+        assert!(LintConfig::parse("[severity]\nW002 = 'ignore # not a comment'").is_err());
+        assert_eq!(config.severity(Kind::MultipleMoves), Severity::Warning);
+        let mut diagnostics = vec![
+            Diagnostic::new(Kind::DuplicateProperty, 0, Vec::new(), String::new()),
+            Diagnostic::new(Kind::MultipleMoves, 0, vec![0], String::new()),
+            Diagnostic::new(Kind::BadPoint, 0, vec![0], String::new()),
+        ];
+        config.apply(&mut diagnostics);
+        let severities: Vec<Severity> = diagnostics.iter().map(|d| d.severity).collect();
+        assert_eq!(severities, vec![Severity::Warning, Severity::Error]);
+        assert!(LintConfig::parse("W002 = \"ignore\"").is_err());
+        assert!(LintConfig::parse("[severity]\nW999 = \"ignore\"").is_err());
+    }
+}
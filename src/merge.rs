@@ -0,0 +1,367 @@
+use std::fmt;
+
+use super::info::is_pass;
+use super::resize;
+use super::text::{join_paragraphs, MULTILINE_PROPERTIES};
+use super::vertex::{Color, GameTree, Node, Sequence};
+
+// the clock properties each player keeps for themselves
+const BLACK_CLOCK: [&str; 2] = ["BL", "OB"];
+const WHITE_CLOCK: [&str; 2] = ["WL", "OW"];
+
+// where the two records disagree. number is the move the node belongs
+// to, 0 for the root
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    // both records give the property, with different values. the one
+    // from the record of the player who moved, or black's in the root,
+    // is kept
+    Property {
+        number: usize,
+        ident: String,
+        black: Vec<String>,
+        white: Vec<String>,
+    },
+    // the records have different moves from here on. black's line stays
+    // the main line and white's becomes a variation
+    Diverged {
+        number: usize,
+        black: String,
+        white: String,
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::Property { number, ident, black, white } => {
+                write!(f, "move {}: {} is [{}] in black's record, [{}] in white's", number, ident, black.join("]["), white.join("]["))
+            },
+            Conflict::Diverged { number, black, white } => {
+                write!(f, "move {}: black's record has {}, white's {}", number, black, white)
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // the records are of different boards
+    Size(resize::Error),
+    // the records differ from the root on, leaving no game to share
+    RootsDiffer { black: String, white: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Size(e) => write!(f, "{}", e),
+            Error::RootsDiffer { black, white } => {
+                write!(f, "the records differ from the start: black's has {}, white's {}", black, white)
+            },
+        }
+    }
+}
+
+impl From<resize::Error> for Error {
+    fn from(err: resize::Error) -> Error {
+        Error::Size(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// given the move number, the ident and black's and white's value, the
+// value to keep
+pub type AskComment<'a> = dyn FnMut(usize, &str, &str, &str) -> String + 'a;
+
+// what becomes of a comment (C or GC) the two records give differently
+#[derive(Default)]
+pub enum Comments<'a> {
+    // both, as paragraphs, black's first
+    #[default]
+    Join,
+    // both as paragraphs, each starting with the name of the player whose
+    // record it comes from, as PB and PW give it
+    Attribute,
+    Black,
+    White,
+    // an empty answer leaves the property out
+    Ask(Box<AskComment<'a>>),
+}
+
+impl Comments<'_> {
+    // None for names it doesn't know. Ask has no name, since it needs a
+    // callback
+    pub fn parse(s: &str) -> Option<Comments<'static>> {
+        match s {
+            "join" => Some(Comments::Join),
+            "attribute" => Some(Comments::Attribute),
+            "black" => Some(Comments::Black),
+            "white" => Some(Comments::White),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Comments<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comments::Join => write!(f, "Join"),
+            Comments::Attribute => write!(f, "Attribute"),
+            Comments::Black => write!(f, "Black"),
+            Comments::White => write!(f, "White"),
+            Comments::Ask(_) => write!(f, "Ask(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Merged {
+    pub tree: GameTree,
+    pub conflicts: Vec<Conflict>,
+}
+
+// one move node with the nodes without moves that follow it, or the root
+// with the ones before the first move
+struct Unit<'a> {
+    nodes: Vec<&'a Node>,
+}
+
+impl Unit<'_> {
+    fn first(&self) -> &Node {
+        self.nodes[0]
+    }
+}
+
+fn units(gt: &GameTree) -> Vec<Unit<'_>> {
+    let mut units: Vec<Unit> = Vec::new();
+    for node in gt.mainline() {
+        match units.last_mut() {
+            Some(unit) if node.get_move().is_none() => unit.nodes.push(node),
+            _ => units.push(Unit { nodes: vec![node] }),
+        }
+    }
+    units
+}
+
+// unites the main lines of a game recorded twice, once by each player.
+// moves are matched in order; each side's clock comes from its own
+// record, comments from both are kept as paragraphs and other
+// properties are united, with clashes reported. variations of the
+// records are dropped. the records must be of the same board and agree
+// on the root
+pub fn merge_halves(black: &GameTree, white: &GameTree) -> Result<Merged> {
+    merge_halves_with(black, white, &mut Comments::Join)
+}
+
+// merge_halves, with comments that differ settled by comments
+pub fn merge_halves_with(black: &GameTree, white: &GameTree, comments: &mut Comments) -> Result<Merged> {
+    black.check_same_size(white)?;
+    let (cols, rows) = black.board_size();
+    let same_move = |a: &Node, b: &Node| match (a.get_move(), b.get_move()) {
+        (Some((ca, va)), Some((cb, vb))) => ca == cb && (va == vb || (is_pass(va, cols, rows) && is_pass(vb, cols, rows))),
+        (None, None) => true,
+        _ => false,
+    };
+    let (b_units, w_units) = (units(black), units(white));
+    let mut conflicts = Vec::new();
+    let mut nodes = Vec::new();
+    let player = |gt: &GameTree, ident: &str, default: &str| gt.root().value(ident).filter(|v| !v.trim().is_empty()).unwrap_or(default).to_string();
+    let names = [player(black, "PB", "Black"), player(white, "PW", "White")];
+    // the move number of unit i is i, or i + 1 when the game starts with
+    // a move in the root
+    let first_move = b_units.first().is_some_and(|u| u.first().get_move().is_some()) as usize;
+    let mut i = 0;
+    while i < b_units.len() && i < w_units.len() && same_move(b_units[i].first(), w_units[i].first()) {
+        let mover = b_units[i].first().get_move().map(|(c, _)| c);
+        let mut merger = NodeMerger {
+            mover,
+            number: i + first_move,
+            comments,
+            names: &names,
+            conflicts: &mut conflicts,
+        };
+        nodes.push(merger.merge(b_units[i].first(), w_units[i].first()));
+        nodes.extend(merge_rest(&b_units[i].nodes[1..], &w_units[i].nodes[1..]));
+        i += 1;
+    }
+    let rest = |units: &[Unit]| -> Vec<Node> { units.iter().flat_map(|u| u.nodes.iter().map(|n| (*n).clone())).collect() };
+    let (b_rest, w_rest) = (rest(&b_units[i..]), rest(&w_units[i..]));
+    let describe = |n: Option<&Node>| match n.and_then(|n| n.get_move()) {
+        Some((color, value)) => format!("{}[{}]", color.ident(), value),
+        None => "no move".to_string(),
+    };
+    let gametrees = match (b_rest.is_empty(), w_rest.is_empty()) {
+        (true, true) => Vec::new(),
+        // one record stopped early: the other finishes the game
+        (false, true) | (true, false) => {
+            nodes.extend(if b_rest.is_empty() { w_rest } else { b_rest });
+            Vec::new()
+        },
+        (false, false) if i == 0 => {
+            return Err(Error::RootsDiffer {
+                black: describe(b_rest.first()),
+                white: describe(w_rest.first()),
+            });
+        },
+        (false, false) => {
+            conflicts.push(Conflict::Diverged {
+                number: i + first_move,
+                black: describe(b_rest.first()),
+                white: describe(w_rest.first()),
+            });
+            let line = |nodes: Vec<Node>| Box::new(GameTree { sequence: Sequence { nodes }, gametrees: Vec::new() });
+            vec![line(b_rest), line(w_rest)]
+        },
+    };
+    Ok(Merged {
+        tree: GameTree { sequence: Sequence { nodes }, gametrees },
+        conflicts,
+    })
+}
+
+// unites a pair of matching nodes
+struct NodeMerger<'m, 'a> {
+    mover: Option<Color>,
+    number: usize,
+    comments: &'m mut Comments<'a>,
+    // of black and white, for Comments::Attribute
+    names: &'m [String; 2],
+    conflicts: &'m mut Vec<Conflict>,
+}
+
+impl NodeMerger<'_, '_> {
+    fn merge(&mut self, black: &Node, white: &Node) -> Node {
+        let mut node = black.clone();
+        for prop in &white.props {
+            let ident = prop.ident.as_str();
+            let Some(ours) = node.get(ident).map(|p| p.values.clone()) else {
+                node.props.push(prop.clone());
+                continue;
+            };
+            // the moves already match, passes written either way
+            if ours == prop.values || matches!(ident, "B" | "W") {
+                continue;
+            }
+            if MULTILINE_PROPERTIES.contains(&ident) {
+                let comment = self.comment(ident, &ours.join("\n\n"), &prop.values.join("\n\n"));
+                if comment.is_empty() {
+                    node.remove(ident);
+                } else {
+                    node.set(ident, vec![comment]);
+                }
+                continue;
+            }
+            // a player's own clock wins without a conflict, since only they
+            // saw it
+            if BLACK_CLOCK.contains(&ident) {
+                continue;
+            }
+            if WHITE_CLOCK.contains(&ident) {
+                node.set(ident, prop.values.clone());
+                continue;
+            }
+            self.conflicts.push(Conflict::Property {
+                number: self.number,
+                ident: ident.to_string(),
+                black: ours,
+                white: prop.values.clone(),
+            });
+            if self.mover == Some(Color::White) {
+                node.set(ident, prop.values.clone());
+            }
+        }
+        node
+    }
+
+    fn comment(&mut self, ident: &str, black: &str, white: &str) -> String {
+        match self.comments {
+            Comments::Join => join_paragraphs(&[black, white]),
+            Comments::Attribute => {
+                let attributed = |name: &str, text: &str| if text.trim().is_empty() { String::new() } else { format!("{}: {}", name, text.trim()) };
+                join_paragraphs(&[attributed(&self.names[0], black), attributed(&self.names[1], white)])
+            },
+            Comments::Black => black.to_string(),
+            Comments::White => white.to_string(),
+            Comments::Ask(ask) => ask(self.number, ident, black, white),
+        }
+    }
+}
+
+// the nodes without moves after a merged one: black's, then white's that
+// black's record doesn't have
+fn merge_rest(black: &[&Node], white: &[&Node]) -> Vec<Node> {
+    let mut nodes: Vec<Node> = black.iter().map(|n| (*n).clone()).collect();
+    let seen: Vec<String> = black.iter().map(|n| n.to_string()).collect();
+    for node in white {
+        if !seen.contains(&node.to_string()) {
+            nodes.push((*node).clone());
+        }
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn merge1() {
+        let black = gametree("(;PB[Me]RE[B+R];B[pd]BL[590]C[first];W[dd]WL[600];B[pp]BL[570];W[tt])");
+        let white = gametree("(;PB[Me]PW[You]RE[W+R];B[pd]BL[595];W[dd]WL[580]C[hm];B[pp];W[]WL[560];B[dp])");
+        let merged = merge_halves(&black, &white).unwrap();
+        assert_eq!(
+            merged.tree.to_string(),
+            "(;PB[Me]RE[B+R]PW[You];B[pd]BL[590]C[first];W[dd]WL[580]C[hm];B[pp]BL[570];W[tt]WL[560];B[dp])"
+        );
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].to_string(), "move 0: RE is [B+R] in black's record, [W+R] in white's");
+    }
+
+    #[test]
+    fn merge2() {
+        let black = gametree("(;B[pd]C[a];W[dd];N[note];B[pp])");
+        let white = gametree("(;B[pd]C[b];W[dd]MA[aa]C[x];N[note];B[dp]TR[aa];W[pp])");
+        let merged = merge_halves(&black, &white).unwrap();
+        assert_eq!(merged.tree.to_string(), "(;B[pd]C[a\n\nb];W[dd]MA[aa]C[x];N[note](;B[pp])(;B[dp]TR[aa];W[pp]))");
+        assert_eq!(merged.conflicts, vec![Conflict::Diverged { number: 3, black: "B[pp]".to_string(), white: "B[dp]".to_string() }]);
+    }
+
+    #[test]
+    fn merge3() {
+        let black = gametree("(;PB[Me]GC[mine];B[pd]C[good];W[dd]C[same])");
+        let white = gametree("(;PB[Me]GC[ours];B[pd]C[bad];W[dd]C[same])");
+        let merged = merge_halves_with(&black, &white, &mut Comments::Attribute).unwrap();
+        assert_eq!(merged.tree.to_string(), "(;PB[Me]GC[Me: mine\n\nWhite: ours];B[pd]C[Me: good\n\nWhite: bad];W[dd]C[same])");
+        let merged = merge_halves_with(&black, &white, &mut Comments::White).unwrap();
+        assert_eq!(merged.tree.to_string(), "(;PB[Me]GC[ours];B[pd]C[bad];W[dd]C[same])");
+        let merged = merge_halves_with(&black, &white, &mut Comments::Black).unwrap();
+        assert_eq!(merged.tree.to_string(), "(;PB[Me]GC[mine];B[pd]C[good];W[dd]C[same])");
+        let mut asked = Vec::new();
+        let mut ask = Comments::Ask(Box::new(|number, ident, black, white| {
+            asked.push(format!("{} {} {} {}", number, ident, black, white));
+            if ident == "GC" { String::new() } else { white.to_uppercase() }
+        }));
+        let merged = merge_halves_with(&black, &white, &mut ask).unwrap();
+        drop(ask);
+        assert_eq!(merged.tree.to_string(), "(;PB[Me];B[pd]C[BAD];W[dd]C[same])");
+        assert_eq!(asked, vec!["0 GC mine ours", "1 C good bad"]);
+        assert!(merged.conflicts.is_empty());
+        assert!(matches!(Comments::parse("white"), Some(Comments::White)));
+    }
+
+    #[test]
+    fn merge4() {
+        let black = gametree("(;SZ[19];B[pd];W[dd])");
+        assert!(matches!(merge_halves(&black, &gametree("(;SZ[13];B[pd];W[dd])")), Err(Error::Size(_))));
+        let white = gametree("(;SZ[19]B[dd];W[pd])");
+        assert!(matches!(merge_halves(&black, &white), Err(Error::RootsDiffer { .. })));
+        assert!(matches!(merge_halves(&gametree("(;B[pd])"), &gametree("(;B[dd])")), Err(Error::RootsDiffer { .. })));
+    }
+}
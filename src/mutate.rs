@@ -0,0 +1,173 @@
+use std::fmt;
+
+use super::bookmark::format_path;
+use super::point::Point;
+use super::vertex::{Color, GameTree, Node, Sequence};
+
+#[derive(Debug)]
+pub enum Error {
+    NoSuchNode(Vec<usize>),
+    // nothing goes before the root node
+    BeforeRoot,
+    // a variation needs at least one node
+    EmptyVariation,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoSuchNode(path) => write!(f, "no node at {}", format_path(path)),
+            Error::BeforeRoot => write!(f, "can't insert a node before the root"),
+            Error::EmptyVariation => write!(f, "can't add a variation without nodes"),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Node {
+    pub fn new() -> Self {
+        Node { props: Vec::new() }
+    }
+
+    // B or W at p, or a pass
+    pub fn from_move(color: Color, p: Option<Point>) -> Self {
+        let mut node = Node::new();
+        // FF[4] passes are empty values
+        node.set(color.ident(), vec![p.map(|p| p.to_sgf()).unwrap_or_default()]);
+        node
+    }
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node::new()
+    }
+}
+
+impl Sequence {
+    pub fn new(nodes: Vec<Node>) -> Self {
+        Sequence { nodes }
+    }
+
+    pub fn push(&mut self, node: Node) {
+        self.nodes.push(node);
+    }
+
+    pub fn insert_node_at(&mut self, i: usize, node: Node) {
+        self.nodes.insert(i, node);
+    }
+}
+
+// the gametree holding the node at path and the node's place in its
+// sequence, as GameTree::node_mut walks
+pub(crate) fn locate<'a>(mut gt: &'a mut GameTree, path: &[usize]) -> Option<(&'a mut GameTree, usize)> {
+    let mut i = 0;
+    for &step in path {
+        if i + 1 < gt.sequence.nodes.len() {
+            if step != 0 {
+                return None;
+            }
+            i += 1;
+        } else {
+            gt = gt.gametrees.get_mut(step)?;
+            i = 0;
+        }
+    }
+    (i < gt.sequence.nodes.len()).then_some((gt, i))
+}
+
+// makes the nodes after i a variation of their own, so the node at i
+// can branch
+pub(crate) fn split_after(gt: &mut GameTree, i: usize) {
+    if i + 1 < gt.sequence.nodes.len() {
+        let rest = GameTree {
+            sequence: Sequence {
+                nodes: gt.sequence.nodes.split_off(i + 1),
+            },
+            gametrees: std::mem::take(&mut gt.gametrees),
+        };
+        gt.gametrees.push(Box::new(rest));
+    }
+}
+
+// the reverse of split_after, once a single line is left. the paths of
+// the nodes stay the same
+pub(crate) fn join_single(gt: &mut GameTree) {
+    if gt.gametrees.len() == 1 {
+        let child = gt.gametrees.remove(0);
+        gt.sequence.nodes.extend(child.sequence.nodes);
+        gt.gametrees = child.gametrees;
+    }
+}
+
+// every method takes the path of a node as GameTree::nodes gives it and
+// returns the path of the node it added
+impl GameTree {
+    // plays a move after the node at path: at the end of the line, or as
+    // a new variation when the game already goes on from there
+    pub fn add_move(&mut self, path: &[usize], color: Color, p: Option<Point>) -> Result<Vec<usize>> {
+        self.append_node(path, Node::from_move(color, p))
+    }
+
+    pub fn append_node(&mut self, path: &[usize], node: Node) -> Result<Vec<usize>> {
+        self.add_variation(path, vec![node])
+    }
+
+    // puts node in front of the node at path, which moves down one
+    pub fn insert_node_at(&mut self, path: &[usize], node: Node) -> Result<Vec<usize>> {
+        if path.is_empty() {
+            return Err(Error::BeforeRoot);
+        }
+        let (gt, i) = locate(self, path).ok_or_else(|| Error::NoSuchNode(path.to_vec()))?;
+        gt.sequence.insert_node_at(i, node);
+        Ok(path.to_vec())
+    }
+
+    // a line of nodes following the node at path, after the variations
+    // already there
+    pub fn add_variation(&mut self, path: &[usize], nodes: Vec<Node>) -> Result<Vec<usize>> {
+        if nodes.is_empty() {
+            return Err(Error::EmptyVariation);
+        }
+        let (gt, i) = locate(self, path).ok_or_else(|| Error::NoSuchNode(path.to_vec()))?;
+        split_after(gt, i);
+        gt.gametrees.push(Box::new(GameTree {
+            sequence: Sequence::new(nodes),
+            gametrees: Vec::new(),
+        }));
+        let n = gt.gametrees.len() - 1;
+        join_single(gt);
+        let mut first = path.to_vec();
+        first.push(n);
+        Ok(first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn mutate1() {
+        let mut gt = Parser::new("(;GM[1];B[aa])").parse().unwrap().gametrees.remove(0);
+        let w = gt.add_move(&[0], Color::White, Some(Point::new(1, 1))).unwrap();
+        assert_eq!(w, vec![0, 0]);
+        assert_eq!(gt.to_string(), "(;GM[1];B[aa];W[bb])");
+        let pass = gt.add_move(&[0], Color::White, None).unwrap();
+        assert_eq!(pass, vec![0, 1]);
+        assert_eq!(gt.to_string(), "(;GM[1];B[aa](;W[bb])(;W[]))");
+        let mut comment = Node::new();
+        comment.set("C", vec!["setup".to_string()]);
+        assert_eq!(gt.insert_node_at(&[0], comment).unwrap(), vec![0]);
+        assert_eq!(gt.to_string(), "(;GM[1];C[setup];B[aa](;W[bb])(;W[]))");
+        let line = vec![Node::from_move(Color::White, Some(Point::new(2, 2))), Node::from_move(Color::Black, Some(Point::new(3, 3)))];
+        assert_eq!(gt.add_variation(&[0, 0], line).unwrap(), vec![0, 0, 2]);
+        assert_eq!(gt.node(&[0, 0, 2, 0]).unwrap().value("B"), Some("dd"));
+        assert!(gt.insert_node_at(&[], Node::new()).is_err());
+        assert!(matches!(gt.add_variation(&[0], Vec::new()), Err(Error::EmptyVariation)));
+        assert_eq!(gt.node(&[0]).unwrap().value("C"), Some("setup"));
+        assert!(matches!(gt.add_move(&[0, 0, 5], Color::Black, None), Err(Error::NoSuchNode(_))));
+    }
+}
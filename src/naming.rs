@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::digest;
+use super::filter::first_date;
+use super::info::pad_date;
+use super::translit::{self, NameForm};
+use super::vertex::GameTree;
+use super::writer::unescape_text;
+
+pub const DEFAULT_TEMPLATE: &str = "{date}_{pb}_vs_{pw}_{result}.sgf";
+
+// the longest a field may get in a name, in characters
+const MAX_FIELD: usize = 40;
+
+#[derive(Debug)]
+pub enum Error {
+    Unclosed(String),
+    UnknownField(String),
+    Names(translit::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unclosed(s) => write!(f, "unclosed field in {}", s),
+            Error::UnknownField(name) => write!(f, "unknown field {{{}}}", name),
+            Error::Names(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+// a file name pattern like {date}_{pb}_vs_{pw}_{result}.sgf. fields are
+// date (the first date of DT), result (RE), a root property by its ident
+// in lowercase, like pb or ev, and source (the stem of the file the game
+// came from), game (its number in that file) and hash (of its content).
+// a '/' in the template makes directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+    // how the player fields, pb and pw, are written
+    names: NameForm,
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(open) = rest.find(['{', '}']) {
+            if rest[open..].starts_with('}') {
+                return Err(Error::Unclosed(s.to_string()));
+            }
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| Error::Unclosed(s.to_string()))? + open;
+            let name = &rest[open + 1..close];
+            if !is_field(name) {
+                return Err(Error::UnknownField(name.to_string()));
+            }
+            parts.push(Part::Field(name.to_string()));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template {
+            parts,
+            names: NameForm::Keep,
+        })
+    }
+
+    pub fn with_names(mut self, names: NameForm) -> Result<Self> {
+        names.check().map_err(Error::Names)?;
+        self.names = names;
+        Ok(self)
+    }
+
+    // the name for the game number game of the file at source, before
+    // any collision suffix
+    pub fn render(&self, gt: &GameTree, source: &Path, game: usize) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(s) => name.push_str(s),
+                Part::Field(field) => name.push_str(&sanitize(&value(field, gt, source, game, self.names).unwrap_or_default())),
+            }
+        }
+        name
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template::parse(DEFAULT_TEMPLATE).unwrap()
+    }
+}
+
+fn is_field(name: &str) -> bool {
+    matches!(name, "date" | "result" | "source" | "game" | "hash") || (1..=2).contains(&name.len()) && name.chars().all(|c| c.is_ascii_lowercase())
+}
+
+fn value(field: &str, gt: &GameTree, source: &Path, game: usize, names: NameForm) -> Option<String> {
+    let root = gt.root();
+    match field {
+        "date" => first_date(root.value("DT")?).map(pad_date),
+        "result" => root.value("RE").map(unescape_text),
+        "source" => Some(source.file_stem()?.to_string_lossy().to_string()),
+        "game" => Some(game.to_string()),
+        "hash" => Some(format!("{:08x}", digest::content_hash(gt) as u32)),
+        // checked by with_names, so apply can't fail
+        "pb" | "pw" => root.value(&field.to_uppercase()).and_then(|v| names.apply(&unescape_text(v)).ok()),
+        ident => root.value(&ident.to_uppercase()).map(unescape_text),
+    }
+}
+
+// a value made safe in a file name on any system: path separators, the
+// characters windows refuses, controls and whitespace become '-', runs
+// of them one. empty values are "unknown"
+pub fn sanitize(value: &str) -> String {
+    let mut s = String::new();
+    for c in value.chars() {
+        let unsafe_char = c.is_whitespace() || c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|');
+        if !unsafe_char {
+            s.push(c);
+        } else if !s.ends_with('-') {
+            s.push('-');
+        }
+    }
+    // a leading '.' would hide the file, a trailing one windows drops
+    let s: String = s.trim_matches(['-', '.']).chars().take(MAX_FIELD).collect();
+    let s = s.trim_end_matches(['-', '.']);
+    if s.is_empty() { "unknown".to_string() } else { s.to_string() }
+}
+
+// hands out names from a template, adding -2, -3, ... before the
+// extension of a name already given. names that differ only in case are
+// the same on windows and macos, so they count as taken too
+#[derive(Debug, Clone)]
+pub struct Namer {
+    template: Template,
+    taken: HashSet<String>,
+    // where the names are for, when they are file names
+    dir: Option<PathBuf>,
+}
+
+impl Namer {
+    pub fn new(template: Template) -> Self {
+        Namer {
+            template,
+            taken: HashSet::new(),
+            dir: None,
+        }
+    }
+
+    // names for files in dir: anything already there under a name, of
+    // any kind, takes it too. the check is made as each name is given,
+    // so it sees what was written since
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    fn is_taken(&self, name: &str) -> bool {
+        self.taken.contains(&name.to_lowercase()) || self.dir.as_ref().is_some_and(|dir| fs::symlink_metadata(dir.join(name)).is_ok())
+    }
+
+    // keeps a name from being given, like that of a file already there
+    pub fn reserve(&mut self, name: &str) {
+        self.taken.insert(name.to_lowercase());
+    }
+
+    pub fn name(&mut self, gt: &GameTree, source: &Path, game: usize) -> String {
+        let name = self.template.render(gt, source, game);
+        let dir = name.rfind('/').map_or(0, |i| i + 1);
+        let (stem, ext) = match name[dir..].rfind('.') {
+            Some(i) if i > 0 => name.split_at(dir + i),
+            _ => (name.as_str(), ""),
+        };
+        let mut candidate = name.clone();
+        let mut n = 1;
+        while self.is_taken(&candidate) {
+            n += 1;
+            candidate = format!("{}-{}{}", stem, n, ext);
+        }
+        self.reserve(&candidate);
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn naming1() {
+        let coll = Parser::new("(;GM[1]DT[2016-03-09,10]PB[Lee Sedol]PW[AlphaGo]RE[W+R])(;GM[1]PB[a/b: c?]PW[..x..])(;GM[1]PB[lee sedol]PW[AlphaGo]DT[2016-03-09]RE[W+R])").parse().unwrap();
+        let source = Path::new("games/match.sgf");
+        let mut namer = Namer::new(Template::default());
+        namer.reserve("unknown_a-b-c_vs_x_unknown.sgf");
+        let names: Vec<String> = coll.gametrees.iter().enumerate().map(|(i, gt)| namer.name(gt, source, i)).collect();
+        assert_eq!(names, vec!["2016-03-09_Lee-Sedol_vs_AlphaGo_W+R.sgf", "unknown_a-b-c_vs_x_unknown-2.sgf", "2016-03-09_lee-sedol_vs_AlphaGo_W+R-2.sgf"]);
+        let template = Template::parse("{ev}/{source}-{game}").unwrap();
+        assert_eq!(Namer::new(template).name(&coll.gametrees[1], source, 1), "unknown/match-1");
+        let template = Template::parse("{pb}_{ev}").unwrap().with_names(NameForm::Hash).unwrap();
+        assert_eq!(template.render(&coll.gametrees[0], source, 0), template.render(&coll.gametrees[2], source, 2));
+        // only the players are hashed
+        assert!(template.render(&coll.gametrees[0], source, 0).ends_with("_unknown"));
+        assert_eq!(sanitize(&"x".repeat(50)).len(), MAX_FIELD);
+        assert!(matches!(Template::parse("{player}.sgf"), Err(Error::UnknownField(_))));
+        assert!(matches!(Template::parse("{pb.sgf"), Err(Error::Unclosed(_))));
+        assert!(Template::parse("pb}.sgf").is_err());
+        // whatever is in the directory is taken, not only games
+        let dir = std::env::temp_dir().join(format!("sgf-naming1-{}", std::process::id()));
+        fs::create_dir_all(dir.join("match-0")).unwrap();
+        fs::write(dir.join("match-0-2"), "").unwrap();
+        let mut namer = Namer::new(Template::parse("{source}-{game}").unwrap()).in_dir(&dir);
+        assert_eq!(namer.name(&coll.gametrees[0], source, 0), "match-0-3");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+use std::ops::Range;
+
+use super::borrowed::{Collection, GameTree, Node, Parser};
+use super::parser::Result;
+use super::writer::unescape_text;
+
+// comment previews are cut to this many characters
+const PREVIEW_CHARS: usize = 40;
+
+// the structure of a file for editor sidebars, with the byte range of
+// everything in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outline {
+    pub games: Vec<GameOutline>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameOutline {
+    pub title: String,
+    // moves on the main line
+    pub moves: usize,
+    pub span: Range<usize>,
+    pub branches: Vec<Branch>,
+    pub comments: Vec<Comment>,
+}
+
+// a node with more than one continuation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    // the moves played up to and including the node
+    pub move_number: usize,
+    pub path: Vec<usize>,
+    pub variations: usize,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub move_number: usize,
+    pub path: Vec<usize>,
+    // the first line of C, shortened
+    pub preview: String,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Game,
+    Branch,
+    Comment,
+}
+
+// one entry of the outline as a nested list, the shape of an LSP
+// documentSymbol response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub name: String,
+    pub detail: String,
+    pub span: Range<usize>,
+    pub children: Vec<Symbol>,
+}
+
+pub fn outline(source: &str) -> Result<Outline> {
+    Ok(Outline::new(&Parser::new(source).parse()?))
+}
+
+impl Outline {
+    pub fn new(coll: &Collection) -> Self {
+        Outline {
+            games: coll.gametrees.iter().map(GameOutline::new).collect(),
+        }
+    }
+
+    pub fn symbols(&self) -> Vec<Symbol> {
+        self.games.iter().map(|g| g.symbol()).collect()
+    }
+}
+
+impl GameOutline {
+    pub fn new(gt: &GameTree) -> Self {
+        let mut game = GameOutline {
+            title: title(gt.root()),
+            moves: gt.mainline().iter().filter(|n| is_move(n)).count(),
+            span: gt.span.clone(),
+            branches: Vec::new(),
+            comments: Vec::new(),
+        };
+        game.walk(gt, Vec::new(), 0);
+        game
+    }
+
+    fn walk(&mut self, gt: &GameTree, mut path: Vec<usize>, mut moves: usize) {
+        for (i, node) in gt.sequence.nodes.iter().enumerate() {
+            if i > 0 {
+                path.push(0);
+            }
+            if is_move(node) {
+                moves += 1;
+            }
+            if let Some(c) = node.value("C") {
+                self.comments.push(Comment {
+                    move_number: moves,
+                    path: path.clone(),
+                    preview: preview(c),
+                    span: node.span.clone(),
+                });
+            }
+        }
+        if gt.gametrees.len() > 1 {
+            self.branches.push(Branch {
+                move_number: moves,
+                path: path.clone(),
+                variations: gt.gametrees.len(),
+                span: gt.sequence.nodes.last().map_or(gt.span.clone(), |n| n.span.clone()),
+            });
+        }
+        for (i, child) in gt.gametrees.iter().enumerate() {
+            let mut p = path.clone();
+            p.push(i);
+            self.walk(child, p, moves);
+        }
+    }
+
+    // the game with its branches and comments under it, in file order
+    pub fn symbol(&self) -> Symbol {
+        let branches = self.branches.iter().map(|b| Symbol {
+            kind: SymbolKind::Branch,
+            name: format!("branch after move {}", b.move_number),
+            detail: format!("{} variations", b.variations),
+            span: b.span.clone(),
+            children: Vec::new(),
+        });
+        let comments = self.comments.iter().map(|c| Symbol {
+            kind: SymbolKind::Comment,
+            name: format!("comment at move {}", c.move_number),
+            detail: c.preview.clone(),
+            span: c.span.clone(),
+            children: Vec::new(),
+        });
+        let mut children: Vec<Symbol> = comments.chain(branches).collect();
+        children.sort_by_key(|s| s.span.start);
+        Symbol {
+            kind: SymbolKind::Game,
+            name: self.title.clone(),
+            detail: format!("{} moves", self.moves),
+            span: self.span.clone(),
+            children,
+        }
+    }
+}
+
+fn is_move(node: &Node) -> bool {
+    node.has("B") || node.has("W")
+}
+
+fn title(root: &Node) -> String {
+    match (root.value("PB"), root.value("PW")) {
+        (Some(b), Some(w)) => format!("{} (B) vs {} (W)", unescape_text(b), unescape_text(w)),
+        _ => root.value("GN").map_or("Game".to_string(), unescape_text),
+    }
+}
+
+fn preview(value: &str) -> String {
+    let text = unescape_text(value);
+    let line = text.trim().lines().next().unwrap_or("");
+    if line.chars().count() <= PREVIEW_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(PREVIEW_CHARS - 3).collect();
+    format!("{}...", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outline1() {
+        let source = "(;PB[Shin]PW[Park];B[pd];W[dd]C[a very long comment that goes on and on and on\nline two](;B[pp](;W[dp])(;W[qq]C[x]))(;B[dp]))(;GN[two])";
+        let outline = outline(source).unwrap();
+        assert_eq!(outline.games.len(), 2);
+        let game = &outline.games[0];
+        assert_eq!((game.title.as_str(), game.moves), ("Shin (B) vs Park (W)", 4));
+        let branches: Vec<(usize, Vec<usize>, usize)> = game.branches.iter().map(|b| (b.move_number, b.path.clone(), b.variations)).collect();
+        assert_eq!(branches, vec![(2, vec![0, 0], 2), (3, vec![0, 0, 0], 2)]);
+        assert_eq!(game.comments[0].preview, "a very long comment that goes on and...");
+        assert_eq!(&source[game.comments[1].span.clone()], ";W[qq]C[x]");
+        assert_eq!((game.comments[1].move_number, game.comments[1].path.clone()), (4, vec![0, 0, 0, 1]));
+        let symbol = game.symbol();
+        let names: Vec<&str> = symbol.children.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["comment at move 2", "branch after move 2", "branch after move 3", "comment at move 4"]);
+        assert_eq!(outline.symbols()[1].name, "two");
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt;
+
+use super::builder::{self, GameBuilder};
+use super::info::GameResult;
+use super::vertex::GameTree;
+use super::writer::escape_text;
+
+// a club's pairing sheet as csv, one game per line under a header that
+// names the columns, in any order and any case:
+//
+//   round,board,black,black rank,white,white rank,handicap,komi,result
+//   3,1,Ann,2d,Bob,1d,0,6.5,
+//
+// black and white are needed, the rest may be left out or left blank.
+// size, date and event columns are read too, and unknown ones are
+// skipped. a board may be given only once in a round. each game becomes
+// a skeleton SGF for its recorder to fill in
+
+#[derive(Debug)]
+pub enum Error {
+    NoHeader,
+    MissingColumn(&'static str),
+    // a line we could not read, numbered from 1 with the header
+    BadRow(usize, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoHeader => write!(f, "the pairing sheet has no header line"),
+            Error::MissingColumn(name) => write!(f, "the pairing sheet has no {} column", name),
+            Error::BadRow(line, s) => write!(f, "line {}: {}", line, s),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pairing {
+    pub round: Option<String>,
+    pub board: Option<String>,
+    pub black: String,
+    pub black_rank: Option<String>,
+    pub white: String,
+    pub white_rank: Option<String>,
+    pub handicap: usize,
+    pub komi: Option<f64>,
+    pub size: Option<usize>,
+    // as RE has it
+    pub result: Option<String>,
+    pub date: Option<String>,
+    pub event: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Column {
+    Round,
+    Board,
+    Black,
+    BlackRank,
+    White,
+    WhiteRank,
+    Handicap,
+    Komi,
+    Size,
+    Result,
+    Date,
+    Event,
+}
+
+fn column(name: &str) -> Option<Column> {
+    let name: String = name.to_lowercase().chars().filter(|c| c.is_alphanumeric()).collect();
+    let column = match name.as_str() {
+        "round" | "ro" => Column::Round,
+        "board" | "table" => Column::Board,
+        "black" | "pb" => Column::Black,
+        "blackrank" | "br" => Column::BlackRank,
+        "white" | "pw" => Column::White,
+        "whiterank" | "wr" => Column::WhiteRank,
+        "handicap" | "ha" => Column::Handicap,
+        "komi" | "km" => Column::Komi,
+        "size" | "sz" => Column::Size,
+        "result" | "re" => Column::Result,
+        "date" | "dt" => Column::Date,
+        "event" | "ev" => Column::Event,
+        _ => return None,
+    };
+    Some(column)
+}
+
+// the fields of a csv line: commas split them unless quoted, and "" in
+// quotes is a quote. values don't span lines
+pub fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+// RE for a result as sheets write it: "1-0" is a win for black, the
+// player on the left, "0-1" one for white, and "½-½", "0.5-0.5" or "="
+// a draw. anything RE could hold is taken as it is
+pub fn sheet_result(s: &str) -> Option<String> {
+    let re = match s.replace(' ', "").as_str() {
+        "1-0" | "1:0" => "B+".to_string(),
+        "0-1" | "0:1" => "W+".to_string(),
+        "½-½" | "0.5-0.5" | "=" => "0".to_string(),
+        _ => match GameResult::parse(s) {
+            GameResult::Unknown => return None,
+            result => result.to_string(),
+        },
+    };
+    Some(re)
+}
+
+pub fn parse_pairings(text: &str) -> Result<Vec<Pairing>> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or(Error::NoHeader)?;
+    let columns: Vec<Option<Column>> = csv_fields(header.trim_start_matches('\u{feff}')).iter().map(|name| column(name)).collect();
+    for (needed, name) in [(Column::Black, "black"), (Column::White, "white")] {
+        if !columns.contains(&Some(needed)) {
+            return Err(Error::MissingColumn(name));
+        }
+    }
+    let mut pairings = Vec::new();
+    for (i, line) in lines {
+        let err = |msg: String| Error::BadRow(i + 1, msg);
+        let mut pairing = Pairing::default();
+        for (column, value) in columns.iter().zip(csv_fields(line)) {
+            let Some(column) = column else { continue };
+            if value.is_empty() {
+                continue;
+            }
+            match column {
+                Column::Round => pairing.round = Some(value),
+                Column::Board => pairing.board = Some(value),
+                Column::Black => pairing.black = value,
+                Column::BlackRank => pairing.black_rank = Some(value),
+                Column::White => pairing.white = value,
+                Column::WhiteRank => pairing.white_rank = Some(value),
+                Column::Handicap => pairing.handicap = value.parse().map_err(|_| err(format!("bad handicap: {}", value)))?,
+                Column::Komi => pairing.komi = Some(value.parse().map_err(|_| err(format!("bad komi: {}", value)))?),
+                Column::Size => {
+                    let size = value.parse().ok().filter(|size| (1..=52).contains(size));
+                    pairing.size = Some(size.ok_or_else(|| err(format!("bad board size: {}", value)))?);
+                },
+                Column::Result => pairing.result = Some(sheet_result(&value).ok_or_else(|| err(format!("bad result: {}", value)))?),
+                Column::Date => pairing.date = Some(value),
+                Column::Event => pairing.event = Some(value),
+            }
+        }
+        if pairing.black.is_empty() || pairing.white.is_empty() {
+            return Err(err("a game needs both players".to_string()));
+        }
+        if pairing.board.is_some() && pairings.iter().any(|p: &Pairing| p.round == pairing.round && p.board == pairing.board) {
+            return Err(err(format!("board {} is paired twice", pairing.board.as_deref().unwrap_or_default())));
+        }
+        pairings.push(pairing);
+    }
+    Ok(pairings)
+}
+
+impl Pairing {
+    // the root of the game with everything the sheet knows about it.
+    // the board goes in GN, as SGF has no place of its own for it
+    pub fn gametree(&self) -> builder::Result<GameTree> {
+        let mut builder = GameBuilder::new(self.size.unwrap_or(19)).players(&self.black, &self.white);
+        let text = [
+            ("BR", &self.black_rank),
+            ("WR", &self.white_rank),
+            ("EV", &self.event),
+            ("RO", &self.round),
+            ("GN", &self.board.as_ref().map(|b| format!("Board {}", b))),
+            ("DT", &self.date),
+            ("RE", &self.result),
+        ];
+        for (ident, value) in text {
+            if let Some(value) = value {
+                builder = builder.set(ident, &escape_text(value, false));
+            }
+        }
+        if let Some(komi) = self.komi {
+            builder = builder.komi(komi);
+        }
+        Ok(builder.handicap(self.handicap)?.build())
+    }
+
+    // like round-3-board-1.sgf, with game-n in place of the board when
+    // the sheet has none
+    pub fn file_name(&self, n: usize) -> String {
+        let clean = |s: &str| -> String { s.chars().map(|c| if c.is_alphanumeric() { c } else { '-' }).collect() };
+        let mut parts = Vec::new();
+        if let Some(round) = &self.round {
+            parts.push(format!("round-{}", clean(round)));
+        }
+        match &self.board {
+            Some(board) => parts.push(format!("board-{}", clean(board))),
+            None => parts.push(format!("game-{}", n)),
+        }
+        format!("{}.sgf", parts.join("-"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairing1() {
+        let sheet = "\u{feff}Round,Board,Black,Black Rank,White,White Rank,Handicap,Komi,Result,Notes\n\
+                     3,1,\"Lee, Ann\",2d,Bob,1d,0,6.5,,late start\n\
+                     \n\
+                     3,2,Cy,5k,\"Di \"\"D\"\" O\",1k,4,0.5,0-1,\n";
+        let pairings = parse_pairings(sheet).unwrap();
+        assert_eq!(pairings.len(), 2);
+        assert_eq!(pairings[0].black, "Lee, Ann");
+        assert_eq!(pairings[1].white, "Di \"D\" O");
+        assert_eq!(pairings[0].file_name(1), "round-3-board-1.sgf");
+        assert_eq!(
+            pairings[0].gametree().unwrap().to_string(),
+            "(;GM[1]FF[4]SZ[19]PB[Lee, Ann]PW[Bob]BR[2d]WR[1d]RO[3]GN[Board 1]KM[6.5])"
+        );
+        assert_eq!(
+            pairings[1].gametree().unwrap().to_string(),
+            "(;GM[1]FF[4]SZ[19]PB[Cy]PW[Di \"D\" O]BR[5k]WR[1k]RO[3]GN[Board 2]RE[W+]KM[0.5]HA[4]AB[dp][pd][pp][dd]PL[W])"
+        );
+        assert_eq!(sheet_result("1 - 0"), Some("B+".to_string()));
+        assert_eq!(sheet_result("W+Resign"), Some("W+R".to_string()));
+        assert_eq!(sheet_result("won"), None);
+        assert!(matches!(parse_pairings("black,board\nAnn,1"), Err(Error::MissingColumn("white"))));
+        assert!(matches!(parse_pairings("black,white,komi\nAnn,Bob,x"), Err(Error::BadRow(2, _))));
+        assert!(matches!(parse_pairings("black,white\nAnn,"), Err(Error::BadRow(2, _))));
+        assert_eq!(parse_pairings("pb,pw\nA,B").unwrap()[0].file_name(7), "game-7.sgf");
+        assert_eq!(parse_pairings("ro,pb,pw\n2,A,B").unwrap()[0].file_name(7), "round-2-game-7.sgf");
+        assert!(matches!(parse_pairings("black,white,board\nA,B,1\nC,D,2\nE,F,1"), Err(Error::BadRow(4, _))));
+        assert_eq!(parse_pairings("round,black,white,board\n1,A,B,1\n2,C,D,1").unwrap().len(), 2);
+        assert!(matches!(parse_pairings("black,white,size\nA,B,0"), Err(Error::BadRow(2, _))));
+        assert!(matches!(parse_pairings("black,white,size\nA,B,53"), Err(Error::BadRow(2, _))));
+    }
+}
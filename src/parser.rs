@@ -1,19 +1,31 @@
 use std::fmt;
 
+use super::borrowed;
+use super::codes;
 use super::scanner;
-use super::scanner::{Scanner, Token};
-use super::vertex::{Collection, GameTree, Sequence, Node, Property};
+use super::version;
+use super::vertex::Collection;
 
 #[derive(Debug)]
 pub enum Error {
-    ParseError(String),
+    // code is the kind of error, from codes::CATALOG
+    ParseError { code: &'static str, message: String },
     Eof,
 }
 
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ParseError { code, .. } => code,
+            Error::Eof => codes::UNTERMINATED_VALUE,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let t = match self {
-            Error::ParseError(s) => s,
+            Error::ParseError { message, .. } => message,
             Error::Eof => "EOF",
         };
         write!(f, "{}", t)
@@ -22,204 +34,224 @@ impl fmt::Display for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-impl From<scanner::Error> for Error {
-    fn from(err: scanner::Error) -> Error {
-        Error::ParseError(err.to_string())
-    }
+// what to do with properties the registry doesn't know, like private
+// ones other programs add
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownProperties {
+    // as they are, for archiving
+    #[default]
+    Keep,
+    // left out with a warning
+    Drop,
+    // a parse error, for strict viewers
+    Reject,
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    cur: usize,
+// something a lenient parser skipped instead of failing on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub code: &'static str,
+    pub message: String,
 }
 
-impl Parser {
-    pub fn new(data: &str) -> Result<Self> {
-        let tokens = Scanner::new(data).scan()?;
-        let p = Parser {
-            tokens: tokens,
-            cur: 0,
-        };
-        Ok(p)
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
     }
+}
 
-    pub fn peek(&mut self, n: usize) -> Token {
-        if self.cur < self.tokens.len() - n {
-            self.tokens[self.cur + n].clone()
-        } else {
-            Token::Eof
+impl From<scanner::Error> for Error {
+    fn from(err: scanner::Error) -> Error {
+        Error::ParseError {
+            code: err.code(),
+            message: err.to_string(),
         }
     }
+}
 
-    pub fn read(&mut self) -> Token {
-        let ret = self.cur;
+// builds the owned tree. the parsing is borrowed::Parser's, over the
+// caller's text, and this keeps the options between files
+#[derive(Default)]
+pub struct Parser<'a> {
+    text: &'a str,
+    // skip properties with bad identifiers instead of failing
+    lenient: bool,
+    unknown: UnknownProperties,
+    warnings: Vec<Warning>,
+}
 
-        if ret >= self.tokens.len() {
-            self.cur += 1;
-            return Token::Eof;
+impl<'a> Parser<'a> {
+    pub fn new(data: &'a str) -> Self {
+        Parser {
+            text: data,
+            ..Default::default()
         }
+    }
 
-        self.cur += 1;
-        self.tokens[ret].clone()
+    // makes the parser ready for new text, keeping its options
+    pub fn reset(&mut self, data: &'a str) {
+        self.text = data;
+        self.warnings.clear();
     }
 
-    pub fn create_error(&mut self, msg: &str) -> Error {
-        if self.tokens.len() == 0 {
-            return Error::ParseError(format!("empty file"));
-        }
-        if self.cur >= self.tokens.len() {
-            return Error::ParseError(format!("parse_error at {}: {}", self.tokens[self.tokens.len()-1].position(), msg));
-        }
-        Error::ParseError(format!("parse_error at {}: {}", self.tokens[self.cur].position(), msg))
+    // properties whose identifiers aren't uppercase letters, like weird[]
+    // or an FF[4] AddBlack, are skipped with a warning rather than ending
+    // the parse
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
     }
 
-    pub fn unexpected(&mut self, msg: &str) -> Error {
-        let t = self.read();
-        self.create_error(&format!("unexpected {} {}", t, msg))
+    pub fn unknown_properties(mut self, unknown: UnknownProperties) -> Self {
+        self.unknown = unknown;
+        self
     }
 
-    pub fn consume_whitespace(&mut self) {
-        loop {
-            match self.peek(0) {
-                Token::Whitespace | Token::Newline(_) => self.read(),
-                _ => break,
-            };
+    // what the last parse skipped, when lenient or dropping unknown
+    // properties
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn reader(text: &'a str, lenient: bool, unknown: UnknownProperties) -> borrowed::Parser<'a> {
+        let reader = borrowed::Parser::new(text).normalized().unknown_properties(unknown);
+        if lenient {
+            reader.lenient()
+        } else {
+            reader
         }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub fn parse(&mut self) -> Result<Collection> {
-        self.consume_whitespace();
-        let mut gametrees = Vec::new();
-
-        // apparently kgs is ok with sgf files with garbage at the beginning
-        // so i guess we'll do that too why not
-        loop {
-            match self.peek(0) {
-                Token::OpenParen(_) => break,
-                Token::Eof => break,
-                _ => self.read(),
-            };
-        }
+        let mut reader = Parser::reader(self.text, self.lenient, self.unknown);
+        let coll = reader.parse();
+        self.warnings = reader.warnings;
+        Ok(coll?.into_owned())
+    }
 
-        loop {
-            match self.peek(0) {
-                Token::OpenParen(_) => gametrees.push(self.parse_gametree()?),
-                _ => break,
-            }
-        }
-        if gametrees.len() == 0 {
-            return Err(self.create_error("cannot have empty collection"));
-        }
-        Ok(Collection{gametrees})
-    }
-
-    pub fn parse_gametree(&mut self) -> Result<GameTree> {
-        // gametrees start with "("
-        self.read();
-        self.consume_whitespace();
-        let seq = self.parse_sequence()?;
-        self.consume_whitespace();
-        let mut trees = Vec::new();
-        loop {
-            match self.peek(0) {
-                Token::OpenParen(_) => {
-                    trees.push(Box::new(self.parse_gametree()?));
-                    self.consume_whitespace();
-                }
-                Token::CloseParen(_) => {
-                    self.read();
-                    break;
-                }
-                _ => return Err(self.unexpected("in parse_gametree")),
-            }
+    // like borrowed::Parser::parse_with_recovery
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn parse_with_recovery(&mut self) -> (Collection, Vec<Error>) {
+        let mut reader = Parser::reader(self.text, self.lenient, self.unknown);
+        let (coll, errors) = reader.parse_with_recovery();
+        self.warnings = reader.warnings;
+        (coll.into_owned(), errors)
+    }
+}
+
+// what Events reads, in the order of the text
+#[derive(Debug, Clone)]
+pub enum Event<'a> {
+    // the "(" of a game at the top of the collection
+    GameStart,
+    GameEnd,
+    // the "(" of a variation inside a game
+    VariationStart,
+    VariationEnd,
+    NodeStart,
+    // a property of the last node, borrowed like borrowed::Parser's
+    Property(borrowed::Property<'a>),
+}
+
+// one gametree Events is inside of
+struct Open {
+    has_nodes: bool,
+    has_variations: bool,
+}
+
+// a pull parser over the text, for collections too big to build a tree
+// of. it follows Parser's rules and fails where Parser would, after
+// giving the events up to the error. nothing is kept but the gametrees
+// it is inside of
+pub struct Events<'a> {
+    reader: borrowed::Parser<'a>,
+    open: Vec<Open>,
+    // past the ";" of a node, before its properties end
+    in_node: bool,
+    games: usize,
+    done: bool,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Events {
+            reader: borrowed::Parser::new(text).normalized(),
+            open: Vec::new(),
+            in_node: false,
+            games: 0,
+            done: false,
         }
-        Ok(GameTree{sequence: seq, gametrees: trees})
-    }
-
-    pub fn parse_sequence(&mut self) -> Result<Sequence> {
-        // sequences start with node
-        // nodes start with ";"
-        let mut nodes = Vec::new();
-        loop {
-            match self.peek(0) {
-                Token::Semicolon(_) => {
-                    nodes.push(self.parse_node()?);
-                    self.consume_whitespace();
-                }
-                _ => break,
+    }
+
+    fn next_event(&mut self) -> Result<Option<Event<'a>>> {
+        let reader = &mut self.reader;
+        if self.in_node {
+            let start = reader.cur;
+            if let Some(ident) = reader.parse_propident()? {
+                let prop = reader.parse_property(start, ident)?;
+                return Ok(Some(Event::Property(prop)));
             }
+            self.in_node = false;
         }
-        if nodes.len() == 0 {
-            return Err(self.create_error("cannot have empty node list"));
-        }
-        Ok(Sequence{nodes})
-    }
-
-    pub fn parse_node(&mut self) -> Result<Node> {
-        // nodes start with ";"
-        self.read();
-        self.consume_whitespace();
-        let mut props = Vec::new();
-        loop {
-            match self.peek(0) {
-                Token::UcLetter(..) => {
-                    props.push(self.parse_property()?);
-                    self.consume_whitespace();
-                }
-                _ => break,
+        let Some(top) = self.open.last_mut() else {
+            if self.games == 0 {
+                reader.skip_to_game();
             }
-        }
-        Ok(Node{props})
-    }
-
-    pub fn parse_property(&mut self) -> Result<Property> {
-        let ident = self.parse_propident()?;
-        self.consume_whitespace();
-        let mut values = Vec::new();
-        loop {
-            match self.peek(0) {
-                Token::OpenSquare(_) => {
-                    values.push(self.parse_propvalue()?);
-                    self.consume_whitespace();
+            reader.consume_whitespace();
+            // like Parser, anything after the last game is ignored
+            if reader.peek() != Some(b'(') {
+                if self.games == 0 {
+                    return Err(reader.create_error(codes::NO_GAME, "cannot have empty collection"));
                 }
-                _ => break,
+                return Ok(None);
             }
+            reader.cur += 1;
+            reader.version = version::DEFAULT;
+            reader.consume_whitespace();
+            self.games += 1;
+            self.open.push(Open { has_nodes: false, has_variations: false });
+            return Ok(Some(Event::GameStart));
+        };
+        reader.consume_whitespace();
+        match reader.peek() {
+            Some(b';') if !top.has_variations => {
+                reader.cur += 1;
+                reader.consume_whitespace();
+                top.has_nodes = true;
+                self.in_node = true;
+                Ok(Some(Event::NodeStart))
+            },
+            Some(b'(' | b')') if !top.has_nodes => Err(reader.create_error(codes::EMPTY_GAMETREE, "cannot have empty node list")),
+            Some(b'(') => {
+                reader.cur += 1;
+                reader.consume_whitespace();
+                top.has_variations = true;
+                self.open.push(Open { has_nodes: false, has_variations: false });
+                Ok(Some(Event::VariationStart))
+            },
+            Some(b')') => {
+                reader.cur += 1;
+                self.open.pop();
+                Ok(Some(if self.open.is_empty() { Event::GameEnd } else { Event::VariationEnd }))
+            },
+            Some(_) => Err(reader.create_error(codes::UNEXPECTED_CHARACTER, "unexpected character in parse_gametree")),
+            None => Err(reader.create_error(codes::UNEXPECTED_CHARACTER, "unexpected EOF in parse_gametree")),
         }
-        if values.len() == 0 {
-            return Err(self.create_error("cannot have empty property list"));
-        }
-        Ok(Property{ident, values})
     }
+}
 
-    pub fn parse_propident(&mut self) -> Result<String> {
-        match self.read() {
-            Token::UcLetter(_, s) => Ok(s),
-            _ => Err(self.create_error("expected uppercase identifier")),
-        }
-    }
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<Event<'a>>;
 
-    pub fn parse_propvalue(&mut self) -> Result<String> {
-        match self.peek(0) {
-            Token::OpenSquare(_) => self.read(),
-            _ => return Err(self.unexpected("needed '[' in parse_propvalue")),
-        };
-        let mut s = "".to_owned();
-        loop {
-            match self.peek(0) {
-                Token::CloseSquare(_) => break,
-                Token::Eof => return Err(self.unexpected("eof while waiting for ']'")),
-                t => {
-                    s.push_str(&format!("{}", t));
-                    self.read();
-                }
-            }
+    // None after the collection ends or after an error
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
-
-        match self.peek(0) {
-            Token::CloseSquare(_) => self.read(),
-            _ => return Err(self.unexpected("needed ']' in parse_propvalue")),
-        };
-        Ok(s)
+        let event = self.next_event().transpose();
+        self.done = !matches!(event, Some(Ok(_)));
+        event
     }
 }
 
@@ -227,34 +259,91 @@ impl Parser {
 mod tests {
     use super::*;
 
+    #[test]
+    fn events1() {
+        let text = "junk (;FF[4]C[a\\]b];B[aa](;W[bb])(;W[cc]AB[dd][ee]))\n(;GM[1]) trailing";
+        let events: Vec<String> = Events::new(text)
+            .map(|e| match e.unwrap() {
+                Event::Property(prop) => format!("{}={}", prop.ident, prop.values.join(",")),
+                e => format!("{:?}", e),
+            })
+            .collect();
+        let expected = [
+            "GameStart", "NodeStart", "FF=4", "C=a\\]b", "NodeStart", "B=aa", "VariationStart", "NodeStart", "W=bb", "VariationEnd",
+            "VariationStart", "NodeStart", "W=cc", "AB=dd,ee", "VariationEnd", "GameEnd", "GameStart", "NodeStart", "GM=1", "GameEnd",
+        ];
+        assert_eq!(events, expected);
+        for bad in ["(;FF[4]weird[x])", "(;B[aa](;W[bb]);C[x])", "(;B[aa]()", "(;C[x", "()", "", "(;B[aa]"] {
+            let err = Parser::new(bad).parse().unwrap_err();
+            let last = Events::new(bad).last().unwrap();
+            assert_eq!(last.unwrap_err().code(), err.code(), "{}", bad);
+        }
+        let mut events = Events::new("(;C)");
+        assert!(events.nth(2).unwrap().is_err());
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn parse_ident1() {
+        let text = "(;FF[4]GM[1]weird[x][y] ;B[aa]AddBlack[bb])";
+        let err = Parser::new(text).parse().unwrap_err();
+        assert_eq!(err.code(), codes::BAD_IDENTIFIER);
+        assert!(err.to_string().ends_with("property identifiers must be uppercase letters, found 'weird'"));
+        let mut parser = Parser::new(text).lenient();
+        let coll = parser.parse().unwrap();
+        assert_eq!(coll.to_string(), "(;FF[4]GM[1];B[aa])");
+        let skipped: Vec<&str> = parser.warnings().iter().map(|w| w.message.split(' ').nth(1).unwrap()).collect();
+        assert_eq!(skipped, vec!["'weird'", "'AddBlack'"]);
+        // before FF[4] the uppercase letters are the identifier
+        assert_eq!(Parser::new("(;AddBlack[aa]weird[x])").lenient().parse().unwrap().to_string(), "(;AB[aa])");
+    }
+
+    #[test]
+    fn parse_unknown1() {
+        let text = "(;FF[4]GM[1]XX[1]MULTIGOGM[2];B[aa]RSBM[mark])";
+        assert_eq!(Parser::new(text).parse().unwrap().to_string(), text);
+        let mut parser = Parser::new(text).unknown_properties(UnknownProperties::Drop);
+        assert_eq!(parser.parse().unwrap().to_string(), "(;FF[4]GM[1];B[aa])");
+        let dropped: Vec<&str> = parser.warnings().iter().map(|w| w.message.split(' ').nth(3).unwrap()).collect();
+        assert_eq!(dropped, vec!["XX", "MULTIGOGM", "RSBM"]);
+        let err = Parser::new(text).unknown_properties(UnknownProperties::Reject).parse().unwrap_err();
+        assert_eq!(err.code(), codes::UNKNOWN_PROPERTY);
+        assert!(err.to_string().contains("unknown property XX"));
+        // bad identifiers are a different thing, skipped only when lenient
+        let mut parser = Parser::new("(;FF[4]weird[x]XX[y])").lenient().unknown_properties(UnknownProperties::Drop);
+        parser.parse().unwrap();
+        let codes: Vec<&str> = parser.warnings().iter().map(|w| w.code).collect();
+        assert_eq!(codes, vec![codes::BAD_IDENTIFIER, codes::UNKNOWN_PROPERTY]);
+    }
+
     #[test]
     fn parse1() {
         let text = "(;GM[1])";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
 
     #[test]
     fn parse2() {
 		let text = "(;GM[1]AW[ab][bc])";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
 
     #[test]
     fn parse3() {
 		let text = "(;GM[1];B[cc])";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
 
     #[test]
     fn parse4() {
 		let text = "(;ZZ[aoeu [1k\\]])";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
     
     #[test]
     fn parse5() {
 		let text = "(;GM[1](;B[aa];W[ab])(;B[ab];W[ac]))";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
 
     #[test]
@@ -275,7 +364,7 @@ CR[qa][qb][qc]
 TR[sa][sb][sc]
 SQ[ra][rb][rc]
 )";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
 
     #[test]
@@ -309,7 +398,7 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
 	;W[mc]C[White lives])
 (;B[]C[A default consideration]
 	;W[mc]C[White lives easily]))";
-        let _ = Parser::new(text).unwrap().parse().unwrap();
+        let _ = Parser::new(text).parse().unwrap();
     }
 
     /* error cases
@@ -323,10 +412,22 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
         "(;weird[])",
     */
 
+    #[test]
+    fn reset1() {
+        let mut parser = Parser::default();
+        assert!(parser.parse().is_err());
+        parser.reset("(;GM[1];B[aa])");
+        assert_eq!(parser.parse().unwrap().to_string(), "(;GM[1];B[aa])");
+        parser.reset("(;C[99999999999999999999999])");
+        assert!(parser.parse().is_err());
+        parser.reset("(;FF[3]AddWhite[bb])");
+        assert_eq!(parser.parse().unwrap().to_string(), "(;FF[3]AW[bb])");
+    }
+
     #[test]
     fn parse8() {
         let text = "";
-        if let Ok(_) = Parser::new(text).unwrap().parse() {
+        if Parser::new(text).parse().is_ok() {
             panic!();
         }
     }
@@ -334,7 +435,7 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
     #[test]
     fn parse9() {
         let text = "\n";
-        if let Ok(_) = Parser::new(text).unwrap().parse() {
+        if Parser::new(text).parse().is_ok() {
             panic!();
         }
     }
@@ -342,7 +443,7 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
     #[test]
     fn parse10() {
         let text = "\x28\x0a\x3b";
-        if let Ok(_) = Parser::new(text).unwrap().parse() {
+        if Parser::new(text).parse().is_ok() {
             panic!();
         }
     }
@@ -350,7 +451,7 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
     #[test]
     fn parse11() {
         let text = "(;A[";
-        if let Ok(_) = Parser::new(text).unwrap().parse(){
+        if Parser::new(text).parse().is_ok() {
             panic!();
         }
     }
@@ -358,7 +459,7 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
     #[test]
     fn parse12() {
         let text = "(;gm[1])";
-        if let Ok(_) = Parser::new(text).unwrap().parse() {
+        if Parser::new(text).parse().is_ok() {
             panic!();
         }
     }
@@ -366,8 +467,26 @@ AB[na][ra][mb][rb][lc][qc][ld][od][qd][le][pe][qe][mf][nf][of][pg]
     #[test]
     fn parse13() {
         let text = "(;[1])";
-        if let Ok(_) = Parser::new(text).unwrap().parse() {
+        if Parser::new(text).parse().is_ok() {
             panic!();
         }
     }
+
+    #[test]
+    fn parse_recover1() {
+        let text = "junk(;GM[1]C[a;b]XY;B[aa]W;W[bb]]](;B[cc]C)()(;B[dd];W[ee]) ;B[ff])x(;GM[1];B[gg]";
+        let mut p = Parser::new(text);
+        assert_eq!(p.parse().unwrap_err().code(), codes::PROPERTY_WITHOUT_VALUE);
+        let (coll, errors) = Parser::new(text).parse_with_recovery();
+        let codes: Vec<&str> = errors.iter().map(|e| e.code()).collect();
+        assert_eq!(codes, vec!["E004", "E004", "E006", "E004", "E003", "E006", "E006"]);
+        assert_eq!(coll.to_string(), "(;GM[1]C[a;b];B[aa];W[bb](;B[cc])(;B[dd];W[ee]))(;GM[1];B[gg])");
+        let good = "(;GM[1];B[aa](;W[bb])(;W[cc]))";
+        let (coll, errors) = Parser::new(good).parse_with_recovery();
+        assert!(errors.is_empty());
+        assert_eq!(coll.to_string(), Parser::new(good).parse().unwrap().to_string());
+        let (coll, errors) = Parser::new("no games").parse_with_recovery();
+        assert!(coll.gametrees.is_empty());
+        assert_eq!(errors[0].code(), codes::NO_GAME);
+    }
 }
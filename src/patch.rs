@@ -0,0 +1,134 @@
+use std::fmt;
+use std::ops::Range;
+
+use super::borrowed::{self, Parser};
+use super::parser;
+use super::vertex::{Collection, GameTree, Node};
+
+// edits the text of a file through its tree, but rewrites only the
+// properties the edit changed. the rest, from the layout to values the
+// parser would tidy like DT[2024-03-01], stays as it was written. an
+// edit that adds or removes nodes or variations has the gametree it
+// changed written again whole, from values as written, so only its
+// layout is lost. for the commands that change files in place
+
+#[derive(Debug)]
+pub enum Error {
+    ParseError(parser::Error),
+    // the edit added or removed games, which a patch can't follow
+    ShapeChanged,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParseError(e) => write!(f, "{}", e),
+            Error::ShapeChanged => write!(f, "the edit added or removed games"),
+        }
+    }
+}
+
+impl From<parser::Error> for Error {
+    fn from(err: parser::Error) -> Error {
+        Error::ParseError(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// runs edit over the tree of text, with its values as written, and gives
+// the text with the changes made to it and what edit returned. the text
+// comes back as it was when nothing changed
+pub fn patch<R, F>(text: &str, edit: F) -> Result<(String, R)>
+where
+    F: FnOnce(&mut Collection) -> R,
+{
+    let parsed = Parser::new(text).parse()?;
+    let mut coll = parsed.clone().into_owned();
+    let r = edit(&mut coll);
+    if coll.gametrees.len() != parsed.gametrees.len() {
+        return Err(Error::ShapeChanged);
+    }
+    let mut edits = Vec::new();
+    let mut trees: Vec<(&borrowed::GameTree, &GameTree)> = parsed.gametrees.iter().zip(&coll.gametrees).collect();
+    while let Some((old, new)) = trees.pop() {
+        if old.sequence.nodes.len() != new.sequence.nodes.len() || old.gametrees.len() != new.gametrees.len() {
+            edits.push((old.span.clone(), new.to_string()));
+            continue;
+        }
+        for (old, new) in old.sequence.nodes.iter().zip(&new.sequence.nodes) {
+            node_edits(old, new, &mut edits);
+        }
+        trees.extend(old.gametrees.iter().zip(new.gametrees.iter().map(|gt| gt.as_ref())));
+    }
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut out = String::with_capacity(text.len());
+    let mut at = 0;
+    for (range, s) in edits {
+        out.push_str(&text[at..range.start]);
+        out.push_str(&s);
+        at = range.end;
+    }
+    out.push_str(&text[at..]);
+    Ok((out, r))
+}
+
+// the replacements that turn the text of old into new. properties are
+// matched up by ident in order, as edits change values, not places:
+// changed ones are written again where they were, removed ones go with
+// the whitespace before them and new ones are added at the end
+fn node_edits(old: &borrowed::Node, new: &Node, edits: &mut Vec<(Range<usize>, String)>) {
+    let mut matched = vec![false; new.props.len()];
+    for (i, prop) in old.props.iter().enumerate() {
+        let found = (0..new.props.len()).find(|j| !matched[*j] && new.props[*j].ident == prop.ident);
+        match found {
+            Some(j) => {
+                matched[j] = true;
+                if !new.props[j].values.iter().eq(prop.values.iter()) {
+                    edits.push((prop.span.clone(), new.props[j].to_string()));
+                }
+            },
+            None => {
+                let start = match i {
+                    0 => old.span.start + 1,
+                    _ => old.props[i - 1].span.end,
+                };
+                edits.push((start..prop.span.end, String::new()));
+            },
+        }
+    }
+    let added: String = new.props.iter().zip(&matched).filter(|(_, m)| !**m).map(|(p, _)| p.to_string()).collect();
+    if !added.is_empty() {
+        edits.push((old.span.end..old.span.end, added));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch1() {
+        let text = "(;GM[1]DT[2024-03-01]\n  KM[5.5] GN[x]\n;B[pd]C[a  b]\n(;W[dd])(;W[dp]))";
+        let (patched, _) = patch(text, |coll| {
+            let root = &mut coll.gametrees[0].sequence.nodes[0];
+            root.set("KM", vec!["6.5".to_string()]);
+            root.props.retain(|p| p.ident != "GN");
+            root.set("PB", vec!["Ann".to_string()]);
+        })
+        .unwrap();
+        assert_eq!(patched, "(;GM[1]DT[2024-03-01]\n  KM[6.5]PB[Ann]\n;B[pd]C[a  b]\n(;W[dd])(;W[dp]))");
+        assert_eq!(patch(text, |_| ()).unwrap().0, text);
+        // a variation less rewrites the gametree it was in, values and
+        // all as they were
+        let (patched, _) = patch(text, |coll| coll.gametrees[0].gametrees.remove(1)).unwrap();
+        assert_eq!(patched, "(;GM[1]DT[2024-03-01]KM[5.5]GN[x];B[pd]C[a  b](;W[dd]))");
+        let (patched, _) = patch(text, |coll| coll.gametrees[0].gametrees[1].sequence.nodes.clear()).unwrap();
+        assert_eq!(patched, "(;GM[1]DT[2024-03-01]\n  KM[5.5] GN[x]\n;B[pd]C[a  b]\n(;W[dd])(;))");
+        let shape = patch(text, |coll| coll.gametrees.clear());
+        assert!(matches!(shape, Err(Error::ShapeChanged)));
+        // values the parser can't tidy are fine as they are
+        let big = "(;C[99999999999999999999999])";
+        assert_eq!(patch(big, |_| ()).unwrap().0, big);
+    }
+}
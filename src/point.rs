@@ -0,0 +1,283 @@
+use std::fmt;
+
+use super::info::is_pass;
+use super::vertex::{Color, GameTree};
+
+// a board coordinate, zero based from the top left corner like SGF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Point {
+    pub x: u8,
+    pub y: u8,
+}
+
+const GTP_COLUMNS: &str = "ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+fn sgf_coord(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' => Some(c as u8 - b'a'),
+        'A'..='Z' => Some(c as u8 - b'A' + 26),
+        _ => None,
+    }
+}
+
+fn sgf_char(n: u8) -> char {
+    if n < 26 {
+        (b'a' + n) as char
+    } else {
+        (b'A' + n - 26) as char
+    }
+}
+
+impl Point {
+    pub fn new(x: u8, y: u8) -> Self {
+        Point {x, y}
+    }
+
+    // "pd" style coordinates
+    pub fn from_sgf(s: &str) -> Option<Point> {
+        let mut chars = s.chars();
+        let x = sgf_coord(chars.next()?)?;
+        let y = sgf_coord(chars.next()?)?;
+        if chars.next().is_some() {
+            return None;
+        }
+        Some(Point {x, y})
+    }
+
+    pub fn to_sgf(&self) -> String {
+        format!("{}{}", sgf_char(self.x), sgf_char(self.y))
+    }
+
+    // "Q16" style coordinates, which count rows from the bottom and skip I
+    pub fn from_gtp(s: &str, size: usize) -> Option<Point> {
+        let s = s.trim().to_uppercase();
+        let mut chars = s.chars();
+        let col = chars.next()?;
+        let x = GTP_COLUMNS.find(col)?;
+        let row: usize = chars.as_str().parse().ok()?;
+        if row == 0 || row > size || x >= size {
+            return None;
+        }
+        Some(Point {x: x as u8, y: (size - row) as u8})
+    }
+
+    pub fn to_gtp(&self, size: usize) -> String {
+        let col = GTP_COLUMNS.chars().nth(self.x as usize).unwrap_or('?');
+        format!("{}{}", col, size - self.y as usize)
+    }
+
+    pub fn on_board(&self, cols: usize, rows: usize) -> bool {
+        (self.x as usize) < cols && (self.y as usize) < rows
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_sgf())
+    }
+}
+
+impl GameTree {
+    // the main line moves, with None for passes and unreadable points
+    pub fn moves(&self) -> Vec<(Color, Option<Point>)> {
+        let (cols, rows) = self.board_size();
+        let mut moves = Vec::new();
+        for node in self.mainline() {
+            if let Some((color, value)) = node.get_move() {
+                let point = if is_pass(value, cols, rows) {
+                    None
+                } else {
+                    Point::from_sgf(value).filter(|p| p.on_board(cols, rows))
+                };
+                moves.push((color, point));
+            }
+        }
+        moves
+    }
+}
+
+// properties whose values are lists of points
+pub const POINT_LISTS: [&str; 12] = ["AB", "AE", "AW", "CR", "DD", "MA", "SL", "SQ", "TB", "TR", "TW", "VW"];
+
+// setup properties, where one point may only be touched once per node
+pub const SETUP_LISTS: [&str; 3] = ["AB", "AE", "AW"];
+
+// expands a point list value, including the compressed "aa:cc" rectangles
+pub fn parse_point_list(values: &[String]) -> Vec<Point> {
+    let mut points = Vec::new();
+    for value in values {
+        match value.split_once(':') {
+            Some((a, b)) => {
+                if let (Some(a), Some(b)) = (Point::from_sgf(a), Point::from_sgf(b)) {
+                    for y in a.y.min(b.y)..=a.y.max(b.y) {
+                        for x in a.x.min(b.x)..=a.x.max(b.x) {
+                            points.push(Point {x, y});
+                        }
+                    }
+                }
+            },
+            None => {
+                if let Some(p) = Point::from_sgf(value) {
+                    points.push(p);
+                }
+            },
+        }
+    }
+    points
+}
+
+// a rectangle of points, corners included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub from: Point,
+    pub to: Point,
+}
+
+impl Region {
+    pub fn new(a: Point, b: Point) -> Self {
+        Region {
+            from: Point::new(a.x.min(b.x), a.y.min(b.y)),
+            to: Point::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+
+    // "aa:ii", the same form as a compressed point list
+    pub fn parse(s: &str) -> Option<Region> {
+        let (a, b) = s.split_once(':')?;
+        Some(Region::new(Point::from_sgf(a.trim())?, Point::from_sgf(b.trim())?))
+    }
+
+    pub fn cols(&self) -> usize {
+        (self.to.x - self.from.x) as usize + 1
+    }
+
+    pub fn rows(&self) -> usize {
+        (self.to.y - self.from.y) as usize + 1
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        (self.from.x..=self.to.x).contains(&p.x) && (self.from.y..=self.to.y).contains(&p.y)
+    }
+
+    // where p ends up on a board that is just this region
+    pub fn map(&self, p: Point) -> Option<Point> {
+        if self.contains(p) {
+            Some(Point::new(p.x - self.from.x, p.y - self.from.y))
+        } else {
+            None
+        }
+    }
+}
+
+// the eight rotations and reflections of a square board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    Identity,
+    FlipX,
+    FlipY,
+    Rotate180,
+    Transpose,
+    Rotate90,
+    Rotate270,
+    AntiTranspose,
+}
+
+pub const SYMMETRIES: [Symmetry; 8] = [
+    Symmetry::Identity,
+    Symmetry::FlipX,
+    Symmetry::FlipY,
+    Symmetry::Rotate180,
+    Symmetry::Transpose,
+    Symmetry::Rotate90,
+    Symmetry::Rotate270,
+    Symmetry::AntiTranspose,
+];
+
+impl Symmetry {
+    // None for a point off the board, which has no place to go
+    pub fn apply(&self, p: Point, size: usize) -> Option<Point> {
+        if !p.on_board(size, size) {
+            return None;
+        }
+        let m = (size - 1) as u8;
+        let (x, y) = (p.x, p.y);
+        let (x, y) = match self {
+            Symmetry::Identity => (x, y),
+            Symmetry::FlipX => (m - x, y),
+            Symmetry::FlipY => (x, m - y),
+            Symmetry::Rotate180 => (m - x, m - y),
+            Symmetry::Transpose => (y, x),
+            Symmetry::Rotate90 => (m - y, x),
+            Symmetry::Rotate270 => (y, m - x),
+            Symmetry::AntiTranspose => (m - y, m - x),
+        };
+        Some(Point {x, y})
+    }
+
+    // None when any of the points is off the board
+    pub fn apply_all(&self, points: &[Point], size: usize) -> Option<Vec<Point>> {
+        points.iter().map(|p| self.apply(*p, size)).collect()
+    }
+}
+
+// the smallest of the eight transformed sequences, so that games which
+// differ only by board orientation compare equal. points off the board
+// can't be turned, so with any of them the points stay as they are
+pub fn canonical(points: &[Point], size: usize) -> (Vec<Point>, Symmetry) {
+    let mut best = (points.to_vec(), Symmetry::Identity);
+    for sym in SYMMETRIES {
+        let Some(transformed) = sym.apply_all(points, size) else { break };
+        if transformed < best.0 {
+            best = (transformed, sym);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point1() {
+        let p = Point::from_sgf("pd").unwrap();
+        assert_eq!(p, Point::new(15, 3));
+        assert_eq!(p.to_gtp(19), "Q16");
+        assert_eq!(Point::from_gtp("q16", 19), Some(p));
+        assert_eq!(Point::from_gtp("J1", 19), Some(Point::new(8, 18)));
+        assert_eq!(Point::from_gtp("I5", 19), None);
+        assert_eq!(Point::from_sgf("pdd"), None);
+        assert_eq!(Point::from_sgf("zA").unwrap().to_sgf(), "zA");
+    }
+
+    #[test]
+    fn point2() {
+        let values = vec!["aa:bc".to_string(), "dd".to_string()];
+        assert_eq!(parse_point_list(&values).len(), 7);
+    }
+
+    #[test]
+    fn region1() {
+        let region = Region::parse("jj:ff").unwrap();
+        assert_eq!((region.cols(), region.rows()), (5, 5));
+        assert_eq!(region.map(Point::new(6, 9)), Some(Point::new(1, 4)));
+        assert_eq!(region.map(Point::new(4, 9)), None);
+        assert_eq!(Region::parse("aa"), None);
+    }
+
+    #[test]
+    fn symmetry1() {
+        let a = vec![Point::from_sgf("pd").unwrap(), Point::from_sgf("dp").unwrap()];
+        let b = vec![Point::from_sgf("dd").unwrap(), Point::from_sgf("pp").unwrap()];
+        assert_eq!(canonical(&a, 19).0, canonical(&b, 19).0);
+        for sym in SYMMETRIES {
+            assert_eq!(canonical(&sym.apply_all(&a, 19).unwrap(), 19).0, canonical(&a, 19).0);
+        }
+        // off the board, as on a smaller board or tt
+        let off = Point::from_sgf("tt").unwrap();
+        assert_eq!(Symmetry::FlipX.apply(off, 19), None);
+        assert_eq!(Symmetry::FlipX.apply(Point::new(3, 3), 0), None);
+        assert_eq!(Symmetry::Rotate90.apply_all(&[a[0], off], 19), None);
+        assert_eq!(canonical(&[a[0], off], 19), (vec![a[0], off], Symmetry::Identity));
+    }
+}
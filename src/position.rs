@@ -0,0 +1,499 @@
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+use super::board::Board;
+use super::point::Point;
+use super::properties::{describe, Context};
+use super::vertex::{Collection, Color, GameTree, Node, Sequence};
+
+#[derive(Debug)]
+pub enum Error {
+    PositionError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PositionError(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// a position and the node that led to it, so a position found in a
+// search can be taken back to its place in the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodePosition {
+    pub path: Vec<usize>,
+    pub position: Position,
+}
+
+// a position on its own, without the game that led to it. its string
+// form is one line, rows from the top separated by '/', with x for
+// black, o for white and runs of empty points as numbers, then the
+// player to move, the ko point or '-', and the captures by black and
+// by white:
+//
+//   3/1x1/3 w - 0 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub board: Board,
+    pub to_move: Color,
+    pub ko: Option<Point>,
+}
+
+impl Position {
+    pub fn new(board: Board, to_move: Color) -> Self {
+        Position {
+            board,
+            to_move,
+            ko: None,
+        }
+    }
+
+    // the empty board of a game before its root is applied
+    fn start(gt: &GameTree) -> Self {
+        let handicap = gt.root().value("HA").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+        let to_move = if handicap > 1 { Color::White } else { Color::Black };
+        Position::new(Board::for_game(gt), to_move)
+    }
+
+    fn advance(&mut self, node: &Node) {
+        let mv = self.board.move_point(node);
+        let delta = self.board.apply(node);
+        self.ko = None;
+        if let Some((color, _)) = node.get_move() {
+            self.to_move = color.opponent();
+        }
+        if let Some((color, p)) = mv {
+            let captured: Vec<Point> = delta.captured.iter().map(|(q, _)| *q).collect();
+            self.ko = self.board.ko_point(color, p, &captured);
+        }
+        if let Some(color) = node.value("PL").and_then(Color::from_ident) {
+            self.to_move = color;
+        }
+    }
+
+    // the main line position after n moves
+    pub fn at(gt: &GameTree, n: usize) -> Self {
+        Position::located(gt, n).position
+    }
+
+    // like at, along with the path of the last node applied
+    pub fn located(gt: &GameTree, n: usize) -> NodePosition {
+        let mut position = Position::start(gt);
+        let mut number = 0;
+        let mut last = 0;
+        for (i, node) in gt.mainline().into_iter().enumerate() {
+            if node.get_move().is_some() {
+                if number == n {
+                    break;
+                }
+                number += 1;
+            }
+            position.advance(node);
+            last = i;
+        }
+        // main line paths are all zeros, one per node after the root
+        NodePosition {
+            path: vec![0; last],
+            position,
+        }
+    }
+
+    // the position once the node at path has been played
+    pub fn at_path(gt: &GameTree, path: &[usize]) -> Option<Self> {
+        let mut position = Position::start(gt);
+        position.advance(gt.root());
+        for n in 1..=path.len() {
+            position.advance(gt.node(&path[..n])?);
+        }
+        Some(position)
+    }
+
+    pub fn parse(s: &str) -> Result<Position> {
+        let err = |msg: &str| Error::PositionError(format!("{}: {}", msg, s));
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(err("expected 5 fields"));
+        }
+        let mut rows: Vec<Vec<Option<Color>>> = Vec::new();
+        for text in fields[0].split('/') {
+            let mut row = Vec::new();
+            let mut run: usize = 0;
+            for c in text.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    // no run is longer than the widest board
+                    run = run.checked_mul(10).and_then(|r| r.checked_add(d as usize)).filter(|r| *r <= 52).ok_or_else(|| err("bad board shape"))?;
+                    continue;
+                }
+                if row.len() + run >= 52 {
+                    return Err(err("bad board shape"));
+                }
+                row.extend(std::iter::repeat_n(None, run));
+                run = 0;
+                row.push(match c {
+                    'x' => Some(Color::Black),
+                    'o' => Some(Color::White),
+                    _ => return Err(err("bad stone")),
+                });
+            }
+            row.extend(std::iter::repeat_n(None, run));
+            rows.push(row);
+        }
+        let cols = rows[0].len();
+        if cols == 0 || cols > 52 || rows.len() > 52 || rows.iter().any(|r| r.len() != cols) {
+            return Err(err("bad board shape"));
+        }
+        let mut board = Board::new(cols, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, stone) in row.iter().enumerate() {
+                board.set(Point::new(x as u8, y as u8), *stone);
+            }
+        }
+        let to_move = match fields[1] {
+            "b" => Color::Black,
+            "w" => Color::White,
+            _ => return Err(err("bad player to move")),
+        };
+        let ko = match fields[2] {
+            "-" => None,
+            v => match Point::from_sgf(v) {
+                Some(p) if board.contains(p) => Some(p),
+                _ => return Err(err("bad ko point")),
+            },
+        };
+        board.black_captures = fields[3].parse().map_err(|_| err("bad captures"))?;
+        board.white_captures = fields[4].parse().map_err(|_| err("bad captures"))?;
+        Ok(Position { board, to_move, ko })
+    }
+
+    // a new game starting from this position as setup
+    pub fn to_gametree(&self) -> GameTree {
+        let board = &self.board;
+        let size = if board.cols == board.rows {
+            board.cols.to_string()
+        } else {
+            format!("{}:{}", board.cols, board.rows)
+        };
+        let mut root = Node { props: Vec::new() };
+        root.set("GM", vec!["1".to_string()]);
+        root.set("FF", vec!["4".to_string()]);
+        root.set("SZ", vec![size]);
+        for (ident, color) in [("AB", Color::Black), ("AW", Color::White)] {
+            let points: Vec<String> = board.stones().iter().filter(|(_, c)| *c == color).map(|(p, _)| p.to_sgf()).collect();
+            if !points.is_empty() {
+                root.set(ident, points);
+            }
+        }
+        root.set("PL", vec![self.to_move.ident().to_string()]);
+        GameTree {
+            sequence: Sequence { nodes: vec![root] },
+            gametrees: Vec::new(),
+        }
+    }
+}
+
+impl GameTree {
+    // some moves of the main line as a game of their own, counted from 0
+    // like an index, so 20..80 sets up the position after move 20 in the
+    // root and plays moves 21 to 80, their comments and markup included.
+    // the game info comes along, and MN keeps the moves numbered as they
+    // were
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> GameTree {
+        let from = match range.start_bound() {
+            Bound::Included(&a) => a,
+            Bound::Excluded(&a) => a.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        // the number of the last move kept
+        let to = match range.end_bound() {
+            Bound::Included(&b) => Some(b.saturating_add(1)),
+            Bound::Excluded(&b) => Some(b),
+            Bound::Unbounded => None,
+        };
+        let start = Position::located(self, from);
+        let mut gt = start.position.to_gametree();
+        let root = gt.root_mut();
+        for prop in &self.info_node().props {
+            if describe(&prop.ident).is_some_and(|info| info.context == Context::GameInfo) {
+                root.set(&prop.ident, prop.values.clone());
+            }
+        }
+        let mut played = from;
+        for node in self.mainline().into_iter().skip(start.path.len() + 1) {
+            if to.is_some_and(|to| played >= to) {
+                break;
+            }
+            let mut node = node.clone();
+            if node.get_move().is_some() {
+                if played == from && from > 0 {
+                    node.set("MN", vec![from.saturating_add(1).to_string()]);
+                }
+                played += 1;
+            }
+            gt.sequence.nodes.push(node);
+        }
+        gt
+    }
+
+    // the last n moves of the main line, as slice gives them
+    pub fn tail(&self, n: usize) -> GameTree {
+        let moves = self.mainline().iter().filter(|node| node.get_move().is_some()).count();
+        self.slice(moves.saturating_sub(n)..)
+    }
+
+    // the position at every node, variations included, in preorder
+    pub fn positions(&self) -> Vec<NodePosition> {
+        let mut positions = Vec::new();
+        collect_positions(self, Vec::new(), Position::start(self), &mut positions);
+        positions
+    }
+
+    // the nodes where the stones are exactly as on board
+    pub fn find_position(&self, board: &Board) -> Vec<Vec<usize>> {
+        let hash = board.hash();
+        self.positions()
+            .into_iter()
+            .filter(|p| p.position.board.hash() == hash && same_stones(&p.position.board, board))
+            .map(|p| p.path)
+            .collect()
+    }
+}
+
+impl Collection {
+    // every game and node reaching the stones on board
+    pub fn find_position(&self, board: &Board) -> Vec<(usize, Vec<usize>)> {
+        let mut found = Vec::new();
+        for (i, gt) in self.gametrees.iter().enumerate() {
+            found.extend(gt.find_position(board).into_iter().map(|path| (i, path)));
+        }
+        found
+    }
+}
+
+fn same_stones(a: &Board, b: &Board) -> bool {
+    a.cols == b.cols && a.rows == b.rows && a.stones() == b.stones()
+}
+
+fn collect_positions(gt: &GameTree, mut path: Vec<usize>, mut position: Position, out: &mut Vec<NodePosition>) {
+    for (i, node) in gt.sequence.nodes.iter().enumerate() {
+        if i > 0 {
+            path.push(0);
+        }
+        position.advance(node);
+        out.push(NodePosition {
+            path: path.clone(),
+            position: position.clone(),
+        });
+    }
+    for (i, child) in gt.gametrees.iter().enumerate() {
+        let mut p = path.clone();
+        p.push(i);
+        collect_positions(child, p, position.clone(), out);
+    }
+}
+
+fn diagram_cell(token: &str) -> Option<Option<Color>> {
+    match token {
+        "X" | "x" | "#" | "B" => Some(Some(Color::Black)),
+        "O" | "o" | "@" | "W" => Some(Some(Color::White)),
+        "." | "+" | "," | "_" => Some(None),
+        // move numbers and other labels sit on stones we cannot tell apart
+        t if t.chars().all(|c| c.is_ascii_digit()) => Some(None),
+        _ => None,
+    }
+}
+
+// reads the X/O/. grids pasted on forums, including our own ascii
+// output and Sensei's Library diagrams. borders and coordinate lines
+// are skipped, and numbered stones come out as empty points
+pub fn parse_diagram(text: &str) -> Result<Board> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches("$$");
+        let tokens: Vec<String> = if line.trim().contains(' ') {
+            line.split_whitespace().filter(|t| *t != "|").map(|t| t.to_string()).collect()
+        } else {
+            line.trim().trim_matches('|').chars().map(|c| c.to_string()).collect()
+        };
+        if tokens.is_empty() || tokens.iter().all(|t| t.chars().all(|c| "-+|".contains(c))) {
+            continue;
+        }
+        if tokens.iter().all(|t| diagram_cell(t).is_some()) {
+            rows.push(tokens);
+        }
+    }
+    // row numbers down either side
+    let numeric = |t: &String| t.chars().all(|c| c.is_ascii_digit());
+    if rows.len() > 1 && rows.iter().all(|r| r.first().is_some_and(numeric)) {
+        for r in rows.iter_mut() {
+            r.remove(0);
+        }
+    }
+    if rows.len() > 1 && rows.iter().all(|r| r.last().is_some_and(numeric)) {
+        for r in rows.iter_mut() {
+            r.pop();
+        }
+    }
+    if rows.is_empty() {
+        return Err(Error::PositionError("no board in diagram".to_string()));
+    }
+    let cols = rows[0].len();
+    if cols == 0 || cols > 52 || rows.len() > 52 || rows.iter().any(|r| r.len() != cols) {
+        return Err(Error::PositionError("diagram rows have different lengths".to_string()));
+    }
+    let mut board = Board::new(cols, rows.len());
+    for (y, row) in rows.iter().enumerate() {
+        for (x, token) in row.iter().enumerate() {
+            board.set(Point::new(x as u8, y as u8), diagram_cell(token).flatten());
+        }
+    }
+    Ok(board)
+}
+
+// a position string if the text is one, otherwise an ascii diagram with
+// black to move
+pub fn parse_any(text: &str) -> Result<Position> {
+    match Position::parse(text.trim()) {
+        Ok(pos) => Ok(pos),
+        Err(_) => Ok(Position::new(parse_diagram(text)?, Color::Black)),
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let board = &self.board;
+        for y in 0..board.rows {
+            if y > 0 {
+                write!(f, "/")?;
+            }
+            let mut run = 0;
+            for x in 0..board.cols {
+                let c = match board.get(Point::new(x as u8, y as u8)) {
+                    Some(Color::Black) => 'x',
+                    Some(Color::White) => 'o',
+                    None => {
+                        run += 1;
+                        continue;
+                    },
+                };
+                if run > 0 {
+                    write!(f, "{}", run)?;
+                    run = 0;
+                }
+                write!(f, "{}", c)?;
+            }
+            if run > 0 {
+                write!(f, "{}", run)?;
+            }
+        }
+        let to_move = match self.to_move {
+            Color::Black => "b",
+            Color::White => "w",
+        };
+        let ko = self.ko.map(|p| p.to_sgf()).unwrap_or_else(|| "-".to_string());
+        write!(f, " {} {} {} {}", to_move, ko, board.black_captures, board.white_captures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn position1() {
+        let gt = gametree("(;SZ[3];B[bb])");
+        let pos = Position::at(&gt, 1);
+        assert_eq!(pos.to_string(), "3/1x1/3 w - 0 0");
+        assert_eq!(Position::parse("3/1x1/3 w - 0 0").unwrap(), pos);
+    }
+
+    #[test]
+    fn position2() {
+        // a suicide is not a ko
+        let gt = gametree("(;SZ[4]AB[ba][ab];W[aa])");
+        let pos = Position::at(&gt, 1);
+        assert_eq!(pos.ko, None);
+        let gt = gametree("(;SZ[4]AB[ba][ab][bc]AW[ca][bb][db][cc];B[cb])");
+        let pos = Position::at(&gt, 1);
+        // black captures bb with a single stone in atari
+        assert_eq!(pos.ko, Some(Point::new(1, 1)));
+        assert_eq!(pos.to_string(), "1xo1/x1xo/1xo1/4 w bb 1 0");
+        assert_eq!(Position::parse(&pos.to_string()).unwrap(), pos);
+    }
+
+    #[test]
+    fn position3() {
+        assert!(Position::parse("3/1x1/3 w -").is_err());
+        assert!(Position::parse("3/1x/3 w - 0 0").is_err());
+        assert!(Position::parse("3/1y1/3 w - 0 0").is_err());
+        assert!(Position::parse("3/1x1/3 w zz 0 0").is_err());
+        // runs that would overflow, or make rows wider than any board
+        assert!(Position::parse("99999999999999999999999/3/3 w - 0 0").is_err());
+        assert!(Position::parse(&format!("{}x/3 w - 0 0", "9".repeat(6))).is_err());
+        assert!(Position::parse("40x40/40x40 b - 0 0").is_err());
+        assert!(Position::parse("52/52 b - 0 0").is_ok());
+        let gt = gametree("(;SZ[9]HA[2]AB[cc][gg])");
+        assert_eq!(Position::at(&gt, 0).to_move, Color::White);
+    }
+
+    #[test]
+    fn position4() {
+        let gt = gametree("(;SZ[3];B[aa];C[note];W[cc](;B[ca];W[ac];B[bb])(;B[bb];W[ac];B[ca]))");
+        let located = Position::located(&gt, 2);
+        assert_eq!(located.path, vec![0, 0, 0]);
+        assert_eq!(Position::at_path(&gt, &located.path), Some(located.position));
+        assert!(Position::at_path(&gt, &[0, 0, 0, 5]).is_none());
+        let positions = gt.positions();
+        assert_eq!(positions.len(), 10);
+        // both variations transpose into the same position
+        let end = Position::at_path(&gt, &[0, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(gt.find_position(&end.board), vec![vec![0, 0, 0, 0, 0, 0], vec![0, 0, 0, 1, 0, 0]]);
+        let coll = Collection { gametrees: vec![gametree("(;SZ[3];B[bb])"), gt] };
+        assert_eq!(coll.find_position(&end.board).len(), 2);
+        assert_eq!(coll.find_position(&Position::at(&coll.gametrees[0], 1).board), vec![(0, vec![0])]);
+    }
+
+    #[test]
+    fn diagram1() {
+        let text = "   A B C D\n 4 . . . .\n 3 . X O .\n 2 . # + .\n 1 . . . .\n";
+        let board = parse_diagram(text).unwrap();
+        assert_eq!(board.to_string(), "....\n.XO.\n.X..\n....\n");
+        let sl = "$$ +---------+\n$$ | . . . . |\n$$ | . X 1 . |\n$$ | O . , . |\n$$ +---------+\n";
+        assert_eq!(parse_diagram(sl).unwrap().to_string(), "....\n.X..\nO...\n");
+        assert_eq!(parse_diagram("..X\nO..\n...").unwrap().to_string(), "..X\nO..\n...\n");
+        assert!(parse_diagram("..X\nO.\n").is_err());
+        assert!(parse_diagram("hello").is_err());
+    }
+
+    #[test]
+    fn diagram2() {
+        let pos = parse_any(". X\nO .\n").unwrap();
+        let gt = pos.to_gametree();
+        assert_eq!(gt.to_string(), "(;GM[1]FF[4]SZ[2]AB[ba]AW[ab]PL[B])");
+        let pos = parse_any("3/1x1/3 w - 0 0").unwrap();
+        assert_eq!(pos.to_gametree().root().value("PL"), Some("W"));
+    }
+
+    #[test]
+    fn tail1() {
+        let gt = gametree("(;GM[1]SZ[3]PB[x]KM[6.5];B[aa];W[ba];B[ab]C[three](;W[cc];B[bb])(;W[bc]))");
+        assert_eq!(gt.tail(2).to_string(), "(;GM[1]FF[4]SZ[3]AB[aa][ab]AW[ba]PL[W]PB[x]KM[6.5];W[cc]MN[4];B[bb])");
+        assert_eq!(gt.tail(9).to_string(), "(;GM[1]FF[4]SZ[3]PL[B]PB[x]KM[6.5];B[aa];W[ba];B[ab]C[three];W[cc];B[bb])");
+        assert_eq!(gt.tail(0).sequence.nodes.len(), 1);
+        assert_eq!(gt.slice(1..3).to_string(), "(;GM[1]FF[4]SZ[3]AB[aa]PL[W]PB[x]KM[6.5];W[ba]MN[2];B[ab]C[three])");
+        assert_eq!(gt.slice(..=0).to_string(), "(;GM[1]FF[4]SZ[3]PL[B]PB[x]KM[6.5];B[aa])");
+        assert_eq!(gt.slice(2..2).sequence.nodes.len(), 1);
+        // bounds at the end of usize don't overflow
+        assert_eq!(gt.slice(..=usize::MAX).to_string(), gt.slice(..).to_string());
+        let past = gt.slice((Bound::Excluded(usize::MAX), Bound::Unbounded));
+        assert_eq!(past.sequence.nodes.len(), 1);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::properties::is_standard;
+use super::vertex::GameTree;
+
+// distinct example values kept per property
+const EXAMPLES: usize = 3;
+// examples are cut to this many characters
+const EXAMPLE_LEN: usize = 40;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PropertyCount {
+    // nodes carrying the property
+    pub count: usize,
+    // games it appears in at least once
+    pub games: usize,
+    pub examples: Vec<String>,
+}
+
+// which property idents occur across a set of games and how often
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub games: usize,
+    pub properties: BTreeMap<String, PropertyCount>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+
+    pub fn add(&mut self, gt: &GameTree) {
+        self.games += 1;
+        let mut seen: Vec<&str> = Vec::new();
+        for (_, node) in gt.nodes() {
+            for prop in &node.props {
+                let entry = self.properties.entry(prop.ident.clone()).or_default();
+                entry.count += 1;
+                if !seen.contains(&prop.ident.as_str()) {
+                    seen.push(&prop.ident);
+                    entry.games += 1;
+                }
+                if entry.examples.len() < EXAMPLES {
+                    let value: String = prop.values.join(",").chars().take(EXAMPLE_LEN).collect();
+                    let value = value.replace(['\n', '\r', '\t'], " ");
+                    if !entry.examples.contains(&value) {
+                        entry.examples.push(value);
+                    }
+                }
+            }
+        }
+    }
+
+    // the idents outside FF[4], most frequent first
+    pub fn nonstandard(&self) -> Vec<(&str, &PropertyCount)> {
+        let mut props: Vec<(&str, &PropertyCount)> = self
+            .properties
+            .iter()
+            .filter(|(ident, _)| !is_standard(ident))
+            .map(|(ident, count)| (ident.as_str(), count))
+            .collect();
+        props.sort_by_key(|(_, count)| std::cmp::Reverse(count.count));
+        props
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "games: {}", self.games)?;
+        let mut props: Vec<(&String, &PropertyCount)> = self.properties.iter().collect();
+        props.sort_by_key(|(_, count)| std::cmp::Reverse(count.count));
+        for (ident, count) in props {
+            // nonstandard idents are starred
+            let mark = if is_standard(ident) { " " } else { "*" };
+            let examples: Vec<String> = count.examples.iter().map(|e| format!("[{}]", e)).collect();
+            writeln!(f, "{}{:<4} {:>8} {:>6} games  {}", mark, ident, count.count, count.games, examples.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn profile1() {
+        let mut profile = Profile::new();
+        profile.add(&gametree("(;GM[1]XX[a];B[aa]XX[b];W[bb]XX[a]C[hi])"));
+        profile.add(&gametree("(;GM[1];B[cc](;W[dd])(;W[ee]XX[c];B[ff]XX[d]))"));
+        assert_eq!(profile.games, 2);
+        assert_eq!(profile.properties["W"].count, 3);
+        assert_eq!(profile.properties["B"].count, 3);
+        assert_eq!(profile.properties["W"].games, 2);
+        let xx = &profile.properties["XX"];
+        assert_eq!(xx.count, 5);
+        assert_eq!(xx.games, 2);
+        assert_eq!(xx.examples, vec!["a", "b", "c"]);
+        let odd = profile.nonstandard();
+        assert_eq!(odd.len(), 1);
+        assert_eq!(odd[0].0, "XX");
+        assert!(profile.to_string().contains("*XX"));
+    }
+}
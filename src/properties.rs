@@ -0,0 +1,205 @@
+use std::fmt;
+
+// where a property may appear, as FF[4] groups them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    // the root node only
+    Root,
+    // once per line of play, usually in the root
+    GameInfo,
+    // with B or W
+    Move,
+    // without B or W
+    Setup,
+    // applies to the node and everything after it until set again
+    Inherit,
+    // anywhere
+    Any,
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Context::Root => write!(f, "root"),
+            Context::GameInfo => write!(f, "game-info"),
+            Context::Move => write!(f, "move"),
+            Context::Setup => write!(f, "setup"),
+            Context::Inherit => write!(f, "inherit"),
+            Context::Any => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyInfo {
+    pub ident: &'static str,
+    pub name: &'static str,
+    // the value type in the spec's notation
+    pub value_type: &'static str,
+    pub context: Context,
+    pub description: &'static str,
+    // the first FF with the property. only the FF[4] additions are told
+    // apart, so the rest count as FF[1]
+    pub since: u8,
+    // the last FF with the property, for the ones FF[4] dropped
+    pub until: Option<u8>,
+}
+
+impl fmt::Display for PropertyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let versions = match self.until {
+            Some(until) => format!("FF[{}] to FF[{}]", self.since, until),
+            None => format!("since FF[{}]", self.since),
+        };
+        write!(f, "{} ({}): {}, {}, {}. {}", self.ident, self.name, self.value_type, self.context, versions, self.description)
+    }
+}
+
+const fn p(ident: &'static str, name: &'static str, value_type: &'static str, context: Context, description: &'static str) -> PropertyInfo {
+    PropertyInfo { ident, name, value_type, context, description, since: 1, until: None }
+}
+
+// a property FF[4] added
+const fn p4(ident: &'static str, name: &'static str, value_type: &'static str, context: Context, description: &'static str) -> PropertyInfo {
+    PropertyInfo { since: 4, ..p(ident, name, value_type, context, description) }
+}
+
+// a property FF[4] dropped
+const fn p3(ident: &'static str, name: &'static str, value_type: &'static str, context: Context, description: &'static str) -> PropertyInfo {
+    PropertyInfo { until: Some(3), ..p(ident, name, value_type, context, description) }
+}
+
+// the FF[4] properties, general and go specific, then the FF[3] ones
+// FF[4] dropped
+pub const PROPERTIES: [PropertyInfo; 86] = [
+    p("AB", "Add Black", "list of point", Context::Setup, "Places black stones, whatever was on the points before."),
+    p("AE", "Add Empty", "list of point", Context::Setup, "Clears the points."),
+    p("AN", "Annotation", "SimpleText", Context::GameInfo, "Who annotated the game."),
+    p4("AP", "Application", "composed SimpleText ':' SimpleText", Context::Root, "The program that wrote the file and its version."),
+    p4("AR", "Arrow", "list of composed point ':' point", Context::Any, "Draws arrows from the first point to the second."),
+    p("AS", "Who adds stones", "SimpleText", Context::Any, "Lines of Action: who placed the setup stones."),
+    p("AW", "Add White", "list of point", Context::Setup, "Places white stones, whatever was on the points before."),
+    p("B", "Black", "move", Context::Move, "A black move. B[] is a pass, as is B[tt] on boards up to 19x19."),
+    p("BL", "Black time left", "real", Context::Move, "Seconds black has left after the move."),
+    p("BM", "Bad move", "double", Context::Move, "Marks the move as bad; 2 is very bad."),
+    p("BR", "Black rank", "SimpleText", Context::GameInfo, "Black's rank, like 3d or 5k."),
+    p("BT", "Black team", "SimpleText", Context::GameInfo, "The team black plays for."),
+    p("C", "Comment", "Text", Context::Any, "A comment on the node."),
+    p4("CA", "Charset", "SimpleText", Context::Root, "The character set of the file's text, like UTF-8."),
+    p("CP", "Copyright", "SimpleText", Context::GameInfo, "Copyright of the game record."),
+    p("CR", "Circle", "list of point", Context::Any, "Marks the points with circles."),
+    p4("DD", "Dim points", "elist of point", Context::Inherit, "Greys out the points until set again; DD[] ends it."),
+    p("DM", "Even position", "double", Context::Any, "The position is even; 2 is very even."),
+    p("DO", "Doubtful", "none", Context::Move, "Marks the move as doubtful."),
+    p("DT", "Date", "SimpleText", Context::GameInfo, "When the game was played, as YYYY-MM-DD with shortenings for more dates."),
+    p("EV", "Event", "SimpleText", Context::GameInfo, "The tournament or event."),
+    p("FF", "Fileformat", "number (1-4)", Context::Root, "The SGF version of the game; FF[1] when missing."),
+    p("FG", "Figure", "none | composed number ':' SimpleText", Context::Any, "Starts a new printed diagram, with flags and a name."),
+    p("GB", "Good for Black", "double", Context::Any, "The position is good for black; 2 is very good."),
+    p("GC", "Game comment", "Text", Context::GameInfo, "Background on the game, like a summary."),
+    p("GM", "Game", "number", Context::Root, "Which game the file is for; 1 is go."),
+    p("GN", "Game name", "SimpleText", Context::GameInfo, "A name to tell the game apart from others."),
+    p("GW", "Good for White", "double", Context::Any, "The position is good for white; 2 is very good."),
+    p("HA", "Handicap", "number", Context::GameInfo, "The number of handicap stones."),
+    p("HO", "Hotspot", "double", Context::Any, "Marks the node as a turning point of the game; 2 is a major one."),
+    p("IP", "Initial position", "SimpleText", Context::GameInfo, "Lines of Action: the starting setup."),
+    p("IT", "Interesting", "none", Context::Move, "Marks the move as interesting."),
+    p("KM", "Komi", "real", Context::GameInfo, "Points added to white's score."),
+    p("KO", "Ko", "none", Context::Move, "Forces the move in the node even if it is illegal, like a ko retake."),
+    p("LB", "Label", "list of composed point ':' SimpleText", Context::Any, "Writes text on the points."),
+    p4("LN", "Line", "list of composed point ':' point", Context::Any, "Draws lines between the points."),
+    p("MA", "Mark", "list of point", Context::Any, "Marks the points with an X."),
+    p("MN", "Set move number", "number", Context::Move, "Sets the number of the move in the node."),
+    p("N", "Nodename", "SimpleText", Context::Any, "A name for the node, like a chapter heading."),
+    p("OB", "OtStones Black", "number", Context::Move, "Moves black has left in the overtime period."),
+    p("ON", "Opening", "SimpleText", Context::GameInfo, "The opening played."),
+    p("OT", "Overtime", "SimpleText", Context::GameInfo, "The overtime system, like 5x30 byo-yomi."),
+    p("OW", "OtStones White", "number", Context::Move, "Moves white has left in the overtime period."),
+    p("PB", "Player Black", "SimpleText", Context::GameInfo, "The name of the black player."),
+    p("PC", "Place", "SimpleText", Context::GameInfo, "Where the game was played."),
+    p("PL", "Player to play", "color", Context::Setup, "Whose turn it is after the setup."),
+    p("PM", "Print move mode", "number", Context::Inherit, "How move numbers are printed in diagrams."),
+    p("PW", "Player White", "SimpleText", Context::GameInfo, "The name of the white player."),
+    p("RE", "Result", "SimpleText", Context::GameInfo, "The result, like B+R, W+2.5 or 0 for a draw."),
+    p("RO", "Round", "SimpleText", Context::GameInfo, "The round of the event."),
+    p("RU", "Rules", "SimpleText", Context::GameInfo, "The rule set, like Japanese or Chinese."),
+    p("SE", "Markup", "point", Context::Any, "Lines of Action: marks a point and the moves from it."),
+    p("SL", "Selected", "list of point", Context::Any, "Selects the points, left to the application to show."),
+    p("SO", "Source", "SimpleText", Context::GameInfo, "Where the record comes from, like a book or server."),
+    p4("SQ", "Square", "list of point", Context::Any, "Marks the points with squares."),
+    p4("ST", "Style", "number (0-3)", Context::Root, "How variations are shown: children or siblings, marked on the board or not."),
+    p("SZ", "Size", "number | composed number ':' number", Context::Root, "The board size; columns:rows for rectangular boards."),
+    p("TB", "Territory Black", "elist of point", Context::Any, "Black's territory."),
+    p("TE", "Tesuji", "double", Context::Move, "Marks the move as a tesuji, a good move; 2 is very good."),
+    p("TM", "Timelimit", "real", Context::GameInfo, "Each player's main time, in seconds."),
+    p("TR", "Triangle", "list of point", Context::Any, "Marks the points with triangles."),
+    p("TW", "Territory White", "elist of point", Context::Any, "White's territory."),
+    p("UC", "Unclear pos", "double", Context::Any, "The position is unclear; 2 is very unclear."),
+    p("US", "User", "SimpleText", Context::GameInfo, "Who entered the game record."),
+    p("V", "Value", "real", Context::Any, "The estimated score, positive when black is ahead."),
+    p("VW", "View", "elist of point", Context::Inherit, "Shows only part of the board until set again; VW[] shows all of it."),
+    p("W", "White", "move", Context::Move, "A white move. W[] is a pass, as is W[tt] on boards up to 19x19."),
+    p("WL", "White time left", "real", Context::Move, "Seconds white has left after the move."),
+    p("WR", "White rank", "SimpleText", Context::GameInfo, "White's rank, like 3d or 5k."),
+    p("WT", "White team", "SimpleText", Context::GameInfo, "The team white plays for."),
+    p3("BS", "Black species", "number", Context::GameInfo, "Whether black is a human or a program, by number."),
+    p3("CH", "Check mark", "double", Context::Any, "Marks the node as checked."),
+    p3("EL", "Evaluation", "number", Context::Move, "How a program rated the move."),
+    p3("EX", "Expected move", "move", Context::Move, "The move a program expected next."),
+    p3("ID", "Game identifier", "SimpleText", Context::GameInfo, "An identifier for the game in a database."),
+    p3("L", "Letters", "list of point", Context::Any, "Labels the points a, b, c in order. FF[4] uses LB."),
+    p3("LT", "Lose on time", "none", Context::GameInfo, "Running out of time loses the game."),
+    p3("M", "Mark", "list of point", Context::Any, "Marks the points. FF[4] uses MA or TR."),
+    p3("OM", "Moves per overtime", "number", Context::GameInfo, "Moves to play in each overtime period."),
+    p3("OP", "Overtime length", "real", Context::GameInfo, "The length of each overtime period, in seconds."),
+    p3("OV", "Operator overhead", "real", Context::GameInfo, "Seconds allowed per move for entering it."),
+    p3("RG", "Region", "list of point", Context::Any, "Marks a region of the board."),
+    p3("SC", "Secure stones", "list of point", Context::Any, "Marks stones that are safe."),
+    p3("SI", "Sigma", "double", Context::Any, "How sure a program's evaluation is."),
+    p3("TC", "Territory count", "number", Context::Any, "A count of territory."),
+    p3("WS", "White species", "number", Context::GameInfo, "Whether white is a human or a program, by number."),
+];
+
+// the registry entry for an ident. before FF[4] idents could carry
+// lowercase letters, as in AddBlack, which are dropped here as readers do
+pub fn describe(ident: &str) -> Option<&'static PropertyInfo> {
+    let upper: String = ident.trim().chars().filter(|c| c.is_ascii_uppercase()).collect();
+    PROPERTIES.iter().find(|p| p.ident == upper)
+}
+
+// whether FF[4] defines the ident, as written
+pub fn is_standard(ident: &str) -> bool {
+    PROPERTIES.iter().any(|p| p.ident == ident && p.until.is_none())
+}
+
+// whether the ident is one FF[4] added
+pub fn is_ff4_only(ident: &str) -> bool {
+    PROPERTIES.iter().any(|p| p.ident == ident && p.since == 4)
+}
+
+// whether the ident is one FF[4] dropped
+pub fn is_ff3_only(ident: &str) -> bool {
+    PROPERTIES.iter().any(|p| p.ident == ident && p.until.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn properties1() {
+        let km = describe("KM").unwrap();
+        assert_eq!((km.name, km.value_type, km.context), ("Komi", "real", Context::GameInfo));
+        assert_eq!(describe("AddBlack").unwrap().ident, "AB");
+        assert_eq!((describe("AP").unwrap().since, describe("L").unwrap().until), (4, Some(3)));
+        assert!(describe("XY").is_none());
+        assert_eq!(describe("KO").unwrap().to_string().split(". ").next(), Some("KO (Ko): none, move, since FF[1]"));
+        // every ident is described once
+        for info in &PROPERTIES {
+            assert_eq!(PROPERTIES.iter().filter(|p| p.ident == info.ident).count(), 1, "{}", info.ident);
+        }
+        let count = |f: fn(&str) -> bool| PROPERTIES.iter().filter(|p| f(p.ident)).count();
+        assert_eq!((count(is_standard), count(is_ff4_only), count(is_ff3_only)), (70, 7, 16));
+        assert!(is_standard("SQ") && !is_standard("L") && !is_standard("AddBlack") && is_ff3_only("M"));
+    }
+}
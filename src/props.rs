@@ -0,0 +1,222 @@
+use std::fmt;
+
+use super::point::{Point, Region};
+use super::properties::describe;
+use super::value::ValueType;
+use super::vertex::{Color, Node};
+use super::writer::unescape_text;
+
+pub use super::value::PropValue;
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownProperty(String),
+    // the values don't read as the type the registry gives the property
+    BadValue { ident: String, values: Vec<String>, expected: &'static str },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownProperty(ident) => write!(f, "{} is not a known property", ident),
+            Error::BadValue { ident, values, expected } => write!(f, "{}[{}] is not {}", ident, values.join("]["), expected),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// the values of a property read as the registry's type for it. of the
+// alternatives in a type like SZ's, the first that fits wins, so SZ[19]
+// is a number and SZ[19:13] a composed value
+pub fn decode(ident: &str, values: &[String]) -> Result<PropValue> {
+    let info = describe(ident).ok_or_else(|| Error::UnknownProperty(ident.to_string()))?;
+    ValueType::parse(info.value_type)
+        .iter()
+        .find_map(|t| decode_as(t, values))
+        .ok_or_else(|| Error::BadValue {
+            ident: ident.to_string(),
+            values: values.to_vec(),
+            expected: info.value_type,
+        })
+}
+
+fn decode_as(t: &ValueType, values: &[String]) -> Option<PropValue> {
+    let ValueType::List(of, elist) = t else {
+        return match values {
+            [value] => decode_one(t, value),
+            _ => None,
+        };
+    };
+    if *elist && values.len() == 1 && values[0].trim().is_empty() {
+        return Some(PropValue::List(Vec::new()));
+    }
+    let mut list = Vec::new();
+    for value in values {
+        // aa:cc in a point list is every point of the rectangle
+        match (&**of, Region::parse(value)) {
+            (ValueType::Point, Some(region)) => {
+                for y in region.from.y..=region.to.y {
+                    for x in region.from.x..=region.to.x {
+                        list.push(PropValue::Point(Point::new(x, y)));
+                    }
+                }
+            },
+            _ => list.push(decode_one(of, value)?),
+        }
+    }
+    (!list.is_empty()).then_some(PropValue::List(list))
+}
+
+// one value, as it is between the brackets
+fn decode_one(t: &ValueType, value: &str) -> Option<PropValue> {
+    let trimmed = value.trim();
+    let decoded = match t {
+        ValueType::None if trimmed.is_empty() => PropValue::None,
+        ValueType::None => return None,
+        ValueType::Number(range) => {
+            let n: i64 = trimmed.parse().ok()?;
+            if !range.is_none_or(|(lo, hi)| (lo..=hi).contains(&n)) {
+                return None;
+            }
+            PropValue::Number(n)
+        },
+        ValueType::Real => PropValue::Real(trimmed.parse().ok().filter(|r: &f64| r.is_finite())?),
+        ValueType::Double => match trimmed {
+            "1" => PropValue::Double(1),
+            "2" => PropValue::Double(2),
+            _ => return None,
+        },
+        ValueType::Color => PropValue::Color(Color::from_ident(trimmed)?),
+        // SimpleText is one line, so line breaks read as spaces
+        ValueType::SimpleText => PropValue::SimpleText(unescape_text(value).replace("\r\n", " ").replace(['\n', '\r'], " ")),
+        ValueType::Text => PropValue::Text(unescape_text(value)),
+        ValueType::Point => PropValue::Point(Point::from_sgf(trimmed)?),
+        // tt stays a point: whether it is a pass depends on the board size
+        ValueType::Move if trimmed.is_empty() => PropValue::Move(None),
+        ValueType::Move => PropValue::Move(Some(Point::from_sgf(trimmed)?)),
+        ValueType::Compose(a, b) => {
+            let (x, y) = split_compose(value)?;
+            PropValue::compose(decode_one(a, x)?, decode_one(b, y)?)
+        },
+        ValueType::List(..) => return None,
+    };
+    Some(decoded)
+}
+
+// the two halves of a composed value, at the first ':' that isn't escaped
+fn split_compose(value: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ':' => return Some((&value[..i], &value[i + 1..])),
+            _ => {},
+        }
+    }
+    None
+}
+
+impl PropValue {
+    pub fn as_number(&self) -> Option<i64> {
+        match self {
+            PropValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // numbers count, as KM[7] is as good a komi as KM[7.0]
+    pub fn as_real(&self) -> Option<f64> {
+        match self {
+            PropValue::Real(r) => Some(*r),
+            PropValue::Number(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            PropValue::SimpleText(s) | PropValue::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_color(&self) -> Option<Color> {
+        match self {
+            PropValue::Color(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    // a point, or a move that isn't a pass
+    pub fn as_point(&self) -> Option<Point> {
+        match self {
+            PropValue::Point(p) | PropValue::Move(Some(p)) => Some(*p),
+            _ => None,
+        }
+    }
+
+    // the points of a point list, or of a single point
+    pub fn as_points(&self) -> Option<Vec<Point>> {
+        match self {
+            PropValue::List(values) => values.iter().map(|v| v.as_point()).collect(),
+            _ => self.as_point().map(|p| vec![p]),
+        }
+    }
+
+    pub fn as_compose(&self) -> Option<(&PropValue, &PropValue)> {
+        match self {
+            PropValue::Compose(a, b) => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[PropValue]> {
+        match self {
+            PropValue::List(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl Node {
+    // the property read as its type, or None when the node doesn't have it
+    pub fn typed(&self, ident: &str) -> Option<Result<PropValue>> {
+        self.get(ident).map(|prop| decode(&prop.ident, &prop.values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn props1() {
+        let coll = Parser::new("(;GM[1]SZ[19:13]KM[6.5]HA[2]PB[Lee\nSedol]AP[a\\:b:1]AB[aa][bc:cd]DD[]PL[W]FF[5];B[]C[a\\]b]LB[dd:x];W[tt]TE[3])").parse().unwrap();
+        let gt = &coll.gametrees[0];
+        let root = gt.root();
+        assert_eq!(root.typed("KM").unwrap().unwrap().as_real(), Some(6.5));
+        assert_eq!(root.typed("HA").unwrap().unwrap().as_number(), Some(2));
+        assert_eq!(root.typed("SZ").unwrap().unwrap(), PropValue::compose(PropValue::Number(19), PropValue::Number(13)));
+        assert_eq!(root.typed("PB").unwrap().unwrap().as_text(), Some("Lee Sedol"));
+        let ap = root.typed("AP").unwrap().unwrap();
+        assert_eq!(ap.as_compose().unwrap().0.as_text(), Some("a:b"));
+        let ab = root.typed("AB").unwrap().unwrap().as_points().unwrap();
+        assert_eq!(ab, vec![Point::new(0, 0), Point::new(1, 2), Point::new(2, 2), Point::new(1, 3), Point::new(2, 3)]);
+        assert_eq!(root.typed("DD").unwrap().unwrap(), PropValue::List(Vec::new()));
+        assert_eq!(root.typed("PL").unwrap().unwrap().as_color(), Some(Color::White));
+        assert!(matches!(root.typed("FF").unwrap(), Err(Error::BadValue { expected: "number (1-4)", .. })));
+        assert!(root.typed("RE").is_none());
+        let nodes = gt.mainline();
+        assert_eq!(nodes[1].typed("B").unwrap().unwrap(), PropValue::Move(None));
+        assert_eq!(nodes[1].typed("C").unwrap().unwrap(), PropValue::Text("a]b".to_string()));
+        let lb = nodes[1].typed("LB").unwrap().unwrap();
+        assert_eq!(lb, PropValue::List(vec![PropValue::compose(PropValue::Point(Point::new(3, 3)), PropValue::SimpleText("x".to_string()))]));
+        assert_eq!(nodes[2].typed("W").unwrap().unwrap().as_point(), Some(Point::new(19, 19)));
+        assert!(nodes[2].typed("TE").unwrap().is_err());
+        assert!(matches!(decode("XX", &["1".to_string()]), Err(Error::UnknownProperty(_))));
+        assert!(decode("KM", &["6.5".to_string(), "7".to_string()]).is_err());
+    }
+}
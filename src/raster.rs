@@ -0,0 +1,145 @@
+use std::fmt;
+
+use resvg::tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use super::board::Board;
+use super::render::{self, Diagram, RenderOptions};
+use super::vertex;
+
+#[derive(Debug)]
+pub enum Error {
+    RasterError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RasterError(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<render::Error> for Error {
+    fn from(err: render::Error) -> Error {
+        Error::RasterError(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// turns the svg renderer's output into pixels. loading the system fonts
+// is slow, so one rasterizer should be reused for many images
+pub struct Rasterizer {
+    options: usvg::Options<'static>,
+}
+
+impl Default for Rasterizer {
+    fn default() -> Self {
+        Rasterizer::new()
+    }
+}
+
+impl Rasterizer {
+    pub fn new() -> Self {
+        let mut options = usvg::Options::default();
+        let fontdb = options.fontdb_mut();
+        fontdb.load_system_fonts();
+        // sans-serif means Arial unless told otherwise, which many
+        // systems don't have, so fall back to any installed sans face
+        let has = |db: &usvg::fontdb::Database, name: &str| db.faces().any(|f| f.families.iter().any(|(n, _)| n == name));
+        if !has(fontdb, "Arial") {
+            let family = fontdb.faces()
+                .flat_map(|f| f.families.iter().map(|(n, _)| n.clone()))
+                .find(|n| n.contains("Sans") && !n.contains("Mono"))
+                .or_else(|| fontdb.faces().next().and_then(|f| f.families.first()).map(|(n, _)| n.clone()));
+            if let Some(family) = family {
+                fontdb.set_sans_serif_family(family);
+            }
+        }
+        Rasterizer {options}
+    }
+
+    pub fn pixmap(&self, svg: &str) -> Result<Pixmap> {
+        let tree = usvg::Tree::from_str(svg, &self.options).map_err(|e| Error::RasterError(e.to_string()))?;
+        let size = tree.size().to_int_size();
+        let mut pixmap = Pixmap::new(size.width(), size.height())
+            .ok_or_else(|| Error::RasterError("empty image".to_string()))?;
+        resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+        Ok(pixmap)
+    }
+
+    pub fn png(&self, diagram: &Diagram, opts: &RenderOptions) -> Result<Vec<u8>> {
+        let pixmap = self.pixmap(&render::svg(diagram, opts)?)?;
+        pixmap.encode_png().map_err(|e| Error::RasterError(e.to_string()))
+    }
+}
+
+fn paint(r: u8, g: u8, b: u8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(Color::from_rgba8(r, g, b, 255));
+    paint.anti_alias = true;
+    paint
+}
+
+// draws a thumbnail straight into pixels, skipping svg and fonts
+// entirely, which is what makes it fast enough for whole archives
+pub fn thumbnail(board: &Board, size: u32) -> Result<Vec<u8>> {
+    render::check_size(board)?;
+    let mut pixmap = Pixmap::new(size, size).ok_or_else(|| Error::RasterError("empty image".to_string()))?;
+    pixmap.fill(Color::from_rgba8(0xdc, 0xb3, 0x5c, 255));
+    let cell = size as f32 / board.cols.max(board.rows) as f32;
+    let pos = |n: usize| cell / 2.0 + n as f32 * cell;
+    // grid lines are pixel aligned, antialiasing thin rects only blurs them
+    let mut line = paint(0, 0, 0);
+    line.anti_alias = false;
+    let width = (cell / 16.0).max(1.0);
+    for x in 0..board.cols {
+        if let Some(r) = Rect::from_xywh(pos(x) - width / 2.0, pos(0), width, pos(board.rows - 1) - pos(0)) {
+            pixmap.fill_rect(r, &line, Transform::identity(), None);
+        }
+    }
+    for y in 0..board.rows {
+        if let Some(r) = Rect::from_xywh(pos(0), pos(y) - width / 2.0, pos(board.cols - 1) - pos(0), width) {
+            pixmap.fill_rect(r, &line, Transform::identity(), None);
+        }
+    }
+    let black = paint(0, 0, 0);
+    let white = paint(255, 255, 255);
+    for (p, color) in board.stones() {
+        let (cx, cy) = (pos(p.x as usize), pos(p.y as usize));
+        let stone = match color {
+            vertex::Color::Black => &black,
+            vertex::Color::White => &white,
+        };
+        if let Some(circle) = PathBuilder::from_circle(cx, cy, cell * 0.48) {
+            pixmap.fill_path(&circle, stone, FillRule::Winding, Transform::identity(), None);
+        }
+    }
+    pixmap.encode_png().map_err(|e| Error::RasterError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    #[test]
+    fn raster1() {
+        let diagram = Diagram::new(Board::new(9, 9));
+        let opts = RenderOptions {coordinates: false, cell: 10, ..Default::default()};
+        let pixmap = Rasterizer::new().pixmap(&render::svg(&diagram, &opts).unwrap()).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (94, 94));
+        let png = Rasterizer::new().png(&diagram, &opts).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+
+    #[test]
+    fn raster2() {
+        let mut board = Board::new(19, 19);
+        board.play(vertex::Color::Black, crate::point::Point::new(3, 3));
+        let png = thumbnail(&board, 64).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert!(thumbnail(&Board::new(0, 0), 64).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::filter::first_date;
+use super::info::GameResult;
+use super::json::JsonWriter;
+use super::vertex::{Color, GameTree};
+
+// where every player starts, and how far one game moves a rating
+pub const INITIAL_RATING: f64 = 1500.0;
+pub const K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    fn score(&self) -> f64 {
+        match self {
+            Outcome::Win => 1.0,
+            Outcome::Loss => 0.0,
+            Outcome::Draw => 0.5,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Win => "win",
+            Outcome::Loss => "loss",
+            Outcome::Draw => "draw",
+        }
+    }
+}
+
+// one game of the player's, with their rating after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendPoint {
+    pub date: String,
+    pub opponent: String,
+    pub color: Color,
+    pub outcome: Outcome,
+    pub rating: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    pub opponent: String,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl Record {
+    pub fn games(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trend {
+    pub player: String,
+    pub points: Vec<TrendPoint>,
+    // most games first
+    pub opponents: Vec<Record>,
+}
+
+// the player's side of a game: the color they had, the opponent's name
+// and the outcome. games without a date, both names or a decided or
+// drawn result are None
+fn side(gt: &GameTree, player: &str) -> Option<(String, Color, String, Outcome)> {
+    let root = gt.root();
+    let date = root.value("DT").and_then(first_date)?.to_string();
+    let (black, white) = (root.value("PB")?.trim(), root.value("PW")?.trim());
+    let color = if black.eq_ignore_ascii_case(player) {
+        Color::Black
+    } else if white.eq_ignore_ascii_case(player) {
+        Color::White
+    } else {
+        return None;
+    };
+    let opponent = if color == Color::Black { white } else { black };
+    let outcome = match gt.result()? {
+        GameResult::Win(winner, _) if winner == color => Outcome::Win,
+        GameResult::Win(..) => Outcome::Loss,
+        GameResult::Draw => Outcome::Draw,
+        GameResult::Void | GameResult::Unknown => return None,
+    };
+    Some((date, color, opponent.to_string(), outcome))
+}
+
+// dates compared by their numbers, since 2024-2-1 and 2024-02-01 are
+// both found in the wild
+fn date_key(date: &str) -> Vec<u32> {
+    date.split('-').map(|part| part.trim().parse().unwrap_or(0)).collect()
+}
+
+// Elo ratings over the games in date order, with every opponent rated
+// as well from the games they play in the archive. player is matched to
+// PB or PW ignoring case; games not theirs are skipped
+pub fn trend<'a, I: IntoIterator<Item = &'a GameTree>>(player: &str, games: I) -> Trend {
+    let mut sides: Vec<(String, Color, String, Outcome)> = games.into_iter().filter_map(|gt| side(gt, player)).collect();
+    sides.sort_by_cached_key(|s| date_key(&s.0));
+    let mut ratings: HashMap<String, f64> = HashMap::new();
+    let mut rating = INITIAL_RATING;
+    let mut trend = Trend {
+        player: player.to_string(),
+        ..Trend::default()
+    };
+    let mut records: HashMap<String, Record> = HashMap::new();
+    for (date, color, opponent, outcome) in sides {
+        let theirs = ratings.entry(opponent.to_lowercase()).or_insert(INITIAL_RATING);
+        let expected = 1.0 / (1.0 + 10f64.powf((*theirs - rating) / 400.0));
+        let change = K_FACTOR * (outcome.score() - expected);
+        rating += change;
+        *theirs -= change;
+        let record = records.entry(opponent.to_lowercase()).or_insert_with(|| Record {
+            opponent: opponent.clone(),
+            ..Record::default()
+        });
+        match outcome {
+            Outcome::Win => record.wins += 1,
+            Outcome::Loss => record.losses += 1,
+            Outcome::Draw => record.draws += 1,
+        }
+        trend.points.push(TrendPoint {
+            date,
+            opponent,
+            color,
+            outcome,
+            rating,
+        });
+    }
+    trend.opponents = records.into_values().collect();
+    trend.opponents.sort_by(|a, b| b.games().cmp(&a.games()).then_with(|| a.opponent.cmp(&b.opponent)));
+    trend
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Trend {
+    // one line per game, oldest first
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("date,opponent,color,result,rating\n");
+        for p in &self.points {
+            let _ = writeln!(out, "{},{},{},{},{:.0}", csv_field(&p.date), csv_field(&p.opponent), p.color.ident(), p.outcome.name(), p.rating);
+        }
+        out
+    }
+
+    pub fn opponents_csv(&self) -> String {
+        let mut out = String::from("opponent,games,wins,losses,draws\n");
+        for r in &self.opponents {
+            let _ = writeln!(out, "{},{},{},{},{}", csv_field(&r.opponent), r.games(), r.wins, r.losses, r.draws);
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut w = JsonWriter::new();
+        w.begin_object();
+        w.key("player");
+        w.string(&self.player);
+        w.key("games");
+        w.begin_array();
+        for p in &self.points {
+            w.begin_object();
+            w.key("date");
+            w.string(&p.date);
+            w.key("opponent");
+            w.string(&p.opponent);
+            w.key("color");
+            w.string(p.color.ident());
+            w.key("result");
+            w.string(p.outcome.name());
+            w.key("rating");
+            w.number(format!("{:.0}", p.rating));
+            w.end_object();
+        }
+        w.end_array();
+        w.key("opponents");
+        w.begin_array();
+        for r in &self.opponents {
+            w.begin_object();
+            w.key("opponent");
+            w.string(&r.opponent);
+            for (key, n) in [("wins", r.wins), ("losses", r.losses), ("draws", r.draws)] {
+                w.key(key);
+                w.number(n);
+            }
+            w.end_object();
+        }
+        w.end_array();
+        w.end_object();
+        w.finish()
+    }
+
+    // a line chart of the rating after each game, with the lowest and
+    // highest ratings marked
+    pub fn to_svg(&self, width: usize, height: usize) -> String {
+        let pad = 30.0;
+        let (w, h) = (width as f64, height as f64);
+        let ratings: Vec<f64> = std::iter::once(INITIAL_RATING).chain(self.points.iter().map(|p| p.rating)).collect();
+        let lo = ratings.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = ratings.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let span = (hi - lo).max(1.0);
+        let step = (w - 2.0 * pad) / (ratings.len() - 1).max(1) as f64;
+        let points: Vec<String> = ratings
+            .iter()
+            .enumerate()
+            .map(|(i, r)| format!("{:.1},{:.1}", pad + i as f64 * step, h - pad - (r - lo) / span * (h - 2.0 * pad)))
+            .collect();
+        let mut s = String::new();
+        let _ = writeln!(s, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height);
+        let _ = writeln!(s, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", width, height);
+        let _ = writeln!(s, "<polyline points=\"{}\" fill=\"none\" stroke=\"#36c\" stroke-width=\"2\"/>", points.join(" "));
+        let _ = writeln!(s, "<text x=\"2\" y=\"{:.0}\" font-size=\"10\">{:.0}</text>", pad, hi);
+        let _ = writeln!(s, "<text x=\"2\" y=\"{:.0}\" font-size=\"10\">{:.0}</text>", h - pad, lo);
+        s.push_str("</svg>\n");
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn rating1() {
+        let coll = Parser::new(
+            "(;PB[Me]PW[Ann]DT[2024-03-01]RE[B+R])\
+             (;PB[ann]PW[me]DT[2024-01-05]RE[B+2.5])\
+             (;PB[Bob]PW[Me]DT[2024-02-10]RE[0])\
+             (;PB[Me]PW[Dee]DT[2024-10-01]RE[W+T])\
+             (;PB[Bob]PW[Me]RE[W+R])\
+             (;PB[Bob]PW[Cy]DT[2024-02-11]RE[W+R])",
+        )
+        
+        .parse()
+        .unwrap();
+        let trend = trend("Me", &coll.gametrees);
+        let games: Vec<(&str, Outcome, i64)> = trend.points.iter().map(|p| (p.date.as_str(), p.outcome, p.rating.round() as i64)).collect();
+        // a loss to an equal player costs K/2, a draw with a stronger one
+        // gains a little and a win over the raised Ann more than K/2. the
+        // scanner drops the leading zeros, so 2024-10-1 has to sort last
+        assert_eq!(
+            games,
+            vec![("2024-1-5", Outcome::Loss, 1484), ("2024-2-10", Outcome::Draw, 1485), ("2024-3-1", Outcome::Win, 1502), ("2024-10-1", Outcome::Loss, 1486)]
+        );
+        assert_eq!(trend.opponents[0], Record { opponent: "ann".to_string(), wins: 1, losses: 1, draws: 0 });
+        assert!(trend.to_csv().ends_with("2024-3-1,Ann,B,win,1502\n2024-10-1,Dee,B,loss,1486\n"));
+        assert!(trend.to_json().starts_with("{\"player\":\"Me\",\"games\":[{\"date\":\"2024-1-5\",\"opponent\":\"ann\",\"color\":\"W\""));
+        assert_eq!(trend.opponents_csv().lines().nth(2), Some("Bob,1,0,0,1"));
+        assert_eq!(trend.opponents.len(), 3);
+        assert_eq!(trend.to_svg(300, 100).matches("<polyline").count(), 1);
+    }
+}
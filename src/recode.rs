@@ -0,0 +1,200 @@
+use std::fmt;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+use super::patch;
+
+// how the encoding of a file was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    // a byte order mark
+    Bom,
+    // the bytes are valid utf-8, which text in other encodings rarely is
+    Utf8,
+    // the CA property, and the bytes decode in it
+    Declared,
+    // told by the caller
+    Given,
+    // guessed from the bytes
+    Guessed,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Bom => write!(f, "byte order mark"),
+            Source::Utf8 => write!(f, "valid utf-8"),
+            Source::Declared => write!(f, "CA"),
+            Source::Given => write!(f, "given"),
+            Source::Guessed => write!(f, "guessed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub encoding: &'static Encoding,
+    pub source: Source,
+    // false when the guess barely beat the other candidates
+    pub confident: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // the best guess, not trusted enough to convert with
+    Unsure(Detection),
+    // the bytes aren't valid in the given encoding
+    Undecodable(&'static str),
+    // the text has characters the target encoding doesn't have
+    Unmappable(&'static str),
+    // encoding_rs only decodes utf-16
+    Unwritable(&'static str),
+    ParseError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unsure(d) => write!(f, "not sure of the encoding, {} is a guess", d.encoding.name()),
+            Error::Undecodable(name) => write!(f, "not valid {}", name),
+            Error::Unmappable(name) => write!(f, "has characters {} can't hold", name),
+            Error::Unwritable(name) => write!(f, "can't write {}", name),
+            Error::ParseError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// an encoding by any of the names CA values and users give it, like
+// UTF-8, gb2312, Shift_JIS or latin1
+pub fn encoding(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+// the value of the first CA in the raw bytes, which are ascii there in
+// every encoding we read
+fn declared(data: &[u8]) -> Option<&str> {
+    let at = data.windows(3).enumerate().find(|(i, w)| *w == b"CA[" && (*i == 0 || !data[i - 1].is_ascii_uppercase()))?.0 + 3;
+    let len = data[at..].iter().position(|b| *b == b']')?;
+    std::str::from_utf8(&data[at..at + len]).ok()
+}
+
+fn decodes(encoding: &'static Encoding, data: &[u8]) -> bool {
+    !encoding.decode_with_bom_removal(data).1
+}
+
+pub fn detect(data: &[u8]) -> Detection {
+    let found = |encoding, source, confident| Detection { encoding, source, confident };
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        return found(encoding, Source::Bom, true);
+    }
+    if std::str::from_utf8(data).is_ok() {
+        return found(UTF_8, Source::Utf8, true);
+    }
+    // CA is often left at a program's default, so it only counts when the
+    // bytes are valid in it
+    if let Some(encoding) = declared(data).and_then(encoding) {
+        if decodes(encoding, data) {
+            return found(encoding, Source::Declared, true);
+        }
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    let (encoding, confident) = detector.guess_assess(None, false);
+    found(encoding, Source::Guessed, confident && decodes(encoding, data))
+}
+
+#[derive(Debug, Clone)]
+pub struct Recoded {
+    pub data: Vec<u8>,
+    pub detection: Detection,
+    // false for files already in the target encoding with CA saying so,
+    // whose data is returned as it was
+    pub changed: bool,
+}
+
+// decodes data in the encoding it was detected in, or from when given,
+// and encodes it in to with every game's CA updated and nothing else
+// touched. files we can't place with confidence are refused, as
+// converting from a wrong encoding garbles every name and comment
+pub fn recode(data: &[u8], from: Option<&'static Encoding>, to: &'static Encoding) -> Result<Recoded> {
+    if to.output_encoding() != to {
+        return Err(Error::Unwritable(to.name()));
+    }
+    let detection = match from {
+        Some(encoding) => Detection {
+            encoding,
+            source: Source::Given,
+            confident: true,
+        },
+        None => detect(data),
+    };
+    if !detection.confident {
+        return Err(Error::Unsure(detection));
+    }
+    let (text, errors) = detection.encoding.decode_with_bom_removal(data);
+    if errors {
+        return Err(Error::Undecodable(detection.encoding.name()));
+    }
+    // only CA is rewritten, the rest of the text goes over as it is
+    let (text, labelled) = patch::patch(&text, |coll| {
+        let mut labelled = true;
+        for gt in &mut coll.gametrees {
+            if gt.root().value("CA").and_then(encoding) != Some(to) {
+                gt.root_mut().set("CA", vec![to.name().to_string()]);
+                labelled = false;
+            }
+        }
+        labelled
+    })
+    .map_err(|e| Error::ParseError(e.to_string()))?;
+    if detection.encoding == to && labelled {
+        return Ok(Recoded {
+            data: data.to_vec(),
+            detection,
+            changed: false,
+        });
+    }
+    let (out, _, unmappable) = to.encode(&text);
+    if unmappable {
+        return Err(Error::Unmappable(to.name()));
+    }
+    Ok(Recoded {
+        data: out.into_owned(),
+        detection,
+        changed: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{EUC_KR, GBK, SHIFT_JIS, WINDOWS_1252};
+
+    #[test]
+    fn recode1() {
+        let gb = GBK.encode("(;GM[1]CA[gb2312]PB[李昌镐]PW[曹薰铉])").0.into_owned();
+        assert_eq!(detect(&gb), Detection { encoding: GBK, source: Source::Declared, confident: true });
+        let done = recode(&gb, None, UTF_8).unwrap();
+        assert!(done.changed);
+        assert_eq!(String::from_utf8(done.data).unwrap(), "(;GM[1]CA[UTF-8]PB[李昌镐]PW[曹薰铉])");
+        let latin = WINDOWS_1252.encode("(;GM[1]DT[2024-03-01]\nPB[Jörg  Ü])").0.into_owned();
+        let done = recode(&latin, Some(WINDOWS_1252), UTF_8).unwrap();
+        assert_eq!(String::from_utf8(done.data).unwrap(), "(;GM[1]DT[2024-03-01]\nPB[Jörg  Ü]CA[UTF-8])");
+        // no CA, or one that is wrong, leaves it to the detector
+        let ko = EUC_KR.encode("(;GM[1]CA[UTF-8]PB[이세돌]PW[박정환]C[흑이 불계승했습니다. 좋은 대국이었습니다.])").0.into_owned();
+        assert_eq!(detect(&ko).encoding, EUC_KR);
+        let sjis = SHIFT_JIS.encode("(;GM[1]PB[井山裕太]C[黒の中押し勝ち。序盤から厚みを生かした碁でした。])").0.into_owned();
+        let found = detect(&sjis);
+        assert_eq!((found.encoding, found.source), (SHIFT_JIS, Source::Guessed));
+        assert!(String::from_utf8(recode(&sjis, None, UTF_8).unwrap().data).unwrap().starts_with("(;GM[1]PB[井山裕太]"));
+        // already converted
+        let utf8 = "(;GM[1]CA[utf-8]PB[李昌镐])".as_bytes();
+        assert!(!recode(utf8, None, UTF_8).unwrap().changed);
+        assert!(matches!(recode(utf8, None, WINDOWS_1252), Err(Error::Unmappable("windows-1252"))));
+        assert!(matches!(recode(&gb, Some(UTF_8), UTF_8), Err(Error::Undecodable("UTF-8"))));
+        assert_eq!(encoding("latin1"), Some(WINDOWS_1252));
+    }
+}
@@ -0,0 +1,424 @@
+use std::fmt::{self, Write};
+
+use super::board::Board;
+use super::point::{parse_point_list, Point};
+use super::view::{Figure, PrintMode};
+use super::vertex::{Color, GameTree, Node};
+
+#[derive(Debug)]
+pub enum Error {
+    // a board needs two lines each way to be drawn as a grid
+    BoardTooSmall(usize, usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BoardTooSmall(cols, rows) => write!(f, "can't draw a {}x{} board", cols, rows),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// the images draw lines from the first to the last one each way
+pub fn check_size(board: &Board) -> Result<()> {
+    if board.cols < 2 || board.rows < 2 {
+        return Err(Error::BoardTooSmall(board.cols, board.rows));
+    }
+    Ok(())
+}
+
+// a position to draw along with what the diagram options need to know
+// about how it came about
+#[derive(Debug, Clone)]
+pub struct Diagram {
+    pub board: Board,
+    // stones still on the board with the move that placed them, oldest first
+    pub numbered: Vec<(Point, Color, usize)>,
+    pub last: Option<(Point, Color)>,
+    // the VW in effect, None for the whole board
+    pub view: Option<Vec<Point>>,
+    pub print_mode: PrintMode,
+}
+
+impl Diagram {
+    pub fn new(board: Board) -> Self {
+        Diagram {
+            board,
+            numbered: Vec::new(),
+            last: None,
+            view: None,
+            print_mode: PrintMode::default(),
+        }
+    }
+
+    // the main line position after n moves, with the inherited VW and PM
+    pub fn at(gt: &GameTree, n: usize) -> Self {
+        Diagram::play(gt, gt.mainline(), n)
+    }
+
+    // the position once the node at path has been played, None when there
+    // is no such node
+    pub fn at_path(gt: &GameTree, path: &[usize]) -> Option<Self> {
+        let nodes = (0..=path.len()).map(|n| gt.node(&path[..n])).collect::<Option<Vec<&Node>>>()?;
+        Some(Diagram::play(gt, nodes, usize::MAX))
+    }
+
+    // the position at the end of a figure from GameTree::figures, in its
+    // print mode. only the figure's own moves keep their numbers
+    pub fn figure(gt: &GameTree, figure: &Figure) -> Self {
+        let mainline = gt.mainline();
+        let before = mainline[..figure.nodes.start].iter().filter(|n| n.get_move().is_some()).count();
+        let mut diagram = Diagram::play(gt, mainline[..figure.nodes.end].iter().copied(), usize::MAX);
+        diagram.numbered.retain(|(_, _, n)| *n > before);
+        diagram.print_mode = figure.print_mode;
+        diagram
+    }
+
+    // the nodes in order, stopping before move n
+    fn play<'a, I: IntoIterator<Item = &'a Node>>(gt: &GameTree, nodes: I, n: usize) -> Self {
+        let mut diagram = Diagram::new(Board::for_game(gt));
+        let mut number = 0;
+        for node in nodes {
+            let is_move = node.get_move().is_some();
+            if is_move && number == n {
+                break;
+            }
+            if let Some(vw) = node.get("VW") {
+                let points = parse_point_list(&vw.values);
+                diagram.view = if points.is_empty() { None } else { Some(points) };
+            }
+            if let Some(pm) = node.value("PM").and_then(PrintMode::parse) {
+                diagram.print_mode = pm;
+            }
+            let mv = diagram.board.move_point(node);
+            diagram.board.apply(node);
+            if is_move {
+                number += 1;
+                diagram.last = None;
+            }
+            if let Some((color, p)) = mv {
+                diagram.numbered.retain(|(q, _, _)| *q != p);
+                diagram.numbered.push((p, color, number));
+                diagram.last = Some((p, color));
+            }
+        }
+        // captured or overwritten stones lose their numbers
+        let board = &diagram.board;
+        diagram.numbered.retain(|(p, c, _)| board.get(*p) == Some(*c));
+        diagram
+    }
+
+    pub fn in_view(&self, p: Point) -> bool {
+        match &self.view {
+            Some(points) => points.contains(&p),
+            None => true,
+        }
+    }
+
+    fn label(&self, p: Point, opts: &RenderOptions) -> Option<String> {
+        if opts.numbers == 0 {
+            return None;
+        }
+        let recent = self.numbered.iter().rev().take(opts.numbers);
+        for (q, _, n) in recent {
+            if *q == p {
+                return self.print_mode.label(*n);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub coordinates: bool,
+    pub mark_last: bool,
+    // show move numbers on the stones of the last n moves
+    pub numbers: usize,
+    pub dim_outside_view: bool,
+    // svg pixels per line spacing
+    pub cell: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            coordinates: true,
+            mark_last: true,
+            numbers: 0,
+            dim_outside_view: true,
+            cell: 24,
+        }
+    }
+}
+
+impl RenderOptions {
+    // the options for drawing a figure: every move of it numbered, and
+    // coordinates only when its FG flags have them
+    pub fn for_figure(&self, figure: &Figure) -> Self {
+        RenderOptions {
+            coordinates: self.coordinates && figure.flags.coordinates,
+            numbers: usize::MAX,
+            ..self.clone()
+        }
+    }
+}
+
+const COLUMNS: &str = "ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+fn column_label(x: usize) -> String {
+    match COLUMNS.chars().nth(x) {
+        Some(c) => c.to_string(),
+        None => (x + 1).to_string(),
+    }
+}
+
+pub fn hoshi(cols: usize, rows: usize) -> Vec<Point> {
+    if cols != rows || cols < 7 {
+        return Vec::new();
+    }
+    let edge = if cols >= 13 { 3 } else { 2 };
+    let far = cols.saturating_sub(1 + edge);
+    let mut lines = vec![edge, far];
+    if cols % 2 == 1 && (cols >= 13 || cols == 9) {
+        lines.push(cols / 2);
+    }
+    let mut points = Vec::new();
+    for &y in &lines {
+        for &x in &lines {
+            // smaller boards only have the corners and tengen
+            if cols < 19 && (x == cols / 2) != (y == cols / 2) {
+                continue;
+            }
+            points.push(Point::new(x as u8, y as u8));
+        }
+    }
+    points
+}
+
+// X and O for stones, # and @ for the last black and white move, and
+// move numbers in place of the stone when requested. points outside the
+// view are left blank
+pub fn ascii(diagram: &Diagram, opts: &RenderOptions) -> String {
+    let board = &diagram.board;
+    let mut width = 1;
+    for (p, _, _) in &diagram.numbered {
+        if let Some(label) = diagram.label(*p, opts) {
+            width = width.max(label.len());
+        }
+    }
+    let row_width = board.rows.to_string().len();
+    let mut s = String::new();
+    if opts.coordinates {
+        s.push_str(&" ".repeat(row_width));
+        for x in 0..board.cols {
+            let _ = write!(s, " {:>width$}", column_label(x), width = width);
+        }
+        s.push('\n');
+    }
+    let stars = hoshi(board.cols, board.rows);
+    for y in 0..board.rows {
+        if opts.coordinates {
+            let _ = write!(s, "{:>width$}", board.rows - y, width = row_width);
+        }
+        for x in 0..board.cols {
+            let p = Point::new(x as u8, y as u8);
+            let cell = if opts.dim_outside_view && !diagram.in_view(p) {
+                " ".to_string()
+            } else if let Some(label) = board.get(p).and_then(|_| diagram.label(p, opts)) {
+                label
+            } else {
+                let last = opts.mark_last && diagram.last.map(|(q, _)| q) == Some(p);
+                match (board.get(p), last) {
+                    (Some(Color::Black), false) => "X".to_string(),
+                    (Some(Color::White), false) => "O".to_string(),
+                    (Some(Color::Black), true) => "#".to_string(),
+                    (Some(Color::White), true) => "@".to_string(),
+                    (None, _) if stars.contains(&p) => "+".to_string(),
+                    (None, _) => ".".to_string(),
+                }
+            };
+            let _ = write!(s, " {:>width$}", cell, width = width);
+        }
+        s.push('\n');
+    }
+    s
+}
+
+pub fn svg(diagram: &Diagram, opts: &RenderOptions) -> Result<String> {
+    let board = &diagram.board;
+    check_size(board)?;
+    let cell = opts.cell.max(4);
+    let margin = if opts.coordinates { cell * 3 / 2 } else { cell / 2 + 2 };
+    let width = margin * 2 + cell * (board.cols - 1);
+    let height = margin * 2 + cell * (board.rows - 1);
+    let pos = |n: usize| margin + n * cell;
+    let radius = cell as f64 * 0.48;
+    let font = cell * 11 / 24;
+
+    let mut s = String::new();
+    let _ = writeln!(s, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height);
+    let _ = writeln!(s, "<rect width=\"{}\" height=\"{}\" fill=\"#dcb35c\"/>", width, height);
+    for x in 0..board.cols {
+        let _ = writeln!(s, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#000\"/>", pos(x), pos(0), pos(x), pos(board.rows - 1));
+    }
+    for y in 0..board.rows {
+        let _ = writeln!(s, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#000\"/>", pos(0), pos(y), pos(board.cols - 1), pos(y));
+    }
+    for p in hoshi(board.cols, board.rows) {
+        let _ = writeln!(s, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#000\"/>", pos(p.x as usize), pos(p.y as usize), cell / 8 + 1);
+    }
+    if opts.coordinates {
+        let text = |s: &mut String, x: usize, y: usize, label: &str| {
+            let _ = writeln!(s, "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>", x, y, font, label);
+        };
+        for x in 0..board.cols {
+            text(&mut s, pos(x), margin / 3, &column_label(x));
+            text(&mut s, pos(x), height - margin / 3, &column_label(x));
+        }
+        for y in 0..board.rows {
+            let label = (board.rows - y).to_string();
+            text(&mut s, margin / 3, pos(y), &label);
+            text(&mut s, width - margin / 3, pos(y), &label);
+        }
+    }
+    for (p, color) in board.stones() {
+        let (fill, ink) = match color {
+            Color::Black => ("#000", "#fff"),
+            Color::White => ("#fff", "#000"),
+        };
+        let (cx, cy) = (pos(p.x as usize), pos(p.y as usize));
+        let _ = writeln!(s, "<circle cx=\"{}\" cy=\"{}\" r=\"{:.1}\" fill=\"{}\" stroke=\"#000\"/>", cx, cy, radius, fill);
+        if let Some(label) = diagram.label(p, opts) {
+            let _ = writeln!(s, "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>", cx, cy, font, ink, label);
+        } else if opts.mark_last && diagram.last.map(|(q, _)| q) == Some(p) {
+            let _ = writeln!(s, "<circle cx=\"{}\" cy=\"{}\" r=\"{:.1}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>", cx, cy, radius / 2.0, ink);
+        }
+    }
+    if opts.dim_outside_view && diagram.view.is_some() {
+        let half = cell / 2;
+        for y in 0..board.rows {
+            for x in 0..board.cols {
+                if !diagram.in_view(Point::new(x as u8, y as u8)) {
+                    let _ = writeln!(s, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#fff\" fill-opacity=\"0.6\"/>", pos(x) - half, pos(y) - half, cell, cell);
+                }
+            }
+        }
+    }
+    s.push_str("</svg>\n");
+    Ok(s)
+}
+
+// the fast path for archive browsers: a fixed size image with only the
+// grid and the stones, no coordinates, text or markers
+pub fn thumbnail_svg(board: &Board, size: usize) -> Result<String> {
+    check_size(board)?;
+    let cell = size as f64 / board.cols.max(board.rows) as f64;
+    let pos = |n: usize| cell / 2.0 + n as f64 * cell;
+    let mut grid = String::new();
+    for x in 0..board.cols {
+        let _ = write!(grid, "M{:.1} {:.1}V{:.1}", pos(x), pos(0), pos(board.rows - 1));
+    }
+    for y in 0..board.rows {
+        let _ = write!(grid, "M{:.1} {:.1}H{:.1}", pos(0), pos(y), pos(board.cols - 1));
+    }
+    let mut s = String::new();
+    let _ = write!(s, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">", size, size);
+    let _ = write!(s, "<rect width=\"{}\" height=\"{}\" fill=\"#dcb35c\"/>", size, size);
+    let _ = write!(s, "<path d=\"{}\" stroke=\"#000\" stroke-width=\"0.5\"/>", grid);
+    for (p, color) in board.stones() {
+        let fill = match color {
+            Color::Black => "#000",
+            Color::White => "#fff",
+        };
+        let _ = write!(s, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>", pos(p.x as usize), pos(p.y as usize), cell * 0.48, fill);
+    }
+    s.push_str("</svg>\n");
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn render1() {
+        let gt = gametree("(;SZ[5];B[bb];W[cc];B[dd])");
+        let plain = RenderOptions {coordinates: false, ..Default::default()};
+        assert_eq!(ascii(&Diagram::at(&gt, 2), &plain), " . . . . .\n . X . . .\n . . @ . .\n . . . . .\n . . . . .\n");
+        let numbered = RenderOptions {numbers: 2, ..plain.clone()};
+        let s = ascii(&Diagram::at(&gt, 3), &numbered);
+        assert_eq!(s.lines().nth(1), Some(" . X . . ."));
+        assert_eq!(s.lines().nth(3), Some(" . . . 3 ."));
+        let coords = ascii(&Diagram::at(&gt, 0), &RenderOptions::default());
+        assert_eq!(coords.lines().next(), Some("  A B C D E"));
+        assert_eq!(coords.lines().nth(1), Some("5 . . . . ."));
+    }
+
+    #[test]
+    fn render2() {
+        let gt = gametree("(;SZ[5]VW[aa:bb]PM[2];B[aa];W[bb];B[ee])");
+        let opts = RenderOptions {coordinates: false, numbers: 5, ..Default::default()};
+        let diagram = Diagram::at(&gt, 3);
+        assert_eq!(diagram.print_mode, PrintMode::Modulo100);
+        let s = ascii(&diagram, &opts);
+        assert_eq!(s.lines().next(), Some(" 1 .      "));
+        assert_eq!(s.lines().nth(1), Some(" . 2      "));
+        assert_eq!(s.lines().nth(4), Some("          "));
+        let svg = svg(&diagram, &opts).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("fill-opacity").count(), 21);
+    }
+
+    #[test]
+    fn render3() {
+        let gt = gametree("(;SZ[5];B[bb](;W[cc])(;W[dd];B[ee]))");
+        let plain = RenderOptions {coordinates: false, ..Default::default()};
+        let diagram = Diagram::at_path(&gt, &[0, 1]).unwrap();
+        assert_eq!(ascii(&diagram, &plain), " . . . . .\n . X . . .\n . . . . .\n . . . @ .\n . . . . .\n");
+        assert!(Diagram::at_path(&gt, &[0, 2]).is_none());
+    }
+
+    #[test]
+    fn render4() {
+        let gt = gametree("(;SZ[5]PM[2];B[aa];W[bb]FG[1:two]PM[0];B[cc]PM[1];W[dd])");
+        let figures = gt.figures();
+        let opts = RenderOptions::default();
+        let first = Diagram::figure(&gt, &figures[0]);
+        assert_eq!(ascii(&first, &opts.for_figure(&figures[0])).lines().nth(1), Some("5 1 . . . ."));
+        // the second figure starts with the moves before it unnumbered,
+        // hides its numbers and has no coordinates
+        let second = Diagram::figure(&gt, &figures[1]);
+        let opts = opts.for_figure(&figures[1]);
+        assert!(!opts.coordinates);
+        assert_eq!(ascii(&second, &opts), " X . . . .\n . O . . .\n . . X . .\n . . . @ .\n . . . . .\n");
+        assert_eq!(second.numbered.len(), 3);
+    }
+
+    #[test]
+    fn thumbnail1() {
+        let gt = gametree("(;SZ[9];B[aa];W[bb])");
+        let s = thumbnail_svg(&gt.final_position(), 72).unwrap();
+        assert!(s.contains("width=\"72\""));
+        assert_eq!(s.matches("<circle").count(), 2);
+        assert!(!s.contains("<text"));
+        assert!(matches!(thumbnail_svg(&Board::new(0, 0), 72), Err(Error::BoardTooSmall(0, 0))));
+        assert!(svg(&Diagram::new(Board::new(1, 1)), &RenderOptions::default()).is_err());
+        assert!(svg(&Diagram::new(Board::new(2, 2)), &RenderOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn hoshi1() {
+        assert_eq!(hoshi(19, 19).len(), 9);
+        assert_eq!(hoshi(13, 13).len(), 5);
+        assert_eq!(hoshi(9, 9).len(), 5);
+        assert!(hoshi(5, 5).is_empty());
+    }
+}
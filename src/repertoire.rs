@@ -0,0 +1,250 @@
+use std::fmt;
+
+use super::info::is_pass;
+use super::point::{Point, Symmetry, SYMMETRIES};
+use super::vertex::{Color, GameTree};
+
+pub type Move = (Color, Option<Point>);
+
+// one move of the repertoire. nodes without moves are left out, and lines
+// starting with the same moves share them
+#[derive(Debug, Clone)]
+struct Step {
+    mv: Option<Move>,
+    // the node path of the move in the repertoire, for finding it there
+    path: Vec<usize>,
+    children: Vec<usize>,
+    // games that played the move while in book
+    visits: usize,
+}
+
+// a tree of prepared lines, matched against games in any orientation
+#[derive(Debug, Clone)]
+pub struct Repertoire {
+    size: usize,
+    steps: Vec<Step>,
+}
+
+// where a game left the prepared lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deviation {
+    pub number: usize,
+    pub played: Move,
+    // the moves the repertoire has there, in its orientation
+    pub expected: Vec<Move>,
+    // the node path of the last move in book, [] when the first move was
+    // already out
+    pub path: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameCoverage {
+    // the game's moves that were in book
+    pub depth: usize,
+    // how the game was turned to match the repertoire
+    pub symmetry: Symmetry,
+    // None when the game followed a line to its end, or stopped in book
+    pub deviation: Option<Deviation>,
+    // the game played every move of a line
+    pub completed: bool,
+}
+
+// one line of the repertoire, from the start to one of its ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    // the node path of its last move
+    pub path: Vec<usize>,
+    pub moves: usize,
+    // the move from which it is a line of its own, after it branches
+    // off from the others
+    pub own_from: usize,
+    // games that played that move, and games that played all of it
+    pub entered: usize,
+    pub completed: usize,
+}
+
+impl Repertoire {
+    pub fn new(gt: &GameTree) -> Self {
+        let (cols, rows) = gt.board_size();
+        let mut rep = Repertoire {
+            size: cols.max(rows),
+            steps: vec![Step {
+                mv: None,
+                path: Vec::new(),
+                children: Vec::new(),
+                visits: 0,
+            }],
+        };
+        rep.add(gt, 0, Vec::new());
+        rep
+    }
+
+    fn add(&mut self, gt: &GameTree, mut at: usize, mut path: Vec<usize>) {
+        for (i, node) in gt.sequence.nodes.iter().enumerate() {
+            if i > 0 {
+                path.push(0);
+            }
+            let Some((color, value)) = node.get_move() else { continue };
+            // read as GameTree::moves reads them
+            let point = if is_pass(value, self.size, self.size) {
+                None
+            } else {
+                Point::from_sgf(value).filter(|p| p.on_board(self.size, self.size))
+            };
+            let mv = (color, point);
+            at = match self.steps[at].children.iter().find(|c| self.steps[**c].mv == Some(mv)) {
+                Some(&child) => child,
+                None => {
+                    self.steps.push(Step {
+                        mv: Some(mv),
+                        path: path.clone(),
+                        children: Vec::new(),
+                        visits: 0,
+                    });
+                    let child = self.steps.len() - 1;
+                    self.steps[at].children.push(child);
+                    child
+                },
+            };
+        }
+        for (i, child) in gt.gametrees.iter().enumerate() {
+            let mut p = path.clone();
+            p.push(i);
+            self.add(child, at, p);
+        }
+    }
+
+    // how far the moves stay in book, trying every orientation of a square
+    // board and keeping the deepest
+    fn walk(&self, moves: &[Move]) -> (Symmetry, Vec<usize>) {
+        let mut best = (Symmetry::Identity, Vec::new());
+        for sym in SYMMETRIES {
+            let mut at = 0;
+            let mut visited = Vec::new();
+            for (color, point) in moves {
+                let mv = match point {
+                    Some(p) => match sym.apply(*p, self.size) {
+                        Some(p) => (*color, Some(p)),
+                        None => break,
+                    },
+                    None => (*color, None),
+                };
+                match self.steps[at].children.iter().find(|c| self.steps[**c].mv == Some(mv)) {
+                    Some(&child) => {
+                        visited.push(child);
+                        at = child;
+                    },
+                    None => break,
+                }
+            }
+            if visited.len() > best.1.len() {
+                best = (sym, visited);
+            }
+        }
+        best
+    }
+
+    // follows the game's main line through the repertoire, counting the
+    // moves it played for lines()
+    pub fn check(&mut self, gt: &GameTree) -> GameCoverage {
+        let moves = gt.moves();
+        let (cols, rows) = gt.board_size();
+        let (symmetry, visited) = if cols == rows && cols == self.size { self.walk(&moves) } else { (Symmetry::Identity, Vec::new()) };
+        for step in &visited {
+            self.steps[*step].visits += 1;
+        }
+        let last = visited.last().copied().unwrap_or(0);
+        let depth = visited.len();
+        let children = &self.steps[last].children;
+        let deviation = match moves.get(depth) {
+            Some((color, point)) if !children.is_empty() => Some(Deviation {
+                number: depth + 1,
+                // a point off the board is shown as it was played
+                played: (*color, point.map(|p| symmetry.apply(p, self.size).unwrap_or(p))),
+                expected: children.iter().filter_map(|c| self.steps[*c].mv).collect(),
+                path: self.steps[last].path.clone(),
+            }),
+            _ => None,
+        };
+        GameCoverage {
+            depth,
+            symmetry,
+            deviation,
+            completed: depth > 0 && children.is_empty(),
+        }
+    }
+
+    // every line with what the games checked so far did with it
+    pub fn lines(&self) -> Vec<Line> {
+        let mut lines = Vec::new();
+        self.collect(0, 0, 1, 0, &mut lines);
+        lines
+    }
+
+    fn collect(&self, at: usize, depth: usize, own_from: usize, entered: usize, lines: &mut Vec<Line>) {
+        let step = &self.steps[at];
+        if step.children.is_empty() {
+            if at != 0 {
+                lines.push(Line {
+                    path: step.path.clone(),
+                    moves: depth,
+                    own_from,
+                    entered,
+                    completed: step.visits,
+                });
+            }
+            return;
+        }
+        let branches = step.children.len() > 1;
+        for &child in &step.children {
+            let (own_from, entered) = if branches || at == 0 { (depth + 1, self.steps[child].visits) } else { (own_from, entered) };
+            self.collect(child, depth + 1, own_from, entered, lines);
+        }
+    }
+
+    // a move as B Q16 or W pass
+    pub fn describe(&self, m: &Move) -> String {
+        match m.1 {
+            Some(p) => format!("{} {}", m.0.ident(), p.to_gtp(self.size)),
+            None => format!("{} pass", m.0.ident()),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|i| i.to_string()).collect();
+        write!(f, "line {} ({} moves, its own from move {}): entered by {}, completed by {}", path.join("."), self.moves, self.own_from, self.entered, self.completed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn repertoire1() {
+        // Q16 D4, then Q4 D16 or R4, and a line starting on the 3-4 point
+        let mut rep = Repertoire::new(&gametree("(;GM[1]C[book](;B[pd];W[dp](;B[pp];W[dd])(;B[qp]))(;B[qd]))"));
+        // flipped, this plays Q16 D4, then leaves the book with Q17
+        let game = rep.check(&gametree("(;B[dd];W[pp];B[dc])"));
+        assert_eq!((game.depth, game.completed), (2, false));
+        let deviation = game.deviation.unwrap();
+        assert_eq!(deviation.number, 3);
+        assert_eq!((game.symmetry, deviation.path), (Symmetry::FlipX, vec![0, 0]));
+        assert_eq!(rep.describe(&deviation.played), "B Q17");
+        assert_eq!(deviation.expected.iter().map(|m| rep.describe(m)).collect::<Vec<_>>(), vec!["B Q4", "B R4"]);
+        let done = rep.check(&gametree("(;B[pd];W[dp];B[pp];W[dd];B[qq])"));
+        assert!(done.completed && done.deviation.is_none() && done.depth == 4);
+        let out = rep.check(&gametree("(;B[jj])"));
+        assert_eq!((out.depth, out.deviation.unwrap().expected.len()), (0, 2));
+        let lines: Vec<(usize, usize, usize, usize)> = rep.lines().iter().map(|l| (l.moves, l.own_from, l.entered, l.completed)).collect();
+        assert_eq!(lines, vec![(4, 3, 1, 1), (3, 3, 0, 0), (1, 1, 0, 0)]);
+        assert_eq!(rep.lines()[0].path, vec![0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,120 @@
+use super::board::Board;
+use super::vertex::{GameTree, Node};
+
+// replays the main line of a game. positions are normally rebuilt from
+// the root on every call; with_cache keeps a snapshot every k moves so
+// scrubbing back and forth through a long game stays cheap
+pub struct Replay<'a> {
+    nodes: Vec<&'a Node>,
+    // for move n, the main line index just past the node that played it.
+    // ends[0] covers the setup before the first move
+    ends: Vec<usize>,
+    start: Board,
+    every: usize,
+    snapshots: Vec<Board>,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(gt: &'a GameTree) -> Self {
+        let nodes = gt.mainline();
+        let mut ends = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            if node.get_move().is_some() {
+                if ends.is_empty() {
+                    ends.push(i);
+                }
+                ends.push(i + 1);
+            }
+        }
+        if ends.is_empty() {
+            ends.push(nodes.len());
+        }
+        Replay {
+            nodes,
+            ends,
+            start: Board::for_game(gt),
+            every: 0,
+            snapshots: Vec::new(),
+        }
+    }
+
+    pub fn with_cache(mut self, every: usize) -> Self {
+        self.every = every.max(1);
+        self.snapshots = Vec::new();
+        let mut board = self.start.clone();
+        let mut applied = 0;
+        for n in (0..self.ends.len()).step_by(self.every) {
+            for node in &self.nodes[applied..self.ends[n]] {
+                board.apply(node);
+            }
+            applied = self.ends[n];
+            self.snapshots.push(board.clone());
+        }
+        self
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.ends.len() - 1
+    }
+
+    pub fn nodes(&self) -> &[&'a Node] {
+        &self.nodes
+    }
+
+    // the path of the node position_after(n) stops at, for jumping
+    // from a position back into the tree
+    pub fn path_after(&self, n: usize) -> Vec<usize> {
+        let n = n.min(self.move_count());
+        vec![0; self.ends[n].saturating_sub(1)]
+    }
+
+    // the position once n moves have been played, clamped to the last move
+    pub fn position_after(&self, n: usize) -> Board {
+        let n = n.min(self.move_count());
+        let (mut board, applied) = if self.snapshots.is_empty() {
+            (self.start.clone(), 0)
+        } else {
+            let i = n / self.every;
+            (self.snapshots[i].clone(), self.ends[i * self.every])
+        };
+        for node in &self.nodes[applied..self.ends[n]] {
+            board.apply(node);
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn replay1() {
+        let text = "(;SZ[9]AB[aa];C[setup only];B[bb];W[cc];AW[dd];B[ee];W[ff];B[gg]C[last])";
+        let gt = Parser::new(text).parse().unwrap().gametrees.remove(0);
+        let plain = Replay::new(&gt);
+        assert_eq!(plain.move_count(), 5);
+        assert_eq!(plain.position_after(0).stones().len(), 1);
+        assert_eq!(plain.position_after(2).stones().len(), 3);
+        // the setup node after move 2 belongs to move 3
+        assert_eq!(plain.position_after(3).stones().len(), 5);
+        assert_eq!(plain.position_after(99), gt.final_position());
+        assert_eq!(plain.path_after(2), vec![0; 3]);
+        assert_eq!(gt.node(&plain.path_after(2)).unwrap().value("W"), Some("cc"));
+        for k in 1..7 {
+            let cached = Replay::new(&gt).with_cache(k);
+            for n in 0..7 {
+                assert_eq!(cached.position_after(n), plain.position_after(n));
+            }
+        }
+    }
+
+    #[test]
+    fn replay2() {
+        let gt = Parser::new("(;SZ[9]AB[aa]C[no moves])").parse().unwrap().gametrees.remove(0);
+        let replay = Replay::new(&gt).with_cache(10);
+        assert_eq!(replay.move_count(), 0);
+        assert_eq!(replay.position_after(0).stones().len(), 1);
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use super::info::is_pass;
+use super::point::{parse_point_list, Point, Region, POINT_LISTS};
+use super::vertex::{GameTree, Node, Sequence};
+
+// a point that has no place on the board a game is being moved to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misfit {
+    pub path: Vec<usize>,
+    pub ident: String,
+    pub point: Point,
+}
+
+impl fmt::Display for Misfit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|i| i.to_string()).collect();
+        write!(f, "{}[{}] at node [{}]", self.ident, self.point, path.join(","))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // the target size and every point that wouldn't be on it
+    DoesNotFit((usize, usize), Vec<Misfit>),
+    // games that can't be combined without reinterpreting coordinates
+    SizeMismatch((usize, usize), (usize, usize)),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DoesNotFit((cols, rows), misfits) => {
+                let list: Vec<String> = misfits.iter().map(|m| m.to_string()).collect();
+                write!(f, "{} point(s) don't fit on {}x{}: {}", misfits.len(), cols, rows, list.join(", "))
+            },
+            Error::SizeMismatch((c1, r1), (c2, r2)) => write!(f, "board sizes differ: {}x{} and {}x{}", c1, r1, c2, r2),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// every point a node refers to, with the property it is in. passes
+// aren't points
+fn node_points(node: &Node, cols: usize, rows: usize) -> Vec<(&str, Point)> {
+    let mut points = Vec::new();
+    for prop in &node.props {
+        let ident = prop.ident.as_str();
+        match ident {
+            "B" | "W" => {
+                if let Some(p) = prop.values.first().filter(|v| !is_pass(v, cols, rows)).and_then(|v| Point::from_sgf(v)) {
+                    points.push((ident, p));
+                }
+            },
+            "LB" | "AR" | "LN" => {
+                for value in &prop.values {
+                    let Some((a, b)) = value.split_once(':') else { continue };
+                    points.extend(Point::from_sgf(a).map(|p| (ident, p)));
+                    if ident != "LB" {
+                        points.extend(Point::from_sgf(b).map(|p| (ident, p)));
+                    }
+                }
+            },
+            _ if POINT_LISTS.contains(&ident) => {
+                points.extend(parse_point_list(&prop.values).into_iter().map(|p| (ident, p)));
+            },
+            _ => {},
+        }
+    }
+    points
+}
+
+impl GameTree {
+    // the points that fail the check, in tree order
+    fn misfits_by<F: Fn(Point) -> bool>(&self, fits: F) -> Vec<Misfit> {
+        let (cols, rows) = self.board_size();
+        let mut misfits = Vec::new();
+        for (path, node) in self.nodes() {
+            for (ident, point) in node_points(node, cols, rows) {
+                if !fits(point) {
+                    misfits.push(Misfit {
+                        path: path.clone(),
+                        ident: ident.to_string(),
+                        point,
+                    });
+                }
+            }
+        }
+        misfits
+    }
+
+    // the points that wouldn't be on a cols x rows board
+    pub fn misfits(&self, cols: usize, rows: usize) -> Vec<Misfit> {
+        self.misfits_by(|p| p.on_board(cols, rows))
+    }
+
+    // moves the game to a board of another size, keeping every point
+    // where it is relative to the top left corner. fails listing the
+    // points that would be off the board instead of dropping them. tt
+    // passes become B[] and W[], since tt is a real point past 19x19
+    pub fn resize(&self, cols: usize, rows: usize) -> Result<Self> {
+        if !(1..=52).contains(&cols) || !(1..=52).contains(&rows) {
+            return Err(Error::DoesNotFit((cols, rows), Vec::new()));
+        }
+        let misfits = self.misfits(cols, rows);
+        if !misfits.is_empty() {
+            return Err(Error::DoesNotFit((cols, rows), misfits));
+        }
+        let size = self.board_size();
+        let mut gt = explicit_passes(self, size);
+        let sz = if cols == rows { cols.to_string() } else { format!("{}:{}", cols, rows) };
+        gt.root_mut().set("SZ", vec![sz]);
+        Ok(gt)
+    }
+
+    // crop that refuses to drop anything outside the region
+    pub fn crop_exact(&self, region: Region) -> Result<Self> {
+        let misfits = self.misfits_by(|p| region.contains(p));
+        if !misfits.is_empty() {
+            return Err(Error::DoesNotFit((region.cols(), region.rows()), misfits));
+        }
+        Ok(self.crop(region))
+    }
+
+    // whether the moves of other can be put in this game as they are
+    pub fn check_same_size(&self, other: &GameTree) -> Result<()> {
+        let (a, b) = (self.board_size(), other.board_size());
+        if a != b {
+            return Err(Error::SizeMismatch(a, b));
+        }
+        Ok(())
+    }
+}
+
+fn explicit_passes(gt: &GameTree, size: (usize, usize)) -> GameTree {
+    let mut nodes = gt.sequence.nodes.clone();
+    for node in &mut nodes {
+        for prop in &mut node.props {
+            if (prop.ident == "B" || prop.ident == "W") && prop.values.first().is_some_and(|v| is_pass(v, size.0, size.1)) {
+                prop.values = vec![String::new()];
+            }
+        }
+    }
+    GameTree {
+        sequence: Sequence { nodes },
+        gametrees: gt.gametrees.iter().map(|child| Box::new(explicit_passes(child, size))).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn resize1() {
+        let gt = gametree("(;SZ[19]AB[aa];B[cc];W[tt](;B[ss]LB[rr:x])(;B[dd]))");
+        assert_eq!(gt.resize(21, 21).unwrap().to_string(), "(;SZ[21]AB[aa];B[cc];W[](;B[ss]LB[rr:x])(;B[dd]))");
+        let err = gt.resize(13, 13).unwrap_err();
+        let Error::DoesNotFit(_, misfits) = &err else { panic!() };
+        assert_eq!(misfits.len(), 2);
+        assert_eq!(misfits[0].path, vec![0, 0, 0]);
+        assert_eq!(err.to_string(), "2 point(s) don't fit on 13x13: B[ss] at node [0,0,0], LB[rr] at node [0,0,0]");
+    }
+
+    #[test]
+    fn resize2() {
+        let gt = gametree("(;SZ[19]AB[pc];B[qd])");
+        let region = Region::parse("oa:si").unwrap();
+        assert_eq!(gt.crop_exact(region).unwrap().to_string(), "(;SZ[5:9]AB[bc];B[cd])");
+        assert!(gt.crop_exact(Region::parse("qa:si").unwrap()).is_err());
+        assert!(gt.check_same_size(&gametree("(;SZ[9])")).is_err());
+        assert!(gt.check_same_size(&gametree("(;B[aa])")).is_ok());
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+
+#[cfg(feature = "board")]
+use super::life::Estimate;
+#[cfg(feature = "board")]
+use super::replay::Replay;
+use super::vertex::{Color, GameTree, Node, Sequence};
+
+// the private property with_score_diffs writes gains to
+pub const DIFF_PROPERTY: &str = "DIFF";
+
+// where with_score_diffs puts each move's gain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffTarget {
+    // a line added to the move's comment
+    Comment,
+    // DIFF[-3.5] on the move node, for tools to read back
+    Property,
+}
+
+// how much a main line move changed the score, for the player who made
+// it: negative is a loss
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveDiff {
+    pub number: usize,
+    pub color: Color,
+    pub path: Vec<usize>,
+    pub gain: f64,
+}
+
+// the score with black ahead positive before the first move and after
+// every main line move, from the rough estimate of the final position
+// heuristic. good for spotting big swings like dropped groups, not for
+// judging close moves
+#[cfg(feature = "board")]
+pub fn estimated_scores(gt: &GameTree) -> Vec<f64> {
+    let komi = gt.root().value("KM").and_then(|k| k.trim().parse().ok()).unwrap_or(0.0);
+    let replay = Replay::new(gt);
+    (0..=replay.move_count()).map(|n| Estimate::new(&replay.position_after(n)).score(komi)).collect()
+}
+
+// scores supplied on the nodes in V, which FF[4] defines as the
+// estimated score with black ahead positive. the first entry is the
+// last V before the first move
+pub fn node_scores(gt: &GameTree) -> Vec<Option<f64>> {
+    let mut scores = vec![None];
+    for node in gt.mainline() {
+        if node.get_move().is_some() {
+            scores.push(None);
+        }
+        if let Some(v) = node.value("V").and_then(|v| v.trim().parse().ok()) {
+            *scores.last_mut().unwrap() = Some(v);
+        }
+    }
+    scores
+}
+
+// the gain of every main line move given the score after each one as
+// above. moves with no score on either side are skipped
+pub fn score_diffs(gt: &GameTree, scores: &[Option<f64>]) -> Vec<MoveDiff> {
+    let mut diffs = Vec::new();
+    let mut number = 0;
+    for (i, node) in gt.mainline().into_iter().enumerate() {
+        let Some((color, _)) = node.get_move() else { continue };
+        number += 1;
+        let (Some(Some(before)), Some(Some(after))) = (scores.get(number - 1), scores.get(number)) else { continue };
+        let gain = match color {
+            Color::Black => after - before,
+            Color::White => before - after,
+        };
+        diffs.push(MoveDiff {
+            number,
+            color,
+            path: vec![0; i],
+            gain,
+        });
+    }
+    diffs
+}
+
+impl GameTree {
+    // writes each move's gain into its node. DIFF replaces the value of
+    // an earlier run, comment lines are added after what is there
+    pub fn with_score_diffs(&self, diffs: &[MoveDiff], target: DiffTarget) -> Self {
+        let mut nodes: Vec<_> = self.mainline().into_iter().cloned().collect();
+        for diff in diffs {
+            let node = &mut nodes[diff.path.len()];
+            let text = format!("{:+.1}", diff.gain);
+            match target {
+                DiffTarget::Property => node.set(DIFF_PROPERTY, vec![text]),
+                DiffTarget::Comment => {
+                    let line = format!("{} {}: {}", diff.color.ident(), diff.number, text);
+                    let comment = match node.value("C") {
+                        Some(c) if !c.is_empty() => format!("{}\n{}", c, line),
+                        _ => line,
+                    };
+                    node.set("C", vec![comment]);
+                },
+            }
+        }
+        with_mainline(self, &mut nodes.into_iter())
+    }
+}
+
+// the tree with its main line nodes swapped for the given ones
+fn with_mainline(gt: &GameTree, nodes: &mut impl Iterator<Item = Node>) -> GameTree {
+    let sequence = Sequence {
+        nodes: gt.sequence.nodes.iter().map(|_| nodes.next().unwrap()).collect(),
+    };
+    let mut gametrees = gt.gametrees.clone();
+    if let Some(first) = gametrees.first_mut() {
+        **first = with_mainline(first, nodes);
+    }
+    GameTree { sequence, gametrees }
+}
+
+// the moves that lost at least threshold points, worst first
+pub fn losses(diffs: &[MoveDiff], threshold: f64) -> Vec<&MoveDiff> {
+    let mut losses: Vec<&MoveDiff> = diffs.iter().filter(|d| d.gain <= -threshold).collect();
+    losses.sort_by(|a, b| a.gain.total_cmp(&b.gain));
+    losses
+}
+
+// the move annotations a summary picks up, in the order it lists them
+pub const MOVE_MARKS: [&str; 4] = ["BM", "DO", "IT", "TE"];
+
+// why a move made the summary
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    // one of MOVE_MARKS, with BM and TE's emphasis of 1 or 2
+    Marked(&'static str, u8),
+    // the points the move lost
+    Loss(f64),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Marked("BM", 2) => write!(f, "very bad move"),
+            Reason::Marked("BM", _) => write!(f, "bad move"),
+            Reason::Marked("DO", _) => write!(f, "doubtful"),
+            Reason::Marked("IT", _) => write!(f, "interesting"),
+            Reason::Marked("TE", 2) => write!(f, "very good move"),
+            Reason::Marked(_, _) => write!(f, "tesuji"),
+            Reason::Loss(points) => write!(f, "lost {:.1}", -points),
+        }
+    }
+}
+
+// a main line move worth a second look
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flagged {
+    pub number: usize,
+    pub color: Color,
+    pub path: Vec<usize>,
+    pub reasons: Vec<Reason>,
+}
+
+// the main line moves carrying a move annotation or losing at least
+// threshold points by diffs, in move order
+pub fn flagged_moves(gt: &GameTree, diffs: &[MoveDiff], threshold: f64) -> Vec<Flagged> {
+    let mut flagged = Vec::new();
+    let mut number = 0;
+    for (i, node) in gt.mainline().into_iter().enumerate() {
+        let Some((color, _)) = node.get_move() else { continue };
+        number += 1;
+        let mut reasons: Vec<Reason> = MOVE_MARKS
+            .iter()
+            .filter_map(|ident| {
+                let value = node.value(ident)?;
+                Some(Reason::Marked(ident, if value.trim() == "2" { 2 } else { 1 }))
+            })
+            .collect();
+        if let Some(diff) = diffs.iter().find(|d| d.number == number && d.gain <= -threshold) {
+            reasons.push(Reason::Loss(diff.gain));
+        }
+        if !reasons.is_empty() {
+            flagged.push(Flagged {
+                number,
+                color,
+                path: vec![0; i],
+                reasons,
+            });
+        }
+    }
+    flagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn diff1() {
+        let gt = gametree("(;V[0.5];B[pd]V[1];W[dd]C[hm]V[-4];B[dp];W[pp]V[-4.5](;B[qq])(;B[qc]))");
+        let scores = node_scores(&gt);
+        assert_eq!(scores, vec![Some(0.5), Some(1.0), Some(-4.0), None, Some(-4.5), None]);
+        let diffs = score_diffs(&gt, &scores);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!((diffs[1].number, diffs[1].color, diffs[1].gain), (2, Color::White, 5.0));
+        let gt2 = gt.with_score_diffs(&diffs, DiffTarget::Comment);
+        assert_eq!(gt2.to_string(), "(;V[0.5];B[pd]V[1]C[B 1: +0.5];W[dd]C[hm\nW 2: +5.0]V[-4];B[dp];W[pp]V[-4.5](;B[qq])(;B[qc]))");
+        let scores = vec![Some(0.0), Some(-10.0), Some(-9.0)];
+        let diffs = score_diffs(&gt, &scores);
+        let gt3 = gt.with_score_diffs(&diffs, DiffTarget::Property);
+        assert_eq!(gt3.mainline()[1].value(DIFF_PROPERTY), Some("-10.0"));
+        let worst = losses(&diffs, 5.0);
+        assert_eq!(worst.len(), 1);
+        assert_eq!(worst[0].number, 1);
+    }
+
+    #[test]
+    fn flagged1() {
+        let gt = gametree("(;B[pd]V[0];W[dd]BM[2]V[6];B[dp]TE[1]V[6];W[pp]DO[](;B[qq]BM[1]))");
+        let diffs = score_diffs(&gt, &node_scores(&gt));
+        let flagged = flagged_moves(&gt, &diffs, 3.0);
+        let numbers: Vec<usize> = flagged.iter().map(|f| f.number).collect();
+        assert_eq!(numbers, vec![2, 3, 4, 5]);
+        assert_eq!(flagged[0].reasons, vec![Reason::Marked("BM", 2), Reason::Loss(-6.0)]);
+        assert_eq!(flagged[0].reasons[1].to_string(), "lost 6.0");
+        assert_eq!(flagged[3].path, vec![0, 0, 0, 0]);
+        assert_eq!(flagged[3].reasons[0].to_string(), "bad move");
+    }
+
+    #[test]
+    #[cfg(feature = "board")]
+    fn diff2() {
+        let gt = gametree("(;SZ[5]KM[0.5]AB[ba][bb][bc][bd][be][ab][ad][ae][ca][cb][cc][cd][ce]AW[db][dc][dd][de];W[ea];B[eb])");
+        let scores = estimated_scores(&gt);
+        assert_eq!(scores.len(), 3);
+        // black's two eyes are all that is settled
+        assert_eq!(scores[0], 15.0 - 4.0 - 0.5);
+    }
+}
@@ -0,0 +1,227 @@
+use std::ops::Range;
+
+use super::parser::Parser;
+use super::properties;
+use super::validate::{self, Severity};
+use super::vertex::GameTree;
+
+// the root properties that open nearly every real game, used to tell a
+// new game from a variation when the game before it never closed
+const ROOT_IDENTS: [&str; 6] = ["FF", "GM", "SZ", "CA", "AP", "ST"];
+
+// a game found in damaged data. span is where its text was, in bytes
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    pub span: Range<usize>,
+    pub gametree: GameTree,
+    // the game was cut off and closed here, dropping its last unfinished
+    // property
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Salvage {
+    pub games: Vec<Recovered>,
+    // candidates that didn't parse or validate, with the reason
+    pub lost: Vec<(Range<usize>, String)>,
+}
+
+// whether a game starts at i: "(" and ";", maybe with whitespace between.
+// inside another game only ones opening with a root property count
+fn game_start(data: &[u8], i: usize, top_level: bool) -> bool {
+    let mut rest = data[i..].iter().copied();
+    if rest.next() != Some(b'(') {
+        return false;
+    }
+    let mut rest = rest.skip_while(|b| b.is_ascii_whitespace());
+    if rest.next() != Some(b';') {
+        return false;
+    }
+    if top_level {
+        return true;
+    }
+    let ident: Vec<u8> = rest.skip_while(|b| b.is_ascii_whitespace()).take_while(|b| b.is_ascii_uppercase()).collect();
+    ROOT_IDENTS.iter().any(|r| r.as_bytes() == ident.as_slice())
+}
+
+// whether the value opening at i, a "[", is free text. that of an
+// unknown property is taken to be
+fn text_value(data: &[u8], i: usize, text: bool) -> bool {
+    let before = data[..i].iter().rposition(|b| !b.is_ascii_whitespace());
+    let Some(end) = before else { return text };
+    if data[end] == b']' {
+        // another value of the same property
+        return text;
+    }
+    let start = data[..=end].iter().rposition(|b| !b.is_ascii_alphabetic()).map_or(0, |s| s + 1);
+    let ident = String::from_utf8_lossy(&data[start..=end]);
+    properties::describe(&ident).is_none_or(|p| p.value_type.contains("Text"))
+}
+
+// the end of the game starting at start, and whether its brackets
+// closed. an unclosed game ends where the next one starts, even inside a
+// point or number, since a damaged "]" would otherwise swallow everything
+// after it. a start inside text, like a comment quoting a game, is only
+// given back as the third value: the text may be quoting or it may have
+// lost its "]", which only parsing can tell
+fn candidate(data: &[u8], start: usize) -> (usize, bool, Option<usize>) {
+    let mut depth = 0;
+    let mut in_value = false;
+    let mut text = false;
+    let mut escaped = false;
+    let mut split = None;
+    for (i, &b) in data.iter().enumerate().skip(start) {
+        if in_value {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b']' {
+                in_value = false;
+            } else if game_start(data, i, false) {
+                if !text {
+                    return (i, false, split);
+                }
+                split.get_or_insert(i);
+            }
+            continue;
+        }
+        if i > start && game_start(data, i, false) {
+            return (i, false, split);
+        }
+        match b {
+            b'[' => {
+                in_value = true;
+                text = text_value(data, i, text);
+            },
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return (i + 1, true, split);
+                }
+            },
+            _ => {},
+        }
+    }
+    (data.len(), false, split)
+}
+
+// closes a game cut off anywhere: everything after the last complete
+// value goes, then the open gametrees are closed
+fn repair(text: &str) -> Option<String> {
+    let mut depth = 0;
+    let mut in_value = false;
+    let mut escaped = false;
+    let mut last = None;
+    for (i, c) in text.char_indices() {
+        if in_value {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == ']' {
+                in_value = false;
+                last = Some((i + 1, depth));
+            }
+            continue;
+        }
+        match c {
+            '[' => in_value = true,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                last = Some((i + 1, depth));
+            },
+            _ => {},
+        }
+    }
+    let (end, depth) = last.filter(|(_, depth)| *depth > 0)?;
+    Some(format!("{}{}", &text[..end], ")".repeat(depth)))
+}
+
+// the game in data, and whether it had to be repaired
+fn recover(data: &[u8], closed: bool) -> Result<(GameTree, bool), String> {
+    let text = String::from_utf8_lossy(data);
+    if closed {
+        parse(&text).map(|gt| (gt, false))
+    } else {
+        repair(&text).ok_or_else(|| "nothing complete to keep".to_string()).and_then(|t| parse(&t)).map(|gt| (gt, true))
+    }
+}
+
+fn parse(text: &str) -> Result<GameTree, String> {
+    let mut coll = Parser::new(text).parse().map_err(|e| e.to_string())?;
+    if coll.gametrees.len() != 1 {
+        return Err(format!("{} games where one was expected", coll.gametrees.len()));
+    }
+    let gt = coll.gametrees.remove(0);
+    match validate::validate_gametree(0, &gt).into_iter().find(|d| d.severity == Severity::Error) {
+        Some(d) => Err(d.to_string()),
+        None => Ok(gt),
+    }
+}
+
+// scans bytes of any encoding for games and keeps the ones that parse and
+// validate on their own. bytes that aren't utf-8 become U+FFFD in values
+pub fn salvage(data: &[u8]) -> Salvage {
+    let mut found = Salvage::default();
+    let mut i = 0;
+    while i < data.len() {
+        if !game_start(data, i, true) {
+            i += 1;
+            continue;
+        }
+        let (mut end, closed, split) = candidate(data, i);
+        let mut result = recover(&data[i..end], closed);
+        // a game that only makes sense cut where another starts in one of
+        // its values had that value broken off
+        if let (Err(_), Some(split)) = (&result, split) {
+            end = split;
+            result = recover(&data[i..end], false);
+        }
+        match result {
+            Ok((gametree, repaired)) => found.games.push(Recovered {
+                span: i..end,
+                gametree,
+                repaired,
+            }),
+            Err(e) => found.lost.push((i..end, e)),
+        }
+        i = end;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn salvage1() {
+        let mut data = b"\x00\xff junk (;FF[4]GM[1]C[caf\xe9];B[pd](;W[dd])(;W[dp]))".to_vec();
+        // cut off inside a value, and the next game right after the damage
+        data.extend_from_slice(b"\n(;GM[1]SZ[19];B[aa];W[b\xff\xfe(;FF[4];B[cc]C[ok])");
+        data.extend_from_slice(b"(;SZ[9];B)garbage(;GM[1];W[qq]");
+        let found = salvage(&data);
+        let games: Vec<String> = found.games.iter().map(|g| g.gametree.to_string()).collect();
+        assert_eq!(
+            games,
+            vec![
+                "(;FF[4]GM[1]C[caf\u{fffd}];B[pd](;W[dd])(;W[dp]))",
+                "(;GM[1]SZ[19];B[aa])",
+                "(;FF[4];B[cc]C[ok])",
+                "(;GM[1];W[qq])"
+            ]
+        );
+        assert_eq!(found.games.iter().map(|g| g.repaired).collect::<Vec<_>>(), vec![false, true, false, true]);
+        assert_eq!(&data[found.games[2].span.clone()], b"(;FF[4];B[cc]C[ok])");
+        // B without a value
+        assert_eq!(found.lost.len(), 1);
+        assert!(salvage(b"(;").games.is_empty());
+        // games quoted in comments stay in them
+        let quoted = salvage(b"(;GM[1]C[see (;GM[1\\]SZ[9\\] for this];B[aa])(;GM[1]C[(;GM[1];B[bb])");
+        let games: Vec<String> = quoted.games.iter().map(|g| g.gametree.to_string()).collect();
+        assert_eq!(games, vec!["(;GM[1]C[see (;GM[1\\]SZ[9\\] for this];B[aa])", "(;GM[1]C[(;GM[1];B[bb])"]);
+    }
+}
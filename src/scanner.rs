@@ -1,5 +1,8 @@
 use std::fmt;
 
+use super::borrowed::normalize_value;
+use super::codes;
+
 #[derive(Debug)]
 pub enum Error {
     ScanError(String),
@@ -16,6 +19,16 @@ impl fmt::Display for Error {
     }
 }
 
+impl Error {
+    // scanning only fails on numbers, so far
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ScanError(_) => codes::NUMBER_TOO_LARGE,
+            Error::Eof => codes::UNTERMINATED_VALUE,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 impl From<std::num::ParseIntError> for Error {
@@ -63,6 +76,10 @@ pub enum Token {
     Integer(Position, u64),
 
     Escaped(Position, String),
+    // everything between [ and ], spelled as the tokens above would
+    // spell it: escapes kept, whitespace runs as one space, newline runs
+    // as one newline and numbers without leading zeros
+    Value(Position, String),
 
     Ascii(Position, String),
     Bytes(Position, String),
@@ -90,6 +107,7 @@ impl Token {
             Token::Float(pos, _) => *pos,
             Token::Integer(pos, _) => *pos,
             Token::Escaped(pos, _) => *pos,
+            Token::Value(pos, _) => *pos,
             Token::Ascii(pos, _) => *pos,
             Token::Bytes(pos, _) => *pos,
         }
@@ -101,7 +119,7 @@ impl fmt::Display for Token {
         match self {
             Token::Eof => write!(f, ""),
             Token::Whitespace => write!(f, " "),
-            Token::Newline(_) => write!(f, "\n"),
+            Token::Newline(_) => writeln!(f),
             Token::Identifier(_, s) => write!(f, "{}", s),
             Token::UcLetter(_, s) => write!(f, "{}", s),
             Token::OpenParen(_) => write!(f, "("),
@@ -112,6 +130,7 @@ impl fmt::Display for Token {
             Token::Float(_, d) => write!(f, "{}", d),
             Token::Integer(_, i) => write!(f, "{}", i),
             Token::Escaped(_, s) => write!(f, "\\{}", s),
+            Token::Value(_, s) => write!(f, "{}", s),
             Token::Ascii(_, s) => write!(f, "{}", s),
             Token::Bytes(_, s) => write!(f, "{}", s),
         }
@@ -122,6 +141,17 @@ pub struct Scanner {
     input: Vec<char>,
     cur: usize,
     pos: Position,
+    // values are only scanned whole once the first game has started, so
+    // a stray '[' in the junk some servers put before it is harmless
+    in_game: bool,
+    // just past a '['
+    in_value: bool,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Scanner::new("")
+    }
 }
 
 impl Scanner {
@@ -131,33 +161,69 @@ impl Scanner {
             input: data.chars().collect(),
             cur: 0,
             pos: Position {row: 1, col: 0},
+            in_game: false,
+            in_value: false,
         }
     }
 
+    // starts over on new text, reusing the buffer of the last one
+    pub fn reset(&mut self, data: &str) {
+        self.input.clear();
+        self.input.extend(data.chars());
+        self.cur = 0;
+        self.pos = Position {row: 1, col: 0};
+        self.in_game = false;
+        self.in_value = false;
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub fn scan(&mut self) -> Result<Vec<Token>> {
-        let mut tokens = vec![];
+        let mut tokens = Vec::new();
+        self.scan_into(&mut tokens)?;
+        Ok(tokens)
+    }
+
+    // scan into a buffer the caller keeps, which is cleared first, so a
+    // batch run can reuse one token buffer for every file
+    pub fn scan_into(&mut self, tokens: &mut Vec<Token>) -> Result<()> {
+        tokens.clear();
+        // about one token for every three characters of a typical game
+        tokens.reserve(self.input.len() / 3);
         loop {
             match self.scan_token() {
                 Ok(Token::Eof) => break,
-                //Ok(Token::Whitespace) | Ok(Token::Newline(_)) => continue,
                 Ok(tok) => tokens.push(tok),
                 Err(e) => return Err(self.create_error(e.to_string())),
             }
         }
-        Ok(tokens)
+        trace!(tokens = tokens.len(), "scanned");
+        Ok(())
     }
 
     pub fn scan_token(&mut self) -> Result<Token> {
+        if self.in_value {
+            self.in_value = false;
+            // a NUL in a value is a character, not the end of the input
+            if self.input.get(self.cur).is_some_and(|c| *c != ']') {
+                return self.scan_value();
+            }
+        }
         // this should be comprehensive
-        let token = match self.peek(0) {
+        match self.peek() {
             '\0' => Ok(Token::Eof),
             ' ' | '\t' | '\r' => self.scan_whitespace(),
             '\n' => self.scan_newlines(),
             '\\' => self.scan_escaped(),
-            '(' => self.create_token(Token::OpenParen(self.pos)),
+            '(' => {
+                self.in_game = true;
+                self.create_token(Token::OpenParen(self.pos))
+            },
             ')' => self.create_token(Token::CloseParen(self.pos)),
 
-            '[' => self.create_token(Token::OpenSquare(self.pos)),
+            '[' => {
+                self.in_value = self.in_game;
+                self.create_token(Token::OpenSquare(self.pos))
+            },
             ']' => self.create_token(Token::CloseSquare(self.pos)),
 
             '0'..='9' => self.scan_number(),
@@ -165,141 +231,131 @@ impl Scanner {
             ';' => self.create_token(Token::Semicolon(self.pos)),
             '\u{20}'..='\u{7e}' => self.scan_ascii(),
             _ => self.scan_bytes(),
-            //c => Err(self.create_error(format!("invalid character: {}", c))),
-        };
-        token
+        }
     }
 
-    pub fn create_error(&mut self, msg: String) -> Error {
+    fn create_error(&mut self, msg: String) -> Error {
         Error::ScanError(format!("scan_error at {}: {}", self.pos, msg))
     }
 
-    pub fn create_token(&mut self, tok: Token) -> Result<Token> {
+    fn create_token(&mut self, tok: Token) -> Result<Token> {
         self.read();
         Ok(tok)
     }
 
-    pub fn peek(&mut self, n: usize) -> char {
-        if self.cur < self.input.len() - n {
-            self.input[self.cur + n]
-        } else {
-            '\0'
-        }
+    fn peek(&self) -> char {
+        self.input.get(self.cur).copied().unwrap_or('\0')
     }
 
-    pub fn read(&mut self) -> char {
-        let ret = self.cur;
-
-        if ret >= self.input.len() {
+    fn read(&mut self) -> char {
+        let Some(&c) = self.input.get(self.cur) else {
             self.cur += 1;
             return '\0';
-        }
-
-        if self.input[ret] == '\n' {
+        };
+        if c == '\n' {
             self.pos.row += 1;
             self.pos.col = 0;
         } else {
             self.pos.col += 1;
         }
-
         self.cur += 1;
-        self.input[ret]
+        c
     }
 
-    pub fn scan_whitespace(&mut self) -> Result<Token> {
-        loop {
-            match self.peek(0) {
-                ' ' | '\t' | '\r' => self.read(),
-                _ => break,
-            };
-        }
+    // moves past the run of characters from cur that match, none of
+    // which may be a newline, and returns where it started
+    fn skip_run<F: Fn(char) -> bool>(&mut self, matches: F) -> usize {
+        let start = self.cur;
+        let len = self.input[start..].iter().take_while(|c| matches(**c)).count();
+        self.cur += len;
+        self.pos.col += len as u32;
+        start
+    }
+
+    fn text(&self, start: usize) -> String {
+        self.input[start..self.cur].iter().collect()
+    }
+
+    fn scan_whitespace(&mut self) -> Result<Token> {
+        self.skip_run(|c| matches!(c, ' ' | '\t' | '\r'));
         Ok(Token::Whitespace)
     }
 
-    pub fn scan_newlines(&mut self) -> Result<Token> {
-        loop {
-            match self.peek(0) {
-                '\n' => self.read(),
-                _ => break,
-            };
-        };
+    fn scan_newlines(&mut self) -> Result<Token> {
+        let len = self.input[self.cur..].iter().take_while(|c| **c == '\n').count();
+        self.cur += len;
+        self.pos.row += len as u32;
+        self.pos.col = 0;
         Ok(Token::Newline(self.pos))
     }
 
-    pub fn scan_escaped(&mut self) -> Result<Token> {
+    fn scan_escaped(&mut self) -> Result<Token> {
         self.read();
-        let mut char_vec: Vec<char> = Vec::new();
-        char_vec.push(self.read());
-        let s: String = char_vec.into_iter().collect();
-        Ok(Token::Escaped(self.pos, s))
+        let c = self.read();
+        Ok(Token::Escaped(self.pos, c.to_string()))
     }
 
-    pub fn scan_ascii(&mut self) -> Result<Token> {
-        let mut char_vec: Vec<char> = Vec::new();
-        char_vec.push(self.read());
-        let s: String = char_vec.into_iter().collect();
-        Ok(Token::Ascii(self.pos, s))
+    // the whole value up to its ']' as one token, spelled the way
+    // borrowed::Parser gives it when normalized
+    fn scan_value(&mut self) -> Result<Token> {
+        let pos = self.pos;
+        let start = self.cur;
+        let mut escaped = false;
+        while let Some(&c) = self.input.get(self.cur) {
+            if c == ']' && !escaped {
+                break;
+            }
+            escaped = c == '\\' && !escaped;
+            self.read();
+        }
+        let value = normalize_value(&self.text(start)).map_err(Error::ScanError)?.into_owned();
+        Ok(Token::Value(pos, value))
     }
 
-    pub fn scan_bytes(&mut self) -> Result<Token> {
-        let mut char_vec: Vec<char> = Vec::new();
-        char_vec.push(self.read());
-        let s: String = char_vec.into_iter().collect();
-        Ok(Token::Bytes(self.pos, s))
+    // value text is mostly runs of characters with no meaning to the
+    // parser, so each run is one token rather than one per character
+    fn scan_ascii(&mut self) -> Result<Token> {
+        let start = self.skip_run(is_plain_ascii);
+        Ok(Token::Ascii(self.pos, self.text(start)))
     }
 
-    pub fn scan_number(&mut self) -> Result<Token> {
-        let mut char_vec: Vec<char> = Vec::new();
-        loop {
-            match self.peek(0) {
-                '0'..='9' => char_vec.push(self.read()),
-                _ => break,
-            }
-        }
-        let s: String = char_vec.into_iter().collect();
-        let n: u64 = s.parse()?;
+    fn scan_bytes(&mut self) -> Result<Token> {
+        let start = self.skip_run(|c| !c.is_ascii() || (c.is_ascii_control() && !matches!(c, '\0' | '\t' | '\r' | '\n')));
+        Ok(Token::Bytes(self.pos, self.text(start)))
+    }
+
+    fn scan_number(&mut self) -> Result<Token> {
+        let start = self.skip_run(is_digit);
+        let n: u64 = self.text(start).parse()?;
         Ok(Token::Integer(self.pos, n))
     }
 
-    pub fn scan_identifier(&mut self) -> Result<Token> {
-        let mut upper = true;
-        let mut char_vec: Vec<char> = Vec::new();
-        loop {
-            let c = self.peek(0);
-            if is_identifier(c) {
-                if c < 'A' || c > 'Z' {
-                    upper = false;
-                }
-
-                char_vec.push(c);
-                self.read();
-            } else {
-                break;
-            }
-        }
-        let s: String = char_vec.into_iter().collect();
-        match s.as_str() {
-            _ => {
-                if upper {
-                    return Ok(Token::UcLetter(self.pos, s));
-                } else {
-                    return Ok(Token::Identifier(self.pos, s));
-                }
-            },
+    fn scan_identifier(&mut self) -> Result<Token> {
+        let start = self.skip_run(is_identifier);
+        let s = self.text(start);
+        if s.chars().all(|c| c.is_ascii_uppercase()) {
+            Ok(Token::UcLetter(self.pos, s))
+        } else {
+            Ok(Token::Identifier(self.pos, s))
         }
     }
 }
 
+// printable ascii that doesn't start any other token
+fn is_plain_ascii(c: char) -> bool {
+    matches!(c, '\u{21}'..='\u{7e}') && !matches!(c, '\\' | '(' | ')' | '[' | ']' | ';') && !is_identifier(c)
+}
+
 fn is_digit(c: char) -> bool {
-    return c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
 fn is_identifier_start(c: char) -> bool {
-    return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+    c.is_ascii_alphabetic() || c == '_'
 }
 
 fn is_identifier(c: char) -> bool {
-    return is_digit(c) || is_identifier_start(c)
+    is_digit(c) || is_identifier_start(c)
 }
 
 #[cfg(test)]
@@ -418,4 +474,17 @@ KM[375]HA[0]RU[Chinese]AP[GNU Go:3.8]RN[3]RE[B+R]TM[1200]TC[3]TT[60]AP[foxwq]RL[
         let _ = Scanner::new(text).scan().unwrap();
     }
 
+    #[test]
+    fn scan9() {
+        let mut tokens = Vec::new();
+        Scanner::new("[x (;C[a  007\\]b\n\n] )").scan_into(&mut tokens).unwrap();
+        // junk before the game is scanned as before, values whole
+        assert_eq!(tokens[0], Token::OpenSquare(Position {row: 1, col: 0}));
+        assert!(matches!(&tokens[1], Token::Identifier(_, s) if s == "x"));
+        assert!(matches!(&tokens[7], Token::Value(_, s) if s == "a 7\\]b\n"));
+        let n = tokens.len();
+        Scanner::new("(;B[])").scan_into(&mut tokens).unwrap();
+        assert!(tokens.len() < n);
+        assert!(matches!(tokens[4], Token::CloseSquare(_)));
+    }
 }
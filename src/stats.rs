@@ -0,0 +1,168 @@
+use std::fmt;
+
+use super::vertex::GameTree;
+
+// the shape of a game tree or of one branch of it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub nodes: usize,
+    pub moves: usize,
+    pub comments: usize,
+    // ends of variations
+    pub leaves: usize,
+    // nodes on the longest path
+    pub depth: usize,
+    // nodes at each depth, the root being depth 0
+    pub width: Vec<usize>,
+}
+
+impl TreeStats {
+    fn add(&mut self, gt: &GameTree, depth: usize) {
+        for (i, node) in gt.sequence.nodes.iter().enumerate() {
+            let d = depth + i;
+            if self.width.len() <= d {
+                self.width.resize(d + 1, 0);
+            }
+            self.width[d] += 1;
+            self.nodes += 1;
+            if node.get_move().is_some() {
+                self.moves += 1;
+            }
+            if node.has("C") {
+                self.comments += 1;
+            }
+        }
+        let end = depth + gt.sequence.nodes.len();
+        if gt.gametrees.is_empty() {
+            self.leaves += 1;
+        }
+        for child in &gt.gametrees {
+            self.add(child, end);
+        }
+    }
+
+    pub fn of(gt: &GameTree) -> Self {
+        let mut stats = TreeStats::default();
+        stats.add(gt, 0);
+        stats.depth = stats.width.len();
+        stats
+    }
+
+    // commented nodes per node
+    pub fn comment_density(&self) -> f64 {
+        if self.nodes == 0 {
+            0.0
+        } else {
+            self.comments as f64 / self.nodes as f64
+        }
+    }
+}
+
+// one of the variations at the first fork in the tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    // the first move of the branch in SGF notation, if it starts with one
+    pub first_move: Option<String>,
+    pub stats: TreeStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub total: TreeStats,
+    // nodes before the first fork
+    pub trunk: usize,
+    pub branches: Vec<Branch>,
+}
+
+impl GameTree {
+    pub fn stats(&self) -> Stats {
+        // the first fork may come after sequences with a single child
+        let mut gt = self;
+        let mut trunk = gt.sequence.nodes.len();
+        while gt.gametrees.len() == 1 {
+            gt = &gt.gametrees[0];
+            trunk += gt.sequence.nodes.len();
+        }
+        let branches = gt
+            .gametrees
+            .iter()
+            .map(|child| Branch {
+                first_move: child.sequence.nodes.first().and_then(|n| n.get_move()).map(|(c, v)| format!("{}[{}]", c.ident(), v)),
+                stats: TreeStats::of(child),
+            })
+            .collect();
+        Stats {
+            total: TreeStats::of(self),
+            trunk,
+            branches,
+        }
+    }
+}
+
+impl fmt::Display for TreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} moves, depth {}, {} leaves, {} comments ({:.0}%)",
+            self.nodes,
+            self.moves,
+            self.depth,
+            self.leaves,
+            self.comments,
+            self.comment_density() * 100.0
+        )
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total: {}", self.total)?;
+        writeln!(f, "trunk: {} nodes", self.trunk)?;
+        if let Some((depth, width)) = self.total.width.iter().enumerate().max_by_key(|(d, w)| (**w, usize::MAX - d)) {
+            writeln!(f, "widest: {} nodes at depth {}", width, depth)?;
+        }
+        for (i, branch) in self.branches.iter().enumerate() {
+            let first = branch.first_move.as_deref().unwrap_or("-");
+            writeln!(f, "variation {} {}: {}", i, first, branch.stats)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn stats1() {
+        let gt = gametree("(;GM[1];B[aa](;W[bb]C[main];B[cc](;W[dd])(;W[ee]))(;W[cc]))");
+        let stats = gt.stats();
+        assert_eq!(stats.total.nodes, 7);
+        assert_eq!(stats.total.moves, 6);
+        assert_eq!(stats.total.depth, 5);
+        assert_eq!(stats.total.leaves, 3);
+        assert_eq!(stats.total.width, vec![1, 1, 2, 1, 2]);
+        assert_eq!(stats.trunk, 2);
+        assert_eq!(stats.branches.len(), 2);
+        assert_eq!(stats.branches[0].first_move.as_deref(), Some("W[bb]"));
+        assert_eq!(stats.branches[0].stats.nodes, 4);
+        assert_eq!(stats.branches[0].stats.depth, 3);
+        assert_eq!(stats.branches[0].stats.comments, 1);
+        assert_eq!(stats.branches[1].stats.depth, 1);
+    }
+
+    #[test]
+    fn stats2() {
+        let gt = gametree("(;GM[1];B[aa];W[bb])");
+        let stats = gt.stats();
+        assert!(stats.branches.is_empty());
+        assert_eq!(stats.trunk, 3);
+        assert_eq!(stats.total.depth, 3);
+        assert_eq!(stats.total.comment_density(), 0.0);
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use super::batch::filter_ascii;
+use super::parser::{self, Parser};
+#[cfg(feature = "encoding")]
+use super::recode;
+use super::vertex::GameTree;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    ParseError(parser::Error),
+    // the game had more bytes than the reader allows
+    TooLarge(usize),
+    // a game that isn't utf-8 and couldn't be decoded, when decoding
+    Undecodable(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::ParseError(e) => write!(f, "{}", e),
+            Error::TooLarge(n) => write!(f, "game larger than {} bytes", n),
+            Error::Undecodable(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<parser::Error> for Error {
+    fn from(err: parser::Error) -> Error {
+        Error::ParseError(err)
+    }
+}
+
+// splits a collection into its top level games without reading it all,
+// so only one game at a time has to fit in memory
+pub struct GameReader<R: Read> {
+    reader: BufReader<R>,
+    max_bytes: Option<usize>,
+    decode: bool,
+    done: bool,
+}
+
+impl<R: Read> GameReader<R> {
+    pub fn new(reader: R) -> Self {
+        GameReader {
+            reader: BufReader::new(reader),
+            max_bytes: None,
+            decode: false,
+            done: false,
+        }
+    }
+
+    // games bigger than this are skipped with Error::TooLarge
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    // games that aren't utf-8 are decoded from the encoding they were
+    // written in, with CA updated to match, instead of losing everything
+    // outside of ascii. games that can't be placed are Error::Undecodable
+    pub fn decoding(mut self) -> Self {
+        self.decode = true;
+        self
+    }
+
+    // the raw text of the next game, from its "(" to the matching ")".
+    // a game cut off by the end of input is returned as is, for the
+    // parser to complain about
+    pub fn next_text(&mut self) -> Result<Option<String>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut buf: Vec<u8> = Vec::new();
+        let mut started = false;
+        let mut too_large = false;
+        let mut depth = 0;
+        let mut in_value = false;
+        let mut escaped = false;
+        loop {
+            let chunk = self.reader.fill_buf()?;
+            if chunk.is_empty() {
+                self.done = true;
+                break;
+            }
+            let mut used = 0;
+            let mut finished = false;
+            for &b in chunk {
+                used += 1;
+                if !started {
+                    // garbage between games is skipped, like the parser does
+                    if b != b'(' {
+                        continue;
+                    }
+                    started = true;
+                }
+                if !too_large {
+                    buf.push(b);
+                    if let Some(max) = self.max_bytes {
+                        if buf.len() > max {
+                            too_large = true;
+                            buf = Vec::new();
+                        }
+                    }
+                }
+                if in_value {
+                    if escaped {
+                        escaped = false;
+                    } else if b == b'\\' {
+                        escaped = true;
+                    } else if b == b']' {
+                        in_value = false;
+                    }
+                    continue;
+                }
+                match b {
+                    b'[' => in_value = true,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            finished = true;
+                            break;
+                        }
+                    },
+                    _ => {},
+                }
+            }
+            self.reader.consume(used);
+            if finished {
+                break;
+            }
+        }
+        if too_large {
+            return Err(Error::TooLarge(self.max_bytes.unwrap_or(0)));
+        }
+        if !started {
+            return Ok(None);
+        }
+        let text = match String::from_utf8(buf) {
+            Ok(s) => s,
+            Err(e) if self.decode => decode(e.into_bytes())?,
+            Err(e) => filter_ascii(e.into_bytes()),
+        };
+        Ok(Some(text))
+    }
+
+    fn next_game(&mut self) -> Result<Option<GameTree>> {
+        let text = match self.next_text()? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let mut coll = Parser::new(&text).parse()?;
+        Ok(Some(coll.gametrees.remove(0)))
+    }
+}
+
+#[cfg(feature = "encoding")]
+fn decode(data: Vec<u8>) -> Result<String> {
+    let recoded = recode::recode(&data, None, encoding_rs::UTF_8).map_err(|e| Error::Undecodable(e.to_string()))?;
+    Ok(String::from_utf8(recoded.data).expect("recoded to utf-8"))
+}
+
+#[cfg(not(feature = "encoding"))]
+fn decode(_data: Vec<u8>) -> Result<String> {
+    Err(Error::Undecodable("not UTF-8, and built without the encoding feature".to_string()))
+}
+
+impl<R: Read> Iterator for GameReader<R> {
+    type Item = Result<GameTree>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_game() {
+            Ok(Some(gt)) => Some(Ok(gt)),
+            Ok(None) => None,
+            Err(Error::Io(e)) => {
+                // there's no way to resync after a read error
+                self.done = true;
+                Some(Err(Error::Io(e)))
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream1() {
+        let text = "junk (;GM[1]C[a (tricky\\] one)];B[aa](;W[bb])(;W[cc]))\n\n(;GM[1];B[dd])";
+        let games: Vec<GameTree> = GameReader::new(text.as_bytes()).map(|g| g.unwrap()).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].root().value("C"), Some("a (tricky\\] one)"));
+        assert_eq!(games[0].gametrees.len(), 2);
+        assert_eq!(games[1].to_string(), "(;GM[1];B[dd])");
+    }
+
+    #[test]
+    fn stream2() {
+        let text = "(;GM[1];B[aa];W[bb];B[cc])(;GM[1])(;GM[1]";
+        let mut reader = GameReader::new(text.as_bytes()).with_max_bytes(12);
+        assert!(matches!(reader.next(), Some(Err(Error::TooLarge(12)))));
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert!(matches!(reader.next(), Some(Err(Error::ParseError(_)))));
+        assert!(reader.next().is_none());
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use super::vertex::Node;
+use super::writer::{escape_text, unescape_text};
+
+// the Text properties, which may hold several paragraphs. the other
+// text properties are SimpleText and stay on one line
+pub const MULTILINE_PROPERTIES: [&str; 2] = ["C", "GC"];
+
+// a json string literal, also safe to put inside a <script> element
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '<' => out.push_str("\\u003c"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// \r\n, \n\r and a lone \r all as \n, since FF[4] allows any of them
+pub fn normalize_newlines(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                s.push('\n');
+            },
+            '\n' => {
+                chars.next_if_eq(&'\r');
+                s.push('\n');
+            },
+            _ => s.push(c),
+        }
+    }
+    s
+}
+
+// the paragraphs of a text, split at blank lines, with trailing spaces
+// dropped. line breaks inside a paragraph are kept
+pub fn paragraphs(text: &str) -> Vec<String> {
+    let text = normalize_newlines(text);
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+pub fn join_paragraphs<S: AsRef<str>>(paragraphs: &[S]) -> String {
+    let paragraphs: Vec<&str> = paragraphs.iter().map(|p| p.as_ref().trim()).filter(|p| !p.is_empty()).collect();
+    paragraphs.join("\n\n")
+}
+
+// breaks the lines of an escaped value after a space once they pass
+// width characters, using soft line breaks. readers remove those, so the
+// text is unchanged; clients that show the raw file get short lines.
+// words longer than width are left whole
+pub fn soft_wrap(value: &str, width: usize) -> String {
+    let mut s = String::with_capacity(value.len() + value.len() / width.max(1) * 2);
+    let mut column = 0;
+    // where in s the line could last be broken, and its column there
+    let mut last_space: Option<(usize, usize)> = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            s.push(c);
+            column += 1;
+            if let Some(next) = chars.next() {
+                s.push(next);
+                column = if next == '\n' { 0 } else { column + 1 };
+                if next == '\n' {
+                    last_space = None;
+                }
+            }
+            continue;
+        }
+        s.push(c);
+        if c == '\n' {
+            column = 0;
+            last_space = None;
+            continue;
+        }
+        column += 1;
+        if c == ' ' {
+            last_space = Some((s.len(), column));
+        }
+        if column > width {
+            if let Some((at, at_column)) = last_space.take() {
+                s.insert_str(at, "\\\n");
+                column -= at_column;
+            }
+        }
+    }
+    s
+}
+
+// what Writer::with_wrap does to each node
+pub(crate) fn wrap_node(node: &mut Node, width: usize) {
+    for prop in &mut node.props {
+        if MULTILINE_PROPERTIES.contains(&prop.ident.as_str()) {
+            for value in &mut prop.values {
+                *value = soft_wrap(&normalize_newlines(value), width);
+            }
+        }
+    }
+}
+
+impl Node {
+    // the text of a property as a reader sees it: unescaped, with soft
+    // line breaks removed and newlines normalized. several values are
+    // joined as paragraphs
+    pub fn text(&self, ident: &str) -> Option<String> {
+        let prop = self.get(ident)?;
+        let texts: Vec<String> = prop.values.iter().map(|v| normalize_newlines(&unescape_text(v))).collect();
+        Some(texts.join("\n\n"))
+    }
+
+    // sets a property from plain text, escaping it. an empty text
+    // removes the property
+    pub fn set_text(&mut self, ident: &str, text: &str) {
+        let text = normalize_newlines(text);
+        if text.trim().is_empty() {
+            self.remove(ident);
+        } else {
+            self.set(ident, vec![escape_text(text.trim_end(), false)]);
+        }
+    }
+
+    pub fn paragraphs(&self, ident: &str) -> Vec<String> {
+        self.text(ident).map(|t| paragraphs(&t)).unwrap_or_default()
+    }
+
+    pub fn set_paragraphs<S: AsRef<str>>(&mut self, ident: &str, paragraphs: &[S]) {
+        self.set_text(ident, &join_paragraphs(paragraphs));
+    }
+
+    // adds a paragraph after the ones already in the property
+    pub fn push_paragraph(&mut self, ident: &str, paragraph: &str) {
+        let mut all = self.paragraphs(ident);
+        all.push(paragraph.to_string());
+        self.set_paragraphs(ident, &all);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn node(text: &str) -> Node {
+        Parser::new(text).parse().unwrap().gametrees.remove(0).sequence.nodes.remove(0)
+    }
+
+    #[test]
+    fn paragraphs1() {
+        assert_eq!(normalize_newlines("a\r\nb\n\rc\rd"), "a\nb\nc\nd");
+        let mut n = node("(;C[x\\]\\\ny])");
+        n.set("GC", vec![" one\r\n two \r\n\r\n\r\n three".to_string()]);
+        assert_eq!(n.paragraphs("GC"), vec![" one\n two", " three"]);
+        assert_eq!(n.text("C").unwrap(), "x]y");
+        n.push_paragraph("C", "B 3: -2.0]");
+        assert_eq!(n.value("C"), Some("x\\]y\n\nB 3: -2.0\\]"));
+        n.set_text("C", " \n");
+        assert!(!n.has("C"));
+    }
+
+    #[test]
+    fn wrap1() {
+        let wrapped = soft_wrap("one two three four\nfive six", 8);
+        assert_eq!(wrapped, "one two \\\nthree \\\nfour\nfive six");
+        assert_eq!(unescape_text(&wrapped), "one two three four\nfive six");
+        // escapes aren't split and long words stay whole
+        assert_eq!(soft_wrap("ab\\] cdefghijk l", 4), "ab\\] \\\ncdefghijk \\\nl");
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// rewrites many files all or nothing. the new contents are staged next
+// to the files they replace, so the renames stay on one file system,
+// and only commit puts them in place. a transaction dropped without
+// committing leaves every file as it was
+
+#[derive(Debug)]
+pub enum Error {
+    // the new content of the file couldn't be written out
+    Stage(PathBuf, io::Error),
+    // a rename failed and everything was put back
+    Commit(PathBuf, io::Error),
+    // putting a file back failed too: its old content is at the second
+    // path
+    Rollback(PathBuf, PathBuf, io::Error),
+}
+
+impl Error {
+    // the file the error is about
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Stage(path, _) | Error::Commit(path, _) | Error::Rollback(path, _, _) => path,
+        }
+    }
+}
+
+// without the path, which batch runs print in front
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Stage(_, e) => write!(f, "couldn't stage: {}", e),
+            Error::Commit(_, e) => write!(f, "{}, no files were changed", e),
+            Error::Rollback(_, backup, e) => write!(f, "couldn't restore: {}, the original is at {}", e, backup.display()),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+struct Staged {
+    path: PathBuf,
+    temp: PathBuf,
+    // a link to the old file during commit, when there is one
+    backup: Option<PathBuf>,
+}
+
+// a hidden file beside path, like .game.sgf.1234.staged
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.{}", name, std::process::id(), suffix))
+}
+
+// writes data to temp, synced and with the permissions of path
+fn write_beside(path: &Path, temp: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(temp, meta.permissions())?;
+    }
+    Ok(())
+}
+
+// rewrites one file so that it never holds half its new content: the
+// data goes to a file beside it, which is then renamed over it
+pub fn replace_file<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp = sibling(path, "staged");
+    let result = write_beside(path, &temp, data).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// stage takes &self, so the workers of a batch run can share one
+#[derive(Default)]
+pub struct Transaction {
+    staged: Mutex<Vec<Staged>>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Transaction::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.staged.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // writes data beside path, to replace it on commit. the staged file
+    // is synced and gets the permissions of the one it replaces
+    pub fn stage<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        let temp = sibling(path, "staged");
+        if let Err(e) = write_beside(path, &temp, data) {
+            let _ = fs::remove_file(&temp);
+            return Err(Error::Stage(path.to_path_buf(), e));
+        }
+        self.staged.lock().unwrap().push(Staged {
+            path: path.to_path_buf(),
+            temp,
+            backup: None,
+        });
+        Ok(())
+    }
+
+    // renames every staged file over the one it replaces. the old files
+    // are kept as backups first, linked or copied rather than moved, so
+    // each path always holds either its old or its new content. when a
+    // rename fails the files already replaced get their backups back, so
+    // either all files have their new content or all have their old
+    pub fn commit(self) -> Result<()> {
+        let mut staged = std::mem::take(&mut *self.staged.lock().unwrap());
+        let mut failed = None;
+        for s in staged.iter_mut().filter(|s| s.path.exists()) {
+            let backup = sibling(&s.path, "backup");
+            if let Err(e) = fs::hard_link(&s.path, &backup).or_else(|_| fs::copy(&s.path, &backup).map(|_| ())) {
+                failed = Some(Error::Commit(s.path.clone(), e));
+                break;
+            }
+            s.backup = Some(backup);
+        }
+        let mut placed = 0;
+        if failed.is_none() {
+            for s in &staged {
+                if let Err(e) = fs::rename(&s.temp, &s.path) {
+                    failed = Some(Error::Commit(s.path.clone(), e));
+                    break;
+                }
+                placed += 1;
+            }
+        }
+        let Some(failed) = failed else {
+            for backup in staged.iter().filter_map(|s| s.backup.as_ref()) {
+                let _ = fs::remove_file(backup);
+            }
+            return Ok(());
+        };
+        // back out in reverse: the files already replaced get their old
+        // content back, and ones that didn't exist go again
+        let mut lost = None;
+        for s in staged[..placed].iter().rev() {
+            match &s.backup {
+                Some(backup) => {
+                    if let Err(e) = fs::rename(backup, &s.path) {
+                        lost.get_or_insert(Error::Rollback(s.path.clone(), backup.clone(), e));
+                    }
+                },
+                None => {
+                    let _ = fs::remove_file(&s.path);
+                },
+            }
+        }
+        for s in &staged[placed..] {
+            let _ = fs::remove_file(&s.temp);
+            if let Some(backup) = &s.backup {
+                let _ = fs::remove_file(backup);
+            }
+        }
+        Err(lost.unwrap_or(failed))
+    }
+}
+
+// an abandoned transaction removes what it staged
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        for s in self.staged.get_mut().unwrap().drain(..) {
+            let _ = fs::remove_file(&s.temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction1() {
+        let dir = std::env::temp_dir().join(format!("sgf-transaction1-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.sgf"), dir.join("b.sgf"));
+        fs::write(&a, "(;GM[1])").unwrap();
+        fs::write(&b, "(;GM[1])").unwrap();
+
+        let txn = Transaction::new();
+        txn.stage(&a, b"(;GM[1]KM[6.5])").unwrap();
+        drop(txn);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "(;GM[1])");
+
+        // b's staged file goes missing, after a is already in place,
+        // and a new file placed before it goes again
+        let txn = Transaction::new();
+        txn.stage(dir.join("c.sgf"), b"(;GM[1])").unwrap();
+        txn.stage(&a, b"(;GM[1]KM[6.5])").unwrap();
+        txn.stage(&b, b"(;GM[1]KM[6.5])").unwrap();
+        fs::remove_file(sibling(&b, "staged")).unwrap();
+        assert!(matches!(txn.commit(), Err(Error::Commit(path, _)) if path == b));
+        assert_eq!(fs::read_to_string(&a).unwrap(), "(;GM[1])");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "(;GM[1])");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let txn = Transaction::new();
+        txn.stage(&a, b"(;GM[1]KM[6.5])").unwrap();
+        txn.stage(&b, b"(;GM[1]KM[0.5])").unwrap();
+        assert_eq!(txn.len(), 2);
+        txn.commit().unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "(;GM[1]KM[6.5])");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "(;GM[1]KM[0.5])");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
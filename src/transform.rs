@@ -0,0 +1,400 @@
+use super::info::is_pass;
+use super::point::{parse_point_list, Point, Region, POINT_LISTS, SETUP_LISTS};
+use super::vertex::{GameTree, Node, Property, Sequence};
+
+// properties that only decorate the board and don't change the game
+pub const MARKUP: [&str; 9] = ["AR", "CR", "DD", "LB", "LN", "MA", "SL", "SQ", "TR"];
+
+// markup that claims a single point; the spec allows only one per point
+const POINT_MARKUP: [&str; 6] = ["CR", "LB", "MA", "SL", "SQ", "TR"];
+
+impl Node {
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty()
+    }
+
+    pub fn is_markup_only(&self) -> bool {
+        !self.is_empty() && self.props.iter().all(|p| MARKUP.contains(&p.ident.as_str()))
+    }
+}
+
+impl GameTree {
+    // drops nodes without properties. variations that end up empty are
+    // removed and their own variations are lifted into the parent
+    pub fn remove_empty_nodes(&self) -> Self {
+        let mut trees = remove_empty(self, true);
+        trees.remove(0)
+    }
+
+    // folds nodes that only carry markup into the preceding node of the
+    // same sequence, as long as that doesn't put two marks on one point
+    pub fn merge_markup_nodes(&self) -> Self {
+        let mut nodes: Vec<Node> = Vec::new();
+        for node in &self.sequence.nodes {
+            if let Some(prev) = nodes.last_mut() {
+                if node.is_markup_only() && can_merge_markup(prev, node) {
+                    merge_markup(prev, node);
+                    continue;
+                }
+            }
+            nodes.push(node.clone());
+        }
+        let mut gametrees = Vec::new();
+        for gt in &self.gametrees {
+            gametrees.push(Box::new(gt.merge_markup_nodes()));
+        }
+        GameTree{
+            sequence: Sequence{nodes},
+            gametrees,
+        }
+    }
+
+    // removes repeated points from point lists, and points that a node
+    // sets up more than once (say in both AB and AW). the first mention
+    // in the node wins and lists left empty are dropped
+    pub fn dedup_points(&self) -> Self {
+        let mut nodes = Vec::new();
+        for node in &self.sequence.nodes {
+            nodes.push(node.dedup_points());
+        }
+        let mut gametrees = Vec::new();
+        for gt in &self.gametrees {
+            gametrees.push(Box::new(gt.dedup_points()));
+        }
+        GameTree{
+            sequence: Sequence{nodes},
+            gametrees,
+        }
+    }
+
+    // cuts the board down to region, moving its top left corner to aa.
+    // moves, stones and markup outside it are dropped, VW goes since the
+    // new board is the view, and SZ becomes the size of the region
+    pub fn crop(&self, region: Region) -> Self {
+        let (cols, rows) = self.board_size();
+        let mut gt = crop(self, region, cols, rows);
+        let sz = if region.cols() == region.rows() {
+            region.cols().to_string()
+        } else {
+            format!("{}:{}", region.cols(), region.rows())
+        };
+        gt.root_mut().set("SZ", vec![sz]);
+        gt
+    }
+
+    // the game up to move n of every line, counting moves from the root.
+    // the nodes after it go, and with them the variations that branch
+    // later. with n at 0 only the nodes before the first move are left
+    pub fn head(&self, n: usize) -> Self {
+        head(self, 0, n).unwrap_or_else(|| GameTree {
+            sequence: Sequence { nodes: vec![self.root().clone()] },
+            gametrees: Vec::new(),
+        })
+    }
+
+    pub fn cleanup(&self) -> Self {
+        self.remove_empty_nodes().merge_markup_nodes()
+    }
+
+    // tidies a tree that was being edited before it is saved for good:
+    // properties without an ident or values and nodes left empty go
+    pub fn finalize(&self) -> Self {
+        let mut gt = self.clone();
+        drop_unfinished(&mut gt);
+        gt.remove_empty_nodes()
+    }
+}
+
+// gt cut after move n, with played moves before it. None when nothing of
+// it is left
+fn head(gt: &GameTree, mut played: usize, n: usize) -> Option<GameTree> {
+    let mut nodes = Vec::new();
+    let mut cut = false;
+    for node in &gt.sequence.nodes {
+        let is_move = node.get_move().is_some();
+        if played == n && (n > 0 || is_move) {
+            cut = true;
+            break;
+        }
+        if is_move {
+            played += 1;
+        }
+        nodes.push(node.clone());
+    }
+    if nodes.is_empty() {
+        return None;
+    }
+    let gametrees = if cut {
+        Vec::new()
+    } else {
+        gt.gametrees.iter().filter_map(|child| head(child, played, n)).map(Box::new).collect()
+    };
+    Some(GameTree {
+        sequence: Sequence { nodes },
+        gametrees,
+    })
+}
+
+impl Node {
+    pub fn dedup_points(&self) -> Self {
+        let mut props = Vec::new();
+        let mut setup: Vec<Point> = Vec::new();
+        for prop in &self.props {
+            let ident = prop.ident.as_str();
+            // DD[] and VW[] are meaningful empty lists, so leave those alone
+            if !POINT_LISTS.contains(&ident) || prop.values.iter().all(|v| v.is_empty()) {
+                props.push(prop.clone());
+                continue;
+            }
+            let points = parse_point_list(&prop.values);
+            let mut kept: Vec<Point> = Vec::new();
+            for p in points {
+                if kept.contains(&p) || (SETUP_LISTS.contains(&ident) && setup.contains(&p)) {
+                    continue;
+                }
+                kept.push(p);
+            }
+            if SETUP_LISTS.contains(&ident) {
+                setup.extend(kept.iter());
+            }
+            if kept.is_empty() {
+                continue;
+            }
+            // untouched lists keep their original, possibly compressed, form
+            let unchanged = parse_point_list(&prop.values) == kept;
+            let values = if unchanged {
+                prop.values.clone()
+            } else {
+                kept.iter().map(|p| p.to_sgf()).collect()
+            };
+            props.push(Property{ident: prop.ident.clone(), values});
+        }
+        Node{props}
+    }
+}
+
+fn crop(gt: &GameTree, region: Region, cols: usize, rows: usize) -> GameTree {
+    let nodes = gt.sequence.nodes.iter().map(|node| crop_node(node, region, cols, rows)).collect();
+    let gametrees = gt.gametrees.iter().map(|child| Box::new(crop(child, region, cols, rows))).collect();
+    GameTree{
+        sequence: Sequence{nodes},
+        gametrees,
+    }
+}
+
+fn crop_point(value: &str, region: Region) -> Option<String> {
+    Point::from_sgf(value).and_then(|p| region.map(p)).map(|p| p.to_sgf())
+}
+
+fn crop_node(node: &Node, region: Region, cols: usize, rows: usize) -> Node {
+    let mut props = Vec::new();
+    for prop in &node.props {
+        let ident = prop.ident.as_str();
+        let values: Vec<String> = match ident {
+            "VW" => continue,
+            "B" | "W" => match prop.values.first() {
+                Some(v) if is_pass(v, cols, rows) => vec![String::new()],
+                Some(v) => match crop_point(v, region) {
+                    Some(p) => vec![p],
+                    None => continue,
+                },
+                None => continue,
+            },
+            // DD[] undims everything, which still makes sense
+            "DD" if prop.values.iter().all(|v| v.is_empty()) => prop.values.clone(),
+            _ if POINT_LISTS.contains(&ident) => {
+                parse_point_list(&prop.values).into_iter().filter_map(|p| region.map(p)).map(|p| p.to_sgf()).collect()
+            },
+            "LB" => prop
+                .values
+                .iter()
+                .filter_map(|v| {
+                    let (p, text) = v.split_once(':')?;
+                    Some(format!("{}:{}", crop_point(p, region)?, text))
+                })
+                .collect(),
+            // arrows and lines need both ends on the board
+            "AR" | "LN" => prop
+                .values
+                .iter()
+                .filter_map(|v| {
+                    let (a, b) = v.split_once(':')?;
+                    Some(format!("{}:{}", crop_point(a, region)?, crop_point(b, region)?))
+                })
+                .collect(),
+            _ => prop.values.clone(),
+        };
+        if !values.is_empty() {
+            props.push(Property{ident: prop.ident.clone(), values});
+        }
+    }
+    Node{props}
+}
+
+fn drop_unfinished(gt: &mut GameTree) {
+    for node in &mut gt.sequence.nodes {
+        node.props.retain(|p| !p.ident.is_empty() && !p.values.is_empty());
+    }
+    for child in &mut gt.gametrees {
+        drop_unfinished(child);
+    }
+}
+
+fn remove_empty(gt: &GameTree, is_root: bool) -> Vec<GameTree> {
+    let mut nodes = Vec::new();
+    for (i, node) in gt.sequence.nodes.iter().enumerate() {
+        // the root node holds game info even when empty, so it stays
+        if !node.is_empty() || (is_root && i == 0) {
+            nodes.push(node.clone());
+        }
+    }
+    let mut children = Vec::new();
+    for child in &gt.gametrees {
+        children.extend(remove_empty(child, false));
+    }
+    if nodes.is_empty() {
+        return children;
+    }
+    // a single remaining variation is just a continuation
+    if children.len() == 1 {
+        let child = children.remove(0);
+        nodes.extend(child.sequence.nodes);
+        children = child.gametrees.into_iter().map(|gt| *gt).collect();
+    }
+    vec![GameTree{
+        sequence: Sequence{nodes},
+        gametrees: children.into_iter().map(Box::new).collect(),
+    }]
+}
+
+fn marked_points(node: &Node) -> Vec<(&str, &str)> {
+    let mut points = Vec::new();
+    for prop in &node.props {
+        if !POINT_MARKUP.contains(&prop.ident.as_str()) {
+            continue;
+        }
+        for value in &prop.values {
+            let point = match value.split_once(':') {
+                Some((p, _)) => p,
+                None => value.as_str(),
+            };
+            points.push((prop.ident.as_str(), point));
+        }
+    }
+    points
+}
+
+fn can_merge_markup(prev: &Node, node: &Node) -> bool {
+    // DD replaces the dimmed area, so two of them can't be combined
+    if prev.has("DD") && node.has("DD") {
+        return false;
+    }
+    let existing = marked_points(prev);
+    for (ident, point) in marked_points(node) {
+        for (other_ident, other_point) in &existing {
+            if point == *other_point && (ident != *other_ident || ident == "LB") {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn merge_markup(prev: &mut Node, node: &Node) {
+    for prop in &node.props {
+        match prev.get_mut(&prop.ident) {
+            Some(existing) => {
+                for value in &prop.values {
+                    if !existing.values.contains(value) {
+                        existing.values.push(value.clone());
+                    }
+                }
+            },
+            None => prev.props.push(prop.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::point::Region;
+    use crate::vertex::{GameTree, Node, Property, Sequence};
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn empty1() {
+        let gt = gametree("(;GM[1];;B[aa];;W[bb];)");
+        assert_eq!(gt.remove_empty_nodes().to_string(), "(;GM[1];B[aa];W[bb])");
+        let gt = gametree("(;;B[aa])");
+        assert_eq!(gt.remove_empty_nodes().to_string(), "(;;B[aa])");
+    }
+
+    #[test]
+    fn partial1() {
+        // a tree in the middle of an edit still writes out parseably
+        let mut gt = gametree("(;GM[1];B[aa])");
+        gt.sequence.nodes.push(Node { props: vec![Property { ident: "W".to_string(), values: Vec::new() }] });
+        gt.sequence.nodes[1].props.push(Property { ident: "C".to_string(), values: vec!["a]b\\".to_string()] });
+        gt.sequence.nodes[1].props.push(Property { ident: String::new(), values: vec!["x".to_string()] });
+        gt.gametrees.push(Box::new(GameTree { sequence: Sequence { nodes: Vec::new() }, gametrees: Vec::new() }));
+        let text = gt.to_string();
+        assert_eq!(text, "(;GM[1];B[aa]C[a\\]b\\\\];W[](;))");
+        assert!(Parser::new(&text).parse().is_ok());
+        assert_eq!(gt.finalize().to_string(), "(;GM[1];B[aa]C[a\\]b\\\\])");
+    }
+
+    #[test]
+    fn empty2() {
+        let gt = gametree("(;GM[1];B[aa](;)(;W[bb]))");
+        assert_eq!(gt.remove_empty_nodes().to_string(), "(;GM[1];B[aa];W[bb])");
+        let gt = gametree("(;GM[1];B[aa](;(;W[cc])(;W[dd]))(;W[bb]))");
+        assert_eq!(gt.remove_empty_nodes().to_string(), "(;GM[1];B[aa](;W[cc])(;W[dd])(;W[bb]))");
+    }
+
+    #[test]
+    fn dedup1() {
+        let gt = gametree("(;GM[1]AB[aa][bb][aa]AW[cc][bb]AE[bb];TR[aa:ab][ab]CR[cc:dd])");
+        assert_eq!(gt.dedup_points().to_string(), "(;GM[1]AB[aa][bb]AW[cc];TR[aa][ab]CR[cc:dd])");
+        let gt = gametree("(;GM[1]VW[];DD[])");
+        assert_eq!(gt.dedup_points().to_string(), "(;GM[1]VW[];DD[])");
+    }
+
+    #[test]
+    fn crop1() {
+        let gt = gametree("(;SZ[19]AB[pc][dd]AW[pa:qb]VW[];B[qc]LB[qc:1][dd:2];W[cc]AR[pc:qc][pc:dd](;B[tt])(;B[ra]TR[rr]))");
+        let region = Region::parse("oa:si").unwrap();
+        assert_eq!(
+            gt.crop(region).to_string(),
+            "(;SZ[5:9]AB[bc]AW[ba][ca][bb][cb];B[cc]LB[cc:1];AR[bc:cc](;B[])(;B[da]))"
+        );
+    }
+
+    #[test]
+    fn markup1() {
+        let gt = gametree("(;GM[1];B[aa];CR[bb][cc];TR[dd];W[ee])");
+        assert_eq!(gt.merge_markup_nodes().to_string(), "(;GM[1];B[aa]CR[bb][cc]TR[dd];W[ee])");
+    }
+
+    #[test]
+    fn markup2() {
+        // conflicting marks on one point stay in separate nodes
+        let gt = gametree("(;GM[1];B[aa]CR[bb];TR[bb])");
+        assert_eq!(gt.merge_markup_nodes().to_string(), "(;GM[1];B[aa]CR[bb];TR[bb])");
+        // the first node of a variation belongs to only one branch
+        let gt = gametree("(;GM[1];B[aa](;TR[bb])(;W[cc]))");
+        assert_eq!(gt.merge_markup_nodes().to_string(), "(;GM[1];B[aa](;TR[bb])(;W[cc]))");
+    }
+
+    #[test]
+    fn head1() {
+        let gt = gametree("(;GM[1];C[start];B[aa];C[note](;W[bb];B[cc])(;W[dd]C[x];TR[aa];B[ee])(;C[only markup]))");
+        assert_eq!(gt.head(2).to_string(), "(;GM[1];C[start];B[aa];C[note](;W[bb])(;W[dd]C[x])(;C[only markup]))");
+        assert_eq!(gt.head(1).to_string(), "(;GM[1];C[start];B[aa])");
+        assert_eq!(gt.head(0).to_string(), "(;GM[1];C[start])");
+        assert_eq!(gt.head(9).to_string(), gt.to_string());
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use super::digest::fnv;
+use super::info::normalize_text;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnknownForm(String),
+    // ascii spellings need the translit feature
+    NoTranslit,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownForm(s) => write!(f, "bad name form: {}, use ascii or hash", s),
+            Error::NoTranslit => write!(f, "ascii names need the translit feature"),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// how player names are written for places that only take ascii, like
+// file names or the keys of another system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameForm {
+    #[default]
+    Keep,
+    // spelled in latin letters: hangul in revised romanization, kana as
+    // romaji and chinese characters in pinyin. kanji are chinese
+    // characters too, so japanese names in kanji come out in pinyin, not
+    // in their japanese reading: 井山裕太 is JingShanYuTai. 李昌镐 is
+    // LiChangGao
+    Ascii,
+    // p and sixteen hex digits, the same for every spelling that differs
+    // only in case and spacing. names that are already plain ascii are
+    // hashed too, so every key has one shape
+    Hash,
+}
+
+impl NameForm {
+    pub fn parse(s: &str) -> Result<NameForm> {
+        match s {
+            "keep" => Ok(NameForm::Keep),
+            "ascii" => Ok(NameForm::Ascii),
+            "hash" => Ok(NameForm::Hash),
+            _ => Err(Error::UnknownForm(s.to_string())),
+        }
+    }
+
+    // whether names can be written this way in this build
+    pub fn check(&self) -> Result<()> {
+        match self {
+            NameForm::Ascii if cfg!(not(feature = "translit")) => Err(Error::NoTranslit),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, name: &str) -> Result<String> {
+        match self {
+            NameForm::Keep => Ok(name.to_string()),
+            NameForm::Ascii => ascii(name),
+            NameForm::Hash => Ok(format!("p{:016x}", fnv(&normalize_text(name).to_lowercase()))),
+        }
+    }
+}
+
+#[cfg(feature = "translit")]
+fn ascii(name: &str) -> Result<String> {
+    Ok(normalize_text(&any_ascii::any_ascii(name)))
+}
+
+#[cfg(not(feature = "translit"))]
+fn ascii(_: &str) -> Result<String> {
+    Err(Error::NoTranslit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translit1() {
+        assert_eq!(NameForm::Keep.apply("李昌镐").unwrap(), "李昌镐");
+        let key = NameForm::Hash.apply("李昌镐").unwrap();
+        assert!(key.len() == 17 && key.is_ascii());
+        assert_eq!(NameForm::Hash.apply("Lee  Sedol"), NameForm::Hash.apply("lee sedol"));
+        assert_ne!(NameForm::Hash.apply("Lee Sedol"), NameForm::Hash.apply("Lee Changho"));
+        assert_eq!(NameForm::parse("hash"), Ok(NameForm::Hash));
+        assert_eq!(NameForm::parse("ascii"), Ok(NameForm::Ascii));
+        assert_eq!(NameForm::parse("pinyin"), Err(Error::UnknownForm("pinyin".to_string())));
+        assert_eq!(NameForm::Ascii.check().is_ok(), cfg!(feature = "translit"));
+    }
+
+    #[test]
+    #[cfg(feature = "translit")]
+    fn translit2() {
+        assert_eq!(NameForm::Ascii.apply("李昌镐").unwrap(), "LiChangGao");
+        assert_eq!(NameForm::Ascii.apply("井山 裕太").unwrap(), "JingShan YuTai");
+        assert_eq!(NameForm::Ascii.apply("いやま").unwrap(), "iyama");
+        assert_eq!(NameForm::Ascii.apply("이세돌").unwrap(), "ISeDol");
+        assert_eq!(NameForm::Ascii.apply("Lee Sedol").unwrap(), "Lee Sedol");
+    }
+}
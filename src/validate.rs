@@ -0,0 +1,688 @@
+use std::fmt;
+
+#[cfg(feature = "board")]
+use super::board::{Board, KoRule};
+use super::codes;
+use super::point::{parse_point_list, Point, POINT_LISTS, SETUP_LISTS};
+use super::properties::describe;
+#[cfg(feature = "board")]
+use super::vertex::Color;
+use super::version::{self, check_version};
+use super::vertex::{Collection, GameTree, Node};
+
+// properties that may only appear in the root node
+pub const ROOT_PROPERTIES: [&str; 6] = ["AP", "CA", "FF", "GM", "ST", "SZ"];
+
+const SETUP_PROPERTIES: [&str; 4] = ["AB", "AE", "AW", "PL"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    ParseError,
+    BadBoardSize,
+    MisplacedRootProperty,
+    DuplicateProperty,
+    MixedMoveSetup,
+    MultipleMoves,
+    BadPoint,
+    DuplicatePoint,
+    ConflictingSetup,
+    KoViolation,
+    SuperkoViolation,
+    OccupiedPoint,
+    Suicide,
+    NotInVersion,
+    NewlineInSimpleText,
+    ControlCharacter,
+    BlankValue,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::ParseError => "parse-error",
+            Kind::BadBoardSize => "bad-board-size",
+            Kind::MisplacedRootProperty => "misplaced-root-property",
+            Kind::DuplicateProperty => "duplicate-property",
+            Kind::MixedMoveSetup => "mixed-move-setup",
+            Kind::MultipleMoves => "multiple-moves",
+            Kind::BadPoint => "bad-point",
+            Kind::DuplicatePoint => "duplicate-point",
+            Kind::ConflictingSetup => "conflicting-setup",
+            Kind::KoViolation => "ko-violation",
+            Kind::SuperkoViolation => "superko-violation",
+            Kind::OccupiedPoint => "occupied-point",
+            Kind::Suicide => "suicide",
+            Kind::NotInVersion => "not-in-version",
+            Kind::NewlineInSimpleText => "newline-in-simple-text",
+            Kind::ControlCharacter => "control-character",
+            Kind::BlankValue => "blank-value",
+        }
+    }
+
+    // the code in codes::CATALOG. parse errors have several, and this is
+    // the general one
+    pub fn code(&self) -> &'static str {
+        match self {
+            Kind::ParseError => codes::PARSE_ERROR,
+            Kind::BadBoardSize => codes::BAD_BOARD_SIZE,
+            Kind::MisplacedRootProperty => codes::MISPLACED_ROOT_PROPERTY,
+            Kind::DuplicateProperty => codes::DUPLICATE_PROPERTY,
+            Kind::MixedMoveSetup => codes::MIXED_MOVE_SETUP,
+            Kind::MultipleMoves => codes::MULTIPLE_MOVES,
+            Kind::BadPoint => codes::BAD_POINT,
+            Kind::DuplicatePoint => codes::DUPLICATE_POINT,
+            Kind::ConflictingSetup => codes::CONFLICTING_SETUP,
+            Kind::KoViolation => codes::KO_VIOLATION,
+            Kind::SuperkoViolation => codes::SUPERKO_VIOLATION,
+            Kind::OccupiedPoint => codes::OCCUPIED_POINT,
+            Kind::Suicide => codes::SUICIDE,
+            Kind::NotInVersion => codes::NOT_IN_VERSION,
+            Kind::NewlineInSimpleText => codes::NEWLINE_IN_SIMPLE_TEXT,
+            Kind::ControlCharacter => codes::CONTROL_CHARACTER,
+            Kind::BlankValue => codes::BLANK_VALUE,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Kind::ParseError
+            | Kind::BadBoardSize
+            | Kind::MultipleMoves
+            | Kind::ConflictingSetup
+            | Kind::KoViolation
+            | Kind::SuperkoViolation
+            | Kind::OccupiedPoint
+            | Kind::Suicide => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: Kind,
+    // the stable code, which for parse errors says more than kind
+    pub code: &'static str,
+    pub severity: Severity,
+    // which game of the collection, and the node path inside it
+    pub game: usize,
+    pub path: Vec<usize>,
+    pub message: String,
+    // another node the problem refers to, like the earlier occurrence
+    // of a repeated position
+    pub related: Option<Vec<usize>>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: Kind, game: usize, path: Vec<usize>, message: String) -> Self {
+        Diagnostic {
+            kind,
+            code: kind.code(),
+            severity: kind.severity(),
+            game,
+            path,
+            message,
+            related: None,
+        }
+    }
+
+    // a file that didn't parse, with the parser's code
+    pub fn parse_error(code: &'static str, message: String) -> Self {
+        Diagnostic {
+            code,
+            ..Diagnostic::new(Kind::ParseError, 0, Vec::new(), message)
+        }
+    }
+
+    // the catalog's name for the code, like unterminated-value
+    pub fn name(&self) -> &'static str {
+        codes::lookup(self.code).map_or(self.kind.name(), |c| c.name)
+    }
+
+    pub fn with_related(mut self, path: Vec<usize>) -> Self {
+        self.related = Some(path);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|i| i.to_string()).collect();
+        write!(f, "{}[{} {}] game {} node [{}]: {}", self.severity, self.code, self.name(), self.game, path.join(","), self.message)
+    }
+}
+
+pub fn validate(coll: &Collection) -> Vec<Diagnostic> {
+    validate_as(coll, None)
+}
+
+// validates against a target FF instead of the one each game declares
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn validate_as(coll: &Collection, ff: Option<u8>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (i, gt) in coll.gametrees.iter().enumerate() {
+        diagnostics.extend(validate_gametree_as(i, gt, ff));
+    }
+    trace!(games = coll.gametrees.len(), diagnostics = diagnostics.len(), "validated");
+    diagnostics
+}
+
+pub fn validate_gametree(game: usize, gt: &GameTree) -> Vec<Diagnostic> {
+    validate_gametree_as(game, gt, None)
+}
+
+pub fn validate_gametree_as(game: usize, gt: &GameTree, ff: Option<u8>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let (cols, rows) = gt.board_size();
+    if let Some(sz) = gt.root().value("SZ") {
+        if !valid_size(sz) {
+            let msg = format!("invalid board size {}", sz);
+            diagnostics.push(Diagnostic::new(Kind::BadBoardSize, game, Vec::new(), msg));
+        }
+    }
+    for (path, node) in gt.nodes() {
+        let is_root = path.is_empty();
+        check_node(node, is_root, cols, rows, &mut |kind, msg| {
+            diagnostics.push(Diagnostic::new(kind, game, path.clone(), msg));
+        });
+    }
+    // games that don't state FF aren't held to FF[1]
+    let declared = gt.root().value("FF").and_then(version::parse);
+    if let Some(ff) = ff.or(declared) {
+        diagnostics.extend(check_version(game, gt, ff));
+    }
+    // checking moves needs the board engine
+    #[cfg(feature = "board")]
+    diagnostics.extend(check_legality(game, gt));
+    diagnostics
+}
+
+// rules that let a player capture their own stones
+#[cfg(feature = "board")]
+fn allows_suicide(ru: &str) -> bool {
+    let ru = ru.trim().to_lowercase();
+    ru == "nz" || ru.starts_with("new zealand") || ru.contains("tromp") || ru == "ing" || ru == "goe"
+}
+
+// replays every line of play and reports each illegal move under the
+// game's rules (RU): one played on a stone, a suicide where the rules
+// forbid it, and a ko retake or repeated position. a node with KO is
+// forced and never reported
+#[cfg(feature = "board")]
+pub fn check_legality(game: usize, gt: &GameTree) -> Vec<Diagnostic> {
+    let ru = gt.root().value("RU").unwrap_or("");
+    let checks = Checks {
+        rule: KoRule::from_rules(ru),
+        placement: true,
+        suicide: !allows_suicide(ru),
+    };
+    walk_moves(game, gt, checks)
+}
+
+// a position reached during replay: its hash, the node that produced it
+// and the move number
+#[cfg(feature = "board")]
+#[derive(Clone)]
+struct Seen {
+    hash: u64,
+    path: Vec<usize>,
+    number: usize,
+}
+
+// replays every line of play and reports moves that repeat an earlier
+// position in a way the ko rule forbids
+#[cfg(feature = "board")]
+pub fn check_repetition(game: usize, gt: &GameTree, rule: KoRule) -> Vec<Diagnostic> {
+    let checks = Checks {
+        rule,
+        placement: false,
+        suicide: false,
+    };
+    walk_moves(game, gt, checks)
+}
+
+// what walk_moves reports besides repetition
+#[cfg(feature = "board")]
+#[derive(Clone, Copy)]
+struct Checks {
+    rule: KoRule,
+    // moves on a point that already has a stone
+    placement: bool,
+    suicide: bool,
+}
+
+#[cfg(feature = "board")]
+fn walk_moves(game: usize, gt: &GameTree, checks: Checks) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let board = Board::for_game(gt);
+    let start = Seen {hash: board.hash(), path: Vec::new(), number: 0};
+    walk_line(game, gt, checks, board, vec![start], Vec::new(), &mut diagnostics);
+    diagnostics
+}
+
+#[cfg(feature = "board")]
+fn walk_line(
+    game: usize,
+    gt: &GameTree,
+    checks: Checks,
+    mut board: Board,
+    mut history: Vec<Seen>,
+    mut path: Vec<usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for (i, node) in gt.sequence.nodes.iter().enumerate() {
+        if i > 0 {
+            path.push(0);
+        }
+        let number = history.last().map(|s| s.number).unwrap_or(0);
+        let forced = node.get("KO").is_some();
+        let placed = board.move_point(node);
+        if let Some((color, p)) = placed.filter(|_| checks.placement && !forced) {
+            if let Some(stone) = board.get(p) {
+                let msg = format!("move {} plays {} on a {} stone at {}", number + 1, color.ident(), stone.ident(), p.to_sgf());
+                diagnostics.push(Diagnostic::new(Kind::OccupiedPoint, game, path.clone(), msg));
+            }
+        }
+        let delta = board.apply(node);
+        if let Some((color, p)) = placed.filter(|_| checks.suicide && !forced) {
+            if delta.captured.iter().any(|&(q, lost)| q == p && lost == color) {
+                let msg = format!("move {} at {} is suicide, taking {} {} stone(s)", number + 1, p.to_sgf(), delta.captured.len(), color.ident());
+                diagnostics.push(Diagnostic::new(Kind::Suicide, game, path.clone(), msg));
+            }
+        }
+        let mv = match node.get_move() {
+            Some((color, _)) => color,
+            None => {
+                // setup starts a new position history
+                if !delta.is_empty() {
+                    history = vec![Seen {hash: board.hash(), path: path.clone(), number}];
+                }
+                continue;
+            },
+        };
+        let rule = checks.rule;
+        let mut hash = board.hash();
+        if rule == KoRule::SituationalSuperko && mv == Color::Black {
+            // black just moved, so white is to play
+            hash = !hash;
+        }
+        // a pass repeats the position by definition, so it is never checked
+        let is_pass = delta.placed.is_empty();
+        let seen = Seen {hash, path: path.clone(), number: number + 1};
+        if !is_pass && !forced {
+            let earlier = match rule {
+                KoRule::Simple => history.len().checked_sub(2).map(|j| &history[j]).filter(|s| s.hash == hash),
+                _ => history.iter().find(|s| s.hash == hash),
+            };
+            if let Some(earlier) = earlier {
+                let (kind, what) = match rule {
+                    KoRule::Simple => (Kind::KoViolation, "retakes the ko"),
+                    _ => (Kind::SuperkoViolation, "repeats an earlier position"),
+                };
+                let when = if earlier.number == 0 {
+                    "the start".to_string()
+                } else {
+                    format!("move {}", earlier.number)
+                };
+                let msg = format!("move {} {}, first seen after {}", number + 1, what, when);
+                diagnostics.push(Diagnostic::new(kind, game, path.clone(), msg).with_related(earlier.path.clone()));
+            }
+        }
+        history.push(seen);
+    }
+    for (i, child) in gt.gametrees.iter().enumerate() {
+        let mut p = path.clone();
+        p.push(i);
+        walk_line(game, child, checks, board.clone(), history.clone(), p, diagnostics);
+    }
+}
+
+fn valid_size(sz: &str) -> bool {
+    let dims: Vec<&str> = sz.split(':').collect();
+    dims.len() <= 2 && dims.iter().all(|d| matches!(d.trim().parse::<usize>(), Ok(1..=52)))
+}
+
+fn check_node<F: FnMut(Kind, String)>(node: &Node, is_root: bool, cols: usize, rows: usize, report: &mut F) {
+    let mut seen: Vec<&str> = Vec::new();
+    for prop in &node.props {
+        let ident = prop.ident.as_str();
+        if seen.contains(&ident) {
+            report(Kind::DuplicateProperty, format!("{} appears more than once", ident));
+        }
+        seen.push(ident);
+        if !is_root && ROOT_PROPERTIES.contains(&ident) {
+            report(Kind::MisplacedRootProperty, format!("{} outside of the root node", ident));
+        }
+        let controls = control_characters(&prop.values);
+        if !controls.is_empty() {
+            report(Kind::ControlCharacter, format!("{} has control characters: {}", ident, control_names(&controls)));
+        }
+        if is_simple_text(ident) && prop.values.iter().any(|v| has_newline(v)) {
+            report(Kind::NewlineInSimpleText, format!("{} has a line break", ident));
+        }
+        if is_simple_text(ident) && prop.values.iter().any(|v| is_blank(v)) {
+            report(Kind::BlankValue, format!("{} is only whitespace", ident));
+        }
+    }
+    let has_b = node.has("B");
+    let has_w = node.has("W");
+    if has_b && has_w {
+        report(Kind::MultipleMoves, "B and W in the same node".to_string());
+    }
+    if (has_b || has_w) && SETUP_PROPERTIES.iter().any(|p| node.has(p)) {
+        report(Kind::MixedMoveSetup, "move and setup properties in the same node".to_string());
+    }
+    let mut setup: Vec<(Point, &str)> = Vec::new();
+    for prop in &node.props {
+        let ident = prop.ident.as_str();
+        if !POINT_LISTS.contains(&ident) {
+            continue;
+        }
+        let mut points = parse_point_list(&prop.values);
+        let total = points.len();
+        points.sort();
+        points.dedup();
+        if points.len() < total {
+            report(Kind::DuplicatePoint, format!("{} lists {} point(s) more than once", ident, total - points.len()));
+        }
+        if SETUP_LISTS.contains(&ident) {
+            for p in points {
+                if let Some((_, other)) = setup.iter().find(|(q, _)| *q == p) {
+                    report(Kind::ConflictingSetup, format!("{} is in both {} and {}", p, other, ident));
+                }
+                setup.push((p, ident));
+            }
+        }
+    }
+    if let Some((_, value)) = node.get_move() {
+        // passes are B[] or B[tt], and tt happens to be a point off small boards
+        let ok = value.is_empty() || value == "tt" || match Point::from_sgf(value) {
+            Some(p) => p.on_board(cols, rows),
+            None => false,
+        };
+        if !ok {
+            report(Kind::BadPoint, format!("move {} is not on the board", value));
+        }
+    }
+}
+
+// properties whose text, or part of it, is SimpleText
+fn is_simple_text(ident: &str) -> bool {
+    describe(ident).is_some_and(|info| info.value_type.contains("SimpleText"))
+}
+
+// a line break that isn't escaped. escaped ones are soft breaks, which
+// readers remove
+fn has_newline(value: &str) -> bool {
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            },
+            '\n' | '\r' => return true,
+            _ => {},
+        }
+    }
+    false
+}
+
+// the value with each line break, and the spaces around it, made one
+// space. works on the escaped value, so composed values keep their ':'
+fn join_lines(value: &str) -> String {
+    let mut s = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                s.push(c);
+                s.extend(chars.next());
+            },
+            '\n' | '\r' => {
+                s.truncate(s.trim_end_matches([' ', '\t']).len());
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if !s.is_empty() && chars.peek().is_some() {
+                    s.push(' ');
+                }
+            },
+            _ => s.push(c),
+        }
+    }
+    s
+}
+
+// control characters other than line breaks and tabs, which old clients
+// leave in values and strict parsers reject
+fn is_stray_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\n' | '\r' | '\t')
+}
+
+fn control_name(c: char) -> String {
+    match c {
+        '\0' => "NUL".to_string(),
+        '\x0b' => "VT".to_string(),
+        '\x0c' => "FF".to_string(),
+        '\x1b' => "ESC".to_string(),
+        '\x7f' => "DEL".to_string(),
+        _ => format!("U+{:04X}", c as u32),
+    }
+}
+
+// the distinct control characters in the values, in order of appearance
+fn control_characters(values: &[String]) -> Vec<char> {
+    let mut found: Vec<char> = Vec::new();
+    for c in values.iter().flat_map(|v| v.chars()).filter(|c| is_stray_control(*c)) {
+        if !found.contains(&c) {
+            found.push(c);
+        }
+    }
+    found
+}
+
+fn control_names(chars: &[char]) -> String {
+    chars.iter().map(|c| control_name(*c)).collect::<Vec<_>>().join(", ")
+}
+
+// a value with text in it that is only whitespace, like PB[ ]
+fn is_blank(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(char::is_whitespace)
+}
+
+// fixes what validation finds that has only one sensible fix: control
+// characters are removed, line breaks in SimpleText become spaces and
+// SimpleText of only whitespace is emptied. gives a diagnostic for
+// each property changed, saying what was done
+pub fn fix(coll: &mut Collection) -> Vec<Diagnostic> {
+    let mut fixed = Vec::new();
+    for (game, gt) in coll.gametrees.iter_mut().enumerate() {
+        fix_gametree(game, gt, Vec::new(), &mut fixed);
+    }
+    fixed
+}
+
+fn fix_gametree(game: usize, gt: &mut GameTree, mut path: Vec<usize>, fixed: &mut Vec<Diagnostic>) {
+    for (i, node) in gt.sequence.nodes.iter_mut().enumerate() {
+        if i > 0 {
+            path.push(0);
+        }
+        for prop in &mut node.props {
+            let mut report = |kind, msg| fixed.push(Diagnostic::new(kind, game, path.clone(), msg));
+            let ident = prop.ident.as_str();
+            let controls = control_characters(&prop.values);
+            if !controls.is_empty() {
+                for value in &mut prop.values {
+                    value.retain(|c| !is_stray_control(c));
+                }
+                report(Kind::ControlCharacter, format!("removed {} from {}", control_names(&controls), ident));
+            }
+            if !is_simple_text(ident) {
+                continue;
+            }
+            if prop.values.iter().any(|v| has_newline(v)) {
+                for value in prop.values.iter_mut().filter(|v| has_newline(v)) {
+                    *value = join_lines(value);
+                }
+                report(Kind::NewlineInSimpleText, format!("made the line breaks in {} spaces", ident));
+            }
+            if prop.values.iter().any(|v| is_blank(v)) {
+                for value in prop.values.iter_mut().filter(|v| is_blank(v)) {
+                    value.clear();
+                }
+                report(Kind::BlankValue, format!("emptied {}, which was only whitespace", ident));
+            }
+        }
+    }
+    for (i, child) in gt.gametrees.iter_mut().enumerate() {
+        let mut p = path.clone();
+        p.push(i);
+        fix_gametree(game, child, p, fixed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn kinds(text: &str) -> Vec<Kind> {
+        let coll = Parser::new(text).parse().unwrap();
+        validate(&coll).into_iter().map(|d| d.kind).collect()
+    }
+
+    #[test]
+    fn validate1() {
+        assert!(kinds("(;GM[1]FF[4]SZ[19];B[pd];W[tt];B[])").is_empty());
+        assert_eq!(kinds("(;GM[1]SZ[0])"), vec![Kind::BadBoardSize]);
+        assert_eq!(kinds("(;GM[1];B[aa]SZ[9])"), vec![Kind::MisplacedRootProperty]);
+        assert_eq!(kinds("(;GM[1];B[aa]C[x]C[y])"), vec![Kind::DuplicateProperty]);
+        assert_eq!(kinds("(;GM[1];B[aa]W[bb])"), vec![Kind::MultipleMoves]);
+        assert_eq!(kinds("(;GM[1];B[aa]AW[bb])"), vec![Kind::MixedMoveSetup]);
+        assert_eq!(kinds("(;GM[1]SZ[9];B[jj])"), vec![Kind::BadPoint]);
+        assert_eq!(kinds("(;GM[1]AB[aa][bb][aa])"), vec![Kind::DuplicatePoint]);
+        assert_eq!(kinds("(;GM[1]TR[aa:bb][ab])"), vec![Kind::DuplicatePoint]);
+        assert_eq!(kinds("(;GM[1]AB[aa][bb]AW[cc][bb])"), vec![Kind::ConflictingSetup]);
+        assert!(kinds("(;GM[1]AB[aa];AW[aa])").is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "board")]
+    fn ko1() {
+        // white captures at bb, then black retakes at cb right away
+        let ko = "SZ[5];B[ba];W[ca];B[ab];W[db];B[bc];W[cc];B[cb];W[bb]";
+        assert!(kinds(&format!("(;RU[Japanese]{})", ko)).is_empty());
+        let text = format!("(;RU[Japanese]{};B[cb])", ko);
+        let coll = Parser::new(&text).parse().unwrap();
+        let diagnostics = validate(&coll);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, Kind::KoViolation);
+        assert_eq!(diagnostics[0].path.len(), 9);
+        assert_eq!(diagnostics[0].related.as_ref().unwrap().len(), 7);
+        assert_eq!(kinds(&format!("(;RU[Chinese]{};B[cb])", ko)), vec![Kind::SuperkoViolation]);
+        // after a ko threat exchange the retake is fine
+        assert!(kinds(&format!("(;RU[Japanese]{};B[ee];W[ed];B[cb])", ko)).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "board")]
+    fn ko2() {
+        let ko = "SZ[5];B[ba];W[ca];B[ab];W[db];B[bc];W[cc];B[cb];W[bb]";
+        let text = format!("(;RU[AGA]{};B[];W[];B[cb])", ko);
+        let coll = Parser::new(&text).parse().unwrap();
+        let diagnostics = validate(&coll);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "move 11 repeats an earlier position, first seen after move 7");
+        // simple ko allows retaking once both players passed
+        assert!(kinds(&format!("(;RU[Japanese]{};B[];W[];B[cb])", ko)).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "board")]
+    fn legality1() {
+        assert!(kinds("(;GM[1]SZ[5];B[aa];W[ba];B[bb];W[ab])").is_empty());
+        let coll = Parser::new("(;GM[1]SZ[5];B[ab];W[cc](;B[ab])(;B[ba];W[aa]))").parse().unwrap();
+        let messages: Vec<String> = validate(&coll).iter().map(|d| format!("{} {:?} {}", d.code, d.path, d.message)).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "E016 [0, 0, 0] move 3 plays B on a B stone at ab",
+                "E017 [0, 0, 1, 0] move 4 at aa is suicide, taking 1 W stone(s)",
+            ]
+        );
+        // forced with KO, or under rules that allow suicide
+        assert!(kinds("(;GM[1]SZ[5];B[aa];W[aa]KO[])").is_empty());
+        assert!(kinds("(;GM[1]RU[NZ]SZ[5];B[ab];W[bb];B[ba];W[aa])").is_empty());
+        assert_eq!(kinds("(;GM[1]RU[Japanese]SZ[5];B[ab];W[bb];B[ba];W[aa])"), vec![Kind::Suicide]);
+    }
+
+    #[test]
+    fn validate3() {
+        assert_eq!(kinds("(;FF[3]AB[aa:bb])"), vec![Kind::NotInVersion]);
+        let coll = Parser::new("(;GM[1]CA[UTF-8];B[])").parse().unwrap();
+        assert!(validate(&coll).is_empty());
+        assert_eq!(validate_as(&coll, Some(3)).len(), 2);
+    }
+
+    #[test]
+    fn validate4() {
+        let text = "(;GM[1]PB[Lee\n  Sedol]EV[a \\\n b]C[two\nlines]LB[aa:one\r\ntwo];B[aa]N[end\n])";
+        assert_eq!(kinds(text), vec![Kind::NewlineInSimpleText, Kind::NewlineInSimpleText, Kind::NewlineInSimpleText]);
+        let mut coll = Parser::new(text).parse().unwrap();
+        assert_eq!(fix(&mut coll).len(), 3);
+        // soft breaks and Text are left alone
+        assert_eq!(coll.to_string(), "(;GM[1]PB[Lee Sedol]EV[a \\\n b]C[two\nlines]LB[aa:one two];B[aa]N[end])");
+        assert!(validate(&coll).is_empty());
+    }
+
+    #[test]
+    fn validate5() {
+        let text = "(;GM[1]PB[\u{0}Lee\u{b}]PW[ \t]C[a\u{0}\tb];B[aa]N[x\u{b}])";
+        let coll = Parser::new(text).parse().unwrap();
+        let messages: Vec<String> = validate(&coll).iter().map(|d| format!("{} {}", d.code, d.message)).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "W008 PB has control characters: NUL, VT",
+                "W009 PW is only whitespace",
+                "W008 C has control characters: NUL",
+                "W008 N has control characters: VT",
+            ]
+        );
+        let mut coll = coll;
+        let fixed = fix(&mut coll);
+        assert_eq!(fixed[0].message, "removed NUL, VT from PB");
+        assert_eq!((fixed.len(), fixed[3].path.clone()), (4, vec![0]));
+        // a tab is whitespace, not a stray control character
+        assert_eq!(coll.to_string(), "(;GM[1]PB[Lee]PW[]C[a b];B[aa]N[x])");
+        assert!(validate(&coll).is_empty());
+    }
+
+    #[test]
+    fn validate2() {
+        let coll = Parser::new("(;GM[1];B[aa](;W[bb])(;W[bb]W[cc]))").parse().unwrap();
+        let diagnostics = validate(&coll);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, vec![0, 1]);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+}
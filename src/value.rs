@@ -0,0 +1,215 @@
+use std::fmt;
+
+use super::point::Point;
+use super::properties::describe;
+use super::vertex::{Color, Node};
+use super::writer::escape_text;
+
+// a property value as what it means rather than how it is written
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+    None,
+    Number(i64),
+    Real(f64),
+    // 1 for normal, 2 for emphasized
+    Double(u8),
+    Color(Color),
+    SimpleText(String),
+    Text(String),
+    Point(Point),
+    // None is a pass
+    Move(Option<Point>),
+    Compose(Box<PropValue>, Box<PropValue>),
+    // the values of a list or elist property
+    List(Vec<PropValue>),
+}
+
+impl PropValue {
+    pub fn compose(a: PropValue, b: PropValue) -> PropValue {
+        PropValue::Compose(Box::new(a), Box::new(b))
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            PropValue::None => "none",
+            PropValue::Number(_) => "number",
+            PropValue::Real(_) => "real",
+            PropValue::Double(_) => "double",
+            PropValue::Color(_) => "color",
+            PropValue::SimpleText(_) => "SimpleText",
+            PropValue::Text(_) => "Text",
+            PropValue::Point(_) => "point",
+            PropValue::Move(_) => "move",
+            PropValue::Compose(..) => "composed value",
+            PropValue::List(values) if values.is_empty() => "an empty list",
+            PropValue::List(_) => "list",
+        }
+    }
+
+    // the text between the brackets, escaped. composed values also need
+    // their ':' escaped
+    fn format(&self, composed: bool) -> String {
+        match self {
+            PropValue::None | PropValue::Move(None) => String::new(),
+            PropValue::Number(n) => n.to_string(),
+            // 6.5 stays 6.5 and 7.0 is written 7
+            PropValue::Real(r) => r.to_string(),
+            PropValue::Double(d) => d.to_string(),
+            PropValue::Color(c) => c.ident().to_string(),
+            PropValue::SimpleText(s) | PropValue::Text(s) => escape_text(s, composed),
+            PropValue::Point(p) | PropValue::Move(Some(p)) => p.to_sgf(),
+            PropValue::Compose(a, b) => format!("{}:{}", a.format(true), b.format(true)),
+            PropValue::List(values) => values.iter().map(|v| v.format(composed)).collect::<Vec<_>>().join(","),
+        }
+    }
+
+    // the values to write, one per bracket
+    pub fn to_values(&self) -> Vec<String> {
+        match self {
+            PropValue::List(values) => values.iter().map(|v| v.format(false)).collect(),
+            _ => vec![self.format(false)],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownProperty(String),
+    Mismatch { ident: String, expected: &'static str, found: &'static str },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownProperty(ident) => write!(f, "{} is not a known property", ident),
+            Error::Mismatch { ident, expected, found } => write!(f, "{} takes {}, not {}", ident, expected, found),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// the spec's notation for value types, as the registry writes them
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ValueType {
+    None,
+    Number(Option<(i64, i64)>),
+    Real,
+    Double,
+    Color,
+    SimpleText,
+    Text,
+    Point,
+    Move,
+    Compose(Box<ValueType>, Box<ValueType>),
+    // an elist may be empty
+    List(Box<ValueType>, bool),
+}
+
+impl ValueType {
+    // the alternatives of a type like "number | composed number ':' number"
+    pub(crate) fn parse(notation: &str) -> Vec<ValueType> {
+        notation.split(" | ").filter_map(|alt| ValueType::parse_one(alt.trim())).collect()
+    }
+
+    fn parse_one(s: &str) -> Option<ValueType> {
+        if let Some(rest) = s.strip_prefix("list of ") {
+            return Some(ValueType::List(Box::new(ValueType::parse_one(rest)?), false));
+        }
+        if let Some(rest) = s.strip_prefix("elist of ") {
+            return Some(ValueType::List(Box::new(ValueType::parse_one(rest)?), true));
+        }
+        if let Some(rest) = s.strip_prefix("composed ") {
+            let (a, b) = rest.split_once(" ':' ")?;
+            return Some(ValueType::Compose(Box::new(ValueType::parse_one(a)?), Box::new(ValueType::parse_one(b)?)));
+        }
+        if let Some(range) = s.strip_prefix("number (").and_then(|r| r.strip_suffix(')')) {
+            let (lo, hi) = range.split_once('-')?;
+            return Some(ValueType::Number(Some((lo.parse().ok()?, hi.parse().ok()?))));
+        }
+        match s {
+            "none" => Some(ValueType::None),
+            "number" => Some(ValueType::Number(None)),
+            "real" => Some(ValueType::Real),
+            "double" => Some(ValueType::Double),
+            "color" => Some(ValueType::Color),
+            "SimpleText" => Some(ValueType::SimpleText),
+            "Text" => Some(ValueType::Text),
+            "point" => Some(ValueType::Point),
+            "move" => Some(ValueType::Move),
+            _ => None,
+        }
+    }
+
+    // numbers are fine where reals go, and a point is a move. only an
+    // elist may be empty
+    fn accepts(&self, value: &PropValue) -> bool {
+        match (self, value) {
+            (ValueType::None, PropValue::None) => true,
+            (ValueType::Number(range), PropValue::Number(n)) => range.is_none_or(|(lo, hi)| (lo..=hi).contains(n)),
+            (ValueType::Real, PropValue::Real(r)) => r.is_finite(),
+            (ValueType::Real, PropValue::Number(_)) => true,
+            (ValueType::Double, PropValue::Double(d)) => matches!(d, 1 | 2),
+            (ValueType::Color, PropValue::Color(_)) => true,
+            (ValueType::SimpleText, PropValue::SimpleText(_)) => true,
+            (ValueType::Text, PropValue::Text(_) | PropValue::SimpleText(_)) => true,
+            (ValueType::Point, PropValue::Point(_)) => true,
+            (ValueType::Move, PropValue::Move(_) | PropValue::Point(_)) => true,
+            (ValueType::Compose(a, b), PropValue::Compose(x, y)) => a.accepts(x) && b.accepts(y),
+            (ValueType::List(of, elist), PropValue::List(values)) => (*elist || !values.is_empty()) && values.iter().all(|v| of.accepts(v)),
+            // a single value for a list of one
+            (ValueType::List(of, _), value) => of.accepts(value),
+            _ => false,
+        }
+    }
+}
+
+impl Node {
+    // sets a property from a value checked against the registry's type for
+    // it, so KM[banana] or a list in SZ can't be written by mistake. private
+    // properties have no type to check and are refused; use set for those
+    pub fn set_typed(&mut self, ident: &str, value: PropValue) -> Result<()> {
+        let info = describe(ident).ok_or_else(|| Error::UnknownProperty(ident.to_string()))?;
+        let types = ValueType::parse(info.value_type);
+        if !types.iter().any(|t| t.accepts(&value)) {
+            return Err(Error::Mismatch {
+                ident: ident.to_string(),
+                expected: info.value_type,
+                found: value.kind(),
+            });
+        }
+        self.set(ident, value.to_values());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn value1() {
+        let mut gt = Parser::new("(;GM[1])").parse().unwrap().gametrees.remove(0);
+        let root = &mut gt.sequence.nodes[0];
+        root.set_typed("KM", PropValue::Real(6.5)).unwrap();
+        root.set_typed("HA", PropValue::Number(2)).unwrap();
+        root.set_typed("SZ", PropValue::compose(PropValue::Number(19), PropValue::Number(13))).unwrap();
+        root.set_typed("AP", PropValue::compose(PropValue::SimpleText("a:b".to_string()), PropValue::SimpleText("1]".to_string()))).unwrap();
+        root.set_typed("AB", PropValue::List(vec![PropValue::Point(Point::new(3, 3)), PropValue::Point(Point::new(15, 15))])).unwrap();
+        root.set_typed("C", PropValue::Text("a ] b".to_string())).unwrap();
+        root.set_typed("TE", PropValue::Double(2)).unwrap();
+        root.set_typed("DD", PropValue::List(Vec::new())).unwrap();
+        assert_eq!(root.to_string(), ";GM[1]KM[6.5]HA[2]SZ[19:13]AP[a\\:b:1\\]]AB[dd][pp]C[a \\] b]TE[2]DD[]");
+        assert!(matches!(root.set_typed("KM", PropValue::SimpleText("banana".to_string())), Err(Error::Mismatch { .. })));
+        assert!(root.set_typed("KM", PropValue::Real(f64::NAN)).is_err());
+        assert!(root.set_typed("FF", PropValue::Number(5)).is_err());
+        assert!(root.set_typed("SZ", PropValue::List(vec![PropValue::Number(19)])).is_err());
+        assert!(matches!(root.set_typed("AW", PropValue::List(Vec::new())), Err(Error::Mismatch { found: "an empty list", .. })));
+        assert!(matches!(root.set_typed("XX", PropValue::None), Err(Error::UnknownProperty(_))));
+        root.set_typed("KM", PropValue::Real(7.0)).unwrap();
+        root.set_typed("W", PropValue::Move(None)).unwrap();
+        assert_eq!(root.value("KM"), Some("7"));
+        assert_eq!(root.value("W"), Some(""));
+    }
+}
@@ -0,0 +1,201 @@
+use super::info::is_pass;
+use super::point::{parse_point_list, POINT_LISTS};
+use super::properties::{is_ff3_only, is_ff4_only, is_standard};
+use super::validate::{Diagnostic, Kind};
+use super::writer::{escape_text, unescape_text};
+use super::vertex::{GameTree, Node, Property};
+
+// the FF a game is in when it doesn't say
+pub const DEFAULT: u8 = 1;
+
+pub fn parse(value: &str) -> Option<u8> {
+    match value.trim().parse() {
+        Ok(ff @ 1..=4) => Some(ff),
+        _ => None,
+    }
+}
+
+impl GameTree {
+    // the file format version from FF, FF[1] when missing or unreadable
+    pub fn version(&self) -> u8 {
+        self.root().value("FF").and_then(parse).unwrap_or(DEFAULT)
+    }
+
+    // rewrites the tree for another FF: passes, compressed point lists
+    // and the FF[3] markup idents are converted and FF is set. properties
+    // with no counterpart are left for the validator to point out
+    pub fn to_version(&self, ff: u8) -> Self {
+        let (cols, rows) = self.board_size();
+        let mut gt = convert(self, ff, cols, rows);
+        gt.root_mut().set("FF", vec![ff.to_string()]);
+        gt
+    }
+
+    // brings an old game up to FF[4]. properties FF[4] has no place for
+    // are moved into the node's comment as text, like "CH[1]", so their
+    // content survives for anyone reading the game. games already in
+    // FF[4] are returned as they are
+    pub fn upgrade(&self) -> Self {
+        if self.version() >= 4 {
+            return self.clone();
+        }
+        let mut gt = self.to_version(4);
+        comment_unknown(&mut gt);
+        let root = gt.root_mut();
+        if !root.has("GM") {
+            root.set("GM", vec!["1".to_string()]);
+        }
+        gt
+    }
+}
+
+fn comment_unknown(gt: &mut GameTree) {
+    for node in &mut gt.sequence.nodes {
+        node.comment_unknown();
+    }
+    for child in &mut gt.gametrees {
+        comment_unknown(child);
+    }
+}
+
+impl Node {
+    pub fn comment_unknown(&mut self) {
+        let mut lines = Vec::new();
+        self.props.retain(|prop| {
+            if is_standard(&prop.ident) {
+                return true;
+            }
+            let values: Vec<String> = prop.values.iter().map(|v| unescape_text(v)).collect();
+            lines.push(format!("{}[{}]", prop.ident, values.join("][")));
+            false
+        });
+        if lines.is_empty() {
+            return;
+        }
+        let kept = escape_text(&lines.join("\n"), false);
+        let comment = match self.value("C") {
+            Some(c) if !c.is_empty() => format!("{}\n\n{}", c, kept),
+            _ => kept,
+        };
+        self.set("C", vec![comment]);
+    }
+}
+
+fn convert(gt: &GameTree, ff: u8, cols: usize, rows: usize) -> GameTree {
+    gt.map_nodes(|node| convert_node(node, ff, cols, rows))
+}
+
+// the letters of the nth label: A to Z, then AA to AZ, BA and on, as
+// columns go in a spreadsheet
+fn letters(n: usize) -> String {
+    let mut s = Vec::new();
+    let mut n = n + 1;
+    while n > 0 {
+        n -= 1;
+        s.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    s.iter().rev().map(|b| *b as char).collect()
+}
+
+pub(crate) fn convert_node(node: &Node, ff: u8, cols: usize, rows: usize) -> Node {
+    let mut props = Vec::new();
+    for prop in &node.props {
+        let ident = prop.ident.as_str();
+        let mut prop = prop.clone();
+        let is_move = ident == "B" || ident == "W";
+        if ff >= 4 {
+            if is_move && prop.values.first().is_some_and(|v| is_pass(v, cols, rows)) {
+                prop.values = vec![String::new()];
+            }
+            match ident {
+                "M" => prop.ident = "MA".to_string(),
+                // FF[3] labels were lettered in order
+                "L" => {
+                    prop.ident = "LB".to_string();
+                    prop.values = prop.values.iter().enumerate().map(|(i, p)| format!("{}:{}", p, letters(i))).collect();
+                },
+                _ => {},
+            }
+        } else {
+            // FF[3] has no empty pass, and tt is only off the board up to 19x19
+            if is_move && prop.values.first().is_some_and(|v| v.is_empty()) && cols <= 19 && rows <= 19 {
+                prop.values = vec!["tt".to_string()];
+            }
+            if POINT_LISTS.contains(&ident) && prop.values.iter().any(|v| v.contains(':')) {
+                prop.values = parse_point_list(&prop.values).iter().map(|p| p.to_sgf()).collect();
+            }
+        }
+        props.push(prop);
+    }
+    Node { props }
+}
+
+// what in the tree isn't allowed in the given FF
+pub fn check_version(game: usize, gt: &GameTree, ff: u8) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (path, node) in gt.nodes() {
+        for Property { ident, values } in &node.props {
+            let ident = ident.as_str();
+            let msg = if ff >= 4 && is_ff3_only(ident) {
+                format!("{} was dropped in FF[4]", ident)
+            } else if ff < 4 && is_ff4_only(ident) {
+                format!("{} is new in FF[4]", ident)
+            } else if ff < 4 && (ident == "B" || ident == "W") && values.first().is_some_and(|v| v.is_empty()) {
+                format!("{}[] passes are new in FF[4]", ident)
+            } else if ff < 4 && POINT_LISTS.contains(&ident) && values.iter().any(|v| v.contains(':')) {
+                format!("compressed point lists in {} are new in FF[4]", ident)
+            } else {
+                continue;
+            };
+            diagnostics.push(Diagnostic::new(Kind::NotInVersion, game, path.clone(), msg));
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn gametree(text: &str) -> GameTree {
+        Parser::new(text).parse().unwrap().gametrees.remove(0)
+    }
+
+    #[test]
+    fn version1() {
+        let gt = gametree("(;FF[3]AddBlack[aa]GaMe[1];B[tt]L[bb][cc]M[dd])");
+        assert_eq!(gt.version(), 3);
+        assert_eq!(gt.to_string(), "(;FF[3]AB[aa]GM[1];B[tt]L[bb][cc]M[dd])");
+        assert_eq!(gt.to_version(4).to_string(), "(;FF[4]AB[aa]GM[1];B[]LB[bb:A][cc:B]MA[dd])");
+        // past Z labels go on with two letters
+        let points: String = (0..30).map(|i| format!("[{}a]", (b'a' + (i % 19) as u8) as char)).collect();
+        let upgraded = gametree(&format!("(;FF[3];L{})", points)).to_version(4).to_string();
+        assert!(upgraded.contains("[ga:Z][ha:AA]") && upgraded.ends_with("[ka:AD])"));
+        assert_eq!((letters(0), letters(701), letters(702)), ("A".to_string(), "ZZ".to_string(), "AAA".to_string()));
+        // lowercase letters are an error from FF[4] on
+        assert!(Parser::new("(;FF[4]AddBlack[aa])").parse().is_err());
+        assert_eq!(gametree("(;GM[1])").version(), 1);
+    }
+
+    #[test]
+    fn upgrade1() {
+        let gt = gametree("(;FF[1]SZ[9]ID[x\\]y];B[tt]CH[1]EL[-3]C[hm];W[ee]L[aa][bb])");
+        assert_eq!(gt.upgrade().to_string(), "(;FF[4]SZ[9]C[ID[x\\]y\\]]GM[1];B[]C[hm\n\nCH[1\\]\nEL[-3\\]];W[ee]LB[aa:A][bb:B])");
+        assert!(check_version(0, &gt.upgrade(), 4).is_empty());
+        let gt = gametree("(;FF[4]KGSDE[x])");
+        assert_eq!(gt.upgrade().to_string(), "(;FF[4]KGSDE[x])");
+    }
+
+    #[test]
+    fn version2() {
+        let gt = gametree("(;FF[4]CA[UTF-8]AB[aa:bb];B[]TR[cc])");
+        let kinds: Vec<String> = check_version(0, &gt, 3).iter().map(|d| d.message.clone()).collect();
+        assert_eq!(kinds, vec!["CA is new in FF[4]", "compressed point lists in AB are new in FF[4]", "B[] passes are new in FF[4]"]);
+        let old = gt.to_version(3);
+        assert_eq!(old.to_string(), "(;FF[3]CA[UTF-8]AB[aa][ba][ab][bb];B[tt]TR[cc])");
+        assert_eq!(check_version(0, &old, 3).len(), 1);
+        assert!(check_version(0, &gametree("(;FF[3]M[aa])"), 4).len() == 1);
+    }
+}
@@ -1,17 +1,131 @@
 use std::fmt;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    White,
+}
+
+impl Color {
+    pub fn from_ident(ident: &str) -> Option<Color> {
+        match ident {
+            "B" => Some(Color::Black),
+            "W" => Some(Color::White),
+            _ => None,
+        }
+    }
+
+    pub fn ident(&self) -> &'static str {
+        match self {
+            Color::Black => "B",
+            Color::White => "W",
+        }
+    }
+
+    pub fn opponent(&self) -> Color {
+        match self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Collection {
     pub gametrees: Vec<GameTree>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GameTree {
     pub sequence: Sequence,
     pub gametrees: Vec<Box<GameTree>>,
 }
 
 impl GameTree {
+    pub fn root(&self) -> &Node {
+        &self.sequence.nodes[0]
+    }
+
+    pub fn root_mut(&mut self) -> &mut Node {
+        &mut self.sequence.nodes[0]
+    }
+
+    // every node in preorder with its path. a path lists, for each step
+    // down from the root, which child was taken: inside a sequence the
+    // only child is 0, at the end of one it is the variation number
+    pub fn nodes(&self) -> Vec<(Vec<usize>, &Node)> {
+        let mut nodes = Vec::new();
+        self.collect_nodes(Vec::new(), &mut nodes);
+        nodes
+    }
+
+    fn collect_nodes<'a>(&'a self, mut path: Vec<usize>, nodes: &mut Vec<(Vec<usize>, &'a Node)>) {
+        for (i, node) in self.sequence.nodes.iter().enumerate() {
+            if i > 0 {
+                path.push(0);
+            }
+            nodes.push((path.clone(), node));
+        }
+        for (i, gt) in self.gametrees.iter().enumerate() {
+            let mut p = path.clone();
+            p.push(i);
+            gt.collect_nodes(p, nodes);
+        }
+    }
+
+    // the node at a path as given by nodes()
+    pub fn node(&self, path: &[usize]) -> Option<&Node> {
+        let mut gt = self;
+        let mut i = 0;
+        for &step in path {
+            if i + 1 < gt.sequence.nodes.len() {
+                if step != 0 {
+                    return None;
+                }
+                i += 1;
+            } else {
+                gt = gt.gametrees.get(step)?;
+                i = 0;
+            }
+        }
+        gt.sequence.nodes.get(i)
+    }
+
+    pub fn node_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        let mut gt = self;
+        let mut i = 0;
+        for &step in path {
+            if i + 1 < gt.sequence.nodes.len() {
+                if step != 0 {
+                    return None;
+                }
+                i += 1;
+            } else {
+                gt = gt.gametrees.get_mut(step)?;
+                i = 0;
+            }
+        }
+        gt.sequence.nodes.get_mut(i)
+    }
+
+    // the nodes of the main line, always following the first variation
+    pub fn mainline(&self) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        let mut gt = self;
+        loop {
+            for node in &gt.sequence.nodes {
+                nodes.push(node);
+            }
+            match gt.gametrees.first() {
+                Some(next) => gt = next,
+                None => break,
+            }
+        }
+        nodes
+    }
+
     pub fn strip_key(&self, key: &str) -> Self {
         let mut gametrees = Vec::new();
         for gt in &self.gametrees {
@@ -19,12 +133,13 @@ impl GameTree {
         }
         GameTree{
             sequence: self.sequence.strip_key(key),
-            gametrees: gametrees,
+            gametrees,
         }
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sequence {
     pub nodes: Vec<Node>,
 }
@@ -36,29 +151,73 @@ impl Sequence {
             nodes.push(node.strip_key(key));
         }
         Sequence{
-            nodes: nodes,
+            nodes,
         }
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub props: Vec<Property>,
 }
 
 impl Node {
+    pub fn get(&self, ident: &str) -> Option<&Property> {
+        self.props.iter().find(|p| p.ident == ident)
+    }
+
+    pub fn get_mut(&mut self, ident: &str) -> Option<&mut Property> {
+        self.props.iter_mut().find(|p| p.ident == ident)
+    }
+
+    // first value of the property, which is all most single-valued
+    // properties (PB, KM, RE, ...) ever have
+    pub fn value(&self, ident: &str) -> Option<&str> {
+        self.get(ident).and_then(|p| p.values.first()).map(|v| v.as_str())
+    }
+
+    pub fn has(&self, ident: &str) -> bool {
+        self.get(ident).is_some()
+    }
+
+    // replaces the values of an existing property, or appends a new one
+    pub fn set(&mut self, ident: &str, values: Vec<String>) {
+        match self.get_mut(ident) {
+            Some(prop) => prop.values = values,
+            None => self.props.push(Property{ident: ident.to_string(), values}),
+        }
+    }
+
+    pub fn remove(&mut self, ident: &str) -> Option<Property> {
+        let i = self.props.iter().position(|p| p.ident == ident)?;
+        Some(self.props.remove(i))
+    }
+
+    // the color and value of the B or W property, if this node is a move
+    pub fn get_move(&self) -> Option<(Color, &str)> {
+        for prop in &self.props {
+            if let Some(color) = Color::from_ident(&prop.ident) {
+                let value = prop.values.first().map(|v| v.as_str()).unwrap_or("");
+                return Some((color, value));
+            }
+        }
+        None
+    }
+
     pub fn strip_key(&self, key: &str) -> Self {
         let mut props = Vec::new();
         for prop in &self.props {
             props.push(prop.strip_key(key));
         }
         Node{
-            props: props,
+            props,
         }
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Property {
     pub ident: String,
     pub values: Vec<String>,
@@ -76,7 +235,7 @@ impl Property {
         }
         Property{
             ident: self.ident.clone(),
-            values: values,
+            values,
         }
     }
 }
@@ -93,13 +252,74 @@ impl fmt::Display for Collection {
 
 impl fmt::Display for GameTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut s = "(".to_owned();
-        s.push_str(&format!("{}", self.sequence));
-        for gt in &self.gametrees {
-            s.push_str(&format!("{}", gt));
+        let mut s = String::new();
+        self.write_into(&mut s, usize::MAX);
+        f.write_str(&s)
+    }
+}
+
+impl GameTree {
+    // writes the tree to s with a stack of its own rather than by
+    // recursion, so games of any depth can be written. gives up, with
+    // false, as soon as s is longer than max
+    pub(crate) fn write_into(&self, s: &mut String, max: usize) -> bool {
+        // None closes the gametree opened before it
+        let mut stack = vec![Some(self)];
+        while let Some(step) = stack.pop() {
+            let Some(gt) = step else {
+                s.push(')');
+                continue;
+            };
+            s.push('(');
+            // a variation without nodes can't be parsed back, so it gets
+            // an empty one while the tree is being edited
+            if gt.sequence.nodes.is_empty() {
+                s.push(';');
+            }
+            for node in &gt.sequence.nodes {
+                s.push_str(&node.to_string());
+                if s.len() > max {
+                    return false;
+                }
+            }
+            stack.push(None);
+            stack.extend(gt.gametrees.iter().rev().map(|child| Some(child.as_ref())));
+        }
+        s.len() <= max
+    }
+
+    // a copy of the tree with f applied to every node, built with a stack
+    // of its own so trees of any depth can be copied
+    pub(crate) fn map_nodes<F: FnMut(&Node) -> Node>(&self, mut f: F) -> GameTree {
+        fn shallow<F: FnMut(&Node) -> Node>(gt: &GameTree, f: &mut F) -> GameTree {
+            GameTree {
+                sequence: Sequence { nodes: gt.sequence.nodes.iter().map(f).collect() },
+                gametrees: Vec::with_capacity(gt.gametrees.len()),
+            }
+        }
+        // each copy with the children of the original still to go
+        let mut open = vec![(shallow(self, &mut f), self.gametrees.iter())];
+        loop {
+            let (_, children) = open.last_mut().unwrap();
+            if let Some(child) = children.next() {
+                open.push((shallow(child, &mut f), child.gametrees.iter()));
+                continue;
+            }
+            let (done, _) = open.pop().unwrap();
+            match open.last_mut() {
+                Some((parent, _)) => parent.gametrees.push(Box::new(done)),
+                None => return done,
+            }
+        }
+    }
+
+    // drops the tree a gametree at a time, as the derived drop recurses
+    // and runs out of stack on very deep trees
+    pub(crate) fn dispose(self) {
+        let mut stack = vec![self];
+        while let Some(mut gt) = stack.pop() {
+            stack.extend(gt.gametrees.drain(..).map(|child| *child));
         }
-        s.push_str(")");
-        write!(f, "{}", s)
     }
 }
 
@@ -125,11 +345,72 @@ impl fmt::Display for Node {
 
 impl fmt::Display for Property {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // half edited properties are written so the output still parses:
+        // no ident drops the property and no values becomes one empty value
+        if self.ident.is_empty() {
+            return Ok(());
+        }
         let mut s = "".to_owned();
         s.push_str(&self.ident);
+        if self.values.is_empty() {
+            s.push_str("[]");
+        }
         for value in &self.values {
-            s.push_str(&format!("[{}]", value));
+            s.push_str(&format!("[{}]", balance_escapes(value)));
         }
         write!(f, "{}", s)
     }
 }
+
+// escapes any ']' that would end the value early and a trailing lone
+// backslash, leaving well formed values untouched
+fn balance_escapes(value: &str) -> std::borrow::Cow<'_, str> {
+    let mut escaped = false;
+    let mut ok = true;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ']' {
+            ok = false;
+        }
+    }
+    if ok && !escaped {
+        return std::borrow::Cow::Borrowed(value);
+    }
+    let mut s = String::with_capacity(value.len() + 2);
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ']' {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    if escaped {
+        s.push('\\');
+    }
+    std::borrow::Cow::Owned(s)
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn serde1() {
+        let coll = Parser::new("(;GM[1]C[a\\]b];B[aa](;W[bb])(;W[cc]AB[dd][ee]))").parse().unwrap();
+        let json = serde_json::to_string(&coll).unwrap();
+        assert_eq!(json, coll.to_json());
+        assert!(json.starts_with(r#"{"gametrees":[{"sequence":{"nodes":[{"props":[{"ident":"GM","values":["1"]}"#));
+        let back: Collection = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_string(), coll.to_string());
+        let node: Node = serde_json::from_str(r#"{"props":[{"ident":"B","values":["aa"]}]}"#).unwrap();
+        assert_eq!(node.value("B"), Some("aa"));
+    }
+}
@@ -0,0 +1,295 @@
+use std::fmt;
+use std::ops::Range;
+
+use super::vertex::GameTree;
+
+// ST tells viewers how the author wants variations shown
+//   bit 0: 0 = variations of the successor node (children)
+//          1 = variations of the current node (siblings)
+//   bit 1: 1 = no automatic board markup for variations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VariationDisplay {
+    Children,
+    Siblings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub variations: VariationDisplay,
+    pub markup: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            variations: VariationDisplay::Children,
+            markup: true,
+        }
+    }
+}
+
+impl Style {
+    pub fn parse(s: &str) -> Option<Style> {
+        let n: u8 = s.trim().parse().ok()?;
+        if n > 3 {
+            return None;
+        }
+        let variations = if n & 1 == 0 {
+            VariationDisplay::Children
+        } else {
+            VariationDisplay::Siblings
+        };
+        Some(Style {
+            variations,
+            markup: n & 2 == 0,
+        })
+    }
+
+    pub fn number(&self) -> u8 {
+        let mut n = 0;
+        if self.variations == VariationDisplay::Siblings {
+            n |= 1;
+        }
+        if !self.markup {
+            n |= 2;
+        }
+        n
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+impl GameTree {
+    // ST is a root property; missing or malformed values fall back
+    // to the spec default
+    pub fn style(&self) -> Style {
+        match self.root().value("ST") {
+            Some(v) => Style::parse(v).unwrap_or_default(),
+            None => Style::default(),
+        }
+    }
+}
+
+// FG flags, where a set bit turns the feature off
+const FG_NO_COORDINATES: u32 = 1;
+const FG_NO_NAME: u32 = 2;
+const FG_NO_HIDDEN_MOVES: u32 = 4;
+const FG_KEEP_CAPTURES: u32 = 256;
+const FG_NO_HOSHI: u32 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FigureFlags {
+    pub coordinates: bool,
+    pub name: bool,
+    // list the moves that can't be shown in the diagram below it
+    pub hidden_moves: bool,
+    pub remove_captures: bool,
+    pub hoshi: bool,
+}
+
+impl Default for FigureFlags {
+    fn default() -> Self {
+        FigureFlags::from_bits(0)
+    }
+}
+
+impl FigureFlags {
+    pub fn from_bits(n: u32) -> Self {
+        FigureFlags {
+            coordinates: n & FG_NO_COORDINATES == 0,
+            name: n & FG_NO_NAME == 0,
+            hidden_moves: n & FG_NO_HIDDEN_MOVES == 0,
+            remove_captures: n & FG_KEEP_CAPTURES == 0,
+            hoshi: n & FG_NO_HOSHI == 0,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        let mut n = 0;
+        if !self.coordinates {
+            n |= FG_NO_COORDINATES;
+        }
+        if !self.name {
+            n |= FG_NO_NAME;
+        }
+        if !self.hidden_moves {
+            n |= FG_NO_HIDDEN_MOVES;
+        }
+        if !self.remove_captures {
+            n |= FG_KEEP_CAPTURES;
+        }
+        if !self.hoshi {
+            n |= FG_NO_HOSHI;
+        }
+        n
+    }
+}
+
+// PM: how move numbers are printed, inherited down the tree
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PrintMode {
+    Hidden,
+    #[default]
+    Plain,
+    Modulo100,
+}
+
+impl PrintMode {
+    pub fn parse(s: &str) -> Option<PrintMode> {
+        match s.trim() {
+            "0" => Some(PrintMode::Hidden),
+            "1" => Some(PrintMode::Plain),
+            "2" => Some(PrintMode::Modulo100),
+            _ => None,
+        }
+    }
+
+    // the label to print on a stone with the given move number
+    pub fn label(&self, n: usize) -> Option<String> {
+        match self {
+            PrintMode::Hidden => None,
+            PrintMode::Plain => Some(n.to_string()),
+            PrintMode::Modulo100 => {
+                let m = n % 100;
+                if m == 0 && n > 0 {
+                    Some("100".to_string())
+                } else {
+                    Some(m.to_string())
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Figure {
+    pub name: Option<String>,
+    pub flags: FigureFlags,
+    // indices into the main line
+    pub nodes: Range<usize>,
+    pub print_mode: PrintMode,
+}
+
+impl Figure {
+    // what to print under the figure: its name, or Figure and its number
+    // from 1 when it has none or its flags hide it
+    pub fn caption(&self, number: usize) -> String {
+        match &self.name {
+            Some(name) if self.flags.name => name.clone(),
+            _ => format!("Figure {}", number),
+        }
+    }
+
+    // FG[] or FG[flags:name]
+    fn parse(value: &str, nodes: Range<usize>, print_mode: PrintMode) -> Self {
+        let (flags, name) = match value.split_once(':') {
+            Some((flags, name)) => (flags.trim().parse().unwrap_or(0), Some(name.to_string())),
+            None => (0, None),
+        };
+        Figure {
+            name,
+            flags: FigureFlags::from_bits(flags),
+            nodes,
+            print_mode,
+        }
+    }
+}
+
+impl GameTree {
+    // splits the main line into figures: a new figure starts at each
+    // node carrying FG. nodes before the first FG form an unnamed figure
+    pub fn figures(&self) -> Vec<Figure> {
+        let mainline = self.mainline();
+        let mut figures: Vec<Figure> = Vec::new();
+        let mut print_mode = PrintMode::default();
+        for (i, node) in mainline.iter().enumerate() {
+            if let Some(pm) = node.value("PM").and_then(PrintMode::parse) {
+                print_mode = pm;
+            }
+            let fg = node.value("FG");
+            if fg.is_none() && !figures.is_empty() {
+                continue;
+            }
+            if let Some(last) = figures.last_mut() {
+                last.nodes.end = i;
+            }
+            figures.push(Figure::parse(fg.unwrap_or(""), i..mainline.len(), print_mode));
+        }
+        figures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn style(text: &str) -> Style {
+        let coll = Parser::new(text).parse().unwrap();
+        coll.gametrees[0].style()
+    }
+
+    #[test]
+    fn style1() {
+        assert_eq!(style("(;GM[1])"), Style::default());
+        assert_eq!(style("(;GM[1]ST[banana])"), Style::default());
+        assert_eq!(style("(;GM[1]ST[7])"), Style::default());
+    }
+
+    #[test]
+    fn style2() {
+        let s = style("(;GM[1]ST[2])");
+        assert_eq!(s.variations, VariationDisplay::Children);
+        assert!(!s.markup);
+        let s = style("(;GM[1]ST[1])");
+        assert_eq!(s.variations, VariationDisplay::Siblings);
+        assert!(s.markup);
+    }
+
+    #[test]
+    fn figure1() {
+        let coll = Parser::new("(;GM[1];B[aa];W[bb])").parse().unwrap();
+        let figures = coll.gametrees[0].figures();
+        assert_eq!(figures.len(), 1);
+        assert_eq!(figures[0].nodes, 0..3);
+        assert_eq!(figures[0].flags, FigureFlags::default());
+    }
+
+    #[test]
+    fn figure2() {
+        let text = "(;GM[1]PM[2];B[aa];W[bb]FG[257:Figure 2]PM[0];B[cc](;W[dd]FG[])(;W[ee]))";
+        let coll = Parser::new(text).parse().unwrap();
+        let figures = coll.gametrees[0].figures();
+        assert_eq!(figures.len(), 3);
+        assert_eq!(figures[0].nodes, 0..2);
+        assert_eq!(figures[0].print_mode, PrintMode::Modulo100);
+        assert_eq!(figures[1].nodes, 2..4);
+        assert_eq!(figures[1].name.as_deref(), Some("Figure 2"));
+        assert!(!figures[1].flags.coordinates);
+        assert!(!figures[1].flags.remove_captures);
+        assert_eq!(figures[1].print_mode, PrintMode::Hidden);
+        assert_eq!(figures[2].nodes, 4..5);
+        assert_eq!(figures[2].name, None);
+    }
+
+    #[test]
+    fn printmode1() {
+        assert_eq!(PrintMode::Hidden.label(5), None);
+        assert_eq!(PrintMode::Plain.label(105).unwrap(), "105");
+        assert_eq!(PrintMode::Modulo100.label(105).unwrap(), "5");
+        assert_eq!(PrintMode::Modulo100.label(200).unwrap(), "100");
+        assert_eq!(FigureFlags::from_bits(773).bits(), 773);
+    }
+
+    #[test]
+    fn style3() {
+        for n in 0..4 {
+            let s = Style::parse(&n.to_string()).unwrap();
+            assert_eq!(s.number(), n);
+        }
+    }
+}
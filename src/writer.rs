@@ -0,0 +1,550 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::text::wrap_node;
+use super::version::convert_node;
+use super::vertex::{Collection, GameTree, Node, Sequence};
+
+pub const APP_NAME: &str = "rustsgf";
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// what to do with the AP (application) root property when writing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppStamp {
+    // leave AP exactly as it was read
+    Keep,
+    // only add our AP to games that don't have one
+    Preserve,
+    // overwrite whatever AP was there with ours
+    Replace,
+    // put ours in AP and move the programs that wrote the game before to
+    // PREVIOUS_APPS, as AP takes one value
+    Chain,
+}
+
+// the private root property Chain keeps earlier AP values in, oldest
+// first. SGF has no property for the history of a file
+pub const PREVIOUS_APPS: &str = "XAP";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    // the output would be bigger than the limit, in bytes
+    TooLarge(usize),
+    // a line of play is longer than the limit, in nodes
+    TooDeep(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::TooLarge(n) => write!(f, "output larger than {} bytes", n),
+            Error::TooDeep(n) => write!(f, "tree deeper than {} nodes", n),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+// properties holding Text or SimpleText
+pub const TEXT_PROPERTIES: [&str; 23] = [
+    "AN", "BR", "BT", "C", "CA", "CP", "DT", "EV", "GC", "GN", "N", "ON", "OT", "PB", "PC", "PW", "RE", "RO", "RU", "SO", "US", "WR", "WT",
+];
+
+// properties composing a point, number or name with SimpleText after
+// the ':'
+pub const COMPOSED_TEXT_PROPERTIES: [&str; 3] = ["AP", "FG", "LB"];
+
+// rewrites text on its way out, given the property ident and the
+// unescaped text
+pub type TextHook = Arc<dyn Fn(&str, &str) -> String + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Writer {
+    pub app: AppStamp,
+    pub text_hook: Option<TextHook>,
+    // guards for pipelines that would rather fail than emit a runaway
+    // tree. only the checked writes (try_write, write_file, live) apply them
+    pub max_bytes: Option<usize>,
+    pub max_depth: Option<usize>,
+    // the FF to write games in, converting them as needed
+    pub version: Option<u8>,
+    // soft-wraps C and GC at this many characters
+    pub wrap: Option<usize>,
+}
+
+impl fmt::Debug for Writer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("app", &self.app)
+            .field("text_hook", &self.text_hook.is_some())
+            .field("max_bytes", &self.max_bytes)
+            .field("max_depth", &self.max_depth)
+            .field("version", &self.version)
+            .field("wrap", &self.wrap)
+            .finish()
+    }
+}
+
+impl Default for Writer {
+    fn default() -> Self {
+        Writer::new(AppStamp::Keep)
+    }
+}
+
+// whether a line of play has more than max nodes, walked without
+// recursion and stopping at the first that does
+fn deeper_than(gt: &GameTree, max: usize) -> bool {
+    let mut stack = vec![(gt, 0)];
+    while let Some((gt, above)) = stack.pop() {
+        let depth = above + gt.sequence.nodes.len();
+        if depth > max {
+            return true;
+        }
+        stack.extend(gt.gametrees.iter().map(|child| (child.as_ref(), depth)));
+    }
+    false
+}
+
+impl Writer {
+    pub fn new(app: AppStamp) -> Self {
+        Writer {app, text_hook: None, max_bytes: None, max_depth: None, version: None, wrap: None}
+    }
+
+    pub fn with_max_bytes(mut self, n: usize) -> Self {
+        self.max_bytes = Some(n);
+        self
+    }
+
+    pub fn with_max_depth(mut self, n: usize) -> Self {
+        self.max_depth = Some(n);
+        self
+    }
+
+    pub fn with_version(mut self, ff: u8) -> Self {
+        self.version = Some(ff);
+        self
+    }
+
+    // normalizes the newlines of comments and breaks their long lines
+    // with soft line breaks, which readers drop again
+    pub fn with_wrap(mut self, width: usize) -> Self {
+        self.wrap = Some(width);
+        self
+    }
+
+    // runs every Text and SimpleText value through f while writing, for
+    // scrubbing things like chat logs without touching the tree
+    pub fn with_text_hook<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &str) -> String + Send + Sync + 'static,
+    {
+        self.text_hook = Some(Arc::new(f));
+        self
+    }
+
+    pub fn write(&self, coll: &Collection) -> String {
+        let mut s = "".to_owned();
+        for gt in &coll.gametrees {
+            self.write_into(gt, &mut s, usize::MAX);
+        }
+        s
+    }
+
+    pub fn write_gametree(&self, gt: &GameTree) -> String {
+        let mut s = String::new();
+        self.write_into(gt, &mut s, usize::MAX);
+        s
+    }
+
+    // adds gt to s, and false when that took s over max
+    fn write_into(&self, gt: &GameTree, s: &mut String, max: usize) -> bool {
+        if self.app == AppStamp::Keep && self.text_hook.is_none() && self.version.is_none() && self.wrap.is_none() {
+            return gt.write_into(s, max);
+        }
+        let mut gt = match self.version {
+            Some(ff) => gt.to_version(ff),
+            None => gt.map_nodes(Node::clone),
+        };
+        stamp_app(&mut gt, self.app);
+        if let Some(hook) = &self.text_hook {
+            apply_text_hook(&mut gt, hook.as_ref());
+        }
+        if let Some(width) = self.wrap {
+            wrap_tree(&mut gt, width);
+        }
+        let fits = gt.write_into(s, max);
+        gt.dispose();
+        fits
+    }
+
+    // like write, but refuses to produce output over the limits. the
+    // size is checked as the output is written, so a huge collection
+    // is given up on as soon as it passes the limit
+    pub fn try_write(&self, coll: &Collection) -> Result<String> {
+        if let Some(max) = self.max_depth {
+            if coll.gametrees.iter().any(|gt| deeper_than(gt, max)) {
+                return Err(Error::TooDeep(max));
+            }
+        }
+        let max = self.max_bytes.unwrap_or(usize::MAX);
+        let mut s = "".to_owned();
+        for gt in &coll.gametrees {
+            if !self.write_into(gt, &mut s, max) {
+                return Err(Error::TooLarge(max));
+            }
+        }
+        Ok(s)
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, coll: &Collection, path: P) -> Result<()> {
+        fs::write(path, self.try_write(coll)?)?;
+        Ok(())
+    }
+
+    // starts a game that is written out node by node as it is played
+    pub fn live<W: Write + Seek>(&self, out: W, root: &Node) -> io::Result<LiveWriter<W>> {
+        LiveWriter::new(self.clone(), out, root)
+    }
+}
+
+// writes a single line game as it grows. after every node the output
+// holds a complete, parseable game: each append overwrites the closing
+// paren and writes a new one after the node, so relays and bots can
+// keep a file on disk current without rewriting all of it
+pub struct LiveWriter<W: Write + Seek> {
+    writer: Writer,
+    out: W,
+    start: u64,
+    // where the closing paren is
+    end: u64,
+    nodes: usize,
+    // board size from the root, for converting passes
+    size: (usize, usize),
+}
+
+impl LiveWriter<File> {
+    pub fn create<P: AsRef<Path>>(writer: &Writer, path: P, root: &Node) -> io::Result<Self> {
+        writer.live(File::create(path)?, root)
+    }
+}
+
+impl<W: Write + Seek> LiveWriter<W> {
+    fn new(writer: Writer, mut out: W, root: &Node) -> io::Result<Self> {
+        let mut gt = GameTree {
+            sequence: Sequence { nodes: vec![root.clone()] },
+            gametrees: Vec::new(),
+        };
+        if let Some(ff) = writer.version {
+            gt = gt.to_version(ff);
+        }
+        stamp_app(&mut gt, writer.app);
+        if let Some(hook) = &writer.text_hook {
+            apply_text_hook(&mut gt, hook.as_ref());
+        }
+        if let Some(width) = writer.wrap {
+            wrap_tree(&mut gt, width);
+        }
+        let size = gt.board_size();
+        let start = out.stream_position()?;
+        let head = format!("({}", gt.sequence);
+        out.write_all(head.as_bytes())?;
+        out.write_all(b")")?;
+        out.flush()?;
+        Ok(LiveWriter {
+            writer,
+            out,
+            start,
+            end: start + head.len() as u64,
+            nodes: 1,
+            size,
+        })
+    }
+
+    pub fn append(&mut self, node: &Node) -> io::Result<()> {
+        let mut node = match self.writer.version {
+            Some(ff) => convert_node(node, ff, self.size.0, self.size.1),
+            None => node.clone(),
+        };
+        if let Some(hook) = &self.writer.text_hook {
+            apply_node_hook(&mut node, hook.as_ref());
+        }
+        if let Some(width) = self.writer.wrap {
+            wrap_node(&mut node, width);
+        }
+        let text = node.to_string();
+        self.nodes += 1;
+        if let Some(max) = self.writer.max_depth {
+            if self.nodes > max {
+                return Err(io::Error::other(Error::TooDeep(max).to_string()));
+            }
+        }
+        if let Some(max) = self.writer.max_bytes {
+            if (self.end - self.start) as usize + text.len() + 1 > max {
+                return Err(io::Error::other(Error::TooLarge(max).to_string()));
+            }
+        }
+        self.out.seek(SeekFrom::Start(self.end))?;
+        self.out.write_all(text.as_bytes())?;
+        self.out.write_all(b")")?;
+        self.out.flush()?;
+        self.end += text.len() as u64;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// the text of a value as read: escapes resolved and soft line breaks
+// (a backslash before a newline) removed
+pub fn unescape_text(value: &str) -> String {
+    let mut s = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\n') | None => {},
+            Some(c) => s.push(c),
+        }
+    }
+    s
+}
+
+// the inverse of unescape_text. composed values also need ':' escaped
+pub fn escape_text(text: &str, composed: bool) -> String {
+    let mut s = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == ']' || c == '\\' || (composed && c == ':') {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s
+}
+
+fn hook_value(hook: &dyn Fn(&str, &str) -> String, ident: &str, value: &str, composed: bool) -> String {
+    let text = unescape_text(value);
+    let new = hook(ident, &text);
+    // untouched values keep their original escaping
+    if new == text {
+        value.to_string()
+    } else {
+        escape_text(&new, composed)
+    }
+}
+
+fn apply_node_hook(node: &mut Node, hook: &dyn Fn(&str, &str) -> String) {
+    for prop in &mut node.props {
+        let ident = prop.ident.as_str();
+        if TEXT_PROPERTIES.contains(&ident) {
+            for value in &mut prop.values {
+                *value = hook_value(hook, ident, value, false);
+            }
+        } else if COMPOSED_TEXT_PROPERTIES.contains(&ident) {
+            for value in &mut prop.values {
+                if let Some(i) = value.find(':') {
+                    let text = hook_value(hook, ident, &value[i + 1..], true);
+                    *value = format!("{}:{}", &value[..i], text);
+                }
+            }
+        }
+    }
+}
+
+// every node of the tree, without recursion
+fn each_node(gt: &mut GameTree, mut f: impl FnMut(&mut Node)) {
+    let mut stack = vec![gt];
+    while let Some(gt) = stack.pop() {
+        gt.sequence.nodes.iter_mut().for_each(&mut f);
+        stack.extend(gt.gametrees.iter_mut().map(|child| child.as_mut()));
+    }
+}
+
+fn apply_text_hook(gt: &mut GameTree, hook: &dyn Fn(&str, &str) -> String) {
+    each_node(gt, |node| apply_node_hook(node, hook));
+}
+
+fn wrap_tree(gt: &mut GameTree, width: usize) {
+    each_node(gt, |node| wrap_node(node, width));
+}
+
+pub fn app_value() -> String {
+    format!("{}:{}", APP_NAME, APP_VERSION)
+}
+
+// applies the stamping policy to the root node of a single game
+pub fn stamp_app(gt: &mut GameTree, stamp: AppStamp) {
+    let ours = app_value();
+    let root = gt.root_mut();
+    match stamp {
+        AppStamp::Keep => {},
+        AppStamp::Preserve => {
+            if !root.has("AP") {
+                root.set("AP", vec![ours]);
+            }
+        },
+        AppStamp::Replace => root.set("AP", vec![ours]),
+        AppStamp::Chain => {
+            let mut previous = match root.get(PREVIOUS_APPS) {
+                Some(prop) => prop.values.clone(),
+                None => Vec::new(),
+            };
+            // an older stamp of ours is updated rather than kept
+            let prefix = format!("{}:", APP_NAME);
+            for value in root.get("AP").map_or(&[][..], |prop| &prop.values[..]) {
+                if !value.is_empty() && value != APP_NAME && !value.starts_with(&prefix) && !previous.contains(value) {
+                    previous.push(value.clone());
+                }
+            }
+            root.set("AP", vec![ours]);
+            if !previous.is_empty() {
+                root.set(PREVIOUS_APPS, previous);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn write(text: &str, app: AppStamp) -> String {
+        let coll = Parser::new(text).parse().unwrap();
+        Writer::new(app).write(&coll)
+    }
+
+    #[test]
+    fn hook1() {
+        let coll = Parser::new("(;GM[1]PB[darn]C[oh darn\\]];B[aa]LB[aa:darn]C[fine])").parse().unwrap();
+        let writer = Writer::default().with_text_hook(|_, text| text.replace("darn", "****"));
+        assert_eq!(writer.write(&coll), "(;GM[1]PB[****]C[oh ****\\]];B[aa]LB[aa:****]C[fine])");
+        let writer = Writer::default().with_text_hook(|ident, text| if ident == "C" { format!("{}: x]", text) } else { text.to_string() });
+        assert_eq!(writer.write(&coll), "(;GM[1]PB[darn]C[oh darn\\]: x\\]];B[aa]LB[aa:darn]C[fine: x\\]])");
+        let coll = Parser::new("(;GM[1]CA[latin1]AP[darn:1.0])").parse().unwrap();
+        let writer = Writer::default().with_text_hook(|_, text| text.to_uppercase());
+        assert_eq!(writer.write(&coll), "(;GM[1]CA[LATIN1]AP[darn:1.0])");
+        let writer = Writer::default().with_text_hook(|ident, text| if ident == "AP" { format!("{}:beta", text) } else { text.to_string() });
+        assert_eq!(writer.write(&coll), "(;GM[1]CA[latin1]AP[darn:1.0\\:beta])");
+    }
+
+    #[test]
+    fn live1() {
+        let root = Parser::new("(;GM[1]SZ[9])").parse().unwrap().gametrees.remove(0).sequence.nodes.remove(0);
+        let writer = Writer::new(AppStamp::Keep).with_text_hook(|_, text| text.to_uppercase());
+        let mut live = writer.live(io::Cursor::new(Vec::new()), &root).unwrap();
+        let mut node = Node { props: Vec::new() };
+        node.set("B", vec!["ee".to_string()]);
+        live.append(&node).unwrap();
+        assert_eq!(live.out.get_ref(), b"(;GM[1]SZ[9];B[ee])");
+        node.set("C", vec!["hi".to_string()]);
+        live.append(&node).unwrap();
+        let text = String::from_utf8(live.into_inner().into_inner()).unwrap();
+        assert_eq!(text, "(;GM[1]SZ[9];B[ee];B[ee]C[HI])");
+        assert!(Parser::new(&text).parse().is_ok());
+    }
+
+    #[test]
+    fn limits1() {
+        let coll = Parser::new("(;GM[1];B[aa];W[bb](;B[cc])(;B[dd];W[ee]))").parse().unwrap();
+        assert!(Writer::default().with_max_depth(5).try_write(&coll).is_ok());
+        assert!(matches!(Writer::default().with_max_depth(4).try_write(&coll), Err(Error::TooDeep(4))));
+        assert!(matches!(Writer::default().with_max_bytes(20).try_write(&coll), Err(Error::TooLarge(20))));
+        let root = coll.gametrees[0].sequence.nodes[0].clone();
+        let writer = Writer::default().with_max_depth(2);
+        let mut live = writer.live(io::Cursor::new(Vec::new()), &root).unwrap();
+        live.append(&coll.gametrees[0].sequence.nodes[1]).unwrap();
+        assert!(live.append(&coll.gametrees[0].sequence.nodes[2]).is_err());
+        // a game nested deeper than the stack would take, built in code as
+        // the parser refuses more than borrowed::MAX_NESTING levels
+        let node = coll.gametrees[0].sequence.nodes[1].clone();
+        let mut deep = GameTree { sequence: Sequence { nodes: vec![node.clone()] }, gametrees: Vec::new() };
+        for _ in 0..100_000 {
+            deep = GameTree { sequence: Sequence { nodes: vec![node.clone()] }, gametrees: vec![Box::new(deep)] };
+        }
+        let coll = Collection { gametrees: vec![deep] };
+        assert_eq!(Writer::default().write(&coll).len(), 100_001 * 8);
+        assert!(matches!(Writer::default().with_max_depth(100_000).try_write(&coll), Err(Error::TooDeep(_))));
+        assert!(matches!(Writer::default().with_max_bytes(90).try_write(&coll), Err(Error::TooLarge(90))));
+        // options write a converted copy, which is made and dropped without recursion too
+        assert_eq!(Writer::default().with_version(3).write(&coll).len(), 100_001 * 8 + 5);
+        assert_eq!(Writer::new(AppStamp::Replace).with_wrap(40).write(&coll).len(), 100_001 * 8 + 4 + app_value().len());
+        coll.gametrees.into_iter().for_each(GameTree::dispose);
+    }
+
+    #[test]
+    fn unescape1() {
+        assert_eq!(unescape_text("a\\]b\\\\c\\\nd"), "a]b\\cd");
+        assert_eq!(escape_text("a]b:c", true), "a\\]b\\:c");
+    }
+
+    #[test]
+    fn stamp1() {
+        let text = "(;GM[1]AP[CGoban:3];B[aa])";
+        assert_eq!(write(text, AppStamp::Keep), text);
+    }
+
+    #[test]
+    fn stamp2() {
+        let text = "(;GM[1]AP[CGoban:3];B[aa])";
+        assert_eq!(write(text, AppStamp::Preserve), text);
+        let text = "(;GM[1];B[aa])";
+        let expected = format!("(;GM[1]AP[{}];B[aa])", app_value());
+        assert_eq!(write(text, AppStamp::Preserve), expected);
+    }
+
+    #[test]
+    fn stamp3() {
+        let text = "(;GM[1]AP[CGoban:3];B[aa])";
+        let expected = format!("(;GM[1]AP[{}];B[aa])", app_value());
+        assert_eq!(write(text, AppStamp::Replace), expected);
+    }
+
+    #[test]
+    fn stamp4() {
+        let text = "(;GM[1]AP[CGoban:3];B[aa])";
+        let expected = format!("(;GM[1]AP[{}]XAP[CGoban:3];B[aa])", app_value());
+        assert_eq!(write(text, AppStamp::Chain), expected);
+        assert_eq!(write(&expected, AppStamp::Chain), expected);
+        // chaining again doesn't keep our own stamp, and the history grows
+        let text = "(;GM[1]AP[rustsgf:0.0.1]XAP[CGoban:3];B[aa])";
+        assert_eq!(write(text, AppStamp::Chain), expected);
+        let text = "(;GM[1]AP[Sabaki:0.52]XAP[CGoban:3];B[aa])";
+        let expected = format!("(;GM[1]AP[{}]XAP[CGoban:3][Sabaki:0.52];B[aa])", app_value());
+        assert_eq!(write(text, AppStamp::Chain), expected);
+        assert_eq!(write("(;GM[1];B[aa])", AppStamp::Chain), format!("(;GM[1]AP[{}];B[aa])", app_value()));
+    }
+
+    #[test]
+    fn version1() {
+        let coll = Parser::new("(;FF[4]SZ[9];B[])").parse().unwrap();
+        assert_eq!(Writer::default().with_version(3).write(&coll), "(;FF[3]SZ[9];B[tt])");
+        let mut live = Writer::default().with_version(3).live(io::Cursor::new(Vec::new()), coll.gametrees[0].root()).unwrap();
+        live.append(&coll.gametrees[0].sequence.nodes[1]).unwrap();
+        assert_eq!(String::from_utf8(live.into_inner().into_inner()).unwrap(), "(;FF[3]SZ[9];B[tt])");
+    }
+
+    #[test]
+    fn wrap1() {
+        let mut coll = Parser::new("(;PB[a long player name])").parse().unwrap();
+        coll.gametrees[0].root_mut().set("GC", vec!["a long game comment\r\nhere".to_string()]);
+        let text = Writer::default().with_wrap(10).write(&coll);
+        assert_eq!(text, "(;PB[a long player name]GC[a long \\\ngame \\\ncomment\nhere])");
+        assert_eq!(Parser::new(&text).parse().unwrap().gametrees[0].root().text("GC").unwrap(), "a long game comment\nhere");
+    }
+}